@@ -0,0 +1,134 @@
+//! Attachment metadata from `BODYSTRUCTURE`
+//!
+//! The server describes a message's MIME tree in its `BODYSTRUCTURE`
+//! response (RFC 3501 Section 7.4.2) without sending any part data, so
+//! attachments can be listed before deciding which messages to
+//! download.
+
+use async_imap::imap_proto::{BodyContentCommon, BodyContentSinglePart, BodyStructure};
+
+/// A non-inline attachment part of a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// The IMAP part number (`2`, `1.3`, ...), usable as a
+    /// `BODY[<part>]` section.
+    pub part: String,
+    /// The MIME type, lowercased (`application/pdf`).
+    pub content_type: String,
+    /// The file name from `Content-Disposition` or, failing that, the
+    /// `name` parameter of `Content-Type`. Encoded words are not
+    /// decoded.
+    pub filename: Option<String>,
+    /// The part size in octets as transferred, i.e. after
+    /// content-transfer-encoding (base64 inflates by about a third).
+    pub size: u32,
+}
+
+/// Collect the attachments described by a `BODYSTRUCTURE`, in part
+/// order.
+pub fn from_body_structure(structure: &BodyStructure<'_>) -> Vec<Attachment> {
+    let mut attachments = Vec::new();
+    for_each_leaf(structure, &mut |part, common, other| {
+        attachments.extend(attachment(part, common, other));
+    });
+    attachments
+}
+
+/// The callback of [`for_each_leaf`].
+pub type LeafVisitor<'v> = dyn FnMut(&str, &BodyContentCommon<'_>, &BodyContentSinglePart<'_>) + 'v;
+
+/// Call `visit` with the part number and description of every leaf
+/// part of a message, in part order.
+pub fn for_each_leaf(structure: &BodyStructure<'_>, visit: &mut LeafVisitor<'_>) {
+    walk(structure, "", visit);
+}
+
+fn walk(structure: &BodyStructure<'_>, part: &str, visit: &mut LeafVisitor<'_>) {
+    match structure {
+        BodyStructure::Multipart { bodies, .. } => {
+            for (i, body) in bodies.iter().enumerate() {
+                let child = if part.is_empty() {
+                    (i + 1).to_string()
+                } else {
+                    format!("{part}.{}", i + 1)
+                };
+                walk(body, &child, visit);
+            }
+        }
+        // A forwarded message/rfc822 part is a single leaf; its own
+        // parts are not visited.
+        BodyStructure::Basic { common, other, .. }
+        | BodyStructure::Text { common, other, .. }
+        | BodyStructure::Message { common, other, .. } => {
+            // A single-part message is part 1 of itself.
+            let part = if part.is_empty() { "1" } else { part };
+            visit(part, common, other);
+        }
+    }
+}
+
+/// Describe a leaf part, or `None` if it is not an attachment.
+pub fn attachment(
+    part: &str,
+    common: &BodyContentCommon<'_>,
+    other: &BodyContentSinglePart<'_>,
+) -> Option<Attachment> {
+    let disposition = common.disposition.as_ref();
+    let filename = disposition
+        .and_then(|d| param(d.params.as_deref(), "filename"))
+        .or_else(|| param(common.ty.params.as_deref(), "name"));
+
+    if !is_attachment(disposition.map(|d| d.ty.as_ref()), filename.is_some()) {
+        return None;
+    }
+
+    Some(Attachment {
+        part: part.to_string(),
+        content_type: format!("{}/{}", common.ty.ty, common.ty.subtype).to_ascii_lowercase(),
+        filename,
+        size: other.octets,
+    })
+}
+
+/// Whether a leaf part is an attachment: explicitly marked
+/// `attachment`, or carrying a file name without being marked
+/// `inline`.
+const fn is_attachment(disposition: Option<&str>, has_filename: bool) -> bool {
+    match disposition {
+        Some(d) if d.eq_ignore_ascii_case("attachment") => true,
+        Some(d) if d.eq_ignore_ascii_case("inline") => false,
+        _ => has_filename,
+    }
+}
+
+/// Look up a MIME parameter by name (case-insensitive).
+fn param<V: AsRef<str>>(params: Option<&[(V, V)]>, name: &str) -> Option<String> {
+    params?
+        .iter()
+        .find(|(key, _)| key.as_ref().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_ref().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disposition_decides_before_filename() {
+        assert!(is_attachment(Some("ATTACHMENT"), false));
+        assert!(!is_attachment(Some("inline"), true));
+        assert!(is_attachment(None, true));
+        assert!(!is_attachment(None, false));
+    }
+
+    #[test]
+    fn params_match_case_insensitively() {
+        let params = [("FILENAME", "report.pdf")];
+        assert_eq!(
+            param(Some(&params[..]), "filename").as_deref(),
+            Some("report.pdf")
+        );
+        assert_eq!(param(Some(&params[..]), "name"), None);
+        assert_eq!(param::<&str>(None, "name"), None);
+    }
+}
@@ -0,0 +1,94 @@
+//! Detection of automatically generated messages
+//!
+//! Bots and ticketing integrations usually want human replies only.
+//! Vacation responders, bulk mail, and bounces announce themselves in
+//! their headers, which are checked here.
+
+use crate::headers;
+use email_extract::Email;
+
+/// `Precedence` values that mark a message as not written by a person.
+const AUTOMATED_PRECEDENCE: [&str; 3] = ["bulk", "junk", "auto_reply"];
+
+/// Whether a parsed email was generated automatically rather than
+/// written by a person.
+///
+/// A message counts as automated if any of these hold:
+///
+/// - `Auto-Submitted` is present with a value other than `no`
+///   (RFC 3834),
+/// - `X-Autoreply` or `X-Autorespond` is present,
+/// - `Precedence` is `bulk`, `junk`, or `auto_reply`,
+/// - `Return-Path` is the null path `<>`, as on bounces and delivery
+///   status notifications (RFC 5321 Section 4.5.5).
+#[must_use]
+pub fn is_auto_reply(email: &Email) -> bool {
+    is_automated(|name| {
+        headers::email_values(email, name)
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+/// Whether a raw message (or header block) was generated
+/// automatically, as in [`is_auto_reply`].
+#[must_use]
+pub fn is_auto_reply_raw(raw: &[u8]) -> bool {
+    is_automated(|name| headers::header_values(raw, name))
+}
+
+/// The checks of [`is_auto_reply`], on the header values `values`
+/// returns for a name.
+fn is_automated(values: impl Fn(&str) -> Vec<String>) -> bool {
+    let auto_submitted = values("Auto-Submitted").iter().any(|v| {
+        let kind = v.split(';').next().unwrap_or_default().trim();
+        !kind.eq_ignore_ascii_case("no")
+    });
+    let autoreply = !values("X-Autoreply").is_empty() || !values("X-Autorespond").is_empty();
+    let precedence = values("Precedence").iter().any(|v| {
+        AUTOMATED_PRECEDENCE
+            .iter()
+            .any(|p| v.trim().eq_ignore_ascii_case(p))
+    });
+    let bounce = values("Return-Path")
+        .iter()
+        .any(|v| v.split_whitespace().collect::<String>() == "<>");
+
+    auto_submitted || autoreply || precedence || bounce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn human_message_is_not_automated() {
+        let raw = b"From: a@b.com\r\nReturn-Path: <a@b.com>\r\nAuto-Submitted: no\r\n\r\nHi";
+        assert!(!is_auto_reply_raw(raw));
+        assert!(!is_auto_reply(&email_extract::parse_email(1, raw).unwrap()));
+    }
+
+    #[test]
+    fn detects_each_indicator() {
+        for header in [
+            "Auto-Submitted: auto-replied",
+            "Auto-Submitted: auto-generated; owner-email=\"x@y.com\"",
+            "X-Autoreply: yes",
+            "Precedence: Bulk",
+            "Precedence: auto_reply",
+            "Return-Path: < >",
+        ] {
+            let raw = format!("From: a@b.com\r\n{header}\r\n\r\nAway");
+            assert!(is_auto_reply_raw(raw.as_bytes()), "{header}");
+            let email = email_extract::parse_email(1, raw.as_bytes()).unwrap();
+            assert!(is_auto_reply(&email), "{header}");
+        }
+    }
+
+    #[test]
+    fn body_text_is_ignored() {
+        assert!(!is_auto_reply_raw(
+            b"From: a@b.com\r\n\r\nPrecedence: bulk\r\n"
+        ));
+    }
+}
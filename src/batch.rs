@@ -0,0 +1,21 @@
+//! The result of a batch fetch
+//!
+//! A bulk fetch should not fail as a whole because one message is too
+//! large or will not parse. [`BatchResult`] carries the messages that
+//! were fetched together with the UIDs that were skipped, and why.
+
+use crate::error::Error;
+use email_extract::Email;
+
+/// What [`ProtonClient::fetch_by_uids`](crate::ProtonClient::fetch_by_uids)
+/// fetched.
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    /// The messages fetched and parsed, in the order the server
+    /// returned them.
+    pub emails: Vec<Email>,
+    /// The UIDs that were skipped, with the reason: [`Error::TooLarge`]
+    /// for a message over the size limit, [`Error::Parse`] for one
+    /// that could not be parsed.
+    pub failed: Vec<(u32, Error)>,
+}
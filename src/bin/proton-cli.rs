@@ -1,13 +1,19 @@
-//! CLI for querying Proton Mail via Proton Bridge (read-only)
+//! CLI for querying Proton Mail via Proton Bridge
+//!
+//! Everything is read-only except `move`, which asks for confirmation
+//! unless `--yes` is given.
 
+use anyhow::Context;
 use chrono::NaiveDate;
 use clap::{Parser, Subcommand};
-use protonmail_client::{Email, Folder, ImapConfig, ProtonClient};
+use protonmail_client::{Email, Flag, Folder, ImapConfig, ProtonClient, ReadWrite, ThreadMessage};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
 use tracing_subscriber::EnvFilter;
 
 #[derive(Parser)]
 #[command(name = "proton-cli")]
-#[command(about = "Read-only CLI for Proton Mail via Proton Bridge")]
+#[command(about = "CLI for Proton Mail via Proton Bridge")]
 struct Args {
     #[command(subcommand)]
     command: Command,
@@ -15,6 +21,10 @@ struct Args {
     /// Output as JSON
     #[arg(long, global = true)]
     json: bool,
+
+    /// Read IMAP settings from this .env file first
+    #[arg(long, global = true)]
+    env_file: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -33,11 +43,11 @@ enum Command {
         #[arg(long)]
         unseen: bool,
 
-        /// Show emails since this date (YYYY-MM-DD)
+        /// Show emails that arrived on or after this date (YYYY-MM-DD)
         #[arg(long, value_parser = parse_date)]
         since: Option<NaiveDate>,
 
-        /// Show emails before this date (YYYY-MM-DD)
+        /// Show emails that arrived before this date (YYYY-MM-DD)
         #[arg(long, value_parser = parse_date)]
         before: Option<NaiveDate>,
     },
@@ -52,9 +62,42 @@ enum Command {
         folder: String,
     },
 
+    /// Show the Received headers of an email, newest hop first
+    Headers {
+        /// Email UID
+        uid: u32,
+
+        /// Folder containing the email
+        #[arg(long, default_value = "INBOX")]
+        folder: String,
+    },
+
+    /// Show the whole thread an email belongs to, oldest first
+    Thread {
+        /// Email UID
+        uid: u32,
+
+        /// Folder containing the email
+        #[arg(long, default_value = "INBOX")]
+        folder: String,
+    },
+
+    /// Show how to unsubscribe from the list an email came from
+    Unsubscribe {
+        /// Email UID
+        uid: u32,
+
+        /// Folder containing the email
+        #[arg(long, default_value = "INBOX")]
+        folder: String,
+    },
+
     /// List available IMAP folders
     Folders,
 
+    /// Show the TLS version and cipher suite negotiated with the bridge
+    Tls,
+
     /// Search emails using an IMAP search query
     Search {
         /// IMAP search query (e.g. "FROM foo@bar.com")
@@ -68,6 +111,31 @@ enum Command {
         #[arg(long, default_value = "50")]
         limit: usize,
     },
+
+    /// Move emails to another folder. UIDs are read from stdin, one
+    /// per line, unless --uids is given
+    Move {
+        /// Folder to move from
+        #[arg(long, default_value = "INBOX")]
+        from: String,
+
+        /// Folder to move to
+        #[arg(long)]
+        to: String,
+
+        /// Comma-separated UIDs (e.g. 1,2,3)
+        #[arg(long, value_delimiter = ',')]
+        uids: Vec<u32>,
+
+        /// Move every email matching this IMAP search query instead
+        /// (e.g. "FROM newsletters@example.com")
+        #[arg(long, conflicts_with = "uids")]
+        search: Option<String>,
+
+        /// Move without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
 }
 
 fn parse_date(s: &str) -> Result<NaiveDate, String> {
@@ -85,8 +153,11 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let args = Args::parse();
-    let config = ImapConfig::from_env()?;
-    let client = ProtonClient::new(config);
+    let config = match &args.env_file {
+        Some(path) => ImapConfig::from_env_path(path)?,
+        None => ImapConfig::from_env()?,
+    };
+    let client = ProtonClient::new(config.clone());
 
     match &args.command {
         Command::List {
@@ -103,9 +174,24 @@ async fn main() -> anyhow::Result<()> {
             let folder = Folder::from(folder.as_str());
             cmd_show(&client, &args, &folder, *uid).await?;
         }
+        Command::Headers { uid, folder } => {
+            let folder = Folder::from(folder.as_str());
+            cmd_headers(&client, &args, &folder, *uid).await?;
+        }
+        Command::Thread { uid, folder } => {
+            let folder = Folder::from(folder.as_str());
+            cmd_thread(&client, &args, &folder, *uid).await?;
+        }
+        Command::Unsubscribe { uid, folder } => {
+            let folder = Folder::from(folder.as_str());
+            cmd_unsubscribe(&client, &args, &folder, *uid).await?;
+        }
         Command::Folders => {
             cmd_folders(&client, &args).await?;
         }
+        Command::Tls => {
+            cmd_tls(&client, &args).await?;
+        }
         Command::Search {
             query,
             folder,
@@ -114,6 +200,22 @@ async fn main() -> anyhow::Result<()> {
             let folder = Folder::from(folder.as_str());
             cmd_search(&client, &args, &folder, query, *limit).await?;
         }
+        Command::Move {
+            from,
+            to,
+            uids,
+            search,
+            yes,
+        } => {
+            let writer = ProtonClient::<ReadWrite>::new(config);
+            let from = Folder::from(from.as_str());
+            let to = Folder::from(to.as_str());
+            if let Some(query) = search {
+                cmd_move_matching(&writer, &args, &from, &to, query, *yes).await?;
+            } else {
+                cmd_move(&writer, &args, &from, &to, uids, *yes).await?;
+            }
+        }
     }
 
     Ok(())
@@ -157,12 +259,82 @@ async fn cmd_show(
     folder: &Folder,
     uid: u32,
 ) -> anyhow::Result<()> {
-    let email = client.fetch_uid(folder, uid).await?;
+    let (email, flags) = client.fetch_uid_full(folder, uid).await?;
+    let flags: Vec<&str> = flags.iter().map(Flag::as_imap_str).collect();
 
     if args.json {
-        println!("{}", serde_json::to_string_pretty(&email)?);
+        let mut value = serde_json::to_value(&email)?;
+        if let Some(object) = value.as_object_mut() {
+            object.insert("flags".to_string(), serde_json::json!(flags));
+        }
+        println!("{}", serde_json::to_string_pretty(&value)?);
     } else {
-        print_email_detail(&email);
+        print_email_detail(&email, &flags);
+    }
+
+    Ok(())
+}
+
+async fn cmd_headers(
+    client: &ProtonClient,
+    args: &Args,
+    folder: &Folder,
+    uid: u32,
+) -> anyhow::Result<()> {
+    let path = client.fetch_delivery_path(folder, uid).await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&path)?);
+    } else if path.is_empty() {
+        println!("No Received headers found.");
+    } else {
+        for (hop, received) in path.iter().enumerate() {
+            println!("{:>2}. {received}", hop + 1);
+        }
+    }
+
+    Ok(())
+}
+
+async fn cmd_thread(
+    client: &ProtonClient,
+    args: &Args,
+    folder: &Folder,
+    uid: u32,
+) -> anyhow::Result<()> {
+    let messages = client.fetch_thread(folder, uid).await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&messages)?);
+    } else {
+        print_thread(&messages);
+    }
+
+    Ok(())
+}
+
+async fn cmd_unsubscribe(
+    client: &ProtonClient,
+    args: &Args,
+    folder: &Folder,
+    uid: u32,
+) -> anyhow::Result<()> {
+    let info = client.fetch_unsubscribe_info(folder, uid).await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else if let Some(info) = info {
+        for url in &info.urls {
+            println!("Web:   {url}");
+        }
+        for mailto in &info.mailto {
+            println!("Email: {mailto}");
+        }
+        if info.one_click {
+            println!("One-click unsubscribe is supported (POST to the web link).");
+        }
+    } else {
+        println!("No unsubscribe link found.");
     }
 
     Ok(())
@@ -182,6 +354,19 @@ async fn cmd_folders(client: &ProtonClient, args: &Args) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn cmd_tls(client: &ProtonClient, args: &Args) -> anyhow::Result<()> {
+    let info = client.tls_info().await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        println!("Protocol:     {}", info.protocol_version);
+        println!("Cipher suite: {}", info.cipher_suite);
+    }
+
+    Ok(())
+}
+
 async fn cmd_search(
     client: &ProtonClient,
     args: &Args,
@@ -201,6 +386,113 @@ async fn cmd_search(
     Ok(())
 }
 
+async fn cmd_move(
+    client: &ProtonClient<ReadWrite>,
+    args: &Args,
+    from: &Folder,
+    to: &Folder,
+    uids: &[u32],
+    yes: bool,
+) -> anyhow::Result<()> {
+    let uids_from_stdin = uids.is_empty();
+    let uids = if uids_from_stdin {
+        read_uids(std::io::stdin().lock())?
+    } else {
+        uids.to_vec()
+    };
+
+    if !uids.is_empty() && !yes {
+        let prompt = format!("Move {} email(s) from {from} to {to}?", uids.len());
+        if !confirm(&prompt, uids_from_stdin)? {
+            anyhow::bail!("Aborted, nothing moved");
+        }
+    }
+
+    client.move_uids(&uids, from, to).await?;
+
+    if args.json {
+        let summary = serde_json::json!({
+            "from": from.as_str(),
+            "to": to.as_str(),
+            "moved": uids,
+        });
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        println!("Moved {} email(s) from {from} to {to}", uids.len());
+    }
+
+    Ok(())
+}
+
+async fn cmd_move_matching(
+    client: &ProtonClient<ReadWrite>,
+    args: &Args,
+    from: &Folder,
+    to: &Folder,
+    query: &str,
+    yes: bool,
+) -> anyhow::Result<()> {
+    if !yes {
+        let prompt = format!("Move all emails matching '{query}' from {from} to {to}?");
+        if !confirm(&prompt, false)? {
+            anyhow::bail!("Aborted, nothing moved");
+        }
+    }
+
+    let moved = client.move_matching(from, query, to).await?;
+
+    if args.json {
+        let summary = serde_json::json!({
+            "from": from.as_str(),
+            "to": to.as_str(),
+            "search": query,
+            "moved": moved,
+        });
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        println!("Moved {moved} email(s) from {from} to {to}");
+    }
+
+    Ok(())
+}
+
+/// Parse one UID per line, skipping blank lines.
+fn read_uids(input: impl BufRead) -> anyhow::Result<Vec<u32>> {
+    let mut uids = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        uids.push(
+            line.parse()
+                .with_context(|| format!("Invalid UID '{line}'"))?,
+        );
+    }
+    Ok(uids)
+}
+
+/// Ask a yes/no question on stderr.
+///
+/// When stdin carries the UIDs the answer has to come from the
+/// terminal instead.
+fn confirm(prompt: &str, stdin_taken: bool) -> anyhow::Result<bool> {
+    eprint!("{prompt} [y/N] ");
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    if stdin_taken {
+        let tty = std::fs::File::open("/dev/tty")
+            .context("No terminal to confirm on; pass --yes to move without asking")?;
+        std::io::BufReader::new(tty).read_line(&mut answer)?;
+    } else {
+        std::io::stdin().read_line(&mut answer)?;
+    }
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
 fn print_email_table(emails: &[&Email]) {
     if emails.is_empty() {
         println!("No emails found.");
@@ -224,7 +516,28 @@ fn print_email_table(emails: &[&Email]) {
     println!("\n{} email(s)", emails.len());
 }
 
-fn print_email_detail(email: &Email) {
+/// Print each message of a thread, indented by its reply depth.
+fn print_thread(messages: &[ThreadMessage]) {
+    for message in messages {
+        let email = &message.email;
+        let indent = "    ".repeat(message.depth);
+        println!(
+            "{indent}[{}] {}  {}",
+            email.uid,
+            email.date.format("%Y-%m-%d %H:%M"),
+            email.from
+        );
+        println!("{indent}Subject: {}", email.subject.original);
+        for line in email.body.best_text().to_string().lines() {
+            println!("{indent}  {line}");
+        }
+        println!();
+    }
+
+    println!("{} message(s) in thread", messages.len());
+}
+
+fn print_email_detail(email: &Email, flags: &[&str]) {
     println!("UID:     {}", email.uid);
     println!("Date:    {}", email.date.format("%Y-%m-%d %H:%M:%S"));
     println!("From:    {}", email.from);
@@ -252,6 +565,14 @@ fn print_email_detail(email: &Email) {
 
     println!("Subject: {}", email.subject.original);
     println!("Msg-ID:  {}", email.message_id);
+    println!(
+        "Flags:   {}",
+        if flags.is_empty() {
+            "(none)".to_string()
+        } else {
+            flags.join(" ")
+        }
+    );
 
     if email.thread.is_reply {
         println!(
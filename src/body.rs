@@ -0,0 +1,232 @@
+//! Choosing between the plain-text and HTML bodies of a message
+//!
+//! Most mail carries its text twice, as `text/plain` and `text/html`
+//! alternatives. [`Email`](crate::Email) picks one heuristically;
+//! these helpers find the wanted variant in the `BODYSTRUCTURE` so
+//! only that part has to be downloaded, then undo its
+//! content-transfer-encoding and charset.
+
+use crate::attachment;
+use async_imap::imap_proto::{BodyStructure, ContentEncoding};
+use serde::Serialize;
+
+/// Which text body to prefer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BodyPreference {
+    /// `text/plain`, e.g. for indexing or quoting.
+    #[default]
+    Plain,
+    /// `text/html`, e.g. for rendering.
+    Html,
+}
+
+/// A `text/plain` or `text/html` part of a message, as listed in its
+/// `BODYSTRUCTURE`. Parts marked as attachments are not included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextPart {
+    /// The IMAP part number (`1`, `1.2`, ...).
+    pub part: String,
+    /// Whether this is the `text/html` rather than the `text/plain`
+    /// part.
+    pub html: bool,
+    /// The `charset` parameter, lowercased.
+    pub charset: Option<String>,
+    encoding: Encoding,
+}
+
+/// A decoded text body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BodyText {
+    /// Whether `text` is HTML.
+    pub html: bool,
+    /// The body, decoded to UTF-8.
+    pub text: String,
+}
+
+/// The content-transfer-encodings that need undoing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Identity,
+    Base64,
+    QuotedPrintable,
+}
+
+/// List the inline `text/plain` and `text/html` parts of a message.
+pub fn text_parts(structure: &BodyStructure<'_>) -> Vec<TextPart> {
+    let mut parts = Vec::new();
+    attachment::for_each_leaf(structure, &mut |part, common, other| {
+        if !common.ty.ty.eq_ignore_ascii_case("text") {
+            return;
+        }
+        let html = match common.ty.subtype.to_ascii_lowercase().as_str() {
+            "plain" => false,
+            "html" => true,
+            _ => return,
+        };
+        if attachment::attachment(part, common, other).is_some() {
+            return;
+        }
+        let charset = common.ty.params.as_deref().and_then(|params| {
+            params
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case("charset"))
+                .map(|(_, value)| value.to_ascii_lowercase())
+        });
+        let encoding = match other.transfer_encoding {
+            ContentEncoding::Base64 => Encoding::Base64,
+            ContentEncoding::QuotedPrintable => Encoding::QuotedPrintable,
+            _ => Encoding::Identity,
+        };
+        parts.push(TextPart {
+            part: part.to_string(),
+            html,
+            charset,
+            encoding,
+        });
+    });
+    parts
+}
+
+/// The first part of the preferred kind, or else the first part of
+/// the other kind. `None` if the message has neither.
+pub fn choose(parts: &[TextPart], preference: BodyPreference) -> Option<&TextPart> {
+    let want_html = preference == BodyPreference::Html;
+    parts
+        .iter()
+        .find(|p| p.html == want_html)
+        .or_else(|| parts.first())
+}
+
+impl TextPart {
+    /// Decode the part's raw bytes as fetched with `BODY[<part>]`.
+    ///
+    /// `base64` and `quoted-printable` are undone. `us-ascii`,
+    /// `utf-8`, and a missing charset are read as UTF-8; `iso-8859-1`
+    /// and `windows-1252` as Latin-1. Other charsets are read as
+    /// UTF-8 too, with invalid sequences replaced.
+    #[must_use]
+    pub fn decode(&self, data: &[u8]) -> BodyText {
+        let bytes = match self.encoding {
+            Encoding::Identity => data.to_vec(),
+            Encoding::Base64 => decode_base64(data),
+            Encoding::QuotedPrintable => decode_quoted_printable(data),
+        };
+        let text = match self.charset.as_deref() {
+            Some("iso-8859-1" | "latin1" | "windows-1252") => {
+                bytes.iter().map(|&b| char::from(b)).collect()
+            }
+            _ => String::from_utf8(bytes)
+                .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()),
+        };
+        BodyText {
+            html: self.html,
+            text,
+        }
+    }
+}
+
+/// Decode base64, skipping line breaks and other characters outside
+/// the alphabet.
+fn decode_base64(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 4 * 3);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &b in data {
+        let value = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => continue,
+        };
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.extend(u8::try_from(acc >> bits).ok());
+            acc &= (1 << bits) - 1;
+        }
+    }
+    out
+}
+
+/// Decode quoted-printable (RFC 2045 Section 6.7): `=XX` escapes and
+/// `=` soft line breaks. Malformed escapes are kept as they are.
+fn decode_quoted_printable(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'=' {
+            let rest = &data[i + 1..];
+            if rest.starts_with(b"\r\n") {
+                i += 3;
+                continue;
+            }
+            if rest.starts_with(b"\n") {
+                i += 2;
+                continue;
+            }
+            let byte = rest
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = byte {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(data[i]);
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(html: bool, charset: Option<&str>, encoding: Encoding) -> TextPart {
+        TextPart {
+            part: "1".to_string(),
+            html,
+            charset: charset.map(str::to_string),
+            encoding,
+        }
+    }
+
+    #[test]
+    fn decodes_base64() {
+        assert_eq!(decode_base64(b"SGVs\r\nbG8h"), b"Hello!");
+        assert_eq!(decode_base64(b"SGk="), b"Hi");
+    }
+
+    #[test]
+    fn decodes_quoted_printable() {
+        assert_eq!(
+            decode_quoted_printable(b"caf=C3=A9 soft=\r\nbreak =ZZ"),
+            "café softbreak =ZZ".as_bytes()
+        );
+    }
+
+    #[test]
+    fn decodes_latin1() {
+        let text = part(false, Some("iso-8859-1"), Encoding::QuotedPrintable).decode(b"caf=E9");
+        assert_eq!(text.text, "café");
+        assert!(!text.html);
+    }
+
+    #[test]
+    fn falls_back_to_other_kind() {
+        let plain_only = [part(false, None, Encoding::Identity)];
+        assert!(!choose(&plain_only, BodyPreference::Html).unwrap().html);
+        let both = [
+            part(false, None, Encoding::Identity),
+            part(true, None, Encoding::Identity),
+        ];
+        assert!(choose(&both, BodyPreference::Html).unwrap().html);
+        assert!(choose(&[], BodyPreference::Plain).is_none());
+    }
+}
@@ -0,0 +1,224 @@
+//! In-memory cache of message bodies
+//!
+//! An interactive client typically lists a folder's headers and then
+//! opens messages one at a time. [`BodyCache`] keeps recently fetched
+//! bodies so that opening a message again, or one prefetched after the
+//! listing, needs no round trip to the server.
+//!
+//! Entries are keyed by folder, UID, and the folder's UIDVALIDITY. A
+//! UID names the same message for as long as UIDVALIDITY is unchanged
+//! (RFC 3501 Section 2.3.1.1), so a cached body never goes stale.
+//! Messages the client moves or deletes are evicted; one expunged by
+//! another client is still served until it is evicted as least
+//! recently used.
+
+use crate::config::ImapConfig;
+use crate::connection;
+use crate::error::Result;
+use crate::fetch::{self, RawFetch};
+use crate::folder::Folder;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+type Key = (String, u32, Option<u32>);
+
+/// A least-recently-used cache of `BODY[]` fetches.
+#[derive(Debug)]
+pub struct BodyCache {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    capacity: usize,
+    /// The UIDVALIDITY last reported for each folder.
+    validity: HashMap<String, Option<u32>>,
+    entries: HashMap<Key, RawFetch>,
+    /// Keys from least to most recently used.
+    order: VecDeque<Key>,
+}
+
+impl BodyCache {
+    /// A cache holding at most `capacity` bodies.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                capacity,
+                validity: HashMap::new(),
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// The cached fetch of `uid` in `folder`, marking it as recently
+    /// used. `None` until the folder's UIDVALIDITY has been observed.
+    pub fn get(&self, folder: &str, uid: u32) -> Option<RawFetch> {
+        let mut inner = self.lock();
+        let key = inner.key(folder, uid)?;
+        let raw = inner.entries.get(&key)?.clone();
+        inner.touch(key);
+        drop(inner);
+        Some(raw)
+    }
+
+    /// Whether `uid` in `folder` is cached.
+    pub fn contains(&self, folder: &str, uid: u32) -> bool {
+        let inner = self.lock();
+        inner
+            .key(folder, uid)
+            .is_some_and(|key| inner.entries.contains_key(&key))
+    }
+
+    /// Record the UIDVALIDITY a SELECT of `folder` reported, dropping
+    /// the folder's entries if it changed.
+    pub fn observe(&self, folder: &str, validity: Option<u32>) {
+        let mut inner = self.lock();
+        let previous = inner.validity.insert(folder.to_string(), validity);
+        if previous.is_some_and(|previous| previous != validity) {
+            inner.entries.retain(|(f, _, _), _| f != folder);
+            inner.order.retain(|(f, _, _)| f != folder);
+        }
+    }
+
+    /// Cache a fetch made while `folder` had UIDVALIDITY `validity`,
+    /// evicting the least recently used entries beyond capacity.
+    pub fn insert(&self, folder: &str, validity: Option<u32>, raw: RawFetch) {
+        self.observe(folder, validity);
+        let mut inner = self.lock();
+        let key = (folder.to_string(), raw.uid, validity);
+        inner.entries.insert(key.clone(), raw);
+        inner.touch(key);
+        while inner.order.len() > inner.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.entries.remove(&oldest);
+            }
+        }
+        drop(inner);
+    }
+
+    /// Drop `uids` in `folder`, e.g. because they were moved away.
+    pub fn remove(&self, folder: &str, uids: &[u32]) {
+        let mut inner = self.lock();
+        let removed = |f: &str, uid: &u32| f == folder && uids.contains(uid);
+        inner.entries.retain(|(f, uid, _), _| !removed(f, uid));
+        inner.order.retain(|(f, uid, _)| !removed(f, uid));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Inner {
+    /// The key of `uid` under the folder's current UIDVALIDITY.
+    fn key(&self, folder: &str, uid: u32) -> Option<Key> {
+        let validity = self.validity.get(folder)?;
+        Some((folder.to_string(), uid, *validity))
+    }
+
+    /// Move `key` to the most recently used end.
+    fn touch(&mut self, key: Key) {
+        self.order.retain(|k| *k != key);
+        self.order.push_back(key);
+    }
+}
+
+/// Fetch the bodies of the `uids` not yet cached into `cache`, on a
+/// connection of its own. Returns how many were fetched.
+///
+/// Messages over [`ImapConfig::max_message_size`] are skipped.
+pub async fn prefetch(
+    config: &ImapConfig,
+    cache: &BodyCache,
+    folder: &Folder,
+    uids: &[u32],
+) -> Result<usize> {
+    let missing: Vec<u32> = uids
+        .iter()
+        .copied()
+        .filter(|&uid| !cache.contains(folder.as_str(), uid))
+        .collect();
+    if missing.is_empty() {
+        return Ok(0);
+    }
+
+    let mut session = connection::connect(config).await?;
+    let status = connection::select_status(&mut session, folder.as_str()).await?;
+    let uids = fetch::within_size(&mut session, folder, &missing, config.max_message_size).await?;
+    let fetches = fetch::fetch_items(&mut session, &uids, "(BODY.PEEK[])").await?;
+    session.logout().await.ok();
+
+    let count = fetches.len();
+    for raw in fetches {
+        cache.insert(folder.as_str(), status.uid_validity, raw);
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(uid: u32) -> RawFetch {
+        RawFetch {
+            uid,
+            flags: Vec::new(),
+            internal_date: None,
+            size: None,
+            header: None,
+            body: Some(format!("Subject: {uid}\r\n\r\nBody").into_bytes()),
+            text: None,
+            attachments: Vec::new(),
+            text_parts: Vec::new(),
+            envelope: None,
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = BodyCache::new(2);
+        cache.insert("INBOX", Some(1), raw(1));
+        cache.insert("INBOX", Some(1), raw(2));
+        assert!(cache.get("INBOX", 1).is_some());
+
+        cache.insert("INBOX", Some(1), raw(3));
+        assert!(cache.contains("INBOX", 1));
+        assert!(!cache.contains("INBOX", 2));
+        assert!(cache.contains("INBOX", 3));
+    }
+
+    #[test]
+    fn uidvalidity_change_drops_folder() {
+        let cache = BodyCache::new(10);
+        cache.insert("INBOX", Some(1), raw(1));
+        cache.insert("Sent", Some(7), raw(1));
+
+        cache.observe("INBOX", Some(1));
+        assert!(cache.contains("INBOX", 1));
+
+        cache.observe("INBOX", Some(2));
+        assert!(cache.get("INBOX", 1).is_none());
+        assert!(cache.contains("Sent", 1));
+    }
+
+    #[test]
+    fn removes_only_the_given_uids() {
+        let cache = BodyCache::new(10);
+        cache.insert("INBOX", Some(1), raw(1));
+        cache.insert("INBOX", Some(1), raw(2));
+        cache.insert("Sent", Some(7), raw(1));
+
+        cache.remove("INBOX", &[1]);
+        assert!(!cache.contains("INBOX", 1));
+        assert!(cache.contains("INBOX", 2));
+        assert!(cache.contains("Sent", 1));
+    }
+
+    #[test]
+    fn unknown_folder_misses() {
+        let cache = BodyCache::new(10);
+        assert!(cache.get("INBOX", 1).is_none());
+        assert!(!cache.contains("INBOX", 1));
+    }
+}
@@ -4,7 +4,7 @@
 //!
 //! - [`ReadOnly`]  -- only read operations (list, fetch, search)
 //! - [`ReadWrite`] -- read **and** write operations (move, flag,
-//!   archive)
+//!   archive, append)
 //!
 //! This prevents accidental use of destructive operations when only
 //! read access is intended.
@@ -51,18 +51,50 @@
 //! let _ = client.archive(1, &Folder::Inbox);
 //! ```
 
+use std::collections::HashMap;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::sync::{Arc, OnceLock};
 
-use crate::config::ImapConfig;
-use crate::connection::{self, ImapSession};
+use crate::attachment::Attachment;
+use crate::auto_reply;
+use crate::batch::BatchResult;
+use crate::body::{self, BodyPreference, BodyText};
+use crate::cache::{self, BodyCache};
+use crate::config::{ImapConfig, MissingFolderBehavior};
+use crate::connection::{self, TlsInfo};
+use crate::conversation::CONVERSATION_ID_HEADER;
+use crate::dedup;
 use crate::error::{Error, Result};
-use crate::flag::Flag;
-use crate::folder::Folder;
+use crate::export::{self, EXPORT_CHUNK, FetchOrder, ManifestEntry};
+use crate::fetch::{self, FetchMacro, RawFetch};
+use crate::flag::{Flag, FlagDelta};
+use crate::folder::{Folder, FolderStatus};
+use crate::headers;
+use crate::list_item::{LIST_FIELDS, ListItem};
+use crate::metrics;
+use crate::move_outcome::MoveOutcome;
+use crate::pgp;
+use crate::response_code::ImapResponseCode;
+use crate::rule::RuleOutcome;
+use crate::search::SearchQuery;
+use crate::session::ProtonSession;
+use crate::special_use::{self, SpecialFolders, SpecialUse};
+use crate::tasks::Tasks;
+use crate::thread::{self, ThreadMessage};
+use crate::unsubscribe::UnsubscribeInfo;
+use crate::watch::{self, WATCH_BUFFER, WatchEvent};
 use chrono::NaiveDate;
-use email_extract::{Email, parse_email};
-use futures::{StreamExt, pin_mut};
+use email_extract::Email;
+use futures::{StreamExt, TryStreamExt, stream};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{OnceCell, mpsc};
 use tracing::{info, warn};
 
+/// How many folders [`ProtonClient::unified_unseen`] searches at
+/// once.
+const UNIFIED_CONCURRENCY: usize = 4;
+
 // ── Access-mode markers ────────────────────────────────────────────
 
 /// Marker: read-only access. Write methods are not available.
@@ -85,6 +117,18 @@ pub struct ReadWrite;
 /// | `ReadWrite` | yes      | yes       |
 pub struct ProtonClient<M = ReadOnly> {
     config: ImapConfig,
+    /// SPECIAL-USE folders, discovered on first use.
+    special_folders: OnceCell<SpecialFolders>,
+    /// Folder hierarchy delimiter, discovered on first use.
+    delimiter: OnceCell<char>,
+    /// Recently fetched bodies, if enabled with [`Self::with_cache`].
+    cache: Option<Arc<BodyCache>>,
+    /// How many bodies [`Self::fetch_headers`] prefetches.
+    prefetch: usize,
+    /// Prefetches and watches, shared with the client's sessions and
+    /// created on first use. Prefetches are aborted when the client is
+    /// dropped.
+    tasks: OnceLock<Arc<Tasks>>,
     _mode: PhantomData<M>,
 }
 
@@ -93,327 +137,2285 @@ impl<M> ProtonClient<M> {
     pub const fn new(config: ImapConfig) -> Self {
         Self {
             config,
+            special_folders: OnceCell::const_new(),
+            delimiter: OnceCell::const_new(),
+            cache: None,
+            prefetch: 0,
+            tasks: OnceLock::new(),
             _mode: PhantomData,
         }
     }
+
+    /// Keep up to `capacity` message bodies in memory, so that
+    /// [`Self::fetch_uid`] serves a message it has seen before without
+    /// contacting the server.
+    ///
+    /// Entries are tied to the folder's UIDVALIDITY and dropped when a
+    /// later SELECT reports a different one. A `capacity` of 0
+    /// disables the cache.
+    #[must_use]
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = (capacity > 0).then(|| Arc::new(BodyCache::new(capacity)));
+        self
+    }
+
+    /// After [`Self::fetch_headers`], fetch the bodies of the `count`
+    /// highest listed UIDs into the cache in the background.
+    /// Prefetches still running when the client is dropped are
+    /// aborted.
+    ///
+    /// Has no effect without [`Self::with_cache`].
+    #[must_use]
+    pub const fn with_prefetch(mut self, count: usize) -> Self {
+        self.prefetch = count;
+        self
+    }
+}
+
+impl<M> Drop for ProtonClient<M> {
+    fn drop(&mut self) {
+        if let Some(tasks) = self.tasks.get() {
+            tasks.abort_prefetches();
+        }
+    }
 }
 
 // ── Read operations (available on any M) ───────────────────────────
 
 impl<M: Send + Sync> ProtonClient<M> {
-    /// List all available IMAP folders.
+    /// Run an operation, reporting it to the configured metrics
+    /// callback.
+    async fn observe<T>(
+        &self,
+        op: &'static str,
+        folder: Option<&Folder>,
+        operation: impl Future<Output = Result<T>> + Send,
+    ) -> Result<T> {
+        metrics::observe(self.config.metrics.as_ref(), op, folder, operation).await
+    }
+
+    /// Like [`Self::observe`], for a read operation: if the folder
+    /// does not exist and the config asks for
+    /// [`MissingFolderBehavior::EmptyResult`], return an empty result
+    /// instead of the error.
+    async fn observe_read<T: Default>(
+        &self,
+        op: &'static str,
+        folder: &Folder,
+        operation: impl Future<Output = Result<T>> + Send,
+    ) -> Result<T> {
+        match self.observe(op, Some(folder), operation).await {
+            Err(e)
+                if self.config.on_missing_folder == MissingFolderBehavior::EmptyResult
+                    && e.response_code() == Some(&ImapResponseCode::NonExistent) =>
+            {
+                info!("{folder} does not exist, returning no results");
+                Ok(T::default())
+            }
+            result => result,
+        }
+    }
+
+    /// Open a persistent session that reuses one connection across
+    /// operations. See [`ProtonSession`].
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection or LIST command fails.
-    pub async fn list_folders(&self) -> Result<Vec<String>> {
-        let mut session = connection::connect(&self.config).await?;
+    /// Returns an error if the connection or login fails.
+    pub async fn session(&self) -> Result<ProtonSession<M>> {
+        let session = connection::connect(&self.config).await?;
+        Ok(ProtonSession::new(
+            session,
+            &self.config,
+            self.tasks().clone(),
+        ))
+    }
 
-        let mut folder_stream = session
-            .list(Some(""), Some("*"))
-            .await
-            .map_err(|e| Error::Imap(format!("List folders failed: {e}")))?;
+    /// How many background tasks (prefetches started by
+    /// [`Self::fetch_headers`] and watches started by [`Self::watch`])
+    /// are still running.
+    #[must_use]
+    pub fn background_tasks(&self) -> usize {
+        self.tasks.get().map_or(0, |tasks| tasks.running())
+    }
 
-        let mut names = Vec::new();
-        while let Some(item) = folder_stream.next().await {
-            if let Ok(name) = item {
-                names.push(name.name().to_string());
-            }
-        }
-        drop(folder_stream);
+    /// The client's background tasks, created on first use.
+    fn tasks(&self) -> &Arc<Tasks> {
+        self.tasks.get_or_init(Arc::default)
+    }
 
-        session.logout().await.ok();
-        Ok(names)
+    /// Resolve the folder with a SPECIAL-USE role.
+    ///
+    /// Folders are discovered from SPECIAL-USE attributes in the LIST
+    /// response on first use and cached for the life of the client.
+    /// Roles the server does not advertise fall back to
+    /// [`SpecialUse::default_folder`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection or LIST fails during
+    /// discovery.
+    pub async fn special_use_folder(&self, special_use: SpecialUse) -> Result<Folder> {
+        let folders = self
+            .special_folders
+            .get_or_try_init(|| {
+                self.observe("special_use_folder", None, async {
+                    let mut session = connection::connect(&self.config).await?;
+                    let folders = special_use::discover(&mut session).await?;
+                    session.logout().await.ok();
+                    Ok(folders)
+                })
+            })
+            .await?;
+
+        Ok(folders
+            .get(&special_use)
+            .cloned()
+            .unwrap_or_else(|| special_use.default_folder()))
     }
 
-    /// Fetch a single email by UID from a folder.
+    /// The Trash folder (`\Trash`).
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection, SELECT, or FETCH fails,
-    /// or if the message body cannot be parsed.
-    pub async fn fetch_uid(&self, folder: &Folder, uid: u32) -> Result<Email> {
-        let mut session = connection::connect(&self.config).await?;
-        connection::select(&mut session, folder.as_str()).await?;
+    /// See [`Self::special_use_folder`].
+    pub async fn trash_folder(&self) -> Result<Folder> {
+        self.special_use_folder(SpecialUse::Trash).await
+    }
 
-        let email = Self::fetch_single(&mut session, uid).await?;
+    /// The Sent folder (`\Sent`).
+    ///
+    /// # Errors
+    ///
+    /// See [`Self::special_use_folder`].
+    pub async fn sent_folder(&self) -> Result<Folder> {
+        self.special_use_folder(SpecialUse::Sent).await
+    }
 
-        session.logout().await.ok();
-        Ok(email)
+    /// The Drafts folder (`\Drafts`).
+    ///
+    /// # Errors
+    ///
+    /// See [`Self::special_use_folder`].
+    pub async fn drafts_folder(&self) -> Result<Folder> {
+        self.special_use_folder(SpecialUse::Drafts).await
     }
 
-    /// Fetch all unseen emails from a folder.
+    /// The Archive folder (`\Archive`).
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection, SELECT, or SEARCH fails.
-    pub async fn fetch_unseen(&self, folder: &Folder) -> Result<Vec<Email>> {
-        self.search(folder, "UNSEEN").await
+    /// See [`Self::special_use_folder`].
+    pub async fn archive_folder(&self) -> Result<Folder> {
+        self.special_use_folder(SpecialUse::Archive).await
     }
 
-    /// Fetch all emails from a folder.
+    /// The Spam folder (`\Junk`).
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection, SELECT, or SEARCH fails.
-    pub async fn fetch_all(&self, folder: &Folder) -> Result<Vec<Email>> {
-        self.search(folder, "ALL").await
+    /// See [`Self::special_use_folder`].
+    pub async fn junk_folder(&self) -> Result<Folder> {
+        self.special_use_folder(SpecialUse::Junk).await
     }
 
-    /// Fetch the N most recent emails from a folder.
+    /// The TLS version and cipher suite negotiated with the server.
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection, SELECT, SEARCH, or
-    /// FETCH fails.
-    pub async fn fetch_last_n(&self, folder: &Folder, n: usize) -> Result<Vec<Email>> {
-        let mut session = connection::connect(&self.config).await?;
-        connection::select(&mut session, folder.as_str()).await?;
+    /// Returns an error if the connection fails or the TLS parameters
+    /// are unavailable.
+    pub async fn tls_info(&self) -> Result<TlsInfo> {
+        self.observe("tls_info", None, async {
+            let mut session = connection::connect(&self.config).await?;
+            let info = connection::tls_info(&session);
+            session.logout().await.ok();
+            info
+        })
+        .await
+    }
 
-        let uids = session
-            .uid_search("ALL")
+    /// The folder hierarchy delimiter, e.g. `/` in `Folders/Work`.
+    ///
+    /// Read from the server with `LIST "" ""` on first use and cached
+    /// for the life of the client. Pass it to [`Folder::nested`] and
+    /// [`Folder::components`]. A server with a flat namespace reports
+    /// no delimiter; `/`, which Proton Bridge uses, is returned then.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection or LIST fails.
+    pub async fn folder_delimiter(&self) -> Result<char> {
+        self.delimiter
+            .get_or_try_init(|| {
+                self.observe("folder_delimiter", None, async {
+                    let mut session = connection::connect(&self.config).await?;
+                    let delimiter = connection::delimiter(&mut session).await?;
+                    session.logout().await.ok();
+                    Ok(delimiter.unwrap_or('/'))
+                })
+            })
             .await
-            .map_err(|e| Error::Imap(format!("Search failed: {e}")))?;
+            .copied()
+    }
 
-        let mut uid_list: Vec<u32> = uids.into_iter().collect();
-        uid_list.sort_unstable();
+    /// List all available IMAP folders.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection or LIST command fails.
+    pub async fn list_folders(&self) -> Result<Vec<String>> {
+        self.observe("list_folders", None, async {
+            let mut session = connection::connect(&self.config).await?;
 
-        let start = uid_list.len().saturating_sub(n);
-        let recent_uids = &uid_list[start..];
+            let names = connection::list_folders(&mut session).await?;
 
-        if recent_uids.is_empty() {
             session.logout().await.ok();
-            return Ok(vec![]);
-        }
+            Ok(names)
+        })
+        .await
+    }
 
-        info!("Fetching {} most recent messages", recent_uids.len());
+    /// Re-SELECT a folder and report its current state.
+    ///
+    /// Nothing is fetched: the counts come from the SELECT response
+    /// plus a `UID SEARCH UNSEEN`. Meant for polling loops on servers
+    /// (or setups) without IDLE, to notice changes made by other
+    /// clients.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn refresh(&self, folder: &Folder) -> Result<FolderStatus> {
+        self.observe("refresh", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            let mut status = connection::select_status(&mut session, folder.as_str()).await?;
+            if let Some(cache) = &self.cache {
+                cache.observe(folder.as_str(), status.uid_validity);
+            }
 
-        let mut emails = Self::fetch_by_uids(&mut session, recent_uids).await?;
-        emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+            let unseen = connection::uid_search(&mut session, "UNSEEN").await?;
+            status.unseen = u32::try_from(unseen.len()).unwrap_or(u32::MAX);
 
-        session.logout().await.ok();
-        Ok(emails)
+            session.logout().await.ok();
+            Ok(status)
+        })
+        .await
+    }
+
+    /// Watch a folder for changes with IDLE (RFC 2177).
+    ///
+    /// Spawns a task on its own connection that forwards every change
+    /// the server announces. The task re-issues IDLE every
+    /// [`ImapConfig::idle_renew`], before the server would end it,
+    /// and reconnects if the connection drops; the receiver sees one
+    /// uninterrupted stream of events. A change in message count
+    /// while reconnecting is reported as [`WatchEvent::Exists`].
+    ///
+    /// Drop the receiver to stop watching; apart from that, only
+    /// [`ProtonSession::shutdown`] ends the watch. A failed
+    /// connection, SELECT, or IDLE is retried with exponential
+    /// backoff, e.g. while the bridge restarts. Failures before the
+    /// first connection succeeds are also sent as errors.
+    #[must_use]
+    pub fn watch(&self, folder: &Folder) -> mpsc::Receiver<Result<WatchEvent>> {
+        let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+        self.tasks()
+            .spawn_watch(watch::run(self.config.clone(), folder.clone(), tx));
+        rx
     }
 
-    /// Fetch emails within a date range from a folder.
+    /// Fetch a single email by UID from a folder.
     ///
-    /// IMAP semantics: SINCE >= date, BEFORE < date.
+    /// With [`Self::with_cache`], a cached body is returned without a
+    /// round trip, and a fetched one is added to the cache.
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection, SELECT, or SEARCH fails.
-    pub async fn fetch_date_range(
-        &self,
-        folder: &Folder,
-        since: NaiveDate,
-        before: NaiveDate,
-    ) -> Result<Vec<Email>> {
-        let since_str = since.format("%-d-%b-%Y").to_string();
-        let before_str = before.format("%-d-%b-%Y").to_string();
-        let query = format!("SINCE {since_str} BEFORE {before_str}");
+    /// Returns [`Error::MessageNotFound`] if no message with `uid`
+    /// exists in `folder`. Returns other errors if the connection,
+    /// SELECT, or FETCH fails, or if the message body cannot be
+    /// parsed.
+    pub async fn fetch_uid(&self, folder: &Folder, uid: u32) -> Result<Email> {
+        self.observe("fetch_uid", Some(folder), async {
+            let cache = self.cache.as_deref();
+            if let Some(raw) = cache.and_then(|c| c.get(folder.as_str(), uid)) {
+                return fetch::parse_fetch(&raw);
+            }
+
+            let mut session = connection::connect(&self.config).await?;
+            let status = connection::select_status(&mut session, folder.as_str()).await?;
 
-        self.search(folder, &query).await
+            let raw = fetch::fetch_raw(&mut session, folder, uid, "(BODY.PEEK[])").await?;
+
+            session.logout().await.ok();
+            let email = fetch::parse_fetch(&raw)?;
+            if let Some(cache) = cache {
+                cache.insert(folder.as_str(), status.uid_validity, raw);
+            }
+            Ok(email)
+        })
+        .await
     }
 
-    /// Search emails using an arbitrary IMAP search query.
+    /// Fetch a single email by UID together with its flags.
+    ///
+    /// Both come back from one `UID FETCH (FLAGS BODY.PEEK[])`, so
+    /// this costs no more than [`Self::fetch_uid`].
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection, SELECT, or SEARCH fails.
-    pub async fn search(&self, folder: &Folder, query: &str) -> Result<Vec<Email>> {
-        let mut session = connection::connect(&self.config).await?;
-        connection::select(&mut session, folder.as_str()).await?;
+    /// Returns [`Error::MessageNotFound`] if the UID does not exist,
+    /// or an error if the connection, SELECT, FETCH, or parsing fails.
+    pub async fn fetch_uid_full(&self, folder: &Folder, uid: u32) -> Result<(Email, Vec<Flag>)> {
+        self.observe("fetch_uid_full", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
 
-        let uids = session
-            .uid_search(query)
-            .await
-            .map_err(|e| Error::Imap(format!("Search failed: {e}")))?;
+            let fetched = fetch::fetch_email_with_flags(&mut session, folder, uid).await?;
 
-        let uid_list: Vec<u32> = uids.into_iter().collect();
-        if uid_list.is_empty() {
             session.logout().await.ok();
-            return Ok(vec![]);
+            Ok(fetched)
+        })
+        .await
+    }
+
+    /// Fetch several messages by UID in one batch, keyed by UID.
+    ///
+    /// A requested UID missing from the map does not exist in
+    /// `folder`, or was skipped for being over
+    /// [`ImapConfig::max_message_size`](crate::ImapConfig::max_message_size)
+    /// or failing to parse (both logged). An empty `uids` slice
+    /// returns an empty map without connecting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn fetch_uids_map(
+        &self,
+        folder: &Folder,
+        uids: &[u32],
+    ) -> Result<HashMap<u32, Email>> {
+        if uids.is_empty() {
+            return Ok(HashMap::new());
         }
 
-        info!("Found {} messages matching '{}'", uid_list.len(), query);
+        self.observe_read("fetch_uids_map", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
 
-        let mut emails = Self::fetch_by_uids(&mut session, &uid_list).await?;
-        emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+            let emails =
+                fetch::fetch_emails(&mut session, folder, uids, self.config.max_message_size)
+                    .await?;
 
-        session.logout().await.ok();
-        Ok(emails)
+            session.logout().await.ok();
+            Ok(emails.into_iter().map(|email| (email.uid, email)).collect())
+        })
+        .await
     }
 
-    // -- private helpers (read) --
+    /// Fetch several messages by UID in one batch, reporting the ones
+    /// that were skipped.
+    ///
+    /// A message over
+    /// [`ImapConfig::max_message_size`](crate::ImapConfig::max_message_size)
+    /// or one that fails to parse does not fail the batch; its UID
+    /// lands in [`BatchResult::failed`] with the reason. A requested
+    /// UID that does not exist in `folder` is in neither list. An empty
+    /// `uids` slice returns an empty result without connecting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn fetch_by_uids(&self, folder: &Folder, uids: &[u32]) -> Result<BatchResult> {
+        if uids.is_empty() {
+            return Ok(BatchResult::default());
+        }
 
-    async fn fetch_by_uids(session: &mut ImapSession, uids: &[u32]) -> Result<Vec<Email>> {
-        let mut emails = Vec::new();
+        self.observe_read("fetch_by_uids", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
 
-        for uid in uids {
-            match Self::fetch_single(session, *uid).await {
-                Ok(email) => emails.push(email),
-                Err(e) => {
-                    warn!("Failed to fetch UID {}: {}", uid, e);
-                }
-            }
-        }
+            let batch =
+                fetch::fetch_batch(&mut session, folder, uids, self.config.max_message_size)
+                    .await?;
 
-        Ok(emails)
+            session.logout().await.ok();
+            Ok(batch)
+        })
+        .await
     }
 
-    async fn fetch_single(session: &mut ImapSession, uid: u32) -> Result<Email> {
-        let uid_set = format!("{uid}");
-        let mut messages = session
-            .uid_fetch(&uid_set, "(BODY.PEEK[])")
-            .await
-            .map_err(|e| Error::Imap(format!("Fetch failed: {e}")))?;
+    /// Fetch only the plain-text or only the HTML body of a message,
+    /// decoded to UTF-8.
+    ///
+    /// The part is located with `BODYSTRUCTURE`, so the other
+    /// alternative and any attachments are not downloaded. If the
+    /// message has no part of the preferred kind, the other kind is
+    /// returned; check [`BodyText::html`]. Returns `None` if it has
+    /// neither (e.g. a message that is only an attachment).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageNotFound`] if the UID does not exist,
+    /// or an error if the connection, SELECT, or FETCH fails.
+    pub async fn fetch_body_text(
+        &self,
+        folder: &Folder,
+        uid: u32,
+        preference: BodyPreference,
+    ) -> Result<Option<BodyText>> {
+        self.observe("fetch_body_text", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
 
-        if let Some(msg_result) = messages.next().await {
-            let msg = msg_result.map_err(|e| Error::Imap(format!("Fetch error: {e}")))?;
-            if let Some(body) = msg.body() {
-                return parse_email(uid, body).map_err(|e| Error::Parse(e.to_string()));
-            }
-        }
+            let structure = fetch::fetch_raw(&mut session, folder, uid, "(BODYSTRUCTURE)").await?;
+            let Some(part) = body::choose(&structure.text_parts, preference) else {
+                session.logout().await.ok();
+                return Ok(None);
+            };
+            let data = fetch::fetch_section_from(&mut session, uid, &part.part, 0)
+                .await?
+                .ok_or_else(|| Error::MessageNotFound {
+                    folder: folder.clone(),
+                    uid,
+                })?;
 
-        Err(Error::Imap(format!("No body found for UID {uid}")))
+            session.logout().await.ok();
+            Ok(Some(part.decode(&data)))
+        })
+        .await
     }
-}
 
-// ── Write operations (only on ReadWrite) ───────────────────────────
-
-impl ProtonClient<ReadWrite> {
-    /// Move an email from one folder to another.
+    /// Resume downloading a body section after `already_have` bytes.
     ///
-    /// Selects `from`, copies the message to `to`, marks it
-    /// `\Deleted` in the source folder, and expunges.
+    /// Requests `BODY.PEEK[<section>]<already_have.*>` so only the
+    /// missing tail is transferred, and appends it to `buf`. `section`
+    /// is an IMAP section spec: empty for the whole message, or e.g.
+    /// `TEXT`, `2`, `1.2.MIME`. Returns the number of bytes appended;
+    /// 0 means the section was already complete.
     ///
     /// # Errors
     ///
-    /// Returns an error if any IMAP command fails.
-    pub async fn move_to_folder(&self, uid: u32, from: &Folder, to: &Folder) -> Result<()> {
-        let mut session = connection::connect(&self.config).await?;
-        connection::select(&mut session, from.as_str()).await?;
+    /// Returns [`Error::MessageNotFound`] if the UID does not exist,
+    /// or an error if `section` is invalid or the connection, SELECT,
+    /// or FETCH fails. `buf` is left untouched on error.
+    pub async fn fetch_body_section_resumable(
+        &self,
+        folder: &Folder,
+        uid: u32,
+        section: &str,
+        already_have: usize,
+        buf: &mut Vec<u8>,
+    ) -> Result<usize> {
+        self.observe("fetch_body_section_resumable", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
 
-        let uid_set = format!("{uid}");
+            let rest = fetch::fetch_section_from(&mut session, uid, section, already_have).await?;
+            session.logout().await.ok();
 
-        // COPY to destination
-        session
-            .uid_copy(&uid_set, to.as_str())
-            .await
-            .map_err(|e| Error::Imap(format!("Copy failed: {e}")))?;
+            let rest = rest.ok_or_else(|| Error::MessageNotFound {
+                folder: folder.clone(),
+                uid,
+            })?;
+            buf.extend_from_slice(&rest);
+            Ok(rest.len())
+        })
+        .await
+    }
 
-        // Mark \Deleted in source
-        let mut store_stream = session
-            .uid_store(&uid_set, "+FLAGS (\\Deleted)")
+    /// Fetch all unseen emails from a folder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_unseen(&self, folder: &Folder) -> Result<Vec<Email>> {
+        self.observe_read("fetch_unseen", folder, self.run_search(folder, "UNSEEN"))
             .await
-            .map_err(|e| Error::Imap(format!("Store +Deleted failed: {e}")))?;
-        while store_stream.next().await.is_some() {}
-        drop(store_stream);
-
-        // Expunge to permanently remove
-        {
-            let expunge_stream = session
-                .expunge()
-                .await
-                .map_err(|e| Error::Imap(format!("Expunge failed: {e}")))?;
-            pin_mut!(expunge_stream);
-            while expunge_stream.next().await.is_some() {}
-        }
+    }
 
-        session.logout().await.ok();
-        Ok(())
+    /// Fetch all emails from a folder that have not been replied to
+    /// (no `\Answered` flag).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_unanswered(&self, folder: &Folder) -> Result<Vec<Email>> {
+        self.observe_read(
+            "fetch_unanswered",
+            folder,
+            self.run_search(folder, "UNANSWERED"),
+        )
+        .await
     }
 
-    /// Add a flag to an email.
+    /// Fetch all starred emails from a folder. Proton's star is the
+    /// `\Flagged` flag.
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection, SELECT, or STORE fails.
-    pub async fn add_flag(&self, uid: u32, folder: &Folder, flag: &Flag) -> Result<()> {
-        let mut session = connection::connect(&self.config).await?;
-        connection::select(&mut session, folder.as_str()).await?;
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_starred(&self, folder: &Folder) -> Result<Vec<Email>> {
+        self.observe_read("fetch_starred", folder, self.run_search(folder, "FLAGGED"))
+            .await
+    }
+
+    /// Fetch the oldest unseen email in a folder, for "jump to first
+    /// unread", without downloading the others.
+    ///
+    /// The message is found from the `[UNSEEN n]` code of the SELECT
+    /// response: its sequence number is turned into a UID with
+    /// `UID SEARCH n`. Servers that leave the code out are asked with
+    /// `UID SEARCH UNSEEN` instead, and the lowest UID is taken.
+    /// Returns `None` if every message has been read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or FETCH
+    /// fails.
+    pub async fn first_unseen(&self, folder: &Folder) -> Result<Option<Email>> {
+        self.observe("first_unseen", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            let status = connection::select_status(&mut session, folder.as_str()).await?;
+
+            let query = status
+                .first_unseen
+                .map_or_else(|| "UNSEEN".to_string(), |seq| seq.to_string());
+            let uids = connection::uid_search(&mut session, &query).await?;
+            let Some(uid) = uids.into_iter().min() else {
+                session.logout().await.ok();
+                return Ok(None);
+            };
+            let raw = fetch::fetch_raw(&mut session, folder, uid, "(BODY.PEEK[])").await?;
 
-        let uid_set = format!("{uid}");
-        let store_arg = format!("+FLAGS ({})", flag.as_imap_str());
+            session.logout().await.ok();
+            fetch::parse_fetch(&raw).map(Some)
+        })
+        .await
+    }
 
-        let mut stream = session
-            .uid_store(&uid_set, &store_arg)
+    /// Fetch all drafts (messages with the `\Draft` flag) from a
+    /// folder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_drafts(&self, folder: &Folder) -> Result<Vec<Email>> {
+        self.observe_read("fetch_drafts", folder, self.run_search(folder, "DRAFT"))
             .await
-            .map_err(|e| Error::Imap(format!("Store failed: {e}")))?;
-        while stream.next().await.is_some() {}
-        drop(stream);
+    }
 
-        session.logout().await.ok();
-        Ok(())
+    /// Fetch the drafts in the Drafts folder, resolved with
+    /// [`Self::drafts_folder`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if folder discovery, or the connection,
+    /// SELECT, or SEARCH fails.
+    pub async fn all_drafts(&self) -> Result<Vec<Email>> {
+        let drafts = self.drafts_folder().await?;
+        self.fetch_drafts(&drafts).await
     }
 
-    /// Remove a flag from an email.
+    /// Fetch unseen emails, skipping messages that are still
+    /// PGP-encrypted.
+    ///
+    /// The bridge cannot decrypt messages encrypted by external
+    /// senders; those are detected with [`crate::is_encrypted_raw`]
+    /// and [`crate::is_encrypted`] and left out of the result.
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection, SELECT, or STORE fails.
-    pub async fn remove_flag(&self, uid: u32, folder: &Folder, flag: &Flag) -> Result<()> {
-        let mut session = connection::connect(&self.config).await?;
-        connection::select(&mut session, folder.as_str()).await?;
+    /// Returns an error if the connection, SELECT, SEARCH, or FETCH
+    /// fails.
+    pub async fn fetch_unseen_decryptable(&self, folder: &Folder) -> Result<Vec<Email>> {
+        self.observe_read("fetch_unseen_decryptable", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
 
-        let uid_set = format!("{uid}");
-        let store_arg = format!("-FLAGS ({})", flag.as_imap_str());
+            let uids = session
+                .uid_search("UNSEEN")
+                .await
+                .map_err(|e| Error::imap("Search failed", &e))?;
+            let uid_list: Vec<u32> = uids.into_iter().collect();
+            let uid_list = fetch::within_size(
+                &mut session,
+                folder,
+                &uid_list,
+                self.config.max_message_size,
+            )
+            .await?;
 
-        let mut stream = session
-            .uid_store(&uid_set, &store_arg)
-            .await
-            .map_err(|e| Error::Imap(format!("Store failed: {e}")))?;
-        while stream.next().await.is_some() {}
-        drop(stream);
+            let fetches = fetch::fetch_items(&mut session, &uid_list, "(BODY.PEEK[])").await?;
 
-        session.logout().await.ok();
-        Ok(())
+            let mut emails = Vec::new();
+            for raw in &fetches {
+                if raw.body.as_deref().is_some_and(pgp::is_encrypted_raw) {
+                    info!("Skipping encrypted UID {} in {}", raw.uid, folder);
+                    continue;
+                }
+                match fetch::parse_fetch(raw) {
+                    Ok(email) if pgp::is_encrypted(&email) => {
+                        info!("Skipping encrypted UID {} in {}", raw.uid, folder);
+                    }
+                    Ok(email) => emails.push(email),
+                    Err(e) => {
+                        warn!("Failed to fetch UID {} from {}: {}", raw.uid, folder, e);
+                    }
+                }
+            }
+            emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+
+            session.logout().await.ok();
+            Ok(emails)
+        })
+        .await
     }
 
-    /// Archive an email by moving it to the Archive folder.
+    /// Fetch all unseen emails from a folder that were written by a
+    /// person.
+    ///
+    /// Auto-replies, bulk mail, and bounces (see
+    /// [`crate::is_auto_reply`]) are left out of the result.
     ///
     /// # Errors
     ///
-    /// Returns an error if the move operation fails.
-    pub async fn archive(&self, uid: u32, from: &Folder) -> Result<()> {
-        self.move_to_folder(uid, from, &Folder::Archive).await
+    /// Returns an error if the connection, SELECT, SEARCH, or FETCH
+    /// fails.
+    pub async fn fetch_unseen_human(&self, folder: &Folder) -> Result<Vec<Email>> {
+        self.observe_read("fetch_unseen_human", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uid_list = connection::uid_search(&mut session, "UNSEEN").await?;
+            let uid_list = fetch::within_size(
+                &mut session,
+                folder,
+                &uid_list,
+                self.config.max_message_size,
+            )
+            .await?;
+
+            let fetches = fetch::fetch_items(&mut session, &uid_list, "(BODY.PEEK[])").await?;
+
+            let mut emails = Vec::new();
+            for raw in &fetches {
+                match fetch::parse_fetch(raw) {
+                    Ok(email) if auto_reply::is_auto_reply(&email) => {
+                        info!("Skipping automated UID {} in {}", raw.uid, folder);
+                    }
+                    Ok(email) => emails.push(email),
+                    Err(e) => {
+                        warn!("Failed to fetch UID {} from {}: {}", raw.uid, folder, e);
+                    }
+                }
+            }
+            emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+
+            session.logout().await.ok();
+            Ok(emails)
+        })
+        .await
     }
 
-    /// Remove the `\Seen` flag from all messages in a folder.
+    /// Fetch the unseen emails of every folder, newest first: one
+    /// screen of everything new.
+    ///
+    /// Folders are searched concurrently, four at a time, each on its
+    /// own connection; `\Noselect` parents are skipped. A message that
+    /// sits in several folders (e.g. INBOX, a label, and All Mail) is
+    /// returned once, paired with the first folder it was found in
+    /// other than All Mail (see [`SpecialUse::All`]). Messages are
+    /// matched by `Message-ID`; those without one are never merged.
     ///
     /// # Errors
     ///
-    /// Returns an error if the connection, SELECT, SEARCH, or STORE
-    /// fails.
-    pub async fn unmark_all_read(&self, folder: &Folder) -> Result<()> {
+    /// Returns an error if the LIST, or the connection, SELECT,
+    /// SEARCH, or FETCH on any folder fails.
+    pub async fn unified_unseen(&self) -> Result<Vec<(Folder, Email)>> {
+        let all_mail = self.special_use_folder(SpecialUse::All).await?;
+        self.observe("unified_unseen", None, async {
+            let mut session = connection::connect(&self.config).await?;
+            let names = connection::list_selectable(&mut session).await?;
+            session.logout().await.ok();
+
+            let fetched: Vec<Vec<_>> = stream::iter(names)
+                .map(|name| self.unseen_with_ids(Folder::from(name)))
+                .buffered(UNIFIED_CONCURRENCY)
+                .try_collect()
+                .await?;
+
+            let messages = fetched.into_iter().flatten().collect();
+            let mut unified = dedup::dedup_by_message_id(messages, &all_mail);
+            unified.sort_by_key(|(_, email)| std::cmp::Reverse(email.date));
+            Ok(unified)
+        })
+        .await
+    }
+
+    /// The unseen emails of `folder`, each with its folder and
+    /// `Message-ID`.
+    async fn unseen_with_ids(
+        &self,
+        folder: Folder,
+    ) -> Result<Vec<(Folder, Option<String>, Email)>> {
         let mut session = connection::connect(&self.config).await?;
         connection::select(&mut session, folder.as_str()).await?;
 
-        let uids = session
-            .uid_search("SEEN")
-            .await
-            .map_err(|e| Error::Imap(format!("Search failed: {e}")))?;
+        let uids = connection::uid_search(&mut session, "UNSEEN").await?;
+        let uids =
+            fetch::within_size(&mut session, &folder, &uids, self.config.max_message_size).await?;
+        let fetches = fetch::fetch_items(&mut session, &uids, "(BODY.PEEK[])").await?;
+        session.logout().await.ok();
 
-        let uid_list: Vec<u32> = uids.into_iter().collect();
-        if uid_list.is_empty() {
-            session.logout().await.ok();
-            return Ok(());
+        let mut messages = Vec::with_capacity(fetches.len());
+        for raw in &fetches {
+            match fetch::parse_fetch(raw) {
+                Ok(email) => {
+                    let message_id = raw
+                        .body
+                        .as_deref()
+                        .and_then(|body| headers::header_value(body, "Message-ID"))
+                        .and_then(|value| headers::message_ids(&value).into_iter().next());
+                    messages.push((folder.clone(), message_id, email));
+                }
+                Err(e) => warn!("Failed to fetch UID {} from {}: {}", raw.uid, folder, e),
+            }
         }
+        Ok(messages)
+    }
 
-        let uid_set = uid_list
-            .iter()
-            .map(ToString::to_string)
-            .collect::<Vec<_>>()
-            .join(",");
-
-        let mut stream = session
-            .uid_store(&uid_set, "-FLAGS (\\Seen)")
+    /// Fetch all emails from a folder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_all(&self, folder: &Folder) -> Result<Vec<Email>> {
+        self.observe_read("fetch_all", folder, self.run_search(folder, "ALL"))
             .await
-            .map_err(|e| Error::Imap(format!("Store failed: {e}")))?;
-        while stream.next().await.is_some() {}
-        drop(stream);
+    }
+
+    /// Fetch the emails in a folder that have attachments, newest
+    /// first, each with its attachment list.
+    ///
+    /// A cheap `BODYSTRUCTURE` fetch over the whole folder picks the
+    /// messages first, so messages without attachments are never
+    /// downloaded. Parts marked `inline` (e.g. images embedded in HTML)
+    /// do not count.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or FETCH
+    /// fails.
+    pub async fn fetch_with_attachments(
+        &self,
+        folder: &Folder,
+    ) -> Result<Vec<(Email, Vec<Attachment>)>> {
+        self.observe_read("fetch_with_attachments", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uids = connection::uid_search(&mut session, "ALL").await?;
+            let structures = fetch::fetch_items(&mut session, &uids, "(BODYSTRUCTURE)").await?;
+            let mut attachments: HashMap<u32, Vec<Attachment>> = structures
+                .into_iter()
+                .filter(|raw| !raw.attachments.is_empty())
+                .map(|raw| (raw.uid, raw.attachments))
+                .collect();
+            info!(
+                "{} of {} messages in {} have attachments",
+                attachments.len(),
+                uids.len(),
+                folder
+            );
+
+            let uids: Vec<u32> = attachments.keys().copied().collect();
+            let emails =
+                fetch::fetch_emails(&mut session, folder, &uids, self.config.max_message_size)
+                    .await?;
+            let mut found: Vec<(Email, Vec<Attachment>)> = emails
+                .into_iter()
+                .map(|email| {
+                    let parts = attachments.remove(&email.uid).unwrap_or_default();
+                    (email, parts)
+                })
+                .collect();
+            found.sort_by_key(|(e, _)| std::cmp::Reverse(e.date));
+
+            session.logout().await.ok();
+            Ok(found)
+        })
+        .await
+    }
+
+    /// Fetch the N most recent emails from a folder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or
+    /// FETCH fails.
+    pub async fn fetch_last_n(&self, folder: &Folder, n: usize) -> Result<Vec<Email>> {
+        self.observe_read("fetch_last_n", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uids = session
+                .uid_search("ALL")
+                .await
+                .map_err(|e| Error::imap("Search failed", &e))?;
+
+            let mut uid_list: Vec<u32> = uids.into_iter().collect();
+            uid_list.sort_unstable();
+
+            let start = uid_list.len().saturating_sub(n);
+            let recent_uids = &uid_list[start..];
+
+            if recent_uids.is_empty() {
+                session.logout().await.ok();
+                return Ok(vec![]);
+            }
+
+            info!("Fetching {} most recent messages", recent_uids.len());
+
+            let mut emails = fetch::fetch_emails(
+                &mut session,
+                folder,
+                recent_uids,
+                self.config.max_message_size,
+            )
+            .await?;
+            emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+
+            session.logout().await.ok();
+            Ok(emails)
+        })
+        .await
+    }
+
+    /// Fetch emails that arrived within a date range from a folder.
+    ///
+    /// IMAP semantics: SINCE >= date, BEFORE < date, both compared
+    /// with the server's INTERNALDATE (arrival), not the `Date:`
+    /// header. The same as [`ProtonClient::fetch_arrival_range`]; use
+    /// [`ProtonClient::fetch_sent_range`] to go by the `Date:` header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_date_range(
+        &self,
+        folder: &Folder,
+        since: NaiveDate,
+        before: NaiveDate,
+    ) -> Result<Vec<Email>> {
+        let query = SearchQuery::new().since(since).before(before);
+        self.observe_read(
+            "fetch_date_range",
+            folder,
+            self.run_search(folder, &query.to_string()),
+        )
+        .await
+    }
+
+    /// Fetch emails whose `Date:` header falls in `[since, before)`
+    /// (`SENTSINCE` / `SENTBEFORE`).
+    ///
+    /// This is when the sender says the message was written, which
+    /// may be far from when it arrived (see
+    /// [`ProtonClient::fetch_arrival_range`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_sent_range(
+        &self,
+        folder: &Folder,
+        since: NaiveDate,
+        before: NaiveDate,
+    ) -> Result<Vec<Email>> {
+        let query = SearchQuery::new().sent_since(since).sent_before(before);
+        self.observe_read(
+            "fetch_sent_range",
+            folder,
+            self.run_search(folder, &query.to_string()),
+        )
+        .await
+    }
+
+    /// Fetch emails that arrived in `[since, before)` (`SINCE` /
+    /// `BEFORE`, which match the server's INTERNALDATE).
+    ///
+    /// Arrival time is set by the server, so it stays reliable for
+    /// mail with a missing or wrong `Date:` header, e.g. messages
+    /// migrated from another account.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_arrival_range(
+        &self,
+        folder: &Folder,
+        since: NaiveDate,
+        before: NaiveDate,
+    ) -> Result<Vec<Email>> {
+        let query = SearchQuery::new().since(since).before(before);
+        self.observe_read(
+            "fetch_arrival_range",
+            folder,
+            self.run_search(folder, &query.to_string()),
+        )
+        .await
+    }
+
+    /// Search emails using an arbitrary IMAP search query.
+    ///
+    /// [`SearchQuery`](crate::SearchQuery) builds the query string,
+    /// including `OR` combinations.
+    ///
+    /// Messages delivered while the matches are being fetched are
+    /// logged but not returned, unless
+    /// [`ImapConfig::with_follow_new`](crate::ImapConfig::with_follow_new)
+    /// is set; then the search is repeated for newer UIDs until no new
+    /// match turns up.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn search(&self, folder: &Folder, query: &str) -> Result<Vec<Email>> {
+        self.observe_read("search", folder, self.run_search(folder, query))
+            .await
+    }
+
+    /// [`Self::search`] without reporting a metrics event, for the
+    /// methods built on it that report under their own name.
+    async fn run_search(&self, folder: &Folder, query: &str) -> Result<Vec<Email>> {
+        let mut session = connection::connect(&self.config).await?;
+        let status = connection::select_status(&mut session, folder.as_str()).await?;
+
+        let mut uid_list = connection::uid_search(&mut session, query).await?;
+        if uid_list.is_empty() {
+            session.logout().await.ok();
+            return Ok(vec![]);
+        }
+
+        info!(
+            "Found {} messages matching '{}' ({} in {})",
+            uid_list.len(),
+            query,
+            status.exists,
+            folder
+        );
+
+        let mut emails = Vec::new();
+        while !uid_list.is_empty() {
+            emails.extend(
+                fetch::fetch_emails(
+                    &mut session,
+                    folder,
+                    &uid_list,
+                    self.config.max_message_size,
+                )
+                .await?,
+            );
+            if !self.config.follow_new {
+                break;
+            }
+
+            // `n:*` always matches the highest UID, even below n.
+            let next = uid_list.iter().max().map_or(1, |uid| uid + 1);
+            let criteria = connection::uid_range_query(&format!("{next}:*"), query);
+            uid_list = connection::uid_search(&mut session, &criteria).await?;
+            uid_list.retain(|&uid| uid >= next);
+            if !uid_list.is_empty() {
+                info!("Following {} new messages in {}", uid_list.len(), folder);
+            }
+        }
+        emails.sort_by_key(|e| std::cmp::Reverse(e.date));
 
         session.logout().await.ok();
-        Ok(())
+        Ok(emails)
+    }
+
+    /// Fetch the messages of a Proton conversation in `folder`, oldest
+    /// first.
+    ///
+    /// Matches on the `X-Pm-ConversationID` header the bridge adds to
+    /// every message (see [`conversation_id`](crate::conversation_id)),
+    /// which is exact where `References` threading is not.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_conversation(
+        &self,
+        folder: &Folder,
+        conversation_id: &str,
+    ) -> Result<Vec<Email>> {
+        let query = SearchQuery::new().header(CONVERSATION_ID_HEADER, conversation_id);
+        let mut emails = self
+            .observe_read(
+                "fetch_conversation",
+                folder,
+                self.run_search(folder, &query.to_string()),
+            )
+            .await?;
+        emails.reverse();
+        Ok(emails)
+    }
+
+    /// Fetch the messages in `folder` that have header `name`,
+    /// e.g. `List-Id`, `X-Mailer`, or `Authentication-Results`.
+    ///
+    /// With a `value`, only messages whose header contains it
+    /// (ignoring case) match; without one, having the header at all
+    /// is enough (`HEADER <name> ""`, RFC 3501 Section 6.4.4).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn fetch_with_header(
+        &self,
+        folder: &Folder,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<Vec<Email>> {
+        let query = SearchQuery::new().header(name, value.unwrap_or_default());
+        self.observe_read(
+            "fetch_with_header",
+            folder,
+            self.run_search(folder, &query.to_string()),
+        )
+        .await
+    }
+
+    /// Fetch the delivery path of a message: its `Received:` headers,
+    /// newest first (see [`delivery_path`](crate::delivery_path)).
+    ///
+    /// Only the header block is fetched, with `BODY.PEEK[HEADER]`,
+    /// which keeps repeated fields and their order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageNotFound`] if the UID does not exist,
+    /// or an error if the connection, SELECT, or FETCH fails.
+    pub async fn fetch_delivery_path(&self, folder: &Folder, uid: u32) -> Result<Vec<String>> {
+        self.observe("fetch_delivery_path", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let raw = fetch::fetch_raw(&mut session, folder, uid, "(BODY.PEEK[HEADER])").await?;
+
+            session.logout().await.ok();
+            Ok(raw.delivery_path())
+        })
+        .await
+    }
+
+    /// Fetch the thread the message `uid` belongs to, oldest first,
+    /// with each message's depth in the reply chain.
+    ///
+    /// The thread is the message's Proton conversation when it has an
+    /// `X-Pm-ConversationID`, and otherwise every message that shares
+    /// its `References` root. Only `folder` is searched, so replies
+    /// filed elsewhere (e.g. in Sent) are not included. Messages over
+    /// [`ImapConfig::max_message_size`] are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageNotFound`] if the UID does not exist,
+    /// or an error if the connection, SELECT, SEARCH, or FETCH fails.
+    pub async fn fetch_thread(&self, folder: &Folder, uid: u32) -> Result<Vec<ThreadMessage>> {
+        self.observe("fetch_thread", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let target = fetch::fetch_raw(&mut session, folder, uid, thread::THREAD_FIELDS).await?;
+            let mut uids = match thread::thread_query(&target) {
+                Some(query) => connection::uid_search(&mut session, &query.to_string()).await?,
+                None => Vec::new(),
+            };
+            if !uids.contains(&uid) {
+                uids.push(uid);
+            }
+            let uids =
+                fetch::within_size(&mut session, folder, &uids, self.config.max_message_size)
+                    .await?;
+            let fetches = fetch::fetch_items(&mut session, &uids, "(BODY.PEEK[])").await?;
+            session.logout().await.ok();
+
+            let mut messages = Vec::new();
+            for raw in &fetches {
+                match fetch::parse_fetch(raw) {
+                    Ok(email) => messages.push(ThreadMessage {
+                        depth: raw.reply_depth(),
+                        email,
+                    }),
+                    Err(e) => warn!("Failed to fetch UID {} from {}: {}", raw.uid, folder, e),
+                }
+            }
+            messages.sort_by_key(|m| (m.email.date, m.email.uid));
+            Ok(messages)
+        })
+        .await
+    }
+
+    /// Search only the messages with UIDs from `start_uid` to
+    /// `end_uid` (inclusive), or to the newest message if `end_uid`
+    /// is `None`.
+    ///
+    /// The range is sent as a `UID start:end` key alongside `query`,
+    /// so the server only looks at that window. Meant for incremental
+    /// tools, e.g. "unseen since UID X" is
+    /// `search_in_range(folder, x, None, "UNSEEN")`. An empty `query`
+    /// matches every message in the range. Results are newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or FETCH
+    /// fails.
+    pub async fn search_in_range(
+        &self,
+        folder: &Folder,
+        start_uid: u32,
+        end_uid: Option<u32>,
+        query: &str,
+    ) -> Result<Vec<Email>> {
+        self.observe_read("search_in_range", folder, async {
+            let (low, high) = end_uid.map_or((start_uid, None), |end| {
+                (start_uid.min(end), Some(start_uid.max(end)))
+            });
+            let range = high.map_or_else(|| format!("{low}:*"), |high| format!("{low}:{high}"));
+            let criteria = connection::uid_range_query(&range, query);
+
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let mut uid_list = connection::uid_search(&mut session, &criteria).await?;
+            // `n:*` always matches the highest UID, even below n.
+            uid_list.retain(|&uid| uid >= low);
+            info!(
+                "Found {} messages matching '{}' in {}",
+                uid_list.len(),
+                criteria,
+                folder
+            );
+
+            let mut emails = if uid_list.is_empty() {
+                Vec::new()
+            } else {
+                fetch::fetch_emails(
+                    &mut session,
+                    folder,
+                    &uid_list,
+                    self.config.max_message_size,
+                )
+                .await?
+            };
+            emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+
+            session.logout().await.ok();
+            Ok(emails)
+        })
+        .await
+    }
+
+    /// Search a folder and return the matching UIDs in chunks of at
+    /// most `chunk`, newest first.
+    ///
+    /// Meant for large result sets: the caller can start fetching the
+    /// first chunk (e.g. with [`Self::fetch_items`]) instead of
+    /// waiting for every match to be fetched as with [`Self::search`].
+    /// A `chunk` of 0 is treated as 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or SEARCH fails.
+    pub async fn search_uids_chunked(
+        &self,
+        folder: &Folder,
+        query: &str,
+        chunk: usize,
+    ) -> Result<std::vec::IntoIter<Vec<u32>>> {
+        self.observe_read("search_uids_chunked", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let mut uid_list = connection::uid_search(&mut session, query).await?;
+            session.logout().await.ok();
+
+            uid_list.sort_unstable_by(|a, b| b.cmp(a));
+            info!("Found {} messages matching '{}'", uid_list.len(), query);
+
+            let mut chunks = Vec::with_capacity(uid_list.len().div_ceil(chunk.max(1)));
+            chunks.extend(uid_list.chunks(chunk.max(1)).map(<[u32]>::to_vec));
+            Ok(chunks.into_iter())
+        })
+        .await
+    }
+
+    /// Fetch arbitrary FETCH data items for a set of UIDs.
+    ///
+    /// `items` is sent verbatim as the FETCH item list, e.g.
+    /// `"(FLAGS INTERNALDATE RFC822.SIZE)"` or
+    /// `"(BODY.PEEK[HEADER])"`. Items that were not requested are
+    /// left empty on the returned [`RawFetch`] values, and UIDs that
+    /// do not exist are absent from the result.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn fetch_items(
+        &self,
+        folder: &Folder,
+        uids: &[u32],
+        items: &str,
+    ) -> Result<Vec<RawFetch>> {
+        self.observe_read("fetch_items", folder, async {
+            if uids.is_empty() {
+                return Ok(vec![]);
+            }
+
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let fetches = fetch::fetch_items(&mut session, uids, items).await?;
+
+            session.logout().await.ok();
+            Ok(fetches)
+        })
+        .await
+    }
+
+    /// The flags of every message in a folder, by UID.
+    ///
+    /// Uses a single `UID FETCH 1:* (FLAGS)`. Keep the result to pass
+    /// to [`Self::flag_delta_since`] later.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn flag_snapshot(&self, folder: &Folder) -> Result<HashMap<u32, Vec<Flag>>> {
+        self.observe_read("flag_snapshot", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            let status = connection::select_status(&mut session, folder.as_str()).await?;
+
+            let snapshot = fetch::flag_snapshot(&mut session, status.exists).await?;
+
+            session.logout().await.ok();
+            Ok(snapshot)
+        })
+        .await
+    }
+
+    /// Fetch the current flags of a folder and compare them with a
+    /// `previous` snapshot, e.g. to pick up messages read or starred
+    /// on another device without a full resync.
+    ///
+    /// Uses a single `UID FETCH 1:* (FLAGS)`. `previous` is the result
+    /// of [`Self::flag_snapshot`] or the [`FlagDelta::snapshot`] of
+    /// an earlier call. A snapshot is only meaningful while the
+    /// folder's UIDVALIDITY is unchanged (see [`Self::refresh`]); after
+    /// a change, take a fresh one instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn flag_delta_since(
+        &self,
+        folder: &Folder,
+        previous: &HashMap<u32, Vec<Flag>>,
+    ) -> Result<FlagDelta> {
+        self.observe("flag_delta_since", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            let status = connection::select_status(&mut session, folder.as_str()).await?;
+
+            let current = fetch::flag_snapshot(&mut session, status.exists).await?;
+
+            session.logout().await.ok();
+            Ok(FlagDelta::between(previous, current))
+        })
+        .await
+    }
+
+    /// Fetch a set of UIDs with one of the FETCH macros, e.g.
+    /// [`FetchMacro::All`] for flags, arrival time, size, and
+    /// [`RawFetch::envelope`].
+    ///
+    /// UIDs that do not exist are absent from the result. An empty
+    /// `uids` slice returns an empty list without connecting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn fetch_macro(
+        &self,
+        folder: &Folder,
+        uids: &[u32],
+        items: FetchMacro,
+    ) -> Result<Vec<RawFetch>> {
+        if uids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.observe_read("fetch_macro", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let fetches = fetch::fetch_items(&mut session, uids, items.as_imap_str()).await?;
+
+            session.logout().await.ok();
+            Ok(fetches)
+        })
+        .await
+    }
+
+    /// Fetch the flags, arrival time, size, and header section of a
+    /// set of UIDs, for listing messages without downloading bodies.
+    ///
+    /// With [`Self::with_prefetch`], the bodies of the highest listed
+    /// UIDs are then fetched into the cache in the background, on a
+    /// separate connection, so opening them with [`Self::fetch_uid`]
+    /// is immediate. Prefetch failures are logged.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn fetch_headers(&self, folder: &Folder, uids: &[u32]) -> Result<Vec<RawFetch>> {
+        let fetches = self
+            .observe_read("fetch_headers", folder, async {
+                if uids.is_empty() {
+                    return Ok(vec![]);
+                }
+
+                let mut session = connection::connect(&self.config).await?;
+                let status = connection::select_status(&mut session, folder.as_str()).await?;
+                if let Some(cache) = &self.cache {
+                    cache.observe(folder.as_str(), status.uid_validity);
+                }
+
+                let fetches = fetch::fetch_items(
+                    &mut session,
+                    uids,
+                    "(FLAGS INTERNALDATE RFC822.SIZE BODY.PEEK[HEADER])",
+                )
+                .await?;
+
+                session.logout().await.ok();
+                Ok(fetches)
+            })
+            .await?;
+
+        self.spawn_prefetch(folder, &fetches);
+        Ok(fetches)
+    }
+
+    /// The `limit` most recent messages of a folder as message-list
+    /// rows, newest (highest UID) first.
+    ///
+    /// Everything comes from a single `UID FETCH` of
+    /// [`ListItem`]'s fields; no part of any message is downloaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or FETCH
+    /// fails.
+    pub async fn list_view(&self, folder: &Folder, limit: usize) -> Result<Vec<ListItem>> {
+        self.observe_read("list_view", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let mut uids = connection::uid_search(&mut session, "ALL").await?;
+            uids.sort_unstable();
+            let window = &uids[uids.len().saturating_sub(limit)..];
+
+            let fetches = fetch::fetch_items(&mut session, window, LIST_FIELDS).await?;
+
+            session.logout().await.ok();
+            let mut items: Vec<ListItem> = fetches.into_iter().map(ListItem::from_fetch).collect();
+            items.sort_by_key(|item| std::cmp::Reverse(item.uid));
+            Ok(items)
+        })
+        .await
+    }
+
+    /// Fetch the bodies of `uids` into the cache, skipping those
+    /// already cached, and return how many were fetched.
+    ///
+    /// Does nothing without [`Self::with_cache`]. Messages over
+    /// [`ImapConfig::with_max_message_size`] are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn prefetch(&self, folder: &Folder, uids: &[u32]) -> Result<usize> {
+        let Some(cache) = &self.cache else {
+            return Ok(0);
+        };
+        self.observe(
+            "prefetch",
+            Some(folder),
+            cache::prefetch(&self.config, cache, folder, uids),
+        )
+        .await
+    }
+
+    /// Start prefetching the bodies of the [`Self::with_prefetch`]
+    /// highest UIDs in `listed`.
+    fn spawn_prefetch(&self, folder: &Folder, listed: &[RawFetch]) {
+        let Some(cache) = self.cache.clone() else {
+            return;
+        };
+        let mut uids: Vec<u32> = listed.iter().map(|raw| raw.uid).collect();
+        uids.sort_unstable_by(|a, b| b.cmp(a));
+        uids.truncate(self.prefetch);
+        if uids.is_empty() {
+            return;
+        }
+
+        let config = self.config.clone();
+        let folder = folder.clone();
+        self.tasks().spawn_prefetch(async move {
+            if let Err(e) = cache::prefetch(&config, &cache, &folder, &uids).await {
+                warn!(
+                    "Prefetching {} bodies from {} failed: {}",
+                    uids.len(),
+                    folder,
+                    e
+                );
+            }
+        });
+    }
+
+    /// Drop `uids` in `folder` from the cache before they are moved or
+    /// deleted. Evicting up front means a failed move only costs a
+    /// later fetch, while a stale entry would serve a message that is
+    /// gone.
+    fn evict(&self, folder: &Folder, uids: &[u32]) {
+        if let Some(cache) = &self.cache {
+            cache.remove(folder.as_str(), uids);
+        }
+    }
+
+    /// Fetch the threading headers (`Message-ID`, `In-Reply-To`,
+    /// `References`) for a set of UIDs.
+    ///
+    /// Use [`RawFetch::references`] and [`RawFetch::reply_depth`] on
+    /// the result to inspect reply chains without downloading bodies.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn fetch_thread_headers(
+        &self,
+        folder: &Folder,
+        uids: &[u32],
+    ) -> Result<Vec<RawFetch>> {
+        self.fetch_items(
+            folder,
+            uids,
+            "(BODY.PEEK[HEADER.FIELDS (MESSAGE-ID IN-REPLY-TO REFERENCES)])",
+        )
+        .await
+    }
+
+    /// Read a message's unsubscribe links from its `List-Unsubscribe`
+    /// and `List-Unsubscribe-Post` headers.
+    ///
+    /// Only those two header fields are fetched. Returns `None` if the
+    /// message advertises no usable unsubscribe target.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageNotFound`] if no message with `uid`
+    /// exists in `folder`, or another error if the connection,
+    /// SELECT, or FETCH fails.
+    pub async fn fetch_unsubscribe_info(
+        &self,
+        folder: &Folder,
+        uid: u32,
+    ) -> Result<Option<UnsubscribeInfo>> {
+        self.observe("fetch_unsubscribe_info", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let fetches = fetch::fetch_items(
+                &mut session,
+                &[uid],
+                "(BODY.PEEK[HEADER.FIELDS (LIST-UNSUBSCRIBE LIST-UNSUBSCRIBE-POST)])",
+            )
+            .await?;
+            session.logout().await.ok();
+
+            let Some(raw) = fetches.first() else {
+                return Err(Error::MessageNotFound {
+                    folder: folder.clone(),
+                    uid,
+                });
+            };
+            Ok(raw.unsubscribe_info())
+        })
+        .await
+    }
+
+    /// Write every message in `folder` to `writer` as an mbox
+    /// (`mboxrd`) stream and return how many were written.
+    ///
+    /// Bodies are fetched and written a chunk at a time, so memory use
+    /// does not grow with the folder. [`FetchOrder::OldestFirst`]
+    /// sorts by the `Date:` header, which costs an upfront FETCH of
+    /// that field for every message before the first body arrives.
+    /// Messages over
+    /// [`ImapConfig::with_max_message_size`](crate::ImapConfig::with_max_message_size)
+    /// are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or FETCH
+    /// fails, or if writing to `writer` fails. Messages written before
+    /// the error stay written.
+    pub async fn export_mbox<W: AsyncWrite + Unpin + Send>(
+        &self,
+        folder: &Folder,
+        order: FetchOrder,
+        mut writer: W,
+    ) -> Result<usize> {
+        self.observe("export_mbox", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uids = export::ordered_uids(&mut session, order).await?;
+            info!("Exporting {} messages from {}", uids.len(), folder);
+
+            let mut written = 0;
+            for chunk in uids.chunks(EXPORT_CHUNK) {
+                let kept =
+                    fetch::within_size(&mut session, folder, chunk, self.config.max_message_size)
+                        .await?;
+                let chunk: Vec<u32> = chunk
+                    .iter()
+                    .copied()
+                    .filter(|uid| kept.contains(uid))
+                    .collect();
+                let fetches =
+                    export::fetch_in_order(&mut session, &chunk, "(INTERNALDATE BODY.PEEK[])")
+                        .await?;
+                for raw in &fetches {
+                    export::write_mbox_message(&mut writer, raw).await?;
+                }
+                written += fetches.len();
+            }
+            writer.flush().await?;
+
+            session.logout().await.ok();
+            Ok(written)
+        })
+        .await
+    }
+
+    /// Write every message in `folder` to `writer` as a tar archive
+    /// and return how many were written.
+    ///
+    /// Each message becomes a `<uid>.eml` entry dated with its arrival
+    /// time, in ascending UID order. A final `manifest.json` entry
+    /// lists the UID, flags, and arrival time (RFC 3339) of each
+    /// message, so flags survive a restore. Bodies are fetched and
+    /// written a chunk at a time as in [`Self::export_mbox`]; only the
+    /// manifest is kept until the end. Messages over
+    /// [`ImapConfig::with_max_message_size`](crate::ImapConfig::with_max_message_size)
+    /// are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or FETCH
+    /// fails, or if writing to `writer` fails. The archive is then
+    /// left without its manifest and end marker.
+    pub async fn export_tar<W: AsyncWrite + Unpin + Send>(
+        &self,
+        folder: &Folder,
+        mut writer: W,
+    ) -> Result<usize> {
+        self.observe("export_tar", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uids = export::ordered_uids(&mut session, FetchOrder::Uid).await?;
+            info!("Exporting {} messages from {}", uids.len(), folder);
+
+            let mut manifest = Vec::with_capacity(uids.len());
+            for chunk in uids.chunks(EXPORT_CHUNK) {
+                let kept =
+                    fetch::within_size(&mut session, folder, chunk, self.config.max_message_size)
+                        .await?;
+                let chunk: Vec<u32> = chunk
+                    .iter()
+                    .copied()
+                    .filter(|uid| kept.contains(uid))
+                    .collect();
+                let fetches = export::fetch_in_order(
+                    &mut session,
+                    &chunk,
+                    "(FLAGS INTERNALDATE BODY.PEEK[])",
+                )
+                .await?;
+                for raw in &fetches {
+                    export::write_tar_message(&mut writer, raw).await?;
+                    manifest.push(ManifestEntry::new(raw));
+                }
+            }
+            export::finish_tar(&mut writer, &manifest).await?;
+            writer.flush().await?;
+
+            session.logout().await.ok();
+            Ok(manifest.len())
+        })
+        .await
+    }
+
+    /// One date per message in a folder, for per-day histograms.
+    ///
+    /// Only `INTERNALDATE` and the `Date:` header are fetched, never
+    /// bodies. The server arrival time is preferred; the `Date:`
+    /// header is the fallback. Each date is taken in the timezone the
+    /// timestamp was recorded in. Messages with neither are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or FETCH
+    /// fails.
+    pub async fn message_dates(&self, folder: &Folder) -> Result<Vec<NaiveDate>> {
+        self.observe_read("message_dates", folder, async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uids = session
+                .uid_search("ALL")
+                .await
+                .map_err(|e| Error::imap("Search failed", &e))?;
+            let mut uid_list: Vec<u32> = uids.into_iter().collect();
+            uid_list.sort_unstable();
+
+            let fetches = fetch::fetch_items(
+                &mut session,
+                &uid_list,
+                "(INTERNALDATE BODY.PEEK[HEADER.FIELDS (DATE)])",
+            )
+            .await?;
+
+            let dates = fetches
+                .iter()
+                .filter_map(|raw| {
+                    let date = raw
+                        .internal_date
+                        .or_else(|| raw.header.as_deref().and_then(headers::date));
+                    if date.is_none() {
+                        warn!("No date for UID {} in {}", raw.uid, folder);
+                    }
+                    date.map(|d| d.date_naive())
+                })
+                .collect();
+
+            session.logout().await.ok();
+            Ok(dates)
+        })
+        .await
+    }
+
+    /// List the Proton labels applied to a message.
+    ///
+    /// Labels are the message's keyword flags, excluding reserved
+    /// `$`-prefixed keywords (see [`Flag::label_name`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageNotFound`] if no message with `uid`
+    /// exists in `folder`, or another error if the connection,
+    /// SELECT, or FETCH fails.
+    pub async fn labels(&self, folder: &Folder, uid: u32) -> Result<Vec<String>> {
+        self.observe("labels", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let fetches = fetch::fetch_items(&mut session, &[uid], "(FLAGS)").await?;
+            session.logout().await.ok();
+
+            let Some(raw) = fetches.first() else {
+                return Err(Error::MessageNotFound {
+                    folder: folder.clone(),
+                    uid,
+                });
+            };
+
+            Ok(raw
+                .flags
+                .iter()
+                .filter_map(Flag::label_name)
+                .map(ToString::to_string)
+                .collect())
+        })
+        .await
+    }
+}
+
+// ── Write operations (only on ReadWrite) ───────────────────────────
+
+impl ProtonClient<ReadWrite> {
+    /// Move an email from one folder to another.
+    ///
+    /// Selects `from`, copies the message to `to`, marks it
+    /// `\Deleted` in the source folder, and expunges.
+    ///
+    /// Retrying is safe: if `to` already holds a message with the same
+    /// `Message-ID` (e.g. the COPY of an earlier attempt went through
+    /// but the cleanup did not), nothing is copied, the message is
+    /// only removed from `from`, and [`MoveOutcome::AlreadyInDestination`]
+    /// is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any IMAP command fails.
+    pub async fn move_to_folder(
+        &self,
+        uid: u32,
+        from: &Folder,
+        to: &Folder,
+    ) -> Result<MoveOutcome> {
+        self.evict(from, &[uid]);
+        self.observe("move_to_folder", Some(from), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, from.as_str()).await?;
+
+            let uid_set = uid.to_string();
+            let outcome = if copied_to(&mut session, uid, from, to).await? {
+                info!(
+                    "UID {} in {} is already in {}, removing it from {}",
+                    uid, from, to, from
+                );
+                connection::delete_messages(&mut session, &uid_set).await?;
+                MoveOutcome::AlreadyInDestination
+            } else {
+                connection::move_messages(&mut session, &uid_set, to).await?;
+                MoveOutcome::Moved
+            };
+
+            session.logout().await.ok();
+            Ok(outcome)
+        })
+        .await
+    }
+
+    /// Like [`ProtonClient::move_to_folder`], but creates `to` if it
+    /// does not exist yet.
+    ///
+    /// The move is tried first; only when the server answers
+    /// `NO [TRYCREATE]` is `to` created and the move retried, so an
+    /// existing folder costs nothing extra.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any IMAP command fails, including the
+    /// CREATE.
+    pub async fn move_to_folder_creating(
+        &self,
+        uid: u32,
+        from: &Folder,
+        to: &Folder,
+    ) -> Result<MoveOutcome> {
+        match self.move_to_folder(uid, from, to).await {
+            Err(e) if e.response_code() == Some(&ImapResponseCode::TryCreate) => {
+                info!("Creating {} to move UID {} into it", to, uid);
+                self.observe("move_to_folder_creating", Some(to), async {
+                    let mut session = connection::connect(&self.config).await?;
+                    connection::create(&mut session, to.as_str()).await?;
+                    session.logout().await.ok();
+                    Ok(())
+                })
+                .await?;
+                self.move_to_folder(uid, from, to).await
+            }
+            result => result,
+        }
+    }
+
+    /// Move several emails from one folder to another in one batch.
+    ///
+    /// All UIDs go into a single COPY / STORE / EXPUNGE sequence on
+    /// one connection. An empty `uids` slice is a no-op.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any IMAP command fails.
+    pub async fn move_uids(&self, uids: &[u32], from: &Folder, to: &Folder) -> Result<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+
+        self.evict(from, uids);
+        self.observe("move_uids", Some(from), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, from.as_str()).await?;
+
+            connection::move_messages(&mut session, &fetch::uid_set_compact(uids), to).await?;
+
+            session.logout().await.ok();
+            Ok(())
+        })
+        .await
+    }
+
+    /// Move every email in `folder` matching an IMAP search query to
+    /// `to`, in one session. Returns the number of emails moved.
+    ///
+    /// The matches are moved in a single COPY / STORE / EXPUNGE batch.
+    /// When nothing matches, no COPY or EXPUNGE is issued and 0 is
+    /// returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or any
+    /// move command fails.
+    pub async fn move_matching(&self, folder: &Folder, query: &str, to: &Folder) -> Result<usize> {
+        self.observe("move_matching", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uids = connection::uid_search(&mut session, query).await?;
+            if !uids.is_empty() {
+                info!(
+                    "Moving {} messages matching '{}' from {} to {}",
+                    uids.len(),
+                    query,
+                    folder,
+                    to
+                );
+                self.evict(folder, &uids);
+                connection::move_messages(&mut session, &fetch::uid_set_compact(&uids), to).await?;
+            }
+
+            session.logout().await.ok();
+            Ok(uids.len())
+        })
+        .await
+    }
+
+    /// Apply a filter rule to one message in a single session: add
+    /// `set_flags`, then move the message to `move_to` if given.
+    ///
+    /// Applying rules to many messages this way costs one connection
+    /// per message instead of one per step.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, STORE, or move
+    /// fails. Flags stored before a failed move stay set.
+    pub async fn apply_rule(
+        &self,
+        uid: u32,
+        folder: &Folder,
+        set_flags: &[Flag],
+        move_to: Option<&Folder>,
+    ) -> Result<RuleOutcome> {
+        self.observe("apply_rule", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uid_set = uid.to_string();
+            if !set_flags.is_empty() {
+                let names: Vec<&str> = set_flags.iter().map(Flag::as_imap_str).collect();
+                let store_arg = format!("+FLAGS ({})", names.join(" "));
+                connection::store(&mut session, &uid_set, &store_arg).await?;
+            }
+
+            if let Some(to) = move_to {
+                self.evict(folder, &[uid]);
+                connection::move_messages(&mut session, &uid_set, to).await?;
+            }
+
+            session.logout().await.ok();
+            Ok(RuleOutcome {
+                flags_set: set_flags.to_vec(),
+                moved_to: move_to.cloned(),
+            })
+        })
+        .await
+    }
+
+    /// Fetch a single email by UID and mark it as read in the same
+    /// command.
+    ///
+    /// The message is fetched with `BODY[]` instead of
+    /// `BODY.PEEK[]`, so the server sets `\Seen` itself; no separate
+    /// STORE is needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageNotFound`] if no message with `uid`
+    /// exists in `folder`, or another error if the connection,
+    /// SELECT, or FETCH fails or the message cannot be parsed.
+    pub async fn fetch_and_mark_read(&self, folder: &Folder, uid: u32) -> Result<Email> {
+        self.observe("fetch_and_mark_read", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let email = fetch::fetch_email_marking_read(&mut session, folder, uid).await?;
+
+            session.logout().await.ok();
+            Ok(email)
+        })
+        .await
+    }
+
+    /// Fetch emails by UID and mark them all as read in the same
+    /// command, returned in ascending UID order.
+    ///
+    /// Like [`Self::fetch_and_mark_read`], but for a batch: one
+    /// `UID FETCH` with `BODY[]` makes the server set `\Seen` on every
+    /// message it returns. UIDs that do not exist are left out.
+    /// Messages over
+    /// [`ImapConfig::with_max_message_size`](crate::ImapConfig::with_max_message_size)
+    /// are neither returned nor marked read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or FETCH fails.
+    pub async fn fetch_uids_marking_read(
+        &self,
+        folder: &Folder,
+        uids: &[u32],
+    ) -> Result<Vec<Email>> {
+        if uids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.observe("fetch_uids_marking_read", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let mut emails = fetch::fetch_emails_marking_read(
+                &mut session,
+                folder,
+                uids,
+                self.config.max_message_size,
+            )
+            .await?;
+            emails.sort_by_key(|e| e.uid);
+
+            session.logout().await.ok();
+            Ok(emails)
+        })
+        .await
+    }
+
+    /// Add a flag to an email.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or STORE fails.
+    pub async fn add_flag(&self, uid: u32, folder: &Folder, flag: &Flag) -> Result<()> {
+        self.observe("add_flag", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uid_set = format!("{uid}");
+            let store_arg = format!("+FLAGS ({})", flag.as_imap_str());
+
+            connection::store(&mut session, &uid_set, &store_arg).await?;
+
+            session.logout().await.ok();
+            Ok(())
+        })
+        .await
+    }
+
+    /// Remove a flag from an email.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or STORE fails.
+    pub async fn remove_flag(&self, uid: u32, folder: &Folder, flag: &Flag) -> Result<()> {
+        self.observe("remove_flag", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uid_set = format!("{uid}");
+            let store_arg = format!("-FLAGS ({})", flag.as_imap_str());
+
+            connection::store(&mut session, &uid_set, &store_arg).await?;
+
+            session.logout().await.ok();
+            Ok(())
+        })
+        .await
+    }
+
+    /// Star an email (add `\Flagged`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or STORE fails.
+    pub async fn star(&self, uid: u32, folder: &Folder) -> Result<()> {
+        self.add_flag(uid, folder, &Flag::Flagged).await
+    }
+
+    /// Unstar an email (remove `\Flagged`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or STORE fails.
+    pub async fn unstar(&self, uid: u32, folder: &Folder) -> Result<()> {
+        self.remove_flag(uid, folder, &Flag::Flagged).await
+    }
+
+    /// Mark an email as replied to (add `\Answered`), e.g. after
+    /// sending the reply over SMTP, so it shows as answered and drops
+    /// out of [`Self::fetch_unanswered`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, or STORE fails.
+    pub async fn mark_answered(&self, uid: u32, folder: &Folder) -> Result<()> {
+        self.add_flag(uid, folder, &Flag::Answered).await
+    }
+
+    /// Apply a Proton label to an email.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidLabel`] if `label` cannot be sent as an
+    /// IMAP keyword, or another error if the STORE fails.
+    pub async fn add_label(&self, uid: u32, folder: &Folder, label: &str) -> Result<()> {
+        self.add_flag(uid, folder, &label_flag(label)?).await
+    }
+
+    /// Remove a Proton label from an email.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidLabel`] if `label` cannot be sent as an
+    /// IMAP keyword, or another error if the STORE fails.
+    pub async fn remove_label(&self, uid: u32, folder: &Folder, label: &str) -> Result<()> {
+        self.remove_flag(uid, folder, &label_flag(label)?).await
+    }
+
+    /// Append a raw RFC 2822 message to a folder.
+    ///
+    /// `flags` are set on the new message; pass an empty slice for
+    /// none. The folder does not need to be selected.
+    ///
+    /// Each call opens its own connection and uses a synchronizing
+    /// literal. For bulk imports, use [`ProtonSession::append`], which
+    /// reuses the connection and skips the continuation round trip
+    /// when the server supports LITERAL+.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection or APPEND fails (e.g. the
+    /// folder does not exist).
+    pub async fn append(&self, folder: &Folder, raw: &[u8], flags: &[Flag]) -> Result<()> {
+        self.observe("append", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::append(&mut session, folder.as_str(), flags, raw, false).await?;
+
+            session.logout().await.ok();
+            Ok(())
+        })
+        .await
+    }
+
+    /// Stage a draft: append `raw` with `\Draft` and `\Seen` set.
+    ///
+    /// Drafts are the user's own messages, so they are stored as read.
+    /// Use [`Self::append`] to choose the flags explicitly.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection or APPEND fails.
+    pub async fn append_draft(&self, folder: &Folder, raw: &[u8]) -> Result<()> {
+        self.append(folder, raw, &[Flag::Draft, Flag::Seen]).await
+    }
+
+    /// Append several raw RFC 2822 messages to a folder, each with its
+    /// own flags, and return their new UIDs in order.
+    ///
+    /// When the server advertises MULTIAPPEND (RFC 3502), all
+    /// messages go in a single APPEND and the server stores all of
+    /// them or none. Otherwise they are appended one at a time on one
+    /// connection, and a failure leaves the earlier ones stored; a
+    /// warning is logged when that happens. Messages are sent as raw
+    /// bytes, so they need not be UTF-8. UIDs are `None` when the
+    /// server does not report them (`[APPENDUID]`, RFC 4315), which is
+    /// always the case for the one-at-a-time fallback.
+    ///
+    /// An empty `messages` slice returns an empty list without
+    /// connecting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, CAPABILITY, or APPEND fails
+    /// (e.g. the folder does not exist).
+    pub async fn append_many(
+        &self,
+        folder: &Folder,
+        messages: &[(Vec<u8>, Vec<Flag>)],
+    ) -> Result<Vec<Option<u32>>> {
+        if messages.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.observe("append_many", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            let caps = session
+                .capabilities()
+                .await
+                .map_err(|e| Error::imap("Capability failed", &e))?;
+            let literal_plus = connection::has_capability(&caps, "LITERAL+");
+
+            let uids = if connection::has_capability(&caps, "MULTIAPPEND") {
+                connection::multiappend(&mut session, folder.as_str(), messages, literal_plus)
+                    .await?
+            } else {
+                warn!(
+                    "Server lacks MULTIAPPEND; appending {} messages to {} one at a time",
+                    messages.len(),
+                    folder
+                );
+                for (raw, flags) in messages {
+                    connection::append(&mut session, folder.as_str(), flags, raw, literal_plus)
+                        .await?;
+                }
+                vec![None; messages.len()]
+            };
+
+            session.logout().await.ok();
+            Ok(uids)
+        })
+        .await
+    }
+
+    /// Archive an email by moving it to the Archive folder.
+    ///
+    /// The destination is resolved with [`Self::archive_folder`], so
+    /// this works for accounts with localized folder names.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if folder discovery or the move operation
+    /// fails.
+    pub async fn archive(&self, uid: u32, from: &Folder) -> Result<MoveOutcome> {
+        let archive = self.archive_folder().await?;
+        self.move_to_folder(uid, from, &archive).await
+    }
+
+    /// Report an email as spam by moving it to the Spam folder.
+    ///
+    /// The destination is resolved with [`Self::junk_folder`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if folder discovery or the move operation
+    /// fails.
+    pub async fn mark_spam(&self, uid: u32, from: &Folder) -> Result<MoveOutcome> {
+        let junk = self.junk_folder().await?;
+        self.move_to_folder(uid, from, &junk).await
+    }
+
+    /// Move an email wrongly reported as spam back to INBOX.
+    ///
+    /// `uid` is the message's UID in the Spam folder, resolved with
+    /// [`Self::junk_folder`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if folder discovery or the move operation
+    /// fails.
+    pub async fn mark_not_spam(&self, uid: u32) -> Result<MoveOutcome> {
+        let junk = self.junk_folder().await?;
+        self.move_to_folder(uid, &junk, &Folder::Inbox).await
+    }
+
+    /// Remove the `\Seen` flag from all messages in a folder.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection, SELECT, SEARCH, or STORE
+    /// fails.
+    pub async fn unmark_all_read(&self, folder: &Folder) -> Result<()> {
+        self.observe("unmark_all_read", Some(folder), async {
+            let mut session = connection::connect(&self.config).await?;
+            connection::select(&mut session, folder.as_str()).await?;
+
+            let uids = session
+                .uid_search("SEEN")
+                .await
+                .map_err(|e| Error::imap("Search failed", &e))?;
+
+            let uid_list: Vec<u32> = uids.into_iter().collect();
+            if uid_list.is_empty() {
+                session.logout().await.ok();
+                return Ok(());
+            }
+
+            let uid_set = fetch::uid_set_compact(&uid_list);
+            connection::store(&mut session, &uid_set, "-FLAGS (\\Seen)").await?;
+
+            session.logout().await.ok();
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Whether message `uid` in the selected folder `from` already has a
+/// copy in `to`, matched by `Message-ID`. Leaves `from` selected.
+///
+/// A message without a `Message-ID`, a missing `uid`, or a `to` that
+/// cannot be selected counts as not copied, leaving the COPY to report
+/// any error. Moving within one folder is never a copy.
+async fn copied_to(
+    session: &mut connection::ImapSession,
+    uid: u32,
+    from: &Folder,
+    to: &Folder,
+) -> Result<bool> {
+    if from.as_str() == to.as_str() {
+        return Ok(false);
+    }
+
+    let fetches =
+        fetch::fetch_items(session, &[uid], "(BODY.PEEK[HEADER.FIELDS (MESSAGE-ID)])").await?;
+    let Some(message_id) = fetches
+        .first()
+        .and_then(|raw| raw.header.as_deref())
+        .and_then(|header| headers::header_value(header, "Message-ID"))
+        .and_then(|value| headers::message_ids(&value).into_iter().next())
+    else {
+        return Ok(false);
+    };
+
+    if connection::select(session, to.as_str()).await.is_err() {
+        connection::select(session, from.as_str()).await?;
+        return Ok(false);
+    }
+    let query = SearchQuery::new().header("Message-ID", &message_id);
+    let copies = connection::uid_search(session, &query.to_string()).await?;
+    connection::select(session, from.as_str()).await?;
+
+    Ok(!copies.is_empty())
+}
+
+/// Build the keyword flag for a Proton label.
+///
+/// Keywords are IMAP atoms, so the name must be non-empty and free of
+/// spaces, control characters, and atom-specials. Names starting with
+/// `$` are reserved and would not round-trip through [`Flag::label_name`].
+fn label_flag(label: &str) -> Result<Flag> {
+    let is_atom = !label.is_empty()
+        && label
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !"(){%*\"\\]".contains(c));
+
+    if !is_atom || label.starts_with('$') {
+        return Err(Error::InvalidLabel(label.to_string()));
     }
+    Ok(Flag::Keyword(label.to_string()))
 }
@@ -1,45 +1,598 @@
 //! IMAP connection configuration
 
 use crate::error::{Error, Result};
+use crate::metrics::MetricsCallback;
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Default Proton Bridge IMAP host.
+const DEFAULT_HOST: &str = "127.0.0.1";
+
+/// Default Proton Bridge IMAP port.
+const DEFAULT_PORT: u16 = 1143;
+
+/// Default limit for establishing a session (TCP connect, greeting,
+/// STARTTLS, and LOGIN).
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default time after which a watch re-issues IDLE: comfortably below
+/// the 29 minutes after which Proton Bridge (like most servers, per
+/// RFC 2177) ends it.
+const DEFAULT_IDLE_RENEW: Duration = Duration::from_secs(25 * 60);
+
+/// How the connection is secured with TLS.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionSecurity {
+    /// Connect in plaintext and upgrade with STARTTLS, as Proton
+    /// Bridge expects on its default port.
+    #[default]
+    StartTls,
+    /// Start the TLS handshake right away (IMAPS, usually port 993),
+    /// as a bridge configured for SSL or a TLS-terminating proxy
+    /// expects.
+    ImplicitTls,
+}
+
+impl FromStr for ConnectionSecurity {
+    type Err = Error;
+
+    /// Parse `starttls` or `implicit`, ignoring case.
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("starttls") {
+            Ok(Self::StartTls)
+        } else if s.eq_ignore_ascii_case("implicit") {
+            Ok(Self::ImplicitTls)
+        } else {
+            Err(Error::Config(format!(
+                "Invalid connection security {s:?}: expected \"starttls\" or \"implicit\""
+            )))
+        }
+    }
+}
+
+/// What read operations do when the folder they target does not
+/// exist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingFolderBehavior {
+    /// Fail with the server's `NONEXISTENT` error.
+    #[default]
+    Error,
+    /// Return an empty result, as for an empty folder.
+    EmptyResult,
+}
 
 /// IMAP connection configuration for Proton Bridge
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ImapConfig {
     pub host: String,
     pub port: u16,
     pub username: String,
     pub password: String,
+    /// Maximum time allowed to establish a logged-in session.
+    pub connect_timeout: Duration,
+    /// Called with a [`MetricEvent`](crate::MetricEvent) after each
+    /// operation. `None` disables metrics.
+    pub metrics: Option<MetricsCallback>,
+    /// Messages larger than this many bytes (`RFC822.SIZE`) are
+    /// skipped with a warning when fetching full messages. `None`
+    /// fetches everything.
+    pub max_message_size: Option<usize>,
+    /// Ask for COMPRESS=DEFLATE (RFC 4978) after login. See
+    /// [`ImapConfig::with_compress`].
+    pub compress: bool,
+    /// Pick up messages delivered while a search was being fetched.
+    /// See [`ImapConfig::with_follow_new`].
+    pub follow_new: bool,
+    /// Check that the server certificate names `host`. See
+    /// [`ImapConfig::with_verify_hostname`].
+    pub verify_hostname: bool,
+    /// How long a watch stays in one IDLE before re-issuing it. See
+    /// [`ImapConfig::with_idle_renew`].
+    pub idle_renew: Duration,
+    /// STARTTLS or implicit TLS. See
+    /// [`ImapConfig::with_connection_security`].
+    pub connection_security: ConnectionSecurity,
+    /// How read operations treat a folder that does not exist. See
+    /// [`ImapConfig::with_on_missing_folder`].
+    pub on_missing_folder: MissingFolderBehavior,
+}
+
+impl fmt::Debug for ImapConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImapConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &self.password)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("metrics", &self.metrics.as_ref().map(|_| "Fn(MetricEvent)"))
+            .field("max_message_size", &self.max_message_size)
+            .field("compress", &self.compress)
+            .field("follow_new", &self.follow_new)
+            .field("verify_hostname", &self.verify_hostname)
+            .field("idle_renew", &self.idle_renew)
+            .field("connection_security", &self.connection_security)
+            .field("on_missing_folder", &self.on_missing_folder)
+            .finish()
+    }
 }
 
 impl ImapConfig {
+    /// Create a configuration with the default connect timeout.
+    #[must_use]
+    pub fn new(
+        host: impl Into<String>,
+        port: u16,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            username: username.into(),
+            password: password.into(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            metrics: None,
+            max_message_size: None,
+            compress: false,
+            follow_new: false,
+            verify_hostname: false,
+            idle_renew: DEFAULT_IDLE_RENEW,
+            connection_security: ConnectionSecurity::default(),
+            on_missing_folder: MissingFolderBehavior::default(),
+        }
+    }
+
+    /// Set the maximum time allowed to establish a session.
+    #[must_use]
+    pub const fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Report a [`MetricEvent`](crate::MetricEvent) to `callback`
+    /// after each operation.
+    #[must_use]
+    pub fn with_metrics(mut self, callback: MetricsCallback) -> Self {
+        self.metrics = Some(callback);
+        self
+    }
+
+    /// Skip messages larger than `bytes` when fetching full messages.
+    #[must_use]
+    pub const fn with_max_message_size(mut self, bytes: usize) -> Self {
+        self.max_message_size = Some(bytes);
+        self
+    }
+
+    /// Compress the connection with COMPRESS=DEFLATE when the server
+    /// supports it; servers that don't are used uncompressed.
+    ///
+    /// Off by default. Deflate costs CPU on both ends for every byte
+    /// sent, which only pays off when the link is the bottleneck,
+    /// e.g. bulk fetches from a bridge reached over a slow network.
+    /// Against a bridge on localhost it just adds latency.
+    #[must_use]
+    pub const fn with_compress(mut self, enabled: bool) -> Self {
+        self.compress = enabled;
+        self
+    }
+
+    /// Follow concurrent delivery during searches.
+    ///
+    /// A search fetches the messages that matched when it ran; mail
+    /// delivered while they download is missed (the server announces
+    /// it with an `EXISTS` update, which is logged). When enabled,
+    /// [`ProtonClient::search`](crate::ProtonClient::search) repeats
+    /// the search for UIDs above the ones already fetched until
+    /// nothing new turns up. Off by default.
+    #[must_use]
+    pub const fn with_follow_new(mut self, enabled: bool) -> Self {
+        self.follow_new = enabled;
+        self
+    }
+
+    /// Check that the server certificate's subject alternative names
+    /// match `host`.
+    ///
+    /// Bridge certificates are self-signed, so the trust chain is never
+    /// verified. This check still catches a configuration pointing at
+    /// the wrong server. Off by default, since a bridge reached by a
+    /// name its certificate does not list (e.g. `localhost` for a
+    /// certificate issued to `127.0.0.1`) would be rejected.
+    #[must_use]
+    pub const fn with_verify_hostname(mut self, enabled: bool) -> Self {
+        self.verify_hostname = enabled;
+        self
+    }
+
+    /// Set how long [`ProtonClient::watch`](crate::ProtonClient::watch)
+    /// stays in one IDLE command before ending it with `DONE` and
+    /// starting another.
+    ///
+    /// Servers end an IDLE that has run for too long (Proton Bridge
+    /// after 29 minutes), so this must stay below their limit. The
+    /// default is 25 minutes.
+    #[must_use]
+    pub const fn with_idle_renew(mut self, interval: Duration) -> Self {
+        self.idle_renew = interval;
+        self
+    }
+
+    /// Choose between STARTTLS (the default) and implicit TLS.
+    ///
+    /// The two are not interchangeable: a server expecting one sees
+    /// the other as garbage, so this must match the port in use.
+    #[must_use]
+    pub const fn with_connection_security(mut self, security: ConnectionSecurity) -> Self {
+        self.connection_security = security;
+        self
+    }
+
+    /// Choose what read operations do when their folder does not
+    /// exist.
+    ///
+    /// By default they fail with an error whose
+    /// [`response_code`](crate::Error::response_code) is
+    /// [`NonExistent`](crate::ImapResponseCode::NonExistent). With
+    /// [`MissingFolderBehavior::EmptyResult`], operations returning a
+    /// collection (searches, fetches of a list of UIDs, flag
+    /// snapshots) return an empty one instead, which suits callers
+    /// that poll folders which may not have been created yet. Write
+    /// operations and lookups of a single message still fail.
+    #[must_use]
+    pub const fn with_on_missing_folder(mut self, behavior: MissingFolderBehavior) -> Self {
+        self.on_missing_folder = behavior;
+        self
+    }
+
     /// Load IMAP configuration from environment variables
     ///
-    /// Reads from `.env` file if present. Required variables:
+    /// Variables are looked up, in order of precedence, in:
+    ///
+    /// 1. a `.env` file in the current directory (or the nearest
+    ///    parent directory that has one),
+    /// 2. the per-user `.env` file (see [`ImapConfig::user_env_path`]),
+    /// 3. the process environment.
+    ///
+    /// Required variables:
     /// - `IMAP_USERNAME`
     /// - `IMAP_PASSWORD`
     ///
     /// Optional (with defaults):
     /// - `IMAP_HOST` (default: `127.0.0.1`)
     /// - `IMAP_PORT` (default: `1143`)
+    /// - `IMAP_SECURITY`: `starttls` (default) or `implicit`, see
+    ///   [`ConnectionSecurity`]
     ///
     /// # Errors
     ///
     /// Returns an error if required environment variables are
-    /// missing or `IMAP_PORT` is not a valid port number.
+    /// missing, `IMAP_PORT` is not a valid port number,
+    /// `IMAP_SECURITY` is not recognized, or a `.env` file exists but
+    /// cannot be parsed.
     pub fn from_env() -> Result<Self> {
-        dotenvy::dotenv().ok();
-
-        Ok(Self {
-            host: env::var("IMAP_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
-            port: env::var("IMAP_PORT")
-                .unwrap_or_else(|_| "1143".to_string())
-                .parse()
-                .map_err(|e| Error::Config(format!("Invalid IMAP_PORT: {e}")))?,
-            username: env::var("IMAP_USERNAME")
-                .map_err(|_| Error::Config("IMAP_USERNAME not set".into()))?,
-            password: env::var("IMAP_PASSWORD")
-                .map_err(|_| Error::Config("IMAP_PASSWORD not set".into()))?,
+        Self::from_vars(&EnvVars::load(None)?)
+    }
+
+    /// Like [`ImapConfig::from_env`], but with variables from the
+    /// `.env`-style file at `path` taking precedence over all other
+    /// sources.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` cannot be read or parsed, or for
+    /// the same reasons as [`ImapConfig::from_env`].
+    pub fn from_env_path(path: &Path) -> Result<Self> {
+        Self::from_vars(&EnvVars::load(Some(path))?)
+    }
+
+    /// The per-user `.env` file consulted by [`ImapConfig::from_env`]:
+    ///
+    /// - Linux: `$XDG_CONFIG_HOME/protonmail-client/.env` (or
+    ///   `~/.config/...`)
+    /// - macOS: `~/Library/Application Support/protonmail-client/.env`
+    /// - Windows: `%APPDATA%\protonmail-client\.env`
+    ///
+    /// Returns `None` if the config directory cannot be determined.
+    #[must_use]
+    pub fn user_env_path() -> Option<PathBuf> {
+        user_config_dir().map(|dir| dir.join("protonmail-client").join(".env"))
+    }
+
+    fn from_vars(vars: &EnvVars) -> Result<Self> {
+        let security = vars
+            .get("IMAP_SECURITY")
+            .as_deref()
+            .map(str::parse)
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Self::new(
+            vars.get("IMAP_HOST")
+                .unwrap_or_else(|| DEFAULT_HOST.to_string()),
+            port_from_vars(vars)?.unwrap_or(DEFAULT_PORT),
+            vars.get("IMAP_USERNAME")
+                .ok_or_else(|| Error::Config("IMAP_USERNAME not set".into()))?,
+            vars.get("IMAP_PASSWORD")
+                .ok_or_else(|| Error::Config("IMAP_PASSWORD not set".into()))?,
+        )
+        .with_connection_security(security))
+    }
+
+    /// Load configuration, discovering the IMAP port from Proton
+    /// Bridge's own preferences file.
+    ///
+    /// The bridge does not always listen on `1143` (the port can
+    /// change after an update or when it is already taken), but it
+    /// records the active port as `user_port_imap` in `prefs.json`
+    /// under its per-user config directory:
+    ///
+    /// - Linux: `$XDG_CONFIG_HOME/protonmail/bridge-v3/` (or
+    ///   `~/.config/...`)
+    /// - macOS: `~/Library/Application Support/protonmail/bridge-v3/`
+    /// - Windows: `%APPDATA%\protonmail\bridge-v3\`
+    ///
+    /// The legacy `bridge/` directory is checked as well. If no file
+    /// is found, the defaults `127.0.0.1:1143` are used. The bridge
+    /// only listens on localhost, so the host is never read from the
+    /// file. `IMAP_HOST` and `IMAP_PORT` still override the
+    /// discovered values, and credentials always come from the
+    /// environment as in [`ImapConfig::from_env`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credentials are missing, or if a
+    /// bridge preferences file exists but cannot be read or does not
+    /// contain a valid port.
+    pub fn discover() -> Result<Self> {
+        let discovered_port = match bridge_prefs_path() {
+            Some(path) => read_bridge_port(&path)?,
+            None => None,
+        };
+
+        let vars = EnvVars::load(None)?;
+        let mut config = Self::from_vars(&vars)?;
+        if vars.get("IMAP_PORT").is_none() {
+            config.port = discovered_port.unwrap_or(DEFAULT_PORT);
+        }
+        Ok(config)
+    }
+}
+
+/// Configuration variables from `.env` files, layered over the
+/// process environment.
+struct EnvVars {
+    /// Parsed files, highest precedence first.
+    files: Vec<HashMap<String, String>>,
+}
+
+impl EnvVars {
+    /// Read `explicit` (which must exist), the nearest `.env` in the
+    /// current directory or its parents, and the per-user `.env`.
+    fn load(explicit: Option<&Path>) -> Result<Self> {
+        let mut files = Vec::new();
+
+        if let Some(path) = explicit {
+            files.push(read_env_file(path)?);
+        }
+
+        match dotenvy::dotenv_iter() {
+            Ok(iter) => files.push(collect_env(iter, Path::new(".env"))?),
+            Err(e) if e.not_found() => {}
+            Err(e) => return Err(Error::Config(format!("Cannot read .env: {e}"))),
+        }
+
+        if let Some(path) = ImapConfig::user_env_path().filter(|p| p.is_file()) {
+            files.push(read_env_file(&path)?);
+        }
+
+        Ok(Self { files })
+    }
+
+    /// The value of `name` from the first file that sets it, falling
+    /// back to the process environment.
+    fn get(&self, name: &str) -> Option<String> {
+        self.files
+            .iter()
+            .find_map(|file| file.get(name).cloned())
+            .or_else(|| env::var(name).ok())
+    }
+}
+
+/// Parse a `.env`-style file.
+fn read_env_file(path: &Path) -> Result<HashMap<String, String>> {
+    let iter = dotenvy::from_path_iter(path)
+        .map_err(|e| Error::Config(format!("Cannot read {}: {e}", path.display())))?;
+    collect_env(iter, path)
+}
+
+fn collect_env<R: std::io::Read>(
+    iter: dotenvy::Iter<R>,
+    path: &Path,
+) -> Result<HashMap<String, String>> {
+    iter.collect::<std::result::Result<_, _>>()
+        .map_err(|e| Error::Config(format!("Invalid {}: {e}", path.display())))
+}
+
+/// Parse `IMAP_PORT` if it is set.
+fn port_from_vars(vars: &EnvVars) -> Result<Option<u16>> {
+    vars.get("IMAP_PORT")
+        .map(|p| {
+            p.parse()
+                .map_err(|e| Error::Config(format!("Invalid IMAP_PORT: {e}")))
         })
+        .transpose()
+}
+
+/// The platform-specific per-user configuration directory.
+fn user_config_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    }
+}
+
+/// Locate the first existing Proton Bridge `prefs.json`.
+fn bridge_prefs_path() -> Option<PathBuf> {
+    let base = user_config_dir()?.join("protonmail");
+    ["bridge-v3", "bridge"]
+        .iter()
+        .map(|dir| base.join(dir).join("prefs.json"))
+        .find(|path| path.is_file())
+}
+
+/// Read the IMAP port from a bridge preferences file.
+fn read_bridge_port(path: &Path) -> Result<Option<u16>> {
+    let contents = std::fs::read_to_string(path)?;
+    parse_bridge_port(&contents).map_err(|e| {
+        Error::Config(format!(
+            "Invalid bridge preferences {}: {e}",
+            path.display()
+        ))
+    })
+}
+
+/// Extract `user_port_imap` from the bridge preferences JSON.
+///
+/// The bridge stores the port as a string, but a bare number is
+/// accepted too. Returns `Ok(None)` if the key is absent.
+fn parse_bridge_port(contents: &str) -> std::result::Result<Option<u16>, String> {
+    let prefs: serde_json::Value = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+
+    match prefs.get("user_port_imap") {
+        None => Ok(None),
+        Some(serde_json::Value::String(s)) => s
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("user_port_imap: {e}")),
+        Some(serde_json::Value::Number(n)) => n
+            .as_u64()
+            .and_then(|n| u16::try_from(n).ok())
+            .map(Some)
+            .ok_or_else(|| format!("user_port_imap out of range: {n}")),
+        Some(other) => Err(format!("user_port_imap has unexpected type: {other}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(files: &[&[(&str, &str)]]) -> EnvVars {
+        EnvVars {
+            files: files
+                .iter()
+                .map(|file| {
+                    file.iter()
+                        .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn earlier_env_files_take_precedence() {
+        let vars = vars(&[
+            &[("IMAP_USERNAME", "explicit")],
+            &[("IMAP_USERNAME", "cwd"), ("IMAP_PASSWORD", "secret")],
+        ]);
+        assert_eq!(vars.get("IMAP_USERNAME").as_deref(), Some("explicit"));
+        assert_eq!(vars.get("IMAP_PASSWORD").as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn env_files_fall_back_to_process_env() {
+        let vars = vars(&[]);
+        assert_eq!(vars.get("PATH"), env::var("PATH").ok());
+        assert_eq!(vars.get("PROTONMAIL_CLIENT_UNSET_VARIABLE"), None);
+    }
+
+    #[test]
+    fn config_from_vars() {
+        let config = ImapConfig::from_vars(&vars(&[&[
+            ("IMAP_USERNAME", "user"),
+            ("IMAP_PASSWORD", "pass"),
+            ("IMAP_PORT", "1144"),
+        ]]))
+        .unwrap();
+        assert_eq!(config.username, "user");
+        assert_eq!(config.port, 1144);
+    }
+
+    #[test]
+    fn connection_security_from_vars() {
+        let creds = [("IMAP_USERNAME", "user"), ("IMAP_PASSWORD", "pass")];
+        let config = ImapConfig::from_vars(&vars(&[&creds])).unwrap();
+        assert_eq!(config.connection_security, ConnectionSecurity::StartTls);
+
+        let config =
+            ImapConfig::from_vars(&vars(&[&[("IMAP_SECURITY", "Implicit")], &creds])).unwrap();
+        assert_eq!(config.connection_security, ConnectionSecurity::ImplicitTls);
+
+        assert!(matches!(
+            ImapConfig::from_vars(&vars(&[&[("IMAP_SECURITY", "ssl")], &creds])),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn from_env_path_reads_file() {
+        let path = env::temp_dir().join(format!("protonmail-client-{}.env", std::process::id()));
+        std::fs::write(
+            &path,
+            "IMAP_USERNAME=file-user\nIMAP_PASSWORD=file-pass\nIMAP_PORT=1999\n",
+        )
+        .unwrap();
+
+        let config = ImapConfig::from_env_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.username, "file-user");
+        assert_eq!(config.port, 1999);
+    }
+
+    #[test]
+    fn from_env_path_requires_file() {
+        let missing = Path::new("/nonexistent/protonmail-client/.env");
+        assert!(matches!(
+            ImapConfig::from_env_path(missing),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn bridge_port_as_string() {
+        let prefs = r#"{"user_port_imap": "1144", "user_port_smtp": "1026"}"#;
+        assert_eq!(parse_bridge_port(prefs), Ok(Some(1144)));
+    }
+
+    #[test]
+    fn bridge_port_as_number() {
+        assert_eq!(
+            parse_bridge_port(r#"{"user_port_imap": 1145}"#),
+            Ok(Some(1145))
+        );
+    }
+
+    #[test]
+    fn bridge_port_missing() {
+        assert_eq!(parse_bridge_port(r#"{"user_port_smtp": "1025"}"#), Ok(None));
+    }
+
+    #[test]
+    fn bridge_port_invalid() {
+        assert!(parse_bridge_port(r#"{"user_port_imap": "not-a-port"}"#).is_err());
+        assert!(parse_bridge_port(r#"{"user_port_imap": 70000}"#).is_err());
+        assert!(parse_bridge_port("not json").is_err());
     }
 }
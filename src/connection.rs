@@ -1,93 +1,953 @@
 //! Shared IMAP connection and TLS helpers
 //!
-//! Provides the low-level `connect()` and `select()` functions used by
-//! both read and write operations on `ProtonClient`.
+//! Provides the low-level `connect()`, `select()`, `delimiter()`,
+//! `uid_search()`, `store()`, `move_messages()`, `delete_messages()`,
+//! `create()`, `append()`, and `multiappend()`
+//! functions used by both read and write operations on
+//! `ProtonClient` and `ProtonSession`.
 
-use crate::config::ImapConfig;
+use crate::config::{ConnectionSecurity, ImapConfig};
 use crate::error::{Error, Result};
+use crate::flag::Flag;
+use crate::folder::{Folder, FolderStatus};
+use crate::metrics::{self, ConnectTiming};
+use crate::response_code::ImapResponseCode;
+use crate::search::quote;
 use async_imap::Session;
+use async_imap::extensions::compress::DeflateStream;
+use async_imap::imap_proto::{
+    MailboxDatum, RequestId, Response, ResponseCode, Status, UidSetMember,
+};
+use async_imap::types::{Capabilities, Capability, NameAttribute};
+use futures::StreamExt;
+use futures::io::AsyncWriteExt as _;
+use futures::io::{AsyncRead, AsyncWrite};
 use rustls::pki_types::ServerName;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::io;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio_rustls::TlsConnector;
+use tokio_rustls::client::TlsStream;
 use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 /// A TLS-wrapped IMAP session.
-pub type ImapSession = Session<Compat<tokio_rustls::client::TlsStream<TcpStream>>>;
+pub type ImapSession = Session<ImapStream>;
 
-/// Build a TLS connector that accepts all certificates.
+/// The error for a server that speaks the other TLS mode than the one
+/// configured.
+const TLS_MODE_MISMATCH: &str =
+    "TLS mode mismatch: server did not complete TLS handshake; check connection_security setting";
+
+/// The transport under an [`ImapSession`]: TLS, optionally with
+/// COMPRESS=DEFLATE (RFC 4978) layered on top.
+#[derive(Debug)]
+pub enum ImapStream {
+    Tls(Box<Compat<TlsStream<TcpStream>>>),
+    Deflate(Box<DeflateStream<Self>>),
+}
+
+impl ImapStream {
+    /// The TLS stream at the bottom of the stack.
+    fn tls(&self) -> &TlsStream<TcpStream> {
+        match self {
+            Self::Tls(stream) => stream.get_ref(),
+            Self::Deflate(stream) => stream.get_ref().tls(),
+        }
+    }
+}
+
+impl AsyncRead for ImapStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+            Self::Deflate(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ImapStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+            Self::Deflate(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+            Self::Deflate(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_close(cx),
+            Self::Deflate(stream) => Pin::new(stream.as_mut()).poll_close(cx),
+        }
+    }
+}
+
+/// The TLS parameters negotiated with the server.
+///
+/// The certificate's trust chain is never verified, and its host name
+/// only with [`ImapConfig::with_verify_hostname`] (see
+/// [`tls_connector`]), but the protocol version and cipher suite still
+/// show whether the link is using modern cryptography.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TlsInfo {
+    /// The TLS version, e.g. `"TLSv1_3"`.
+    pub protocol_version: String,
+    /// The cipher suite, e.g. `"TLS13_AES_256_GCM_SHA384"`.
+    pub cipher_suite: String,
+}
+
+/// Read the negotiated TLS parameters from a session.
+///
+/// # Errors
+///
+/// Returns [`Error::Tls`] if the handshake did not negotiate a
+/// version or cipher suite.
+pub fn tls_info(session: &ImapSession) -> Result<TlsInfo> {
+    let (_, connection) = session.get_ref().tls().get_ref();
+
+    let protocol_version = connection
+        .protocol_version()
+        .ok_or_else(|| Error::Tls("No TLS version negotiated".into()))?;
+    let cipher_suite = connection
+        .negotiated_cipher_suite()
+        .ok_or_else(|| Error::Tls("No cipher suite negotiated".into()))?;
+
+    Ok(TlsInfo {
+        protocol_version: format!("{protocol_version:?}"),
+        cipher_suite: format!("{:?}", cipher_suite.suite()),
+    })
+}
+
+/// Build a TLS connector that accepts self-signed certificates.
 ///
-/// Proton Bridge uses self-signed certificates, so we skip
-/// verification entirely.
-fn tls_connector() -> TlsConnector {
+/// Proton Bridge uses self-signed certificates, so the trust chain is
+/// never checked. With `verify_hostname`, the certificate must still
+/// name the host we connect to.
+fn tls_connector(verify_hostname: bool) -> TlsConnector {
     let config = rustls::ClientConfig::builder()
         .dangerous()
-        .with_custom_certificate_verifier(Arc::new(DangerousVerifier))
+        .with_custom_certificate_verifier(Arc::new(DangerousVerifier { verify_hostname }))
         .with_no_client_auth();
     TlsConnector::from(Arc::new(config))
 }
 
 /// Open a fresh TLS-wrapped IMAP session.
 ///
-/// Connects to `config.host:config.port` via TCP, issues STARTTLS,
-/// performs the TLS handshake, and logs in.
+/// Connects to `config.host:config.port` via TCP and secures the
+/// connection as `config.connection_security` says: wait for the
+/// server greeting, issue STARTTLS, and perform the TLS handshake, or
+/// for implicit TLS the other way round. Then logs in. The whole
+/// sequence is bounded by `config.connect_timeout`.
+///
+/// The duration of each phase is logged at `debug` and reported as
+/// [`MetricEvent::connect`](crate::MetricEvent::connect).
+///
+/// # Errors
+///
+/// Returns [`Error::Timeout`] if the session is not established in
+/// time, [`Error::ConnectionClosed`] if the server hangs up before
+/// greeting, [`Error::Tls`] if STARTTLS is refused, the handshake
+/// fails, or the server turns out to expect the other TLS mode, or
+/// another error if any step fails.
 pub async fn connect(config: &ImapConfig) -> Result<ImapSession> {
+    // Boxed: the handshake state is large, and would otherwise be
+    // inlined into the future of every operation that connects.
+    tokio::time::timeout(config.connect_timeout, Box::pin(open_session(config)))
+        .await
+        .map_err(|_| Error::Timeout(config.connect_timeout))?
+}
+
+async fn open_session(config: &ImapConfig) -> Result<ImapSession> {
     let addr = format!("{}:{}", config.host, config.port);
     debug!("Connecting to IMAP server at {}", addr);
 
-    let tcp_stream = TcpStream::connect(&addr).await?;
-    let mut client = async_imap::Client::new(tcp_stream.compat());
+    let mut timing = ConnectTiming::default();
+    let start = Instant::now();
+    let mut tcp_stream = TcpStream::connect(&addr).await?;
+    timing.tcp_connect = start.elapsed();
+
+    let start = Instant::now();
+    let tls_stream = match config.connection_security {
+        ConnectionSecurity::StartTls => {
+            read_greeting(&mut tcp_stream).await?;
+            let tcp_stream = starttls(tcp_stream).await?;
+            timing.starttls = Some(start.elapsed());
+
+            let start = Instant::now();
+            let tls_stream = tls_handshake(config, tcp_stream).await?;
+            timing.tls_handshake = start.elapsed();
+            tls_stream
+        }
+        ConnectionSecurity::ImplicitTls => {
+            let mut tls_stream = tls_handshake(config, tcp_stream).await?;
+            read_greeting(&mut tls_stream).await?;
+            timing.tls_handshake = start.elapsed();
+            tls_stream
+        }
+    };
 
-    client
-        .run_command_and_check_ok("STARTTLS", None)
+    let tls_client = async_imap::Client::new(ImapStream::Tls(Box::new(tls_stream.compat())));
+
+    let start = Instant::now();
+    let mut session = tls_client
+        .login(&config.username, &config.password)
         .await
-        .map_err(|e| Error::Tls(format!("STARTTLS failed: {e}")))?;
+        .map_err(|(e, _)| Error::imap("Login failed", &e))?;
+    timing.login = start.elapsed();
+
+    debug!(
+        "Connect timing: tcp_connect={:?} starttls={:?} tls_handshake={:?} login={:?}",
+        timing.tcp_connect, timing.starttls, timing.tls_handshake, timing.login
+    );
+    metrics::record_connect(timing);
+
+    if config.compress {
+        session = compress(session).await?;
+    }
+
+    info!("Connected to IMAP server");
+    Ok(session)
+}
+
+/// Issue STARTTLS on a plaintext connection, handing the stream back
+/// for the TLS handshake.
+///
+/// If the server refuses, it is asked for its capabilities to tell a
+/// server without STARTTLS (typically one expecting implicit TLS)
+/// from one that failed for another reason.
+async fn starttls(tcp_stream: TcpStream) -> Result<TcpStream> {
+    let mut client = async_imap::Client::new(tcp_stream.compat());
+    let result = client.run_command_and_check_ok("STARTTLS", None).await;
+    let mut tcp_stream = client.into_inner().into_inner();
+
+    let Err(e) = result else {
+        return Ok(tcp_stream);
+    };
+    if offers_starttls(&mut tcp_stream).await == Some(false) {
+        debug!("STARTTLS refused: {e}");
+        return Err(Error::Tls(
+            "server does not support STARTTLS; consider ImplicitTls mode".into(),
+        ));
+    }
+    Err(Error::Tls(format!("STARTTLS failed: {e}")))
+}
 
-    let connector = tls_connector();
+/// Ask a plaintext connection whether STARTTLS is among its
+/// capabilities. `None` if the answer could not be read.
+async fn offers_starttls(stream: &mut TcpStream) -> Option<bool> {
+    stream.write_all(b"S1 CAPABILITY\r\n").await.ok()?;
+
+    let mut offered = false;
+    loop {
+        let line = read_line(stream).await.ok()?;
+        let line = String::from_utf8_lossy(&line);
+        if line.starts_with("S1 ") {
+            return Some(offered);
+        }
+        if line.starts_with("* ") {
+            offered |= line
+                .split_ascii_whitespace()
+                .any(|token| token.eq_ignore_ascii_case("STARTTLS"));
+        }
+    }
+}
+
+/// Perform the TLS handshake on `tcp_stream`.
+async fn tls_handshake(config: &ImapConfig, tcp_stream: TcpStream) -> Result<TlsStream<TcpStream>> {
+    let connector = tls_connector(config.verify_hostname);
     let server_name = ServerName::try_from(config.host.clone())
         .map_err(|e| Error::Tls(format!("Invalid server name: {e}")))?;
 
-    let inner = client.into_inner().into_inner();
-    let tls_stream = connector
-        .connect(server_name, inner)
+    connector
+        .connect(server_name, tcp_stream)
         .await
-        .map_err(|e| Error::Tls(e.to_string()))?;
+        .map_err(|e| {
+            if config.connection_security == ConnectionSecurity::ImplicitTls
+                && is_plaintext_reply(&e)
+            {
+                debug!("TLS handshake failed: {e}");
+                Error::Tls(TLS_MODE_MISMATCH.into())
+            } else {
+                Error::Tls(e.to_string())
+            }
+        })
+}
 
-    let tls_client = async_imap::Client::new(tls_stream.compat());
+/// Whether a failed handshake looks like a plaintext server: it
+/// answered with something that is not a TLS record (its greeting),
+/// or hung up.
+fn is_plaintext_reply(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::UnexpectedEof
+        || e.get_ref()
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+            .is_some_and(|inner| matches!(inner, rustls::Error::InvalidMessage(_)))
+}
 
-    let session = tls_client
-        .login(&config.username, &config.password)
+/// Turn on COMPRESS=DEFLATE (RFC 4978) if the server offers it.
+///
+/// Servers without the extension keep the uncompressed session.
+async fn compress(mut session: ImapSession) -> Result<ImapSession> {
+    let caps = session
+        .capabilities()
         .await
-        .map_err(|(e, _)| Error::Imap(format!("Login failed: {e}")))?;
+        .map_err(|e| Error::imap("Capability failed", &e))?;
+    if !has_capability(&caps, "COMPRESS=DEFLATE") {
+        warn!("Server does not support COMPRESS=DEFLATE, continuing uncompressed");
+        return Ok(session);
+    }
 
-    info!("Connected to IMAP server");
-    Ok(session)
+    debug!("Enabling COMPRESS=DEFLATE");
+    session
+        .compress(|stream| ImapStream::Deflate(Box::new(stream)))
+        .await
+        .map_err(|e| Error::imap("Compress failed", &e))
+}
+
+/// Whether `caps` contains the capability spelled `name`. Names are
+/// compared case-insensitively, as servers differ in how they spell
+/// them.
+pub fn has_capability(caps: &Capabilities, name: &str) -> bool {
+    caps.iter().any(|cap| capability_matches(cap, name))
+}
+
+/// Whether `cap` is the capability spelled `name`, ignoring case.
+fn capability_matches(cap: &Capability, name: &str) -> bool {
+    match cap {
+        Capability::Imap4rev1 => name.eq_ignore_ascii_case("IMAP4rev1"),
+        Capability::Auth(mechanism) => name.split_once('=').is_some_and(|(prefix, rest)| {
+            prefix.eq_ignore_ascii_case("AUTH") && rest.eq_ignore_ascii_case(mechanism)
+        }),
+        Capability::Atom(atom) => atom.eq_ignore_ascii_case(name),
+    }
+}
+
+/// Read and check the server greeting (RFC 3501 Section 7.1).
+///
+/// A TLS record where the greeting belongs means the server expects
+/// implicit TLS; it has given up waiting for our handshake.
+async fn read_greeting<S: tokio::io::AsyncRead + Unpin>(stream: &mut S) -> Result<()> {
+    let mut first = [0u8; 1];
+    if stream.read(&mut first).await? == 0 {
+        return Err(Error::ConnectionClosed);
+    }
+    if is_tls_record(first[0]) {
+        return Err(Error::Tls(TLS_MODE_MISMATCH.into()));
+    }
+
+    let mut line = first.to_vec();
+    line.extend(read_line(stream).await?);
+    check_greeting(&line)
+}
+
+/// Whether `byte` opens a TLS record: change cipher spec, alert,
+/// handshake, or application data (RFC 8446 Section 5.1).
+const fn is_tls_record(byte: u8) -> bool {
+    matches!(byte, 0x14..=0x17)
+}
+
+/// Read one CRLF-terminated line.
+///
+/// The line is read one byte at a time so nothing past it is
+/// consumed before the stream is handed to `async-imap`.
+async fn read_line<S: tokio::io::AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<u8>> {
+    let mut line = Vec::new();
+    while !line.ends_with(b"\r\n") {
+        let mut byte = [0u8; 1];
+        if stream.read(&mut byte).await? == 0 {
+            return Err(Error::ConnectionClosed);
+        }
+        line.push(byte[0]);
+    }
+    Ok(line)
+}
+
+/// Classify a greeting line. Status keywords are case-insensitive
+/// (RFC 3501 Section 9), so `* ok` is as good as `* OK`.
+fn check_greeting(line: &[u8]) -> Result<()> {
+    let starts_with = |prefix: &[u8]| {
+        line.get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+    };
+
+    if starts_with(b"* OK") || starts_with(b"* PREAUTH") {
+        Ok(())
+    } else if starts_with(b"* BYE") {
+        Err(Error::ConnectionClosed)
+    } else {
+        Err(Error::Imap(format!(
+            "Unexpected greeting: {}",
+            String::from_utf8_lossy(line).trim_end()
+        )))
+    }
+}
+
+/// LIST all folder names.
+pub async fn list_folders(session: &mut ImapSession) -> Result<Vec<String>> {
+    list_names(session, false).await
+}
+
+/// LIST the folders that can be selected, leaving out `\Noselect`
+/// parents such as Proton's `Folders` and `Labels`.
+pub async fn list_selectable(session: &mut ImapSession) -> Result<Vec<String>> {
+    list_names(session, true).await
+}
+
+async fn list_names(session: &mut ImapSession, selectable_only: bool) -> Result<Vec<String>> {
+    let mut folder_stream = session
+        .list(Some(""), Some("*"))
+        .await
+        .map_err(|e| Error::imap("List folders failed", &e))?;
+
+    let mut names = Vec::new();
+    while let Some(item) = folder_stream.next().await {
+        let Ok(name) = item else { continue };
+        let noselect = name
+            .attributes()
+            .iter()
+            .any(|attr| matches!(attr, NameAttribute::NoSelect));
+        if selectable_only && noselect {
+            continue;
+        }
+        names.push(name.name().to_string());
+    }
+    Ok(names)
 }
 
 /// SELECT a folder on an existing session.
 pub async fn select(session: &mut ImapSession, folder: &str) -> Result<()> {
-    session
-        .select(folder)
+    select_status(session, folder).await?;
+    Ok(())
+}
+
+/// SELECT a folder and return the state the server reported.
+///
+/// SELECT does not report an unseen count, so
+/// [`FolderStatus::unseen`] is left at 0 for the caller to fill in.
+pub async fn select_status(session: &mut ImapSession, folder: &str) -> Result<FolderStatus> {
+    let context = format!("Failed to select {folder}");
+    let id = session
+        .run_command(format!("SELECT {}", quote(folder)))
+        .await
+        .map_err(|e| Error::imap(&context, &e))?;
+
+    let mut status = FolderStatus {
+        exists: 0,
+        recent: 0,
+        unseen: 0,
+        first_unseen: None,
+        uid_next: None,
+        uid_validity: None,
+    };
+    finish_checked(session, &id, &context, |response| match response {
+        Response::MailboxData(MailboxDatum::Exists(n)) => status.exists = *n,
+        Response::MailboxData(MailboxDatum::Recent(n)) => status.recent = *n,
+        Response::Data {
+            status: Status::Ok,
+            code: Some(code),
+            ..
+        } => match code {
+            ResponseCode::Unseen(n) => status.first_unseen = Some(*n),
+            ResponseCode::UidNext(n) => status.uid_next = Some(*n),
+            ResponseCode::UidValidity(n) => status.uid_validity = Some(*n),
+            _ => {}
+        },
+        _ => {}
+    })
+    .await?;
+    Ok(status)
+}
+
+/// The hierarchy delimiter, from `LIST "" ""` (RFC 3501 Section
+/// 6.3.8). `None` if the server has a flat namespace.
+pub async fn delimiter(session: &mut ImapSession) -> Result<Option<char>> {
+    let mut stream = session
+        .list(Some(""), Some(""))
         .await
-        .map_err(|e| Error::Imap(format!("Failed to select {folder}: {e}")))?;
+        .map_err(|e| Error::imap("List folders failed", &e))?;
+
+    let mut delimiter = None;
+    while let Some(item) = stream.next().await {
+        let name = item.map_err(|e| Error::imap("List folders failed", &e))?;
+        if delimiter.is_none() {
+            delimiter = name.delimiter().and_then(|d| d.chars().next());
+        }
+    }
+    Ok(delimiter)
+}
+
+/// Run `UID SEARCH` with `query`.
+///
+/// A query that is not plain ASCII is sent as `CHARSET UTF-8`, with
+/// each non-ASCII string moved into a literal, since IMAP quoted
+/// strings and atoms are 7-bit only (RFC 3501 Section 6.4.4). The
+/// literals are synchronizing: each one's data is only sent once the
+/// server has answered its `{N}` with a `+` continuation.
+pub async fn uid_search(session: &mut ImapSession, query: &str) -> Result<Vec<u32>> {
+    let parts = utf8_search_query(query);
+    let Some((command, literals)) = parts.split_first() else {
+        return Ok(Vec::new());
+    };
+    if !literals.is_empty() {
+        return search_with_literals(session, command, literals).await;
+    }
+
+    let uids = session
+        .uid_search(command)
+        .await
+        .map_err(|e| Error::imap("Search failed", &e))?;
+    Ok(uids.into_iter().collect())
+}
+
+/// Run `UID SEARCH` for a query split at its literals by
+/// [`utf8_search_query`], waiting for the server's continuation
+/// before sending each part after the first (RFC 3501 Section 7.5).
+async fn search_with_literals(
+    session: &mut ImapSession,
+    command: &str,
+    literals: &[String],
+) -> Result<Vec<u32>> {
+    const CONTEXT: &str = "Search failed";
+    let id = session
+        .run_command(format!("UID SEARCH {command}"))
+        .await
+        .map_err(|e| Error::imap(CONTEXT, &e))?;
+    for part in literals {
+        await_continuation(session, &id, CONTEXT).await?;
+        session
+            .run_command_untagged(part)
+            .await
+            .map_err(|e| Error::imap(CONTEXT, &e))?;
+    }
+
+    let mut uids = Vec::new();
+    finish_checked(session, &id, CONTEXT, |response| {
+        if let Response::MailboxData(MailboxDatum::Search(found)) = response {
+            uids.extend(found);
+        }
+    })
+    .await?;
+    Ok(uids)
+}
+
+/// Rewrite a SEARCH query so that it is valid with non-ASCII terms.
+///
+/// ASCII queries, and queries that already name a charset, are
+/// returned unchanged as a single part. Otherwise the query is split
+/// after each literal announcement (`{N}`), so every part but the
+/// first starts with literal data.
+fn utf8_search_query(query: &str) -> Vec<String> {
+    let has_charset = split_charset(query).0.is_some();
+    if query.is_ascii() || has_charset {
+        return vec![query.to_string()];
+    }
+
+    let mut parts = Vec::new();
+    let mut out = String::from("CHARSET UTF-8 ");
+    let mut atom = String::new();
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                push_atom(&mut parts, &mut out, &mut atom);
+                let mut value = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => value.extend(chars.next()),
+                        c => value.push(c),
+                    }
+                }
+                if value.is_ascii() {
+                    out.push_str(&quote(&value));
+                } else {
+                    push_literal(&mut parts, &mut out, &value);
+                }
+            }
+            ' ' | '(' | ')' => {
+                push_atom(&mut parts, &mut out, &mut atom);
+                out.push(c);
+            }
+            c => atom.push(c),
+        }
+    }
+    push_atom(&mut parts, &mut out, &mut atom);
+    parts.push(out);
+    parts
+}
+
+/// Restrict a SEARCH `query` to the UIDs in `range` (e.g. `5:*`).
+///
+/// A `CHARSET` has to come before every search key (RFC 3501 Section
+/// 6.4.4), so a `CHARSET` at the start of `query` is moved in front of
+/// the `UID` key.
+pub fn uid_range_query(range: &str, query: &str) -> String {
+    let (charset, rest) = split_charset(query.trim());
+    let mut criteria = charset.map_or_else(String::new, |name| format!("CHARSET {name} "));
+    let _ = write!(criteria, "UID {range}");
+    if !rest.is_empty() {
+        criteria.push(' ');
+        criteria.push_str(rest);
+    }
+    criteria
+}
+
+/// Split a leading `CHARSET <name>` off a SEARCH query, returning the
+/// charset name, if there is one, and the rest of the query.
+fn split_charset(query: &str) -> (Option<&str>, &str) {
+    let Some(rest) = query
+        .get(..8)
+        .filter(|head| head.eq_ignore_ascii_case("CHARSET "))
+        .map(|_| query[8..].trim_start())
+    else {
+        return (None, query);
+    };
+    let (name, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    (Some(name), rest.trim_start())
+}
+
+/// Flush a bare search term, as a literal if it is not ASCII.
+fn push_atom(parts: &mut Vec<String>, out: &mut String, atom: &mut String) {
+    if atom.is_ascii() {
+        out.push_str(atom);
+    } else {
+        push_literal(parts, out, atom);
+    }
+    atom.clear();
+}
+
+/// Announce `value` as an IMAP literal (`{N}`) at the end of `out`,
+/// move `out` to `parts`, and start the next part with the data.
+fn push_literal(parts: &mut Vec<String>, out: &mut String, value: &str) {
+    let _ = write!(out, "{{{}}}", value.len());
+    parts.push(std::mem::replace(out, value.to_string()));
+}
+
+/// Run `UID STORE`, failing if the server answers `NO` or `BAD`.
+pub async fn store(session: &mut ImapSession, uid_set: &str, store_arg: &str) -> Result<()> {
+    run_checked(
+        session,
+        &format!("UID STORE {uid_set} {store_arg}"),
+        "Store failed",
+    )
+    .await?;
     Ok(())
 }
 
+/// Run `command`, failing unless its tagged response is OK, and
+/// return the UIDs of an `[APPENDUID]` code in that response, if any.
+///
+/// async-imap's streaming commands (`uid_store`, `expunge`) end at the
+/// tagged response without reporting its status, so draining them
+/// would take a `NO` for success. Commands sent here skip the
+/// untagged responses instead (stored flags, `EXPUNGE` notices, or
+/// updates for messages changed concurrently) and check the tagged
+/// one.
+async fn run_checked(
+    session: &mut ImapSession,
+    command: &str,
+    context: &str,
+) -> Result<Option<Vec<u32>>> {
+    let id = session
+        .run_command(command)
+        .await
+        .map_err(|e| Error::imap(context, &e))?;
+    finish_checked(session, &id, context, |_| {}).await
+}
+
+/// Read responses up to the tagged one for `id`, passing the others
+/// to `untagged`, then fail unless it is OK. Returns the UIDs of an
+/// `[APPENDUID]` code in the tagged response, if any.
+async fn finish_checked(
+    session: &mut ImapSession,
+    id: &RequestId,
+    context: &str,
+    mut untagged: impl FnMut(&Response<'_>) + Send,
+) -> Result<Option<Vec<u32>>> {
+    loop {
+        let response = session
+            .read_response()
+            .await
+            .map_err(|e| Error::Imap(format!("{context}: {e}")))?
+            .ok_or(Error::ConnectionClosed)?;
+        match response.parsed() {
+            Response::Done {
+                tag,
+                status,
+                code,
+                information,
+            } if tag == id => {
+                check_status(status, code.as_ref(), information.as_deref(), context)?;
+                return Ok(appended_uids(code.as_ref()));
+            }
+            other => untagged(other),
+        }
+    }
+}
+
+/// Wait for the `+` continuation that lets a synchronizing literal
+/// of command `id` be sent. Fails if the server rejects the command
+/// instead.
+async fn await_continuation(
+    session: &mut ImapSession,
+    id: &RequestId,
+    context: &str,
+) -> Result<()> {
+    loop {
+        let response = session
+            .read_response()
+            .await
+            .map_err(|e| Error::Imap(format!("{context}: {e}")))?
+            .ok_or(Error::ConnectionClosed)?;
+        match response.parsed() {
+            Response::Continue { .. } => return Ok(()),
+            Response::Done {
+                tag,
+                status,
+                code,
+                information,
+            } if tag == id => {
+                check_status(status, code.as_ref(), information.as_deref(), context)?;
+                return Err(Error::Imap(format!(
+                    "{context}: command completed before its literal was sent"
+                )));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Fail unless a tagged response's `status` is OK, keeping its
+/// response code and text in the error.
+fn check_status(
+    status: &Status,
+    code: Option<&ResponseCode<'_>>,
+    information: Option<&str>,
+    context: &str,
+) -> Result<()> {
+    let status = match status {
+        Status::Ok => return Ok(()),
+        Status::Bad => "BAD",
+        _ => "NO",
+    };
+    let code = ImapResponseCode::from_response(code, information);
+    Err(Error::status(context, status, code, information))
+}
+
+/// Move messages to `to` with COPY, `+FLAGS (\Deleted)`, and
+/// EXPUNGE.
+pub async fn move_messages(session: &mut ImapSession, uid_set: &str, to: &Folder) -> Result<()> {
+    run_checked(
+        session,
+        &format!("UID COPY {uid_set} {}", quote(to.as_str())),
+        "Copy failed",
+    )
+    .await?;
+
+    delete_messages(session, uid_set).await
+}
+
+/// Remove messages from the selected folder with `+FLAGS (\Deleted)`
+/// and EXPUNGE.
+pub async fn delete_messages(session: &mut ImapSession, uid_set: &str) -> Result<()> {
+    run_checked(
+        session,
+        &format!("UID STORE {uid_set} +FLAGS (\\Deleted)"),
+        "Store +Deleted failed",
+    )
+    .await?;
+    run_checked(session, "EXPUNGE", "Expunge failed").await?;
+    Ok(())
+}
+
+/// CREATE `folder` (RFC 3501 Section 6.3.3).
+pub async fn create(session: &mut ImapSession, folder: &str) -> Result<()> {
+    run_checked(
+        session,
+        &format!("CREATE {}", quote(folder)),
+        &format!("Create {folder} failed"),
+    )
+    .await?;
+    Ok(())
+}
+
+/// APPEND `raw` to `folder` with `flags` set.
+///
+/// With `literal_plus` (the server advertises LITERAL+, RFC 7888) the
+/// message is sent as a non-synchronizing literal `{N+}` right after
+/// the command, saving the wait for the server's `+` continuation.
+/// The message is sent as raw bytes either way, so it need not be
+/// UTF-8.
+pub async fn append(
+    session: &mut ImapSession,
+    folder: &str,
+    flags: &[Flag],
+    raw: &[u8],
+    literal_plus: bool,
+) -> Result<()> {
+    append_messages(session, folder, &[(raw, flags)], literal_plus).await?;
+    Ok(())
+}
+
+/// APPEND several messages to `folder` in one command (MULTIAPPEND,
+/// RFC 3502), each with its own flags. The server stores all of them
+/// or none.
+///
+/// The messages are sent as raw bytes, in literals that are
+/// non-synchronizing with `literal_plus` (see [`append`]) and
+/// synchronizing otherwise. Returns the new UIDs in order, from the
+/// `[APPENDUID]` response code (RFC 4315), or `None` for every
+/// message when the server does not report them.
+pub async fn multiappend(
+    session: &mut ImapSession,
+    folder: &str,
+    messages: &[(Vec<u8>, Vec<Flag>)],
+    literal_plus: bool,
+) -> Result<Vec<Option<u32>>> {
+    let messages: Vec<(&[u8], &[Flag])> = messages
+        .iter()
+        .map(|(raw, flags)| (raw.as_slice(), flags.as_slice()))
+        .collect();
+    let uids = append_messages(session, folder, &messages, literal_plus)
+        .await?
+        .filter(|uids| uids.len() == messages.len());
+    Ok(uids.map_or_else(
+        || vec![None; messages.len()],
+        |uids| uids.into_iter().map(Some).collect(),
+    ))
+}
+
+/// Send one APPEND of `messages` (raw bytes and flags), each in its
+/// own literal, and check the tagged response. Returns the UIDs of
+/// its `[APPENDUID]` code, if any.
+async fn append_messages(
+    session: &mut ImapSession,
+    folder: &str,
+    messages: &[(&[u8], &[Flag])],
+    literal_plus: bool,
+) -> Result<Option<Vec<u32>>> {
+    let context = format!("Append to {folder} failed");
+    // The text before each message: its flag list and the literal
+    // announcement.
+    let heads: Vec<String> = messages
+        .iter()
+        .map(|(raw, flags)| {
+            let mut head = String::new();
+            if !flags.is_empty() {
+                let names: Vec<&str> = flags.iter().map(Flag::as_imap_str).collect();
+                let _ = write!(head, " ({})", names.join(" "));
+            }
+            let _ = write!(head, " {}", literal(raw.len(), literal_plus));
+            head
+        })
+        .collect();
+    let Some(first) = heads.first() else {
+        return Ok(None);
+    };
+
+    let id = session
+        .run_command(format!("APPEND {}{first}", quote(folder)))
+        .await
+        .map_err(|e| Error::imap(&context, &e))?;
+    for (i, (raw, _)) in messages.iter().enumerate() {
+        let next = heads.get(i + 1).map_or("", String::as_str);
+        send_literal(session, &id, raw, next, literal_plus, &context).await?;
+    }
+    finish_checked(session, &id, &context, |_| {}).await
+}
+
+/// The announcement of a literal of `len` octets: `{N+}` with
+/// `literal_plus`, `{N}` otherwise.
+fn literal(len: usize, literal_plus: bool) -> String {
+    if literal_plus {
+        format!("{{{len}+}}")
+    } else {
+        format!("{{{len}}}")
+    }
+}
+
+/// Send the data of a literal announced at the end of command `id`'s
+/// text so far, followed by `rest` of the command and its line ending.
+/// Unless `literal_plus`, the literal is synchronizing, so the
+/// server's `+` continuation is awaited first.
+async fn send_literal(
+    session: &mut ImapSession,
+    id: &RequestId,
+    data: &[u8],
+    rest: &str,
+    literal_plus: bool,
+    context: &str,
+) -> Result<()> {
+    if !literal_plus {
+        await_continuation(session, id, context).await?;
+    }
+    let stream = session.get_mut();
+    stream.write_all(data).await?;
+    stream.write_all(rest.as_bytes()).await?;
+    stream.write_all(b"\r\n").await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// The UIDs listed in an `[APPENDUID <validity> <uid-set>]` code.
+fn appended_uids(code: Option<&ResponseCode<'_>>) -> Option<Vec<u32>> {
+    let Some(ResponseCode::AppendUid(_, members)) = code else {
+        return None;
+    };
+    Some(
+        members
+            .iter()
+            .flat_map(|member| match member {
+                UidSetMember::Uid(uid) => *uid..=*uid,
+                UidSetMember::UidRange(range) => range.clone(),
+            })
+            .collect(),
+    )
+}
+
 /// Certificate verifier that accepts all certificates
 /// (for Proton Bridge self-signed certs).
+///
+/// With `verify_hostname`, the certificate's subject alternative names
+/// must still match the server name; only the trust chain is ignored.
 #[derive(Debug)]
-struct DangerousVerifier;
+struct DangerousVerifier {
+    verify_hostname: bool,
+}
 
 impl rustls::client::danger::ServerCertVerifier for DangerousVerifier {
     fn verify_server_cert(
         &self,
-        _end_entity: &rustls::pki_types::CertificateDer<'_>,
+        end_entity: &rustls::pki_types::CertificateDer<'_>,
         _intermediates: &[rustls::pki_types::CertificateDer<'_>],
-        _server_name: &rustls::pki_types::ServerName<'_>,
+        server_name: &rustls::pki_types::ServerName<'_>,
         _ocsp_response: &[u8],
         _now: rustls::pki_types::UnixTime,
     ) -> std::result::Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        if self.verify_hostname {
+            let cert = rustls::server::ParsedCertificate::try_from(end_entity)?;
+            rustls::client::verify_server_name(&cert, server_name)?;
+        }
         Ok(rustls::client::danger::ServerCertVerified::assertion())
     }
 
@@ -124,3 +984,95 @@ impl rustls::client::danger::ServerCertVerifier for DangerousVerifier {
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_query_is_unchanged() {
+        assert_eq!(
+            utf8_search_query("SUBJECT \"hello\""),
+            ["SUBJECT \"hello\""]
+        );
+    }
+
+    #[test]
+    fn non_ascii_terms_become_literals() {
+        assert_eq!(
+            utf8_search_query("FROM bob SUBJECT \"Herr Müller\""),
+            ["CHARSET UTF-8 FROM bob SUBJECT {12}", "Herr Müller"]
+        );
+        assert_eq!(
+            utf8_search_query("OR (SUBJECT Müller) (SUBJECT \"a \\\"b\\\"\")"),
+            [
+                "CHARSET UTF-8 OR (SUBJECT {7}",
+                "Müller) (SUBJECT \"a \\\"b\\\"\")"
+            ]
+        );
+    }
+
+    #[test]
+    fn capability_names_ignore_case() {
+        let literal_plus = Capability::Atom("LITERAL+".into());
+        assert!(capability_matches(&literal_plus, "literal+"));
+        assert!(!capability_matches(&literal_plus, "LITERAL-"));
+
+        let plain = Capability::Auth("PLAIN".into());
+        assert!(capability_matches(&plain, "auth=plain"));
+        assert!(!capability_matches(&plain, "PLAIN"));
+
+        assert!(capability_matches(&Capability::Imap4rev1, "imap4rev1"));
+    }
+
+    #[test]
+    fn greeting_status_ignores_case() {
+        assert!(check_greeting(b"* OK IMAP4rev1 ready\r\n").is_ok());
+        assert!(check_greeting(b"* ok ready\r\n").is_ok());
+        assert!(check_greeting(b"* Preauth welcome\r\n").is_ok());
+        assert!(matches!(
+            check_greeting(b"* bye going away\r\n"),
+            Err(Error::ConnectionClosed)
+        ));
+        assert!(matches!(check_greeting(b"* NO\r\n"), Err(Error::Imap(_))));
+    }
+
+    #[tokio::test]
+    async fn tls_record_greeting_is_a_mode_mismatch() {
+        // A fatal handshake_failure alert.
+        let mut alert: &[u8] = &[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28];
+        match read_greeting(&mut alert).await {
+            Err(Error::Tls(msg)) => assert!(msg.starts_with("TLS mode mismatch"), "got {msg}"),
+            other => panic!("expected Error::Tls, got {other:?}"),
+        }
+
+        let mut greeting: &[u8] = b"* OK ready\r\n";
+        assert!(read_greeting(&mut greeting).await.is_ok());
+    }
+
+    #[test]
+    fn explicit_charset_is_kept() {
+        let query = "charset UTF-8 SUBJECT Müller";
+        assert_eq!(utf8_search_query(query), [query]);
+    }
+
+    #[test]
+    fn uid_range_goes_after_charset() {
+        assert_eq!(uid_range_query("1:5", ""), "UID 1:5");
+        assert_eq!(uid_range_query("1:5", "UNSEEN"), "UID 1:5 UNSEEN");
+        assert_eq!(
+            uid_range_query("1:5", "charset UTF-8 SUBJECT \"Müller\""),
+            "CHARSET UTF-8 UID 1:5 SUBJECT \"Müller\""
+        );
+        assert_eq!(
+            uid_range_query("7:*", "CHARSET UTF-8"),
+            "CHARSET UTF-8 UID 7:*"
+        );
+
+        // A non-ASCII query gets its CHARSET in front of the range too.
+        assert_eq!(
+            utf8_search_query(&uid_range_query("1:5", "SUBJECT Grüße")),
+            ["CHARSET UTF-8 UID 1:5 SUBJECT {7}", "Grüße"]
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! Proton conversation IDs
+//!
+//! Proton groups messages into conversations on the server, and the
+//! bridge exposes the grouping as an `X-Pm-ConversationID` header on
+//! every message. Matching on it reconstructs a conversation exactly,
+//! even when a client dropped `References` or `In-Reply-To`.
+
+use crate::headers;
+use email_extract::Email;
+
+/// The header carrying the Proton conversation ID.
+pub const CONVERSATION_ID_HEADER: &str = "X-Pm-ConversationID";
+
+/// The Proton conversation ID of a parsed email.
+///
+/// Returns `None` if the header is missing or empty.
+#[must_use]
+pub fn conversation_id(email: &Email) -> Option<&str> {
+    headers::email_values(email, CONVERSATION_ID_HEADER).find(|id| !id.is_empty())
+}
+
+/// The Proton conversation ID of a raw message or header block, as in
+/// [`conversation_id`]. [`RawFetch::conversation_id`](crate::RawFetch::conversation_id)
+/// does the same for a fetched message.
+#[must_use]
+pub fn conversation_id_raw(raw: &[u8]) -> Option<String> {
+    headers::header_value(raw, CONVERSATION_ID_HEADER).filter(|id| !id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_header_case_insensitively() {
+        let raw = b"From: a@x.com\r\nSubject: Hi\r\nx-pm-conversationid: abc123==\r\n\r\nBody";
+        assert_eq!(conversation_id_raw(raw).as_deref(), Some("abc123=="));
+        let email = email_extract::parse_email(1, raw).unwrap();
+        assert_eq!(conversation_id(&email), Some("abc123=="));
+    }
+
+    #[test]
+    fn missing_or_empty_is_none() {
+        assert_eq!(conversation_id_raw(b"Subject: Hi\r\n\r\nBody"), None);
+        assert_eq!(conversation_id_raw(b"X-Pm-ConversationID:\r\n\r\n"), None);
+        let email = email_extract::parse_email(1, b"From: a@x.com\r\nX-Pm-ConversationID:\r\n\r\n")
+            .unwrap();
+        assert_eq!(conversation_id(&email), None);
+    }
+}
@@ -0,0 +1,228 @@
+//! Duplicate detection by message content
+//!
+//! A forwarded or re-sent message gets a new `Message-ID`, so IDs
+//! alone miss such duplicates. [`content_hash`] keys a message on what
+//! a reader would see instead: sender, subject, and body text.
+//!
+//! Copies of one message in several folders (a labelled message is in
+//! each label's folder and in All Mail) do share a `Message-ID`;
+//! [`dedup_by_message_id`] merges those.
+
+use crate::folder::Folder;
+use email_extract::Email;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+/// Reply and forward prefixes stripped from subjects, lowercase.
+const SUBJECT_PREFIXES: [&str; 4] = ["re:", "fwd:", "fw:", "aw:"];
+
+/// FNV-1a 64-bit offset basis and prime.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Hash an email's content for duplicate detection.
+///
+/// The inputs are normalized first, so cosmetic differences do not
+/// change the hash:
+///
+/// - sender address: trimmed and lowercased;
+/// - subject: trimmed, lowercased, with any leading `Re:`/`Fwd:`/
+///   `Fw:`/`Aw:` prefixes removed;
+/// - body: the best text part, with quoted reply lines (starting with
+///   `>`) dropped, trailing whitespace trimmed from every line, and
+///   leading and trailing blank lines removed.
+///
+/// The hash is FNV-1a, so it is stable across runs and platforms and
+/// may be stored. It is not cryptographic.
+#[must_use]
+pub fn content_hash(email: &Email) -> u64 {
+    let from = email.from.address.trim().to_lowercase();
+    let subject = normalize_subject(&email.subject.original);
+    let body = normalize_body(email.body.best_text());
+
+    let mut hash = FNV_OFFSET;
+    for field in [from.as_str(), subject.as_str(), body.as_str()] {
+        for byte in field.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+/// Remove emails whose [`content_hash`] matches an earlier one,
+/// keeping the first occurrence and the original order.
+pub fn dedup_by_content(emails: &mut Vec<Email>) {
+    let mut seen = HashSet::new();
+    emails.retain(|email| seen.insert(content_hash(email)));
+}
+
+/// Merge messages fetched from several folders, keeping one copy per
+/// `Message-ID`.
+///
+/// `messages` pairs each message with its folder and `Message-ID`, in
+/// folder order. The first copy is kept, except that a copy in
+/// `all_mail` gives way to one from any other folder, which says more
+/// about where the message lives. Messages without a `Message-ID` are
+/// all kept; pass `None` for those rather than the synthetic ID
+/// [`Email::message_id`] holds when the header is missing.
+#[must_use]
+pub fn dedup_by_message_id(
+    messages: Vec<(Folder, Option<String>, Email)>,
+    all_mail: &Folder,
+) -> Vec<(Folder, Email)> {
+    let mut kept: Vec<(Folder, Email)> = Vec::with_capacity(messages.len());
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+
+    for (folder, message_id, email) in messages {
+        let Some(message_id) = message_id else {
+            kept.push((folder, email));
+            continue;
+        };
+        match by_id.entry(message_id) {
+            Entry::Vacant(entry) => {
+                entry.insert(kept.len());
+                kept.push((folder, email));
+            }
+            Entry::Occupied(entry) => {
+                let copy = &mut kept[*entry.get()];
+                if copy.0 == *all_mail && folder != *all_mail {
+                    *copy = (folder, email);
+                }
+            }
+        }
+    }
+    kept
+}
+
+fn normalize_subject(subject: &str) -> String {
+    let mut subject = subject.trim().to_lowercase();
+    while let Some(rest) = SUBJECT_PREFIXES
+        .iter()
+        .find_map(|prefix| subject.strip_prefix(prefix))
+    {
+        subject = rest.trim_start().to_string();
+    }
+    subject
+}
+
+fn normalize_body(body: &str) -> String {
+    let lines: Vec<&str> = body
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('>'))
+        .map(str::trim_end)
+        .collect();
+
+    let start = lines
+        .iter()
+        .position(|l| !l.is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .map_or(start, |i| i + 1);
+    lines[start..end].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use email_extract::parse_email;
+
+    fn email(uid: u32, message_id: &str, subject: &str, body: &str) -> Email {
+        let raw = format!(
+            "From: Alice <Alice@Example.com>\r\n\
+             To: bob@example.com\r\n\
+             Subject: {subject}\r\n\
+             Date: Mon, 01 Jan 2024 12:00:00 +0000\r\n\
+             Message-ID: <{message_id}>\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\
+             \r\n\
+             {body}"
+        );
+        parse_email(uid, raw.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn forwarded_copy_hashes_the_same() {
+        let original = email(1, "a@x", "Invoice 42", "Please pay.\r\n");
+        let forwarded = email(2, "b@x", "Fwd: RE: invoice 42", "\r\nPlease pay.  \r\n\r\n");
+        assert_eq!(content_hash(&original), content_hash(&forwarded));
+    }
+
+    #[test]
+    fn quoted_lines_are_ignored() {
+        let plain = email(1, "a@x", "Hi", "Sounds good.");
+        let quoting = email(2, "b@x", "Hi", "Sounds good.\r\n> earlier message\r\n");
+        assert_eq!(content_hash(&plain), content_hash(&quoting));
+    }
+
+    #[test]
+    fn different_bodies_differ() {
+        let a = email(1, "a@x", "Hi", "Yes");
+        let b = email(2, "b@x", "Hi", "No");
+        assert_ne!(content_hash(&a), content_hash(&b));
+    }
+
+    #[test]
+    fn dedup_keeps_first_occurrence() {
+        let mut emails = vec![
+            email(1, "a@x", "Hi", "Yes"),
+            email(2, "b@x", "Hi", "No"),
+            email(3, "c@x", "Re: Hi", "Yes"),
+        ];
+        dedup_by_content(&mut emails);
+        let uids: Vec<u32> = emails.iter().map(|e| e.uid).collect();
+        assert_eq!(uids, vec![1, 2]);
+    }
+
+    #[test]
+    fn message_id_dedup_prefers_folders_over_all_mail() {
+        let all_mail = Folder::custom("All Mail");
+        let work = Folder::custom("Labels/Work");
+        let messages = vec![
+            (
+                all_mail.clone(),
+                Some("<a@x>".into()),
+                email(1, "a@x", "A", "a"),
+            ),
+            (
+                all_mail.clone(),
+                Some("<b@x>".into()),
+                email(2, "b@x", "B", "b"),
+            ),
+            (all_mail.clone(), None, email(3, "", "C", "c")),
+            (
+                Folder::Inbox,
+                Some("<a@x>".into()),
+                email(7, "a@x", "A", "a"),
+            ),
+            (
+                work.clone(),
+                Some("<a@x>".into()),
+                email(9, "a@x", "A", "a"),
+            ),
+            (work, None, email(4, "", "C", "c")),
+        ];
+
+        let kept: Vec<(Folder, u32)> = dedup_by_message_id(messages, &all_mail)
+            .into_iter()
+            .map(|(folder, email)| (folder, email.uid))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                (Folder::Inbox, 7),
+                (all_mail.clone(), 2),
+                (all_mail, 3),
+                (Folder::custom("Labels/Work"), 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn subject_prefixes_are_stripped_repeatedly() {
+        assert_eq!(normalize_subject("  Re: FWD:  Re:Lunch "), "lunch");
+        assert_eq!(normalize_subject("Regarding lunch"), "regarding lunch");
+    }
+}
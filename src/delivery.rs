@@ -0,0 +1,56 @@
+//! A message's delivery path
+//!
+//! Every server that relays a message prepends a `Received:` header,
+//! so the stack of them records the route it took and when each hop
+//! happened: the first place to look when a message was delayed or
+//! filed as spam.
+
+use crate::headers;
+use email_extract::Email;
+
+/// The `Received:` headers of a parsed email, in the order they
+/// appear.
+///
+/// That is newest first: the first entry is the last hop (usually the
+/// bridge or the recipient's server), the last entry the first hop
+/// from the sender's side.
+#[must_use]
+pub fn delivery_path(email: &Email) -> Vec<String> {
+    headers::email_values(email, "Received")
+        .map(str::to_string)
+        .collect()
+}
+
+/// The `Received:` headers of a raw message (or header block),
+/// unfolded, as in [`delivery_path`].
+#[must_use]
+pub fn delivery_path_raw(raw: &[u8]) -> Vec<String> {
+    headers::header_values(raw, "Received")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_every_hop_in_order() {
+        let raw = b"Received: from relay.example.com by mx.proton.me;\r\n\
+            \tMon, 1 Jan 2024 12:00:05 +0000\r\n\
+            From: a@sender.example.com\r\n\
+            Subject: Hi\r\n\
+            Received: from sender.example.com by relay.example.com;\r\n\
+            \tMon, 1 Jan 2024 12:00:00 +0000\r\n\
+            \r\n\
+            Received: not a header";
+
+        let expected = vec![
+            "from relay.example.com by mx.proton.me; Mon, 1 Jan 2024 12:00:05 +0000",
+            "from sender.example.com by relay.example.com; Mon, 1 Jan 2024 12:00:00 +0000",
+        ];
+        assert_eq!(delivery_path_raw(raw), expected);
+        assert!(delivery_path_raw(b"Subject: Hi\r\n\r\n").is_empty());
+
+        let email = email_extract::parse_email(1, raw).unwrap();
+        assert_eq!(delivery_path(&email), expected);
+    }
+}
@@ -0,0 +1,84 @@
+//! The `ENVELOPE` FETCH item
+//!
+//! The server parses the main headers of a message itself and returns
+//! them as a structure (RFC 3501 Section 7.4.2), so listing messages
+//! needs neither the header block nor a MIME parser on our side.
+
+use async_imap::imap_proto::{Address, Envelope as ImapEnvelope};
+use serde::Serialize;
+
+/// A message's envelope: its main headers, as parsed by the server.
+///
+/// Strings are as sent: the subject and display names may still be
+/// RFC 2047 encoded words, and the date is the raw `Date:` value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Envelope {
+    /// The `Date:` header.
+    pub date: Option<String>,
+    /// The `Subject:` header.
+    pub subject: Option<String>,
+    /// The `From:` addresses.
+    pub from: Vec<EnvelopeAddress>,
+    /// The `Sender:` addresses (the server fills in `From:` when
+    /// there is none).
+    pub sender: Vec<EnvelopeAddress>,
+    /// The `Reply-To:` addresses (likewise defaulting to `From:`).
+    pub reply_to: Vec<EnvelopeAddress>,
+    /// The `To:` addresses.
+    pub to: Vec<EnvelopeAddress>,
+    /// The `Cc:` addresses.
+    pub cc: Vec<EnvelopeAddress>,
+    /// The `Bcc:` addresses.
+    pub bcc: Vec<EnvelopeAddress>,
+    /// The `In-Reply-To:` header.
+    pub in_reply_to: Option<String>,
+    /// The `Message-ID:` header.
+    pub message_id: Option<String>,
+}
+
+/// An address in an [`Envelope`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EnvelopeAddress {
+    /// The display name, if any.
+    pub name: Option<String>,
+    /// `mailbox@host`.
+    pub address: String,
+}
+
+impl Envelope {
+    /// Convert an `async-imap` envelope.
+    pub(crate) fn from_imap(envelope: &ImapEnvelope<'_>) -> Self {
+        Self {
+            date: envelope.date.as_deref().map(text),
+            subject: envelope.subject.as_deref().map(text),
+            from: addresses(envelope.from.as_deref()),
+            sender: addresses(envelope.sender.as_deref()),
+            reply_to: addresses(envelope.reply_to.as_deref()),
+            to: addresses(envelope.to.as_deref()),
+            cc: addresses(envelope.cc.as_deref()),
+            bcc: addresses(envelope.bcc.as_deref()),
+            in_reply_to: envelope.in_reply_to.as_deref().map(text),
+            message_id: envelope.message_id.as_deref().map(text),
+        }
+    }
+}
+
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).into_owned()
+}
+
+/// The addresses of a list. Group markers (addresses without a host,
+/// RFC 3501 Section 7.4.2) are skipped.
+fn addresses(list: Option<&[Address<'_>]>) -> Vec<EnvelopeAddress> {
+    list.unwrap_or_default()
+        .iter()
+        .filter_map(|address| {
+            let mailbox = address.mailbox.as_deref().map(text)?;
+            let host = address.host.as_deref().map(text)?;
+            Some(EnvelopeAddress {
+                name: address.name.as_deref().map(text),
+                address: format!("{mailbox}@{host}"),
+            })
+        })
+        .collect()
+}
@@ -1,5 +1,8 @@
 //! Error types for protonmail-client
 
+use crate::folder::Folder;
+use crate::response_code::ImapResponseCode;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,6 +10,28 @@ pub enum Error {
     #[error("IMAP error: {0}")]
     Imap(String),
 
+    /// A command failed with a response code (e.g. `NO [TRYCREATE]`)
+    /// telling why.
+    #[error("IMAP error: {text}")]
+    ImapCoded {
+        code: ImapResponseCode,
+        text: String,
+    },
+
+    #[error("Message UID {uid} not found in {folder}")]
+    MessageNotFound { folder: Folder, uid: u32 },
+
+    #[error("Message UID {uid} in {folder} is {size} bytes, over the {limit} byte limit")]
+    TooLarge {
+        folder: Folder,
+        uid: u32,
+        size: u32,
+        limit: usize,
+    },
+
+    #[error("Invalid label name: {0:?}")]
+    InvalidLabel(String),
+
     #[error("Email parsing error: {0}")]
     Parse(String),
 
@@ -18,6 +43,57 @@ pub enum Error {
 
     #[error("TLS error: {0}")]
     Tls(String),
+
+    #[error("Timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("Connection closed by server")]
+    ConnectionClosed,
+}
+
+impl Error {
+    /// The error for an IMAP command async-imap ran and reported as
+    /// failed. `context` (e.g. `"Fetch failed"`) leads the text.
+    ///
+    /// async-imap does not keep the response code, so commands whose
+    /// code callers act on are checked with [`Error::status`]
+    /// instead.
+    pub(crate) fn imap(context: &str, e: &async_imap::error::Error) -> Self {
+        Self::Imap(format!("{context}: {e}"))
+    }
+
+    /// The error for a tagged `NO` or `BAD` (`status`, e.g. `"NO"`):
+    /// [`Error::ImapCoded`] if the server gave a response code,
+    /// [`Error::Imap`] otherwise. `context` leads the text.
+    pub(crate) fn status(
+        context: &str,
+        status: &str,
+        code: Option<ImapResponseCode>,
+        information: Option<&str>,
+    ) -> Self {
+        let information = information.unwrap_or_default();
+        match code {
+            Some(code) if !information.starts_with('[') => Self::ImapCoded {
+                text: format!("{context}: {status} [{code}] {information}"),
+                code,
+            },
+            Some(code) => Self::ImapCoded {
+                text: format!("{context}: {status} {information}"),
+                code,
+            },
+            None => Self::Imap(format!("{context}: {status} {information}")),
+        }
+    }
+
+    /// The server's response code, if this is an
+    /// [`Error::ImapCoded`].
+    #[must_use]
+    pub const fn response_code(&self) -> Option<&ImapResponseCode> {
+        match self {
+            Self::ImapCoded { code, .. } => Some(code),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,262 @@
+//! Folder export
+//!
+//! Exports walk a whole folder, so bodies are fetched a chunk at a
+//! time and written out before the next chunk is requested: memory use
+//! stays flat however large the folder is.
+
+use crate::connection::{self, ImapSession};
+use crate::error::Result;
+use crate::fetch::{self, RawFetch};
+use crate::headers;
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// How many message bodies an export fetches per `UID FETCH`.
+pub const EXPORT_CHUNK: usize = 100;
+
+/// The tar block size; headers and padded contents are multiples of
+/// it.
+const TAR_BLOCK: usize = 512;
+
+/// Name of the tar entry listing every exported message.
+pub const TAR_MANIFEST: &str = "manifest.json";
+
+/// One message in a tar export's manifest.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    pub uid: u32,
+    pub flags: Vec<String>,
+    /// Arrival time (`INTERNALDATE`) in RFC 3339, if known.
+    pub date: Option<String>,
+}
+
+impl ManifestEntry {
+    pub fn new(raw: &RawFetch) -> Self {
+        Self {
+            uid: raw.uid,
+            flags: raw.flags.iter().map(ToString::to_string).collect(),
+            date: raw.internal_date.map(|d| d.to_rfc3339()),
+        }
+    }
+}
+
+/// The order in which an export visits messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FetchOrder {
+    /// Ascending UID: the order the server assigned, which is usually
+    /// but not necessarily chronological (imported or migrated mail
+    /// gets fresh UIDs).
+    #[default]
+    Uid,
+    /// Ascending `Date:` header, falling back to the arrival time
+    /// (`INTERNALDATE`). Messages with neither come last.
+    ///
+    /// Costs one extra FETCH of the `Date:` field of every message
+    /// before the first body is downloaded.
+    OldestFirst,
+}
+
+/// The UIDs of every message in the selected folder, in `order`.
+pub async fn ordered_uids(session: &mut ImapSession, order: FetchOrder) -> Result<Vec<u32>> {
+    let mut uids = connection::uid_search(session, "ALL").await?;
+    uids.sort_unstable();
+    if order == FetchOrder::Uid || uids.is_empty() {
+        return Ok(uids);
+    }
+
+    let fetches = fetch::fetch_items(
+        session,
+        &uids,
+        "(INTERNALDATE BODY.PEEK[HEADER.FIELDS (DATE)])",
+    )
+    .await?;
+    let mut dated: Vec<(Option<DateTime<FixedOffset>>, u32)> = fetches
+        .iter()
+        .map(|raw| {
+            let date = raw.header.as_deref().and_then(headers::date);
+            (date.or(raw.internal_date), raw.uid)
+        })
+        .collect();
+    dated.sort_by_key(|&(date, uid)| (date.is_none(), date, uid));
+    Ok(dated.into_iter().map(|(_, uid)| uid).collect())
+}
+
+/// Fetch `uids` with `items` and return them in the order given,
+/// rather than the server's.
+pub async fn fetch_in_order(
+    session: &mut ImapSession,
+    uids: &[u32],
+    items: &str,
+) -> Result<Vec<RawFetch>> {
+    let mut fetches = fetch::fetch_items(session, uids, items).await?;
+    fetches.sort_by_key(|raw| uids.iter().position(|&uid| uid == raw.uid));
+    Ok(fetches)
+}
+
+/// Append one message to an mbox stream in the `mboxrd` variant:
+/// a `From ` separator line dated with the arrival time, the message
+/// with `From ` lines (after any `>`) escaped by one more `>`, and an
+/// empty line. Line endings are written as LF.
+pub async fn write_mbox_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    raw: &RawFetch,
+) -> std::io::Result<()> {
+    let date = raw
+        .internal_date
+        .or_else(|| raw.body.as_deref().and_then(headers::date))
+        .map_or_else(
+            || "Thu Jan  1 00:00:00 1970".to_string(),
+            |d| {
+                d.with_timezone(&Utc)
+                    .format("%a %b %e %H:%M:%S %Y")
+                    .to_string()
+            },
+        );
+
+    let mut out = format!("From MAILER-DAEMON {date}\n").into_bytes();
+    let body = raw.body.as_deref().unwrap_or_default();
+    let body = body.strip_suffix(b"\n").unwrap_or(body);
+    for line in body.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let unquoted = line
+            .iter()
+            .position(|&b| b != b'>')
+            .map_or(&[][..], |i| &line[i..]);
+        if unquoted.starts_with(b"From ") {
+            out.push(b'>');
+        }
+        out.extend_from_slice(line);
+        out.push(b'\n');
+    }
+    out.push(b'\n');
+    writer.write_all(&out).await
+}
+
+/// Append one message to a tar stream as `<uid>.eml`, dated with its
+/// arrival time.
+pub async fn write_tar_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    raw: &RawFetch,
+) -> std::io::Result<()> {
+    let mtime = raw.internal_date.map_or(0, |d| d.timestamp());
+    let body = raw.body.as_deref().unwrap_or_default();
+    write_tar_entry(writer, &format!("{}.eml", raw.uid), mtime, body).await
+}
+
+/// Write the manifest entry and the end-of-archive marker.
+pub async fn finish_tar<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    manifest: &[ManifestEntry],
+) -> std::io::Result<()> {
+    let json = serde_json::to_vec_pretty(manifest)?;
+    write_tar_entry(writer, TAR_MANIFEST, Utc::now().timestamp(), &json).await?;
+    writer.write_all(&[0; 2 * TAR_BLOCK]).await
+}
+
+/// Write a regular file entry: a ustar header, then `data` padded to
+/// a whole block.
+async fn write_tar_entry<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    name: &str,
+    mtime: i64,
+    data: &[u8],
+) -> std::io::Result<()> {
+    writer
+        .write_all(&tar_header(name, data.len(), mtime))
+        .await?;
+    writer.write_all(data).await?;
+    let padding = (TAR_BLOCK - data.len() % TAR_BLOCK) % TAR_BLOCK;
+    writer.write_all(&vec![0; padding]).await
+}
+
+/// A POSIX ustar header for a regular file. `name` must be shorter
+/// than 100 bytes; times before 1970 are written as 0.
+fn tar_header(name: &str, size: usize, mtime: i64) -> [u8; TAR_BLOCK] {
+    let mut header = [0; TAR_BLOCK];
+    let mut put = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    put(0, name.as_bytes());
+    put(100, b"0000644\0");
+    put(108, b"0000000\0");
+    put(116, b"0000000\0");
+    put(124, format!("{size:011o}\0").as_bytes());
+    put(136, format!("{:011o}\0", mtime.max(0)).as_bytes());
+    // The checksum is computed with its own field filled with spaces.
+    put(148, b"        ");
+    put(156, b"0");
+    put(257, b"ustar\0");
+    put(263, b"00");
+
+    let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(body: &[u8]) -> RawFetch {
+        RawFetch {
+            uid: 1,
+            flags: Vec::new(),
+            internal_date: DateTime::parse_from_rfc3339("2024-01-01T13:00:00+01:00").ok(),
+            size: None,
+            header: None,
+            body: Some(body.to_vec()),
+            text: None,
+            attachments: Vec::new(),
+            text_parts: Vec::new(),
+            envelope: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn mbox_escapes_from_lines() {
+        let mut out = Vec::new();
+        let message = b"Subject: Hi\r\n\r\nFrom here\r\n>From there\r\nFromage\r\n";
+        write_mbox_message(&mut out, &raw(message)).await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "From MAILER-DAEMON Mon Jan  1 12:00:00 2024\n\
+             Subject: Hi\n\
+             \n\
+             >From here\n\
+             >>From there\n\
+             Fromage\n\
+             \n"
+        );
+    }
+
+    #[tokio::test]
+    async fn tar_entries_are_block_aligned() {
+        let mut out = Vec::new();
+        write_tar_message(&mut out, &raw(b"Subject: Hi\r\n\r\nHello\r\n"))
+            .await
+            .unwrap();
+        finish_tar(&mut out, &[ManifestEntry::new(&raw(b""))])
+            .await
+            .unwrap();
+
+        assert_eq!(out.len() % TAR_BLOCK, 0);
+        assert_eq!(&out[..6], b"1.eml\0");
+        assert_eq!(&out[124..136], b"00000000026\0");
+        assert_eq!(&out[TAR_BLOCK..TAR_BLOCK + 11], b"Subject: Hi");
+        assert_eq!(&out[2 * TAR_BLOCK..2 * TAR_BLOCK + 14], b"manifest.json\0");
+        assert!(out[out.len() - 2 * TAR_BLOCK..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn tar_header_checksum_matches() {
+        let header = tar_header("1.eml", 5, 1_704_110_400);
+        let stored = std::str::from_utf8(&header[148..154]).unwrap();
+        let mut blanked = header;
+        blanked[148..156].copy_from_slice(b"        ");
+        let sum: u32 = blanked.iter().map(|&b| u32::from(b)).sum();
+        assert_eq!(u32::from_str_radix(stored, 8).unwrap(), sum);
+        assert_eq!(&header[257..263], b"ustar\0");
+    }
+}
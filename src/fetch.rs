@@ -0,0 +1,624 @@
+//! Low-level FETCH support
+//!
+//! Every read operation eventually issues a `UID FETCH` and walks the
+//! returned stream. [`RawFetch`] captures whichever data items the
+//! server sent back, so higher-level methods only decide which items
+//! to request and how to interpret them.
+
+use crate::attachment::{self, Attachment};
+use crate::batch::BatchResult;
+use crate::body::{self, TextPart};
+use crate::connection::ImapSession;
+use crate::conversation;
+use crate::delivery;
+use crate::envelope::Envelope;
+use crate::error::{Error, Result};
+use crate::flag::Flag;
+use crate::folder::Folder;
+use crate::headers;
+use crate::metrics;
+use crate::unsubscribe::{self, UnsubscribeInfo};
+use async_imap::imap_proto::{MessageSection, SectionPath};
+use async_imap::types::{Fetch, Flag as ImapFlag, UnsolicitedResponse};
+use chrono::{DateTime, FixedOffset};
+use email_extract::{Email, parse_email};
+use futures::StreamExt;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// The FETCH macros (RFC 3501 Section 6.4.5): shorthands for common
+/// sets of data items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchMacro {
+    /// `FLAGS INTERNALDATE RFC822.SIZE`.
+    Fast,
+    /// [`FetchMacro::Fast`] plus `ENVELOPE`.
+    All,
+    /// [`FetchMacro::All`] plus `BODY` (the body structure without
+    /// extension data).
+    Full,
+}
+
+impl FetchMacro {
+    /// The macro as sent in a FETCH command.
+    #[must_use]
+    pub const fn as_imap_str(self) -> &'static str {
+        match self {
+            Self::Fast => "FAST",
+            Self::All => "ALL",
+            Self::Full => "FULL",
+        }
+    }
+}
+
+/// The data items returned for a single message by `UID FETCH`.
+///
+/// Fields are `None` (or empty) when the corresponding item was not
+/// requested or not returned by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawFetch {
+    /// The message UID.
+    pub uid: u32,
+    /// Message flags (`FLAGS`).
+    pub flags: Vec<Flag>,
+    /// Server arrival time (`INTERNALDATE`).
+    pub internal_date: Option<DateTime<FixedOffset>>,
+    /// Message size in octets (`RFC822.SIZE`).
+    pub size: Option<u32>,
+    /// The header section (`BODY[HEADER]` or
+    /// `BODY[HEADER.FIELDS (...)]`).
+    pub header: Option<Vec<u8>>,
+    /// The complete message (`BODY[]`).
+    pub body: Option<Vec<u8>>,
+    /// The message text without headers (`BODY[TEXT]`).
+    pub text: Option<Vec<u8>>,
+    /// Attachment parts listed in `BODYSTRUCTURE`.
+    pub attachments: Vec<Attachment>,
+    /// Inline plain-text and HTML parts listed in `BODYSTRUCTURE`.
+    pub text_parts: Vec<TextPart>,
+    /// The server-parsed main headers (`ENVELOPE`).
+    pub envelope: Option<Envelope>,
+}
+
+impl RawFetch {
+    /// Convert an `async-imap` FETCH response.
+    ///
+    /// Returns `None` for responses without a UID, which are
+    /// unsolicited flag updates rather than answers to our request.
+    fn from_fetch(fetch: &Fetch) -> Option<Self> {
+        Some(Self {
+            uid: fetch.uid?,
+            flags: fetch.flags().filter_map(|f| convert_flag(&f)).collect(),
+            internal_date: fetch.internal_date(),
+            size: fetch.size,
+            header: fetch.header().map(<[u8]>::to_vec),
+            body: fetch.body().map(<[u8]>::to_vec),
+            text: fetch.text().map(<[u8]>::to_vec),
+            attachments: fetch
+                .bodystructure()
+                .map(attachment::from_body_structure)
+                .unwrap_or_default(),
+            text_parts: fetch
+                .bodystructure()
+                .map(body::text_parts)
+                .unwrap_or_default(),
+            envelope: fetch.envelope().map(Envelope::from_imap),
+        })
+    }
+
+    /// The message IDs in the `References` header, oldest first.
+    ///
+    /// Read from the header section if it was fetched, otherwise from
+    /// the full message. Empty if neither was fetched or the message
+    /// has no `References` header.
+    #[must_use]
+    pub fn references(&self) -> Vec<String> {
+        self.header_source()
+            .and_then(|raw| headers::header_value(raw, "References"))
+            .map(|value| headers::message_ids(&value))
+            .unwrap_or_default()
+    }
+
+    /// How many messages deep this one sits in its reply chain: 0 for
+    /// a thread starter, 1 for a direct reply, and so on.
+    ///
+    /// Counted from `References`; a message that only carries
+    /// `In-Reply-To` counts as a direct reply.
+    #[must_use]
+    pub fn reply_depth(&self) -> usize {
+        let depth = self.references().len();
+        if depth == 0
+            && self
+                .header_source()
+                .and_then(|raw| headers::header_value(raw, "In-Reply-To"))
+                .is_some()
+        {
+            1
+        } else {
+            depth
+        }
+    }
+
+    /// The unsubscribe targets from `List-Unsubscribe`, read like
+    /// [`Self::references`].
+    #[must_use]
+    pub fn unsubscribe_info(&self) -> Option<UnsubscribeInfo> {
+        self.header_source()
+            .and_then(unsubscribe::unsubscribe_info_raw)
+    }
+
+    /// The Proton conversation ID from `X-Pm-ConversationID`, read
+    /// like [`Self::references`].
+    #[must_use]
+    pub fn conversation_id(&self) -> Option<String> {
+        self.header_source()
+            .and_then(conversation::conversation_id_raw)
+    }
+
+    /// The `Received:` headers, newest first (see
+    /// [`delivery_path`](crate::delivery_path)), read like
+    /// [`Self::references`].
+    #[must_use]
+    pub fn delivery_path(&self) -> Vec<String> {
+        self.header_source()
+            .map(delivery::delivery_path_raw)
+            .unwrap_or_default()
+    }
+
+    /// The raw bytes to read headers from.
+    fn header_source(&self) -> Option<&[u8]> {
+        self.header.as_deref().or(self.body.as_deref())
+    }
+
+    /// Total size of the returned header, body, and text sections.
+    fn data_len(&self) -> usize {
+        [&self.header, &self.body, &self.text]
+            .into_iter()
+            .flatten()
+            .map(Vec::len)
+            .sum()
+    }
+}
+
+/// Map an `async-imap` flag to our [`Flag`].
+///
+/// `\Recent` and `\*` are session/permission markers rather than
+/// message flags, so they are dropped.
+fn convert_flag(flag: &ImapFlag<'_>) -> Option<Flag> {
+    match flag {
+        ImapFlag::Seen => Some(Flag::Seen),
+        ImapFlag::Answered => Some(Flag::Answered),
+        ImapFlag::Flagged => Some(Flag::Flagged),
+        ImapFlag::Deleted => Some(Flag::Deleted),
+        ImapFlag::Draft => Some(Flag::Draft),
+        ImapFlag::Custom(kw) => Some(Flag::Keyword(kw.to_string())),
+        _ => None,
+    }
+}
+
+/// Format UIDs as a compact IMAP UID set, collapsing consecutive runs
+/// into ranges (`1:999,1005`).
+///
+/// The UIDs are sorted and deduplicated first, so the order of the
+/// input does not matter. Use it for commands whose result does not
+/// depend on UID order (FETCH, STORE, COPY); large sets stay short
+/// enough for servers that limit command length.
+pub fn uid_set_compact(uids: &[u32]) -> String {
+    let mut sorted = uids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for uid in sorted {
+        match runs.last_mut() {
+            Some((_, end)) if end.checked_add(1) == Some(uid) => *end = uid,
+            _ => runs.push((uid, uid)),
+        }
+    }
+
+    runs.iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}:{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Issue a single `UID FETCH` for `uids` on the selected folder.
+///
+/// UIDs that do not exist are silently absent from the result, in
+/// server order.
+///
+/// Servers may interleave unsolicited updates (`* <n> EXISTS`,
+/// `* <n> EXPUNGE`, flag changes without a UID) with the FETCH
+/// responses. Those are not part of the answer: they are skipped, and
+/// the ones queued by `async-imap` are discarded afterwards so that a
+/// long-lived session never fills its unsolicited-response channel.
+/// A new `EXISTS` is logged as a warning, since the result may then
+/// miss messages delivered during the FETCH.
+pub async fn fetch_items(
+    session: &mut ImapSession,
+    uids: &[u32],
+    items: &str,
+) -> Result<Vec<RawFetch>> {
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+    fetch_set(session, &uid_set_compact(uids), items).await
+}
+
+/// The flags of every message in the selected folder, by UID, from a
+/// single `UID FETCH 1:* (FLAGS)`.
+///
+/// `exists` is the message count SELECT reported; an empty folder is
+/// not fetched, since `1:*` names no message there.
+pub async fn flag_snapshot(
+    session: &mut ImapSession,
+    exists: u32,
+) -> Result<HashMap<u32, Vec<Flag>>> {
+    if exists == 0 {
+        return Ok(HashMap::new());
+    }
+    let fetches = fetch_set(session, "1:*", "(FLAGS)").await?;
+    Ok(fetches
+        .into_iter()
+        .map(|raw| (raw.uid, raw.flags))
+        .collect())
+}
+
+/// [`fetch_items`] for a UID set in wire format, e.g. `1:*`.
+async fn fetch_set(session: &mut ImapSession, set: &str, items: &str) -> Result<Vec<RawFetch>> {
+    let mut stream = session
+        .uid_fetch(set, items)
+        .await
+        .map_err(|e| Error::imap("Fetch failed", &e))?;
+
+    let mut fetches = Vec::new();
+    while let Some(item) = stream.next().await {
+        let fetch = item.map_err(|e| Error::imap("Fetch error", &e))?;
+        if let Some(raw) = RawFetch::from_fetch(&fetch) {
+            metrics::record_bytes(raw.data_len());
+            fetches.push(raw);
+        }
+    }
+    drop(stream);
+
+    discard_unsolicited(session);
+    Ok(fetches)
+}
+
+/// Fetch `BODY.PEEK[<section>]` from byte `offset` to its end.
+///
+/// Uses a partial fetch (`<origin.count>`, RFC 3501 Section 6.4.5)
+/// so only the bytes past `offset` cross the wire. `section` is an
+/// IMAP section spec: empty for the whole message, or e.g. `TEXT`,
+/// `2`, `1.2.MIME`. Returns `None` if the UID does not exist, and an
+/// empty vector once `offset` is at or past the end of the section.
+///
+/// # Errors
+///
+/// Returns an error if `section` is not a valid section spec, if
+/// `offset` does not fit a partial fetch origin, or if the FETCH
+/// fails.
+pub async fn fetch_section_from(
+    session: &mut ImapSession,
+    uid: u32,
+    section: &str,
+    offset: usize,
+) -> Result<Option<Vec<u8>>> {
+    let path = section_path(section)?;
+    let origin = u32::try_from(offset)
+        .map_err(|_| Error::Imap(format!("Offset {offset} is too large for a partial fetch")))?;
+    let items = format!("(BODY.PEEK[{section}]<{origin}.{}>)", u32::MAX);
+
+    let mut stream = session
+        .uid_fetch(uid.to_string(), &items)
+        .await
+        .map_err(|e| Error::imap("Fetch failed", &e))?;
+
+    let mut data = None;
+    while let Some(item) = stream.next().await {
+        let fetch = item.map_err(|e| Error::imap("Fetch error", &e))?;
+        if fetch.uid != Some(uid) {
+            continue;
+        }
+        let bytes = path
+            .as_ref()
+            .map_or_else(|| fetch.body(), |p| fetch.section(p));
+        data = Some(bytes.map(<[u8]>::to_vec).unwrap_or_default());
+    }
+    drop(stream);
+
+    discard_unsolicited(session);
+    if let Some(bytes) = &data {
+        metrics::record_bytes(bytes.len());
+    }
+    Ok(data)
+}
+
+/// Parse an IMAP section spec (`TEXT`, `2`, `1.2.MIME`, ...).
+///
+/// The empty spec (the whole message) is `None`.
+fn section_path(section: &str) -> Result<Option<SectionPath>> {
+    if section.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || Error::Imap(format!("Unsupported body section {section:?}"));
+
+    let mut parts = Vec::new();
+    let mut text = None;
+    for token in section.split('.') {
+        if text.is_some() {
+            return Err(invalid());
+        }
+        if let Ok(part) = token.parse::<u32>() {
+            parts.push(part);
+            continue;
+        }
+        text = Some(match token.to_ascii_uppercase().as_str() {
+            "HEADER" => MessageSection::Header,
+            "MIME" if !parts.is_empty() => MessageSection::Mime,
+            "TEXT" => MessageSection::Text,
+            _ => return Err(invalid()),
+        });
+    }
+
+    Ok(Some(if parts.is_empty() {
+        SectionPath::Full(text.ok_or_else(invalid)?)
+    } else {
+        SectionPath::Part(parts, text)
+    }))
+}
+
+/// Drop unsolicited responses queued during a command.
+///
+/// An `EXISTS` among them means messages were delivered (or expunged)
+/// while the command ran, so the caller's view of the folder is out
+/// of date; that is logged with the folder's new size.
+fn discard_unsolicited(session: &ImapSession) {
+    let mut exists = None;
+    while let Ok(response) = session.unsolicited_responses.try_recv() {
+        if let UnsolicitedResponse::Exists(n) = response {
+            exists = Some(n);
+        }
+    }
+    if let Some(n) = exists {
+        warn!("Folder changed during FETCH: it now holds {n} messages");
+    }
+}
+
+/// Drop UIDs whose `RFC822.SIZE` exceeds `max_size`.
+///
+/// Sizes are checked with one cheap FETCH before any body is
+/// downloaded. Skipped messages are logged. With no limit, `uids` is
+/// returned unchanged without talking to the server.
+pub async fn within_size(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uids: &[u32],
+    max_size: Option<usize>,
+) -> Result<Vec<u32>> {
+    Ok(split_by_size(session, folder, uids, max_size).await?.0)
+}
+
+/// Like [`within_size`], but also return the dropped UIDs, each with
+/// an [`Error::TooLarge`].
+async fn split_by_size(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uids: &[u32],
+    max_size: Option<usize>,
+) -> Result<(Vec<u32>, Vec<(u32, Error)>)> {
+    let Some(max_size) = max_size else {
+        return Ok((uids.to_vec(), Vec::new()));
+    };
+
+    let sizes = fetch_items(session, uids, "(RFC822.SIZE)").await?;
+    let mut kept = Vec::with_capacity(sizes.len());
+    let mut dropped = Vec::new();
+    for raw in &sizes {
+        match raw.size {
+            Some(size) if usize::try_from(size).map_or(true, |size| size > max_size) => {
+                warn!(
+                    "Skipping UID {} in {}: {} bytes exceeds the {} byte limit",
+                    raw.uid, folder, size, max_size
+                );
+                let error = Error::TooLarge {
+                    folder: folder.clone(),
+                    uid: raw.uid,
+                    size,
+                    limit: max_size,
+                };
+                dropped.push((raw.uid, error));
+            }
+            _ => kept.push(raw.uid),
+        }
+    }
+
+    Ok((kept, dropped))
+}
+
+/// Fetch and parse full messages.
+///
+/// Messages larger than `max_size` are skipped (see
+/// [`within_size`]). Messages that cannot be parsed are logged and
+/// skipped.
+pub async fn fetch_emails(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uids: &[u32],
+    max_size: Option<usize>,
+) -> Result<Vec<Email>> {
+    Ok(fetch_batch(session, folder, uids, max_size).await?.emails)
+}
+
+/// Like [`fetch_emails`], but also report the skipped UIDs and why
+/// each was skipped.
+pub async fn fetch_batch(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uids: &[u32],
+    max_size: Option<usize>,
+) -> Result<BatchResult> {
+    fetch_batch_with(session, folder, uids, max_size, "(BODY.PEEK[])").await
+}
+
+/// Like [`fetch_emails`], but with a plain `BODY[]`, which makes the
+/// server set `\Seen` on every message fetched. Skipped messages stay
+/// unread.
+pub async fn fetch_emails_marking_read(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uids: &[u32],
+    max_size: Option<usize>,
+) -> Result<Vec<Email>> {
+    Ok(
+        fetch_batch_with(session, folder, uids, max_size, "(BODY[])")
+            .await?
+            .emails,
+    )
+}
+
+async fn fetch_batch_with(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uids: &[u32],
+    max_size: Option<usize>,
+    items: &str,
+) -> Result<BatchResult> {
+    let (uids, mut failed) = split_by_size(session, folder, uids, max_size).await?;
+    let fetches = fetch_items(session, &uids, items).await?;
+
+    let mut emails = Vec::new();
+    for raw in &fetches {
+        match parse_fetch(raw) {
+            Ok(email) => emails.push(email),
+            Err(e) => {
+                warn!("Failed to fetch UID {} from {}: {}", raw.uid, folder, e);
+                failed.push((raw.uid, e));
+            }
+        }
+    }
+
+    Ok(BatchResult { emails, failed })
+}
+
+/// Fetch and parse a single message.
+pub async fn fetch_email(session: &mut ImapSession, folder: &Folder, uid: u32) -> Result<Email> {
+    fetch_one(session, folder, uid, "(BODY.PEEK[])").await
+}
+
+/// Fetch and parse a single message with a plain `BODY[]`, which
+/// makes the server set `\Seen` as a side effect.
+pub async fn fetch_email_marking_read(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uid: u32,
+) -> Result<Email> {
+    fetch_one(session, folder, uid, "(BODY[])").await
+}
+
+/// Fetch and parse a single message together with its flags, in one
+/// `UID FETCH`.
+pub async fn fetch_email_with_flags(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uid: u32,
+) -> Result<(Email, Vec<Flag>)> {
+    let raw = fetch_raw(session, folder, uid, "(FLAGS BODY.PEEK[])").await?;
+    let email = parse_fetch(&raw)?;
+    Ok((email, raw.flags))
+}
+
+async fn fetch_one(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uid: u32,
+    items: &str,
+) -> Result<Email> {
+    parse_fetch(&fetch_raw(session, folder, uid, items).await?)
+}
+
+/// Fetch `items` for a single UID.
+pub async fn fetch_raw(
+    session: &mut ImapSession,
+    folder: &Folder,
+    uid: u32,
+    items: &str,
+) -> Result<RawFetch> {
+    let fetches = fetch_items(session, &[uid], items).await?;
+
+    // A FETCH for a UID that no longer exists completes with a
+    // bare tagged OK, so an empty result means the message is gone.
+    fetches
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::MessageNotFound {
+            folder: folder.clone(),
+            uid,
+        })
+}
+
+/// Parse the `BODY[]` of a fetched message.
+pub fn parse_fetch(raw: &RawFetch) -> Result<Email> {
+    let body = raw
+        .body
+        .as_deref()
+        .ok_or_else(|| Error::Imap(format!("No body found for UID {}", raw.uid)))?;
+    parse_email(raw.uid, body).map_err(|e| Error::Parse(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_uid_set_collapses_runs() {
+        assert_eq!(uid_set_compact(&[1, 2, 3, 4]), "1:4");
+        assert_eq!(uid_set_compact(&[1, 2, 3, 7, 9, 10]), "1:3,7,9:10");
+    }
+
+    #[test]
+    fn compact_uid_set_keeps_singletons() {
+        assert_eq!(uid_set_compact(&[5]), "5");
+        assert_eq!(uid_set_compact(&[1, 3, 5]), "1,3,5");
+        assert_eq!(uid_set_compact(&[]), "");
+    }
+
+    #[test]
+    fn compact_uid_set_sorts_unsorted_input() {
+        assert_eq!(uid_set_compact(&[10, 2, 9, 1, 3, 2]), "1:3,9:10");
+        assert_eq!(
+            uid_set_compact(&[u32::MAX, u32::MAX - 1, 1]),
+            format!("1,{}:{}", u32::MAX - 1, u32::MAX)
+        );
+    }
+
+    #[test]
+    fn section_specs_parse() {
+        assert_eq!(section_path("").unwrap(), None);
+        assert_eq!(
+            section_path("text").unwrap(),
+            Some(SectionPath::Full(MessageSection::Text))
+        );
+        assert_eq!(
+            section_path("2").unwrap(),
+            Some(SectionPath::Part(vec![2], None))
+        );
+        assert_eq!(
+            section_path("1.2.MIME").unwrap(),
+            Some(SectionPath::Part(vec![1, 2], Some(MessageSection::Mime)))
+        );
+    }
+
+    #[test]
+    fn invalid_section_specs_are_rejected() {
+        for spec in ["MIME", "TEXT.1", "1.BOGUS", "1..2"] {
+            assert!(section_path(spec).is_err(), "{spec}");
+        }
+    }
+}
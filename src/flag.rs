@@ -3,7 +3,10 @@
 //! Provides a strongly-typed enum for IMAP flags instead of raw
 //! strings. Standard system flags have dedicated variants; arbitrary
 //! keyword flags use the `Keyword` variant.
+//!
+//! [`FlagDelta`] compares two snapshots of a folder's flags.
 
+use std::collections::HashMap;
 use std::fmt;
 
 /// An IMAP message flag.
@@ -54,6 +57,19 @@ impl Flag {
             Self::Keyword(kw) => kw,
         }
     }
+
+    /// The Proton label name carried by this flag, if any.
+    ///
+    /// Proton Bridge exposes labels as keyword flags. Keywords
+    /// starting with `$` (`$Junk`, `$Forwarded`, ...) are reserved
+    /// for system use by RFC 5788 and are not labels.
+    #[must_use]
+    pub fn label_name(&self) -> Option<&str> {
+        match self {
+            Self::Keyword(kw) if !kw.starts_with('$') => Some(kw),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Flag {
@@ -62,10 +78,126 @@ impl fmt::Display for Flag {
     }
 }
 
+/// How a folder's flags changed between two snapshots (UID to
+/// flags), e.g. after another device read or starred messages.
+///
+/// Only messages in both snapshots are compared; the others are
+/// listed in [`FlagDelta::added`] or [`FlagDelta::removed`]. All UID
+/// lists are ascending.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlagDelta {
+    /// Messages that gained `\Seen`.
+    pub newly_seen: Vec<u32>,
+    /// Messages that lost `\Seen`.
+    pub newly_unseen: Vec<u32>,
+    /// Messages that gained `\Flagged`.
+    pub newly_flagged: Vec<u32>,
+    /// Messages that lost `\Flagged`.
+    pub newly_unflagged: Vec<u32>,
+    /// Messages whose flags changed in any way, keywords (labels)
+    /// included.
+    pub changed: Vec<u32>,
+    /// Messages not in the previous snapshot.
+    pub added: Vec<u32>,
+    /// Messages no longer in the folder.
+    pub removed: Vec<u32>,
+    /// The current flags of every message, to diff against next time.
+    pub snapshot: HashMap<u32, Vec<Flag>>,
+}
+
+impl FlagDelta {
+    /// Compare `current` against `previous`.
+    #[must_use]
+    pub fn between(previous: &HashMap<u32, Vec<Flag>>, current: HashMap<u32, Vec<Flag>>) -> Self {
+        let mut delta = Self::default();
+        for (&uid, after) in &current {
+            let Some(before) = previous.get(&uid) else {
+                delta.added.push(uid);
+                continue;
+            };
+            match toggled(before, after, &Flag::Seen) {
+                Some(true) => delta.newly_seen.push(uid),
+                Some(false) => delta.newly_unseen.push(uid),
+                None => {}
+            }
+            match toggled(before, after, &Flag::Flagged) {
+                Some(true) => delta.newly_flagged.push(uid),
+                Some(false) => delta.newly_unflagged.push(uid),
+                None => {}
+            }
+            let same = before.len() == after.len() && before.iter().all(|f| after.contains(f));
+            if !same {
+                delta.changed.push(uid);
+            }
+        }
+        delta.removed = previous
+            .keys()
+            .filter(|uid| !current.contains_key(uid))
+            .copied()
+            .collect();
+
+        for uids in [
+            &mut delta.newly_seen,
+            &mut delta.newly_unseen,
+            &mut delta.newly_flagged,
+            &mut delta.newly_unflagged,
+            &mut delta.changed,
+            &mut delta.added,
+            &mut delta.removed,
+        ] {
+            uids.sort_unstable();
+        }
+        delta.snapshot = current;
+        delta
+    }
+
+    /// Whether nothing changed.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// `Some(true)` if `flag` was gained, `Some(false)` if it was lost.
+fn toggled(before: &[Flag], after: &[Flag], flag: &Flag) -> Option<bool> {
+    let (had, has) = (before.contains(flag), after.contains(flag));
+    (had != has).then_some(has)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn delta_between_snapshots() {
+        let label = Flag::Keyword("Work".to_string());
+        let previous = HashMap::from([
+            (1, vec![]),
+            (2, vec![Flag::Seen, Flag::Flagged]),
+            (3, vec![Flag::Seen]),
+            (4, vec![]),
+        ]);
+        let current = HashMap::from([
+            (1, vec![Flag::Seen]),
+            (2, vec![]),
+            (3, vec![label, Flag::Seen]),
+            (5, vec![]),
+        ]);
+
+        let delta = FlagDelta::between(&previous, current.clone());
+        assert_eq!(delta.newly_seen, vec![1]);
+        assert_eq!(delta.newly_unseen, vec![2]);
+        assert!(delta.newly_flagged.is_empty());
+        assert_eq!(delta.newly_unflagged, vec![2]);
+        assert_eq!(delta.changed, vec![1, 2, 3]);
+        assert_eq!(delta.added, vec![5]);
+        assert_eq!(delta.removed, vec![4]);
+        assert_eq!(delta.snapshot, current);
+        assert!(!delta.is_empty());
+
+        assert!(FlagDelta::between(&current, current.clone()).is_empty());
+    }
+
     #[test]
     fn system_flags() {
         assert_eq!(Flag::Seen.as_imap_str(), "\\Seen");
@@ -81,6 +213,18 @@ mod tests {
         assert_eq!(kw.as_imap_str(), "$Important");
     }
 
+    #[test]
+    fn label_name_for_plain_keyword() {
+        let kw = Flag::Keyword("Work".to_string());
+        assert_eq!(kw.label_name(), Some("Work"));
+    }
+
+    #[test]
+    fn label_name_skips_system_flags_and_reserved_keywords() {
+        assert_eq!(Flag::Seen.label_name(), None);
+        assert_eq!(Flag::Keyword("$Junk".to_string()).label_name(), None);
+    }
+
     #[test]
     fn display_matches_imap_str() {
         assert_eq!(format!("{}", Flag::Seen), "\\Seen");
@@ -49,6 +49,57 @@ impl Folder {
         Self::Custom(name.into())
     }
 
+    /// Every folder with a dedicated variant, i.e. all but
+    /// [`Folder::Custom`], e.g. for a backup tool to walk the standard
+    /// folders.
+    ///
+    /// ```
+    /// use protonmail_client::Folder;
+    ///
+    /// assert!(Folder::well_known().iter().all(Folder::is_well_known));
+    /// assert_eq!(Folder::well_known()[0], Folder::Inbox);
+    /// ```
+    #[must_use]
+    pub const fn well_known() -> [Self; 6] {
+        [
+            Self::Inbox,
+            Self::Sent,
+            Self::Drafts,
+            Self::Trash,
+            Self::Spam,
+            Self::Archive,
+        ]
+    }
+
+    /// Whether this is one of [`Folder::well_known`] rather than a
+    /// [`Folder::Custom`] folder.
+    #[must_use]
+    pub const fn is_well_known(&self) -> bool {
+        !matches!(self, Self::Custom(_))
+    }
+
+    /// Build a nested folder from its path components, joined with
+    /// the server's hierarchy `delimiter` (see
+    /// [`ProtonClient::folder_delimiter`](crate::ProtonClient::folder_delimiter)).
+    ///
+    /// ```
+    /// use protonmail_client::Folder;
+    ///
+    /// let f = Folder::nested(&["Folders", "Work"], '/');
+    /// assert_eq!(f.as_str(), "Folders/Work");
+    /// ```
+    #[must_use]
+    pub fn nested(components: &[&str], delimiter: char) -> Self {
+        Self::from(components.join(&delimiter.to_string()))
+    }
+
+    /// The path components of this folder, split on the server's
+    /// hierarchy `delimiter`.
+    #[must_use]
+    pub fn components(&self, delimiter: char) -> Vec<&str> {
+        self.as_str().split(delimiter).collect()
+    }
+
     /// The IMAP folder name as a string slice.
     #[must_use]
     pub fn as_str(&self) -> &str {
@@ -64,6 +115,26 @@ impl Folder {
     }
 }
 
+/// A folder's state as reported when it is selected.
+///
+/// Returned by [`ProtonClient::refresh`](crate::ProtonClient::refresh).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FolderStatus {
+    /// Number of messages in the folder (`EXISTS`).
+    pub exists: u32,
+    /// Number of messages with the `\Recent` flag (`RECENT`).
+    pub recent: u32,
+    /// Number of messages without the `\Seen` flag.
+    pub unseen: u32,
+    /// Sequence number of the first unseen message (`[UNSEEN n]`),
+    /// if the server reported one.
+    pub first_unseen: Option<u32>,
+    /// The UID the next delivered message will get (`[UIDNEXT n]`).
+    pub uid_next: Option<u32>,
+    /// The folder's UID validity (`[UIDVALIDITY n]`).
+    pub uid_validity: Option<u32>,
+}
+
 impl fmt::Display for Folder {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(self.as_str())
@@ -124,6 +195,15 @@ mod tests {
         assert_eq!(Folder::from("Archive"), Folder::Archive);
     }
 
+    #[test]
+    fn well_known_round_trips_by_name() {
+        for folder in Folder::well_known() {
+            assert!(folder.is_well_known());
+            assert_eq!(Folder::from(folder.as_str()), folder);
+        }
+        assert!(!Folder::custom("Work").is_well_known());
+    }
+
     #[test]
     fn from_str_unknown_becomes_custom() {
         assert_eq!(
@@ -132,6 +212,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn nested_round_trips_components() {
+        let f = Folder::nested(&["Folders", "Clients", "Acme"], '.');
+        assert_eq!(f.as_str(), "Folders.Clients.Acme");
+        assert_eq!(f.components('.'), vec!["Folders", "Clients", "Acme"]);
+        assert_eq!(f.components('/'), vec!["Folders.Clients.Acme"]);
+        assert_eq!(Folder::nested(&["Sent"], '/'), Folder::Sent);
+    }
+
     #[test]
     fn display_matches_as_str() {
         assert_eq!(format!("{}", Folder::Inbox), "INBOX");
@@ -0,0 +1,147 @@
+//! Minimal RFC 2822 header access
+//!
+//! [`Email`](crate::Email) has typed fields for the common headers
+//! only; the rest sit in `headers.all`. These helpers read individual
+//! fields from there, or from a raw header block (as returned by
+//! `BODY[HEADER]` or `BODY[HEADER.FIELDS (...)]`) or a full raw
+//! message, for the places that need something more specific.
+
+use chrono::{DateTime, FixedOffset};
+use email_extract::Email;
+
+/// The header block of a raw message: everything before the first
+/// empty line, or the whole input if there is none.
+pub fn header_block(raw: &[u8]) -> &[u8] {
+    let end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|i| i + 2)
+        .or_else(|| raw.windows(2).position(|w| w == b"\n\n").map(|i| i + 1));
+    end.map_or(raw, |end| &raw[..end])
+}
+
+/// All values of header `name` (case-insensitive), unfolded and
+/// trimmed, in order of appearance.
+pub fn header_values(raw: &[u8], name: &str) -> Vec<String> {
+    let text = String::from_utf8_lossy(header_block(raw));
+
+    let mut fields: Vec<String> = Vec::new();
+    for line in text.lines() {
+        if line.starts_with([' ', '\t']) {
+            // Continuation of the previous field (RFC 2822 folding).
+            if let Some(last) = fields.last_mut() {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+        } else {
+            fields.push(line.trim_end().to_string());
+        }
+    }
+
+    fields
+        .iter()
+        .filter_map(|field| {
+            let (field_name, value) = field.split_once(':')?;
+            field_name
+                .trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+        .collect()
+}
+
+/// The first value of header `name`, if present.
+pub fn header_value(raw: &[u8], name: &str) -> Option<String> {
+    header_values(raw, name).into_iter().next()
+}
+
+/// All values of header `name` (case-insensitive) in a parsed email,
+/// trimmed, in order of appearance.
+pub fn email_values<'a>(email: &'a Email, name: &'a str) -> impl Iterator<Item = &'a str> {
+    email
+        .headers
+        .all
+        .iter()
+        .filter(move |(field_name, _)| field_name.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// The message IDs (`<...>`) in a header value such as `References`,
+/// in order, brackets included.
+pub fn message_ids(value: &str) -> Vec<String> {
+    value
+        .split('<')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('>'))
+        .map(|(id, _)| format!("<{}>", id.trim()))
+        .collect()
+}
+
+/// Parse the `Date:` header, ignoring a trailing comment such as
+/// `(UTC)`.
+pub fn date(raw: &[u8]) -> Option<DateTime<FixedOffset>> {
+    parse_date(&header_value(raw, "Date")?)
+}
+
+/// Parse a `Date:` header value, as in [`date`].
+pub fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.split_once('(').map_or(value, |(date, _)| date).trim();
+    DateTime::parse_from_rfc2822(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &[u8] = b"From: a@b.com\r\n\
+        Subject: Hello\r\n\
+        Received: from a\r\n\
+        \tby b\r\n\
+        received: from c by d\r\n\
+        Date: Tue, 02 Jan 2024 09:30:00 +0100 (CET)\r\n\
+        \r\n\
+        Subject: not a header\r\n";
+
+    #[test]
+    fn header_block_stops_at_blank_line() {
+        let block = header_block(RAW);
+        assert!(block.ends_with(b"(CET)\r\n"));
+        assert_eq!(header_block(b"No-Body: yes"), b"No-Body: yes");
+    }
+
+    #[test]
+    fn values_are_unfolded_and_case_insensitive() {
+        assert_eq!(
+            header_values(RAW, "Received"),
+            vec!["from a by b".to_string(), "from c by d".to_string()]
+        );
+        assert_eq!(header_value(RAW, "subject").as_deref(), Some("Hello"));
+        assert_eq!(header_value(RAW, "X-Missing"), None);
+    }
+
+    #[test]
+    fn email_values_keep_duplicates() {
+        let email = email_extract::parse_email(1, RAW).unwrap();
+        assert_eq!(
+            email_values(&email, "received").collect::<Vec<_>>(),
+            ["from a by b", "from c by d"]
+        );
+        assert_eq!(email_values(&email, "X-Missing").next(), None);
+    }
+
+    #[test]
+    fn message_ids_in_order() {
+        assert_eq!(
+            message_ids("<a@x.com> <b@x.com>\t<c@x.com>"),
+            vec!["<a@x.com>", "<b@x.com>", "<c@x.com>"]
+        );
+        assert_eq!(message_ids("no ids here"), Vec::<String>::new());
+        assert_eq!(message_ids("<unterminated"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn date_ignores_comment() {
+        let date = date(RAW).unwrap();
+        assert_eq!(date.to_rfc3339(), "2024-01-02T09:30:00+01:00");
+    }
+}
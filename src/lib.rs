@@ -13,18 +13,69 @@
 //! - `ProtonClient<ReadWrite>` -- all of the above **plus** move,
 //!   flag, archive, and unmark
 //!
+//! [`ProtonClient::session`] opens a [`ProtonSession`] that keeps one
+//! connection open across several operations.
+//!
 //! Returns parsed [`Email`] structs from the [`email_extract`] crate.
 
+mod attachment;
+mod auto_reply;
+mod batch;
+mod body;
+mod cache;
 mod client;
 mod config;
 mod connection;
+mod conversation;
+mod dedup;
+mod delivery;
+mod envelope;
 mod error;
+mod export;
+mod fetch;
 mod flag;
 mod folder;
+mod headers;
+mod list_item;
+mod metrics;
+mod move_outcome;
+mod pgp;
+mod response_code;
+mod rule;
+mod search;
+mod session;
+mod special_use;
+mod tasks;
+mod thread;
+mod unsubscribe;
+mod watch;
 
+pub use attachment::Attachment;
+pub use auto_reply::{is_auto_reply, is_auto_reply_raw};
+pub use batch::BatchResult;
+pub use body::{BodyPreference, BodyText, TextPart};
 pub use client::{ProtonClient, ReadOnly, ReadWrite};
-pub use config::ImapConfig;
+pub use config::{ConnectionSecurity, ImapConfig, MissingFolderBehavior};
+pub use connection::TlsInfo;
+pub use conversation::{conversation_id, conversation_id_raw};
+pub use dedup::{content_hash, dedup_by_content, dedup_by_message_id};
+pub use delivery::{delivery_path, delivery_path_raw};
 pub use email_extract::Email;
+pub use envelope::{Envelope, EnvelopeAddress};
 pub use error::{Error, Result};
-pub use flag::Flag;
-pub use folder::Folder;
+pub use export::FetchOrder;
+pub use fetch::{FetchMacro, RawFetch};
+pub use flag::{Flag, FlagDelta};
+pub use folder::{Folder, FolderStatus};
+pub use list_item::ListItem;
+pub use metrics::{ConnectTiming, MetricEvent, MetricsCallback};
+pub use move_outcome::MoveOutcome;
+pub use pgp::{is_encrypted, is_encrypted_raw};
+pub use response_code::ImapResponseCode;
+pub use rule::RuleOutcome;
+pub use search::SearchQuery;
+pub use session::{ListedFolder, ProtonSession};
+pub use special_use::SpecialUse;
+pub use thread::{ThreadMessage, references, reply_depth};
+pub use unsubscribe::{UnsubscribeInfo, unsubscribe_info, unsubscribe_info_raw};
+pub use watch::WatchEvent;
@@ -0,0 +1,119 @@
+//! One row of a message list
+//!
+//! A mail client's message list needs a handful of fields per
+//! message, all of which the server can provide without sending any
+//! part of the message itself: `ENVELOPE` for the headers, `FLAGS`,
+//! `RFC822.SIZE`, and `BODYSTRUCTURE` to tell whether there are
+//! attachments.
+
+use crate::envelope::EnvelopeAddress;
+use crate::fetch::RawFetch;
+use crate::flag::Flag;
+use crate::headers;
+use chrono::{DateTime, FixedOffset};
+
+/// The FETCH items [`ListItem::from_fetch`] needs.
+pub const LIST_FIELDS: &str = "(UID ENVELOPE FLAGS RFC822.SIZE BODYSTRUCTURE)";
+
+/// A message as shown in a message list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListItem {
+    /// The message UID.
+    pub uid: u32,
+    /// The `Date:` header, if present and well-formed.
+    pub date: Option<DateTime<FixedOffset>>,
+    /// The first `From:` address.
+    pub from: Option<EnvelopeAddress>,
+    /// The `Subject:` header, possibly still RFC 2047 encoded.
+    pub subject: Option<String>,
+    /// Message flags.
+    pub flags: Vec<Flag>,
+    /// Whether the body structure lists any attachment.
+    pub has_attachments: bool,
+    /// Message size in octets.
+    pub size: Option<u32>,
+}
+
+impl ListItem {
+    /// Build a list row from a message fetched with [`LIST_FIELDS`].
+    pub(crate) fn from_fetch(raw: RawFetch) -> Self {
+        let envelope = raw.envelope.unwrap_or_default();
+        Self {
+            uid: raw.uid,
+            date: envelope.date.as_deref().and_then(headers::parse_date),
+            from: envelope.from.into_iter().next(),
+            subject: envelope.subject,
+            flags: raw.flags,
+            has_attachments: !raw.attachments.is_empty(),
+            size: raw.size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attachment::Attachment;
+    use crate::envelope::Envelope;
+
+    fn raw_fetch() -> RawFetch {
+        RawFetch {
+            uid: 7,
+            flags: vec![Flag::Seen],
+            internal_date: None,
+            size: Some(1234),
+            header: None,
+            body: None,
+            text: None,
+            attachments: vec![],
+            text_parts: vec![],
+            envelope: None,
+        }
+    }
+
+    #[test]
+    fn reads_fields_from_envelope() {
+        let mut raw = raw_fetch();
+        raw.envelope = Some(Envelope {
+            date: Some("Tue, 02 Jan 2024 09:30:00 +0100 (CET)".into()),
+            subject: Some("Hello".into()),
+            from: vec![
+                EnvelopeAddress {
+                    name: Some("Alice".into()),
+                    address: "alice@example.com".into(),
+                },
+                EnvelopeAddress {
+                    name: None,
+                    address: "bob@example.com".into(),
+                },
+            ],
+            ..Envelope::default()
+        });
+
+        let item = ListItem::from_fetch(raw);
+        assert_eq!(item.uid, 7);
+        assert_eq!(item.date.unwrap().to_rfc3339(), "2024-01-02T09:30:00+01:00");
+        assert_eq!(item.from.unwrap().address, "alice@example.com");
+        assert_eq!(item.subject.as_deref(), Some("Hello"));
+        assert_eq!(item.flags, vec![Flag::Seen]);
+        assert!(!item.has_attachments);
+        assert_eq!(item.size, Some(1234));
+    }
+
+    #[test]
+    fn missing_envelope_leaves_headers_empty() {
+        let mut raw = raw_fetch();
+        raw.attachments = vec![Attachment {
+            part: "2".into(),
+            filename: Some("a.pdf".into()),
+            content_type: "application/pdf".into(),
+            size: 10,
+        }];
+
+        let item = ListItem::from_fetch(raw);
+        assert_eq!(item.date, None);
+        assert_eq!(item.from, None);
+        assert_eq!(item.subject, None);
+        assert!(item.has_attachments);
+    }
+}
@@ -0,0 +1,124 @@
+//! Operation-level metrics
+//!
+//! When [`ImapConfig::metrics`] is set, every IMAP operation performed
+//! by `ProtonClient` reports a [`MetricEvent`] to the callback once it
+//! finishes. Wire the callback to Prometheus, statsd, or similar to
+//! track bridge latency and error rates per operation.
+//!
+//! Each event also carries the [`ConnectTiming`] of the connection the
+//! operation opened, to tell a slow TCP connect, TLS handshake, or
+//! bridge login from a slow command.
+//!
+//! Without a callback the operation runs unwrapped, so metrics cost
+//! nothing when unused.
+//!
+//! [`ImapConfig::metrics`]: crate::ImapConfig::metrics
+
+use crate::error::Result;
+use crate::folder::Folder;
+use std::cell::Cell;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Callback invoked with a [`MetricEvent`] after each operation.
+pub type MetricsCallback = Arc<dyn Fn(MetricEvent) + Send + Sync>;
+
+/// A completed IMAP operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricEvent {
+    /// The `ProtonClient` method that ran, e.g. `"search"` or
+    /// `"move_to_folder"`.
+    pub op: &'static str,
+    /// The folder the operation worked on (the source folder for
+    /// moves), if any.
+    pub folder: Option<Folder>,
+    /// Wall-clock time including connect, login, and logout.
+    pub duration: Duration,
+    /// Message data received (headers and bodies), in bytes.
+    pub bytes: u64,
+    /// Whether the operation returned `Ok`.
+    pub success: bool,
+    /// Per-phase timing of the connection the operation opened (the
+    /// last one, if it opened several). `None` if it did not get as
+    /// far as logging in.
+    pub connect: Option<ConnectTiming>,
+}
+
+/// How long each phase of opening a session took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectTiming {
+    /// The TCP connect.
+    pub tcp_connect: Duration,
+    /// Waiting for the greeting and issuing STARTTLS; `None` with
+    /// implicit TLS.
+    pub starttls: Option<Duration>,
+    /// The TLS handshake (with implicit TLS, including the greeting).
+    pub tls_handshake: Duration,
+    /// The LOGIN command.
+    pub login: Duration,
+}
+
+/// What the operation currently being observed has recorded.
+#[derive(Default)]
+struct Observed {
+    /// Bytes fetched.
+    bytes: Cell<u64>,
+    /// Timing of the last connection opened.
+    connect: Cell<Option<ConnectTiming>>,
+}
+
+tokio::task_local! {
+    /// The operation currently being observed.
+    static OBSERVED: Observed;
+}
+
+/// Count `n` fetched bytes towards the operation being observed.
+///
+/// Does nothing outside [`observe`].
+pub fn record_bytes(n: usize) {
+    let _ = OBSERVED.try_with(|observed| {
+        observed
+            .bytes
+            .set(observed.bytes.get().saturating_add(n as u64));
+    });
+}
+
+/// Record the timing of a connection opened by the operation being
+/// observed.
+///
+/// Does nothing outside [`observe`].
+pub fn record_connect(timing: ConnectTiming) {
+    let _ = OBSERVED.try_with(|observed| observed.connect.set(Some(timing)));
+}
+
+/// Run `operation` and report it to `callback`.
+pub async fn observe<T>(
+    callback: Option<&MetricsCallback>,
+    op: &'static str,
+    folder: Option<&Folder>,
+    operation: impl Future<Output = Result<T>> + Send,
+) -> Result<T> {
+    let Some(callback) = callback else {
+        return operation.await;
+    };
+
+    let start = Instant::now();
+    let (result, bytes, connect) = OBSERVED
+        .scope(Observed::default(), async {
+            let result = operation.await;
+            OBSERVED.with(|observed| (result, observed.bytes.get(), observed.connect.get()))
+        })
+        .await;
+
+    callback(MetricEvent {
+        op,
+        folder: folder.cloned(),
+        duration: start.elapsed(),
+        bytes,
+        success: result.is_ok(),
+        connect,
+    });
+
+    result
+}
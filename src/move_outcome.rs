@@ -0,0 +1,18 @@
+//! The result of a move
+//!
+//! A move that is retried after a flaky first attempt may find its
+//! COPY already done. [`MoveOutcome`] tells such a retry apart from a
+//! fresh move.
+
+/// What [`ProtonClient::move_to_folder`](crate::ProtonClient::move_to_folder)
+/// did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveOutcome {
+    /// The message was copied to the destination and removed from
+    /// the source.
+    Moved,
+    /// The destination already held a copy (same `Message-ID`), so
+    /// nothing was copied; the message was only removed from the
+    /// source.
+    AlreadyInDestination,
+}
@@ -0,0 +1,107 @@
+//! Detection of messages that are still PGP-encrypted
+//!
+//! Proton Bridge decrypts Proton-encrypted mail before serving it over
+//! IMAP, but messages encrypted to the user's key by an external
+//! sender reach the client as PGP blocks the bridge cannot open.
+//! These helpers let callers recognise and skip such messages.
+
+use email_extract::Email;
+
+/// The first line of an ASCII-armored PGP message (RFC 4880
+/// Section 6.2).
+const ARMOR_HEADER: &str = "-----BEGIN PGP MESSAGE-----";
+
+/// Whether a parsed email's body is an ASCII-armored PGP message.
+///
+/// This only inspects the decoded body text. PGP/MIME messages
+/// (`multipart/encrypted`) usually have no readable text part at all,
+/// so use [`is_encrypted_raw`] when the raw message is available.
+#[must_use]
+pub fn is_encrypted(email: &Email) -> bool {
+    email.body.best_text().lines().any(is_armor_line)
+}
+
+/// Whether a raw RFC 2822 message is PGP-encrypted.
+///
+/// Detects both PGP/MIME (RFC 3156: a top-level
+/// `Content-Type: multipart/encrypted` with
+/// `protocol="application/pgp-encrypted"`) and inline PGP (an
+/// armored block in the body).
+#[must_use]
+pub fn is_encrypted_raw(raw: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(raw);
+    let header = text
+        .split_once("\r\n\r\n")
+        .or_else(|| text.split_once("\n\n"))
+        .map_or_else(|| text.as_ref(), |(header, _)| header);
+
+    is_pgp_mime(header) || text.lines().any(is_armor_line)
+}
+
+/// Whether the header block declares a PGP/MIME encrypted body.
+fn is_pgp_mime(header: &str) -> bool {
+    // Unfold continuation lines so parameters on the next line are
+    // seen as part of the Content-Type value.
+    let unfolded = header
+        .replace("\r\n", "\n")
+        .replace("\n ", " ")
+        .replace("\n\t", " ")
+        .to_ascii_lowercase();
+
+    unfolded.lines().any(|line| {
+        line.strip_prefix("content-type:").is_some_and(|value| {
+            value.trim_start().starts_with("multipart/encrypted")
+                && value.contains("application/pgp-encrypted")
+        })
+    })
+}
+
+/// Whether `line` opens an armored PGP message.
+fn is_armor_line(line: &str) -> bool {
+    line.trim_end() == ARMOR_HEADER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_message_is_not_encrypted() {
+        let raw = b"From: a@b.com\r\nContent-Type: text/plain\r\n\r\nHello";
+        assert!(!is_encrypted_raw(raw));
+    }
+
+    #[test]
+    fn inline_armor_is_encrypted() {
+        let raw = b"From: a@b.com\r\n\r\n-----BEGIN PGP MESSAGE-----\r\n\r\nhQEMA...\r\n-----END PGP MESSAGE-----\r\n";
+        assert!(is_encrypted_raw(raw));
+    }
+
+    #[test]
+    fn quoted_armor_is_not_encrypted() {
+        let raw =
+            b"From: a@b.com\r\n\r\nThe block starts with -----BEGIN PGP MESSAGE----- usually.";
+        assert!(!is_encrypted_raw(raw));
+    }
+
+    #[test]
+    fn pgp_mime_with_folded_header_is_encrypted() {
+        let raw = b"From: a@b.com\r\n\
+            Content-Type: multipart/encrypted;\r\n\
+            \tprotocol=\"application/pgp-encrypted\"; boundary=\"x\"\r\n\
+            \r\n\
+            --x\r\n\
+            Content-Type: application/pgp-encrypted\r\n\
+            \r\n\
+            Version: 1\r\n\
+            --x--\r\n";
+        assert!(is_encrypted_raw(raw));
+    }
+
+    #[test]
+    fn other_multipart_is_not_encrypted() {
+        let raw =
+            b"Content-Type: multipart/signed; protocol=\"application/pgp-signature\"\r\n\r\nbody";
+        assert!(!is_encrypted_raw(raw));
+    }
+}
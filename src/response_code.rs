@@ -0,0 +1,177 @@
+//! Response codes of failed commands
+//!
+//! A `NO` or `BAD` may carry a bracketed code (RFC 3501 Section 7.1,
+//! RFC 5530), e.g. `NO [TRYCREATE] Folder not found`, saying why the
+//! command failed in a form a program can act on. They are kept on
+//! [`Error::ImapCoded`](crate::Error::ImapCoded).
+
+use async_imap::imap_proto::ResponseCode;
+use std::fmt;
+
+/// The response code of a failed command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImapResponseCode {
+    /// `TRYCREATE`: the target folder does not exist, and creating
+    /// it first should make the command succeed.
+    TryCreate,
+    /// `NONEXISTENT`: the folder does not exist.
+    NonExistent,
+    /// `ALREADYEXISTS`: a folder with that name already exists.
+    AlreadyExists,
+    /// `OVERQUOTA`: the account is over its storage quota.
+    OverQuota,
+    /// `LIMIT`: a server limit (e.g. message size) was hit.
+    Limit,
+    /// `NOPERM`: the user may not do this to the folder.
+    NoPerm,
+    /// `INUSE`: the folder or message is locked by someone else.
+    InUse,
+    /// `UNAVAILABLE`: a temporary failure; retrying later may work.
+    Unavailable,
+    /// `AUTHENTICATIONFAILED`: wrong credentials.
+    AuthenticationFailed,
+    /// `SERVERBUG`: the server hit an internal error.
+    ServerBug,
+    /// `CANNOT`: the server will never do this.
+    Cannot,
+    /// Any other code, upper-cased as sent.
+    Other(String),
+}
+
+impl ImapResponseCode {
+    /// Parse a code atom, ignoring case.
+    #[must_use]
+    pub fn parse(atom: &str) -> Self {
+        let atom = atom.to_ascii_uppercase();
+        match atom.as_str() {
+            "TRYCREATE" => Self::TryCreate,
+            "NONEXISTENT" => Self::NonExistent,
+            "ALREADYEXISTS" => Self::AlreadyExists,
+            "OVERQUOTA" => Self::OverQuota,
+            "LIMIT" => Self::Limit,
+            "NOPERM" => Self::NoPerm,
+            "INUSE" => Self::InUse,
+            "UNAVAILABLE" => Self::Unavailable,
+            "AUTHENTICATIONFAILED" => Self::AuthenticationFailed,
+            "SERVERBUG" => Self::ServerBug,
+            "CANNOT" => Self::Cannot,
+            _ => Self::Other(atom),
+        }
+    }
+
+    /// The code as spelled on the wire, e.g. `"TRYCREATE"`.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::TryCreate => "TRYCREATE",
+            Self::NonExistent => "NONEXISTENT",
+            Self::AlreadyExists => "ALREADYEXISTS",
+            Self::OverQuota => "OVERQUOTA",
+            Self::Limit => "LIMIT",
+            Self::NoPerm => "NOPERM",
+            Self::InUse => "INUSE",
+            Self::Unavailable => "UNAVAILABLE",
+            Self::AuthenticationFailed => "AUTHENTICATIONFAILED",
+            Self::ServerBug => "SERVERBUG",
+            Self::Cannot => "CANNOT",
+            Self::Other(atom) => atom,
+        }
+    }
+
+    /// The code of a tagged `NO` or `BAD`, from the `code` the
+    /// parser recognized or else the `[CODE]` that starts
+    /// `information`.
+    pub(crate) fn from_response(
+        code: Option<&ResponseCode<'_>>,
+        information: Option<&str>,
+    ) -> Option<Self> {
+        match code {
+            Some(ResponseCode::TryCreate) => Some(Self::TryCreate),
+            Some(ResponseCode::Alert) => Some(Self::Other("ALERT".into())),
+            Some(ResponseCode::BadCharset(_)) => Some(Self::Other("BADCHARSET".into())),
+            Some(ResponseCode::Parse) => Some(Self::Other("PARSE".into())),
+            _ => information.and_then(from_text),
+        }
+    }
+}
+
+impl fmt::Display for ImapResponseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The code at the start of a response's text, e.g. `NONEXISTENT`
+/// in `[NONEXISTENT] No such folder`. Codes the parser does not know
+/// are left there. A bracket further into the text is part of the
+/// human-readable message, not a code.
+fn from_text(text: &str) -> Option<ImapResponseCode> {
+    let rest = text.strip_prefix('[')?;
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+        .unwrap_or(rest.len());
+    let atom = &rest[..end];
+    (!atom.is_empty()).then(|| ImapResponseCode::parse(atom))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_atoms() {
+        assert_eq!(
+            ImapResponseCode::parse("trycreate"),
+            ImapResponseCode::TryCreate
+        );
+        assert_eq!(
+            ImapResponseCode::parse("NONEXISTENT"),
+            ImapResponseCode::NonExistent
+        );
+        assert_eq!(
+            ImapResponseCode::parse("Alert"),
+            ImapResponseCode::Other("ALERT".into())
+        );
+        assert_eq!(ImapResponseCode::OverQuota.to_string(), "OVERQUOTA");
+    }
+
+    #[test]
+    fn finds_code_in_text() {
+        assert_eq!(
+            from_text("[NONEXISTENT] No such folder"),
+            Some(ImapResponseCode::NonExistent)
+        );
+        assert_eq!(
+            from_text("[READ-ONLY] Mailbox is read-only"),
+            Some(ImapResponseCode::Other("READ-ONLY".into()))
+        );
+        assert_eq!(from_text("Folder not found"), None);
+        assert_eq!(from_text("[] Empty"), None);
+    }
+
+    #[test]
+    fn ignores_brackets_inside_the_text() {
+        assert_eq!(from_text("Folder [old] missing"), None);
+        assert_eq!(from_text("Name \"[TRYCREATE]\" is odd"), None);
+    }
+
+    #[test]
+    fn parsed_code_comes_first() {
+        assert_eq!(
+            ImapResponseCode::from_response(
+                Some(&ResponseCode::TryCreate),
+                Some("Folder not found")
+            ),
+            Some(ImapResponseCode::TryCreate)
+        );
+        assert_eq!(
+            ImapResponseCode::from_response(None, Some("[OVERQUOTA] Mailbox full")),
+            Some(ImapResponseCode::OverQuota)
+        );
+        assert_eq!(
+            ImapResponseCode::from_response(Some(&ResponseCode::ReadOnly), Some("Read-only")),
+            None
+        );
+        assert_eq!(ImapResponseCode::from_response(None, None), None);
+    }
+}
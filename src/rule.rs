@@ -0,0 +1,18 @@
+//! Filter rule results
+//!
+//! [`ProtonClient::apply_rule`](crate::ProtonClient::apply_rule)
+//! combines a flag update and a move into one session, the way mail
+//! filters usually act. [`RuleOutcome`] reports what was done.
+
+use crate::flag::Flag;
+use crate::folder::Folder;
+
+/// What [`ProtonClient::apply_rule`](crate::ProtonClient::apply_rule)
+/// applied to a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleOutcome {
+    /// Flags added to the message.
+    pub flags_set: Vec<Flag>,
+    /// The folder the message was moved to, if any.
+    pub moved_to: Option<Folder>,
+}
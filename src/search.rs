@@ -0,0 +1,220 @@
+//! IMAP search query builder
+//!
+//! IMAP search keys (RFC 3501 Section 6.4.4) are combined with AND by
+//! juxtaposition and with a prefix `OR` taking exactly two keys, which
+//! makes hand-written queries with `OR` easy to get wrong. [`SearchQuery`]
+//! builds the string and groups multi-key operands in parentheses.
+
+use chrono::NaiveDate;
+use std::fmt;
+
+/// An IMAP `SEARCH` query.
+///
+/// Each method adds a key that must also match (implicit AND);
+/// [`SearchQuery::or`] combines two queries instead. The query is used
+/// through its [`Display`](fmt::Display) form.
+///
+/// # Examples
+///
+/// ```
+/// use protonmail_client::SearchQuery;
+///
+/// let query = SearchQuery::new().flagged().or(SearchQuery::new().unseen());
+/// assert_eq!(query.to_string(), "OR FLAGGED UNSEEN");
+///
+/// let query = SearchQuery::new()
+///     .from("alice@example.com")
+///     .or(SearchQuery::new().unseen().flagged());
+/// assert_eq!(
+///     query.to_string(),
+///     "OR FROM \"alice@example.com\" (UNSEEN FLAGGED)"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    keys: Vec<String>,
+}
+
+impl SearchQuery {
+    /// An empty query, which matches every message (`ALL`).
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { keys: Vec::new() }
+    }
+
+    /// Messages with `\Seen`.
+    #[must_use]
+    pub fn seen(self) -> Self {
+        self.key("SEEN")
+    }
+
+    /// Messages without `\Seen`.
+    #[must_use]
+    pub fn unseen(self) -> Self {
+        self.key("UNSEEN")
+    }
+
+    /// Messages with `\Flagged` (starred).
+    #[must_use]
+    pub fn flagged(self) -> Self {
+        self.key("FLAGGED")
+    }
+
+    /// Messages without `\Flagged`.
+    #[must_use]
+    pub fn unflagged(self) -> Self {
+        self.key("UNFLAGGED")
+    }
+
+    /// Messages with `\Answered`.
+    #[must_use]
+    pub fn answered(self) -> Self {
+        self.key("ANSWERED")
+    }
+
+    /// Messages without `\Answered`.
+    #[must_use]
+    pub fn unanswered(self) -> Self {
+        self.key("UNANSWERED")
+    }
+
+    /// Messages whose `From` header contains `text`.
+    #[must_use]
+    pub fn from(self, text: &str) -> Self {
+        self.key(format!("FROM {}", quote(text)))
+    }
+
+    /// Messages whose `Subject` header contains `text`.
+    #[must_use]
+    pub fn subject(self, text: &str) -> Self {
+        self.key(format!("SUBJECT {}", quote(text)))
+    }
+
+    /// Messages with a header `name` containing `value`. An empty
+    /// `value` matches every message that has the header.
+    #[must_use]
+    pub fn header(self, name: &str, value: &str) -> Self {
+        self.key(format!("HEADER {} {}", quote(name), quote(value)))
+    }
+
+    /// Messages that arrived on or after `date` (`SINCE`, which
+    /// matches the server's INTERNALDATE).
+    #[must_use]
+    pub fn since(self, date: NaiveDate) -> Self {
+        self.key(format!("SINCE {}", imap_date(date)))
+    }
+
+    /// Messages that arrived before `date` (`BEFORE`, INTERNALDATE).
+    #[must_use]
+    pub fn before(self, date: NaiveDate) -> Self {
+        self.key(format!("BEFORE {}", imap_date(date)))
+    }
+
+    /// Messages whose `Date:` header is on or after `date`
+    /// (`SENTSINCE`).
+    #[must_use]
+    pub fn sent_since(self, date: NaiveDate) -> Self {
+        self.key(format!("SENTSINCE {}", imap_date(date)))
+    }
+
+    /// Messages whose `Date:` header is before `date` (`SENTBEFORE`).
+    #[must_use]
+    pub fn sent_before(self, date: NaiveDate) -> Self {
+        self.key(format!("SENTBEFORE {}", imap_date(date)))
+    }
+
+    /// Messages matching either this query or `other`
+    /// (`OR <self> <other>`).
+    #[must_use]
+    pub fn or(self, other: Self) -> Self {
+        let key = format!("OR {} {}", self.into_key(), other.into_key());
+        Self { keys: vec![key] }
+    }
+
+    fn key(mut self, key: impl Into<String>) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// The query as a single search key: parenthesized when it has
+    /// several keys.
+    fn into_key(mut self) -> String {
+        match self.keys.len() {
+            0 => "ALL".to_string(),
+            1 => self.keys.remove(0),
+            _ => format!("({})", self.keys.join(" ")),
+        }
+    }
+}
+
+impl fmt::Display for SearchQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.keys.is_empty() {
+            f.write_str("ALL")
+        } else {
+            f.write_str(&self.keys.join(" "))
+        }
+    }
+}
+
+/// Format `date` as an IMAP `date` (e.g. `5-Mar-2024`).
+fn imap_date(date: NaiveDate) -> impl fmt::Display {
+    date.format("%-d-%b-%Y")
+}
+
+/// Quote a string for use as an IMAP quoted string.
+pub fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_anded() {
+        let query = SearchQuery::new().unseen().subject("Invoice \"42\"");
+        assert_eq!(query.to_string(), "UNSEEN SUBJECT \"Invoice \\\"42\\\"\"");
+        let query = SearchQuery::new().header("X-Pm-ConversationID", "abc==");
+        assert_eq!(
+            query.to_string(),
+            "HEADER \"X-Pm-ConversationID\" \"abc==\""
+        );
+        assert_eq!(SearchQuery::new().to_string(), "ALL");
+    }
+
+    #[test]
+    fn or_groups_multi_key_operands() {
+        let query = SearchQuery::new()
+            .seen()
+            .flagged()
+            .or(SearchQuery::new().unseen())
+            .answered();
+        assert_eq!(query.to_string(), "OR (SEEN FLAGGED) UNSEEN ANSWERED");
+    }
+
+    #[test]
+    fn or_nests() {
+        let query = SearchQuery::new()
+            .flagged()
+            .or(SearchQuery::new().unseen())
+            .or(SearchQuery::new());
+        assert_eq!(query.to_string(), "OR OR FLAGGED UNSEEN ALL");
+    }
+
+    #[test]
+    fn dates_use_imap_format() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        assert_eq!(
+            SearchQuery::new().since(date).before(date).to_string(),
+            "SINCE 5-Mar-2024 BEFORE 5-Mar-2024"
+        );
+        assert_eq!(
+            SearchQuery::new()
+                .sent_since(date)
+                .sent_before(date)
+                .to_string(),
+            "SENTSINCE 5-Mar-2024 SENTBEFORE 5-Mar-2024"
+        );
+    }
+}
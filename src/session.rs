@@ -0,0 +1,314 @@
+//! Persistent IMAP sessions
+//!
+//! Every `ProtonClient` method opens its own connection and logs out
+//! when done. A [`ProtonSession`] instead keeps one logged-in
+//! connection open across operations, saving the connect, STARTTLS,
+//! and LOGIN round trips when a tool performs several operations in a
+//! row. It also remembers the selected folder, so consecutive
+//! operations on the same folder do not re-SELECT it; see
+//! [`ProtonSession::current_folder`].
+//!
+//! Sessions use the same typestate as the client: write methods are
+//! only available on `ProtonSession<ReadWrite>`.
+//!
+//! ```rust,no_run
+//! use protonmail_client::{Flag, Folder, ImapConfig, ProtonClient, ReadWrite};
+//!
+//! # async fn run() -> protonmail_client::Result<()> {
+//! let client: ProtonClient<ReadWrite> = ProtonClient::new(ImapConfig::from_env()?);
+//! let mut session = client.session().await?;
+//!
+//! let email = session.fetch_uid(&Folder::Inbox, 42).await?;
+//! // INBOX is still selected, so no second SELECT is sent.
+//! session.add_flag(email.uid, &Folder::Inbox, &Flag::Seen).await?;
+//!
+//! session.logout().await
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::client::{ReadOnly, ReadWrite};
+use crate::config::ImapConfig;
+use crate::connection::{self, ImapSession};
+use crate::error::{Error, Result};
+use crate::fetch::{self, RawFetch};
+use crate::flag::Flag;
+use crate::folder::Folder;
+use crate::tasks::Tasks;
+use async_imap::types::Capabilities;
+use email_extract::Email;
+
+/// A logged-in IMAP connection reused across operations.
+///
+/// Created with [`ProtonClient::session`](crate::ProtonClient::session).
+pub struct ProtonSession<M = ReadOnly> {
+    session: ImapSession,
+    selected: Option<Folder>,
+    /// Server capabilities, fetched on first use.
+    capabilities: Option<Capabilities>,
+    /// See [`ImapConfig::max_message_size`].
+    max_message_size: Option<usize>,
+    /// Upper bound for LOGOUT in [`Self::shutdown`], taken from
+    /// [`ImapConfig::connect_timeout`].
+    shutdown_timeout: Duration,
+    /// The background tasks of the client this session came from.
+    tasks: Arc<Tasks>,
+    _mode: PhantomData<M>,
+}
+
+/// A folder returned by [`ProtonSession::list_folders`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedFolder {
+    /// The folder name as reported by LIST.
+    pub name: String,
+    /// Whether this is the session's currently selected folder.
+    pub selected: bool,
+}
+
+// ── Read operations (available on any M) ───────────────────────────
+
+impl<M> ProtonSession<M> {
+    pub(crate) const fn new(session: ImapSession, config: &ImapConfig, tasks: Arc<Tasks>) -> Self {
+        Self {
+            session,
+            selected: None,
+            capabilities: None,
+            max_message_size: config.max_message_size,
+            shutdown_timeout: config.connect_timeout,
+            tasks,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Whether the server advertises capability `name` (e.g.
+    /// `"LITERAL+"`).
+    ///
+    /// The CAPABILITY list is requested once and cached for the life
+    /// of the session. Names are compared case-insensitively, as
+    /// servers differ in how they spell them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CAPABILITY command fails.
+    pub async fn has_capability(&mut self, name: &str) -> Result<bool> {
+        if self.capabilities.is_none() {
+            let caps = self
+                .session
+                .capabilities()
+                .await
+                .map_err(|e| Error::imap("Capability failed", &e))?;
+            self.capabilities = Some(caps);
+        }
+        Ok(self
+            .capabilities
+            .as_ref()
+            .is_some_and(|caps| connection::has_capability(caps, name)))
+    }
+
+    /// The folder currently selected on this session, if any.
+    ///
+    /// Set by every successful SELECT and cleared when a SELECT fails
+    /// or on [`Self::unselect`]. Logging out consumes the session.
+    #[must_use]
+    pub const fn current_folder(&self) -> Option<&Folder> {
+        self.selected.as_ref()
+    }
+
+    /// SELECT a folder.
+    ///
+    /// Skipped when `folder` is already selected, unless `force` is
+    /// set. Forcing a re-SELECT makes the server report fresh
+    /// EXISTS/UNSEEN counts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SELECT fails. The session then has no
+    /// folder selected.
+    pub async fn select(&mut self, folder: &Folder, force: bool) -> Result<()> {
+        if !force && self.selected.as_ref() == Some(folder) {
+            return Ok(());
+        }
+
+        self.selected = None;
+        connection::select(&mut self.session, folder.as_str()).await?;
+        self.selected = Some(folder.clone());
+        Ok(())
+    }
+
+    /// Leave the selected folder without expunging it (UNSELECT,
+    /// RFC 3691). Does nothing when no folder is selected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the UNSELECT fails. The session is treated
+    /// as having no folder selected either way.
+    pub async fn unselect(&mut self) -> Result<()> {
+        if self.selected.take().is_none() {
+            return Ok(());
+        }
+        self.session
+            .run_command_and_check_ok("UNSELECT")
+            .await
+            .map_err(|e| Error::imap("Unselect failed", &e))
+    }
+
+    /// List all folders, marking the currently selected one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the LIST command fails.
+    pub async fn list_folders(&mut self) -> Result<Vec<ListedFolder>> {
+        let names = connection::list_folders(&mut self.session).await?;
+        let current = self.selected.as_ref().map(Folder::as_str);
+        Ok(names
+            .into_iter()
+            .map(|name| ListedFolder {
+                selected: current == Some(name.as_str()),
+                name,
+            })
+            .collect())
+    }
+
+    /// Fetch a single email by UID from a folder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageNotFound`] if no message with `uid`
+    /// exists in `folder`, or another error if the SELECT or FETCH
+    /// fails or the message body cannot be parsed.
+    pub async fn fetch_uid(&mut self, folder: &Folder, uid: u32) -> Result<Email> {
+        self.select(folder, false).await?;
+        fetch::fetch_email(&mut self.session, folder, uid).await
+    }
+
+    /// Search emails using an arbitrary IMAP search query.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SELECT, SEARCH, or FETCH fails.
+    pub async fn search(&mut self, folder: &Folder, query: &str) -> Result<Vec<Email>> {
+        self.select(folder, false).await?;
+
+        let uid_list = connection::uid_search(&mut self.session, query).await?;
+
+        let mut emails =
+            fetch::fetch_emails(&mut self.session, folder, &uid_list, self.max_message_size)
+                .await?;
+        emails.sort_by_key(|e| std::cmp::Reverse(e.date));
+        Ok(emails)
+    }
+
+    /// Fetch arbitrary FETCH data items for a set of UIDs.
+    ///
+    /// See [`ProtonClient::fetch_items`](crate::ProtonClient::fetch_items).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SELECT or FETCH fails.
+    pub async fn fetch_items(
+        &mut self,
+        folder: &Folder,
+        uids: &[u32],
+        items: &str,
+    ) -> Result<Vec<RawFetch>> {
+        if uids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.select(folder, false).await?;
+        fetch::fetch_items(&mut self.session, uids, items).await
+    }
+
+    /// Log out and close the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the LOGOUT command fails.
+    pub async fn logout(mut self) -> Result<()> {
+        self.session
+            .logout()
+            .await
+            .map_err(|e| Error::imap("Logout failed", &e))
+    }
+
+    /// Tear the session down deterministically, together with the
+    /// background tasks of the client it came from.
+    ///
+    /// Aborts every prefetch and watch the client spawned (see
+    /// [`ProtonClient::background_tasks`](crate::ProtonClient::background_tasks))
+    /// and waits for them to end. Then sends LOGOUT, waiting at most
+    /// the configured connect timeout for the server to answer, and
+    /// closes the connection whether or not LOGOUT succeeded. Once
+    /// this returns, nothing is left running. Prefer it over
+    /// [`Self::logout`] in long-running services, where a bridge that
+    /// stops responding must not hold up teardown.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if the server does not answer
+    /// LOGOUT in time, or another error if LOGOUT fails. The
+    /// connection is closed in either case.
+    pub async fn shutdown(mut self) -> Result<()> {
+        self.tasks.shutdown().await;
+
+        let timeout = self.shutdown_timeout;
+        let result = tokio::time::timeout(timeout, self.session.logout())
+            .await
+            .map_err(|_| Error::Timeout(timeout))
+            .and_then(|r| r.map_err(|e| Error::imap("Logout failed", &e)));
+        drop(self);
+        result
+    }
+}
+
+// ── Write operations (only on ReadWrite) ───────────────────────────
+
+impl ProtonSession<ReadWrite> {
+    /// Add a flag to an email.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SELECT or STORE fails.
+    pub async fn add_flag(&mut self, uid: u32, folder: &Folder, flag: &Flag) -> Result<()> {
+        self.select(folder, false).await?;
+        let store_arg = format!("+FLAGS ({})", flag.as_imap_str());
+        connection::store(&mut self.session, &uid.to_string(), &store_arg).await
+    }
+
+    /// Remove a flag from an email.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SELECT or STORE fails.
+    pub async fn remove_flag(&mut self, uid: u32, folder: &Folder, flag: &Flag) -> Result<()> {
+        self.select(folder, false).await?;
+        let store_arg = format!("-FLAGS ({})", flag.as_imap_str());
+        connection::store(&mut self.session, &uid.to_string(), &store_arg).await
+    }
+
+    /// Append a raw RFC 2822 message to a folder.
+    ///
+    /// When the server advertises LITERAL+ (RFC 7888) the message is
+    /// streamed with the command instead of waiting for a
+    /// continuation, which adds up over bulk imports.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CAPABILITY or APPEND fails.
+    pub async fn append(&mut self, folder: &Folder, raw: &[u8], flags: &[Flag]) -> Result<()> {
+        let literal_plus = self.has_capability("LITERAL+").await?;
+        connection::append(&mut self.session, folder.as_str(), flags, raw, literal_plus).await
+    }
+
+    /// Stage a draft: append `raw` with `\Draft` and `\Seen` set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CAPABILITY or APPEND fails.
+    pub async fn append_draft(&mut self, folder: &Folder, raw: &[u8]) -> Result<()> {
+        self.append(folder, raw, &[Flag::Draft, Flag::Seen]).await
+    }
+}
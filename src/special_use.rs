@@ -0,0 +1,90 @@
+//! SPECIAL-USE folder resolution (RFC 6154)
+//!
+//! Proton Bridge names system folders in the account's language, so
+//! the Trash folder is not always called "Trash". It does tag them
+//! with SPECIAL-USE attributes (`\Trash`, `\Junk`, ...) in LIST
+//! responses, which is what these helpers resolve against.
+
+use std::collections::HashMap;
+
+use crate::connection::ImapSession;
+use crate::error::{Error, Result};
+use crate::folder::Folder;
+use async_imap::types::NameAttribute;
+use futures::StreamExt;
+
+/// The name Proton Bridge gives the `\All` folder in English.
+const ALL_MAIL: &str = "All Mail";
+
+/// A SPECIAL-USE mailbox role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpecialUse {
+    /// Every message in the account (`\All`), Proton's "All Mail".
+    All,
+    /// Archived messages (`\Archive`).
+    Archive,
+    /// Draft messages (`\Drafts`).
+    Drafts,
+    /// Spam (`\Junk`).
+    Junk,
+    /// Sent messages (`\Sent`).
+    Sent,
+    /// Deleted messages (`\Trash`).
+    Trash,
+}
+
+impl SpecialUse {
+    /// The folder used when the server does not advertise this role.
+    #[must_use]
+    pub fn default_folder(self) -> Folder {
+        match self {
+            Self::All => Folder::custom(ALL_MAIL),
+            Self::Archive => Folder::Archive,
+            Self::Drafts => Folder::Drafts,
+            Self::Junk => Folder::Spam,
+            Self::Sent => Folder::Sent,
+            Self::Trash => Folder::Trash,
+        }
+    }
+
+    const fn from_attribute(attr: &NameAttribute<'_>) -> Option<Self> {
+        match attr {
+            NameAttribute::All => Some(Self::All),
+            NameAttribute::Archive => Some(Self::Archive),
+            NameAttribute::Drafts => Some(Self::Drafts),
+            NameAttribute::Junk => Some(Self::Junk),
+            NameAttribute::Sent => Some(Self::Sent),
+            NameAttribute::Trash => Some(Self::Trash),
+            _ => None,
+        }
+    }
+}
+
+/// Folders the server tagged with a SPECIAL-USE attribute.
+pub type SpecialFolders = HashMap<SpecialUse, Folder>;
+
+/// LIST all folders and collect their SPECIAL-USE roles.
+///
+/// If several folders claim the same role, the first one wins.
+pub async fn discover(session: &mut ImapSession) -> Result<SpecialFolders> {
+    let mut stream = session
+        .list(Some(""), Some("*"))
+        .await
+        .map_err(|e| Error::imap("List folders failed", &e))?;
+
+    let mut folders = SpecialFolders::new();
+    while let Some(item) = stream.next().await {
+        let Ok(name) = item else { continue };
+        for special_use in name
+            .attributes()
+            .iter()
+            .filter_map(SpecialUse::from_attribute)
+        {
+            folders
+                .entry(special_use)
+                .or_insert_with(|| Folder::from(name.name()));
+        }
+    }
+
+    Ok(folders)
+}
@@ -0,0 +1,105 @@
+//! Background tasks of a client
+//!
+//! Body prefetches ([`ProtonClient::with_prefetch`]) and folder
+//! watches ([`ProtonClient::watch`]) run as tokio tasks that outlive
+//! the call that started them. Their handles are kept here, shared by
+//! a client and its sessions, so the tasks can be counted and torn
+//! down with [`ProtonSession::shutdown`].
+//!
+//! [`ProtonClient::with_prefetch`]: crate::ProtonClient::with_prefetch
+//! [`ProtonClient::watch`]: crate::ProtonClient::watch
+//! [`ProtonSession::shutdown`]: crate::ProtonSession::shutdown
+
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tokio::task::JoinHandle;
+
+/// The background tasks spawned by one client.
+#[derive(Debug, Default)]
+pub struct Tasks {
+    prefetches: Mutex<Vec<JoinHandle<()>>>,
+    watches: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Tasks {
+    /// Spawn a body prefetch.
+    pub fn spawn_prefetch(&self, task: impl Future<Output = ()> + Send + 'static) {
+        push(&self.prefetches, tokio::spawn(task));
+    }
+
+    /// Spawn a folder watch.
+    pub fn spawn_watch(&self, task: impl Future<Output = ()> + Send + 'static) {
+        push(&self.watches, tokio::spawn(task));
+    }
+
+    /// How many tasks are still running.
+    pub fn running(&self) -> usize {
+        [&self.prefetches, &self.watches]
+            .into_iter()
+            .map(|handles| lock(handles).iter().filter(|h| !h.is_finished()).count())
+            .sum()
+    }
+
+    /// Abort the prefetches, without waiting for them to end.
+    pub fn abort_prefetches(&self) {
+        for handle in lock(&self.prefetches).drain(..) {
+            handle.abort();
+        }
+    }
+
+    /// Abort every task and wait until all of them have ended.
+    pub async fn shutdown(&self) {
+        let handles: Vec<JoinHandle<()>> = [&self.prefetches, &self.watches]
+            .into_iter()
+            .flat_map(|handles| std::mem::take(&mut *lock(handles)))
+            .collect();
+        for handle in &handles {
+            handle.abort();
+        }
+        for handle in handles {
+            // A cancelled task reports `JoinError::Cancelled`, which is
+            // the expected outcome here.
+            let _ = handle.await;
+        }
+    }
+}
+
+/// Add `handle`, first forgetting tasks that have already ended.
+fn push(handles: &Mutex<Vec<JoinHandle<()>>>, handle: JoinHandle<()>) {
+    let mut handles = lock(handles);
+    handles.retain(|h| !h.is_finished());
+    handles.push(handle);
+}
+
+fn lock(handles: &Mutex<Vec<JoinHandle<()>>>) -> MutexGuard<'_, Vec<JoinHandle<()>>> {
+    handles.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn shutdown_ends_every_task() {
+        let tasks = Tasks::default();
+        tasks.spawn_prefetch(std::future::pending());
+        tasks.spawn_watch(std::future::pending());
+        tasks.spawn_watch(async {});
+        tokio::task::yield_now().await;
+        assert!(tasks.running() >= 2);
+
+        tasks.shutdown().await;
+        assert_eq!(tasks.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn abort_prefetches_leaves_watches() {
+        let tasks = Tasks::default();
+        tasks.spawn_prefetch(std::future::pending());
+        tasks.spawn_watch(std::future::pending());
+
+        tasks.abort_prefetches();
+        assert_eq!(tasks.running(), 1);
+        tasks.shutdown().await;
+    }
+}
@@ -0,0 +1,144 @@
+//! Finding the thread a message belongs to
+//!
+//! The bridge tags every message with its Proton conversation ID,
+//! which is the most reliable grouping. Messages without one (e.g.
+//! imported from elsewhere) fall back to `References` threading: the
+//! thread is every message whose `Message-ID`, `References`, or
+//! `In-Reply-To` names the thread's first message.
+
+use crate::conversation::CONVERSATION_ID_HEADER;
+use crate::fetch::RawFetch;
+use crate::headers;
+use crate::search::SearchQuery;
+use email_extract::Email;
+use serde::Serialize;
+
+/// The FETCH items [`thread_query`] needs.
+pub const THREAD_FIELDS: &str = "(BODY.PEEK[HEADER.FIELDS \
+     (X-Pm-ConversationID Message-ID References In-Reply-To)])";
+
+/// A message of a thread, with its place in the reply chain.
+#[derive(Debug, Serialize)]
+pub struct ThreadMessage {
+    /// How many messages deep this one sits in the reply chain (see
+    /// [`RawFetch::reply_depth`]).
+    pub depth: usize,
+    /// The message itself.
+    #[serde(flatten)]
+    pub email: Email,
+}
+
+/// The search that finds the thread of `raw`, fetched with
+/// [`THREAD_FIELDS`]. `None` if it has neither a conversation ID nor
+/// any message ID to go by, making it a thread of its own.
+pub fn thread_query(raw: &RawFetch) -> Option<SearchQuery> {
+    if let Some(id) = raw.conversation_id() {
+        return Some(SearchQuery::new().header(CONVERSATION_ID_HEADER, &id));
+    }
+
+    let root = raw
+        .references()
+        .into_iter()
+        .next()
+        .or_else(|| first_id(raw, "In-Reply-To"))
+        .or_else(|| first_id(raw, "Message-ID"))?;
+    let query = SearchQuery::new()
+        .header("Message-ID", &root)
+        .or(SearchQuery::new().header("References", &root))
+        .or(SearchQuery::new().header("In-Reply-To", &root));
+    Some(query)
+}
+
+/// The message IDs in the `References` header of `email`, oldest
+/// first.
+#[must_use]
+pub fn references(email: &Email) -> Vec<String> {
+    email
+        .thread
+        .references
+        .iter()
+        .map(|id| id.as_str().to_string())
+        .collect()
+}
+
+/// How many messages deep `email` sits in its reply chain: the number
+/// of message IDs in its `References` header, so 0 for a thread
+/// starter.
+///
+/// [`RawFetch::reply_depth`] does the same for a fetched message and
+/// also counts an `In-Reply-To`-only message as a direct reply.
+#[must_use]
+pub const fn reply_depth(email: &Email) -> usize {
+    email.thread.references.len()
+}
+
+/// The first message ID in header `name`.
+fn first_id(raw: &RawFetch, name: &str) -> Option<String> {
+    let source = raw.header.as_deref().or(raw.body.as_deref())?;
+    let value = headers::header_value(source, name)?;
+    headers::message_ids(&value).into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(header: &str) -> RawFetch {
+        RawFetch {
+            uid: 1,
+            flags: Vec::new(),
+            internal_date: None,
+            size: None,
+            header: Some(header.as_bytes().to_vec()),
+            body: None,
+            text: None,
+            attachments: Vec::new(),
+            text_parts: Vec::new(),
+            envelope: None,
+        }
+    }
+
+    #[test]
+    fn depth_counts_references() {
+        let email = |headers: &str| {
+            email_extract::parse_email(1, format!("From: a@x.com\r\n{headers}\r\nHi").as_bytes())
+                .unwrap()
+        };
+
+        let starter = email("Message-ID: <root@x>\r\n");
+        assert!(references(&starter).is_empty());
+        assert_eq!(reply_depth(&starter), 0);
+
+        let reply = email("References: <root@x>\r\n <b@x>\r\n");
+        assert_eq!(references(&reply), ["<root@x>", "<b@x>"]);
+        assert_eq!(reply_depth(&reply), 2);
+    }
+
+    #[test]
+    fn prefers_conversation_id() {
+        let query = thread_query(&raw(
+            "X-Pm-ConversationID: abc==\r\nReferences: <root@x>\r\n\r\n",
+        ));
+        assert_eq!(
+            query.unwrap().to_string(),
+            "HEADER \"X-Pm-ConversationID\" \"abc==\""
+        );
+    }
+
+    #[test]
+    fn falls_back_to_thread_root() {
+        let query = thread_query(&raw(
+            "Message-ID: <c@x>\r\nReferences: <root@x> <b@x>\r\n\r\n",
+        ));
+        assert_eq!(
+            query.unwrap().to_string(),
+            "OR OR HEADER \"Message-ID\" \"<root@x>\" HEADER \"References\" \"<root@x>\" \
+             HEADER \"In-Reply-To\" \"<root@x>\""
+        );
+
+        let starter = thread_query(&raw("Message-ID: <root@x>\r\n\r\n")).unwrap();
+        assert!(starter.to_string().contains("\"<root@x>\""));
+
+        assert!(thread_query(&raw("Subject: Hi\r\n\r\n")).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+//! Mailing list unsubscribe links
+//!
+//! Lists advertise how to leave them in the `List-Unsubscribe` header
+//! (RFC 2369): a comma-separated list of URIs in angle brackets, either
+//! `mailto:` addresses or web links. `List-Unsubscribe-Post` (RFC 8058)
+//! additionally marks the web link as a one-click POST endpoint.
+
+use crate::headers;
+use email_extract::Email;
+use serde::Serialize;
+
+/// The unsubscribe targets a message advertises.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct UnsubscribeInfo {
+    /// `mailto:` URIs, complete with any `?subject=` or `?body=`
+    /// parameters.
+    pub mailto: Vec<String>,
+    /// `http:` and `https:` URLs.
+    pub urls: Vec<String>,
+    /// Whether `List-Unsubscribe-Post: List-Unsubscribe=One-Click` is
+    /// present and there is an HTTPS URL to POST it to (RFC 8058).
+    pub one_click: bool,
+}
+
+/// Parse the `List-Unsubscribe` and `List-Unsubscribe-Post` headers
+/// of a parsed email.
+///
+/// Returns `None` if there is no `List-Unsubscribe` header or it holds
+/// no `mailto:` or web target. Other URI schemes are ignored.
+#[must_use]
+pub fn unsubscribe_info(email: &Email) -> Option<UnsubscribeInfo> {
+    from_values(
+        email.headers.list_unsubscribe.as_deref(),
+        headers::email_values(email, "List-Unsubscribe-Post"),
+    )
+}
+
+/// Parse the unsubscribe headers of a raw message or header block, as
+/// in [`unsubscribe_info`].
+#[must_use]
+pub fn unsubscribe_info_raw(raw: &[u8]) -> Option<UnsubscribeInfo> {
+    let unsubscribe = headers::header_values(raw, "List-Unsubscribe");
+    let post = headers::header_values(raw, "List-Unsubscribe-Post");
+    from_values(
+        unsubscribe.iter().map(String::as_str),
+        post.iter().map(String::as_str),
+    )
+}
+
+/// Build the info from the `List-Unsubscribe` and
+/// `List-Unsubscribe-Post` values.
+fn from_values<'a>(
+    unsubscribe: impl IntoIterator<Item = &'a str>,
+    post: impl IntoIterator<Item = &'a str>,
+) -> Option<UnsubscribeInfo> {
+    let mut info = UnsubscribeInfo::default();
+    for value in unsubscribe {
+        for uri in bracketed(value) {
+            let scheme = uri.split_once(':').map_or("", |(s, _)| s);
+            if scheme.eq_ignore_ascii_case("mailto") {
+                info.mailto.push(uri);
+            } else if scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https") {
+                info.urls.push(uri);
+            }
+        }
+    }
+    if info.mailto.is_empty() && info.urls.is_empty() {
+        return None;
+    }
+
+    info.one_click = post
+        .into_iter()
+        .any(|v| v.trim().eq_ignore_ascii_case("List-Unsubscribe=One-Click"))
+        && info
+            .urls
+            .iter()
+            .any(|u| u.get(..6).is_some_and(|s| s.eq_ignore_ascii_case("https:")));
+    Some(info)
+}
+
+/// The `<...>` items of a header value, trimmed. Whitespace inside the
+/// brackets (left over from folding) is removed.
+fn bracketed(value: &str) -> Vec<String> {
+    value
+        .split('<')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('>'))
+        .map(|(uri, _)| uri.split_whitespace().collect::<String>())
+        .filter(|uri| !uri.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_mailto_and_urls() {
+        let raw = b"From: news@example.com\r\n\
+            List-Unsubscribe: <mailto:leave@example.com?subject=unsubscribe>,\r\n \
+            <https://example.com/u/42>\r\n\
+            \r\n\
+            Body";
+        let info = unsubscribe_info_raw(raw).unwrap();
+        assert_eq!(
+            info.mailto,
+            ["mailto:leave@example.com?subject=unsubscribe"]
+        );
+        assert_eq!(info.urls, ["https://example.com/u/42"]);
+        assert!(!info.one_click);
+
+        let email = email_extract::parse_email(1, raw).unwrap();
+        assert_eq!(unsubscribe_info(&email), Some(info));
+    }
+
+    #[test]
+    fn one_click_needs_https() {
+        let header = |url: &str| {
+            format!(
+                "List-Unsubscribe: <{url}>\r\n\
+                 List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n\r\n"
+            )
+        };
+        let https = unsubscribe_info_raw(header("https://example.com/u").as_bytes()).unwrap();
+        assert!(https.one_click);
+        let email = email_extract::parse_email(
+            1,
+            format!("From: a@b.com\r\n{}", header("https://example.com/u")).as_bytes(),
+        )
+        .unwrap();
+        assert!(unsubscribe_info(&email).unwrap().one_click);
+        let http = unsubscribe_info_raw(header("http://example.com/u").as_bytes()).unwrap();
+        assert!(!http.one_click);
+    }
+
+    #[test]
+    fn missing_or_unusable_header_is_none() {
+        assert_eq!(unsubscribe_info_raw(b"Subject: Hi\r\n\r\nBody"), None);
+        assert_eq!(
+            unsubscribe_info_raw(b"List-Unsubscribe: <ftp://example.com/x>\r\n\r\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Long-running IDLE (RFC 2177) on a folder
+//!
+//! A single IDLE command does not last forever: servers end it after
+//! a while (Proton Bridge after 29 minutes) and connections drop.
+//! [`run`] hides both from the consumer. It ends each IDLE with `DONE`
+//! and starts a new one every [`ImapConfig::idle_renew`], and when the
+//! connection goes away it reconnects, re-selects the folder, and
+//! carries on with the same event channel. Reconnects that fail (e.g.
+//! while the bridge restarts) are retried with exponential backoff.
+
+use crate::config::ImapConfig;
+use crate::connection::{self, ImapSession, ImapStream};
+use crate::error::{Error, Result};
+use crate::folder::Folder;
+use async_imap::extensions::idle::{Handle, IdleResponse};
+use async_imap::imap_proto::{MailboxDatum, Response};
+use async_imap::types::UnsolicitedResponse;
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// How many events are buffered for a consumer that falls behind.
+pub const WATCH_BUFFER: usize = 64;
+
+/// A change to a watched folder, as announced by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// The folder now holds this many messages. A higher count than
+    /// before means new mail.
+    Exists(u32),
+    /// The message with this sequence number was expunged; later
+    /// messages move down by one.
+    Expunge(u32),
+    /// The flags of the message with this sequence number changed.
+    Flags(u32),
+}
+
+/// The wait before the first retry of a failed connection.
+const RETRY_MIN: Duration = Duration::from_millis(50);
+
+/// The longest wait between retries. A connection that lasted this
+/// long resets the backoff.
+const RETRY_MAX: Duration = Duration::from_secs(30);
+
+type IdleHandle = Handle<ImapStream>;
+
+/// Watch `folder` until `tx` is closed, sending each change to it.
+///
+/// An IDLE that fails once running is treated as a dropped
+/// connection: the watch reconnects, and if the message count changed
+/// in the meantime, reports it as [`WatchEvent::Exists`]. Failing to
+/// connect, select, or enter IDLE is retried with exponential backoff
+/// up to [`RETRY_MAX`]. Until the first connection succeeds, these
+/// failures are also sent to `tx`, since they usually mean a wrong
+/// folder or configuration; later ones are only logged.
+pub async fn run(config: ImapConfig, folder: Folder, tx: Sender<Result<WatchEvent>>) {
+    let mut exists = None;
+    let mut failures = 0;
+    loop {
+        let delay = backoff(failures);
+        if !delay.is_zero() {
+            tokio::select! {
+                () = tokio::time::sleep(delay) => {}
+                () = tx.closed() => return,
+            }
+        }
+
+        match start(&config, &folder, &mut exists, &tx).await {
+            Ok(handle) => {
+                let started = Instant::now();
+                match watch_session(handle, &config, &folder, &mut exists, &tx).await {
+                    Ok(()) => return,
+                    Err(e) => warn!("IDLE on {} was interrupted, reconnecting: {}", folder, e),
+                }
+                if started.elapsed() >= RETRY_MAX {
+                    failures = 0;
+                }
+            }
+            Err(e) => {
+                warn!("Watching {} failed, retrying: {}", folder, e);
+                if exists.is_none() {
+                    tx.send(Err(e)).await.ok();
+                }
+            }
+        }
+        failures = failures.saturating_add(1);
+    }
+}
+
+/// The wait before connecting after `failures` failed attempts in a
+/// row: none at first, then doubling from [`RETRY_MIN`] up to
+/// [`RETRY_MAX`].
+fn backoff(failures: u32) -> Duration {
+    if failures == 0 {
+        return Duration::ZERO;
+    }
+    RETRY_MIN
+        .saturating_mul(2_u32.saturating_pow(failures - 1))
+        .min(RETRY_MAX)
+}
+
+/// Connect, SELECT `folder`, and enter IDLE.
+async fn start(
+    config: &ImapConfig,
+    folder: &Folder,
+    exists: &mut Option<u32>,
+    tx: &Sender<Result<WatchEvent>>,
+) -> Result<IdleHandle> {
+    let mut session = connection::connect(config).await?;
+    let status = connection::select_status(&mut session, folder.as_str()).await?;
+    if exists.is_some_and(|n| n != status.exists) {
+        tx.send(Ok(WatchEvent::Exists(status.exists))).await.ok();
+    }
+    *exists = Some(status.exists);
+    enter(session).await
+}
+
+/// Send IDLE and wait for the server's continuation.
+async fn enter(session: ImapSession) -> Result<IdleHandle> {
+    let mut handle = session.idle();
+    handle.init().await.map_err(|e| idle_error(&e))?;
+    Ok(handle)
+}
+
+/// Forward events from one connection, re-issuing IDLE every
+/// [`ImapConfig::idle_renew`]. Returns `Ok` once the consumer has
+/// gone away, and an error if the connection failed.
+async fn watch_session(
+    mut handle: IdleHandle,
+    config: &ImapConfig,
+    folder: &Folder,
+    exists: &mut Option<u32>,
+    tx: &Sender<Result<WatchEvent>>,
+) -> Result<()> {
+    let mut renew_at = Instant::now() + config.idle_renew;
+    loop {
+        let (wait, _stop) =
+            handle.wait_with_timeout(renew_at.saturating_duration_since(Instant::now()));
+        let response = tokio::select! {
+            response = wait => Some(response),
+            () = tx.closed() => None,
+        };
+        let Some(response) = response else {
+            if let Ok(mut session) = handle.done().await {
+                session.logout().await.ok();
+            }
+            return Ok(());
+        };
+
+        match response.map_err(|e| idle_error(&e))? {
+            IdleResponse::NewData(data) => {
+                if let Some(event) = event(data.parsed()) {
+                    forward(event, exists, tx).await;
+                }
+            }
+            IdleResponse::Timeout => {
+                let session = handle.done().await.map_err(|e| idle_error(&e))?;
+                // Updates that came with the DONE completion.
+                while let Ok(response) = session.unsolicited_responses.try_recv() {
+                    let event = match response {
+                        UnsolicitedResponse::Exists(n) => WatchEvent::Exists(n),
+                        UnsolicitedResponse::Expunge(n) => WatchEvent::Expunge(n),
+                        _ => continue,
+                    };
+                    forward(event, exists, tx).await;
+                }
+                debug!("Re-issuing IDLE on {}", folder);
+                handle = enter(session).await?;
+                renew_at = Instant::now() + config.idle_renew;
+            }
+            IdleResponse::ManualInterrupt => {}
+        }
+    }
+}
+
+/// Send `event` to the consumer and track the message count. An
+/// `EXISTS` that repeats the known count is dropped.
+async fn forward(event: WatchEvent, exists: &mut Option<u32>, tx: &Sender<Result<WatchEvent>>) {
+    match event {
+        WatchEvent::Exists(n) if *exists == Some(n) => return,
+        WatchEvent::Exists(n) => *exists = Some(n),
+        WatchEvent::Expunge(_) => *exists = exists.map(|n| n.saturating_sub(1)),
+        WatchEvent::Flags(_) => {}
+    }
+    tx.send(Ok(event)).await.ok();
+}
+
+/// The event an unsolicited response announces, if any.
+const fn event(response: &Response<'_>) -> Option<WatchEvent> {
+    match *response {
+        Response::MailboxData(MailboxDatum::Exists(n)) => Some(WatchEvent::Exists(n)),
+        Response::Expunge(n) => Some(WatchEvent::Expunge(n)),
+        Response::Fetch(n, _) => Some(WatchEvent::Flags(n)),
+        _ => None,
+    }
+}
+
+fn idle_error(e: &async_imap::error::Error) -> Error {
+    Error::imap("IDLE failed", e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_unsolicited_responses() {
+        assert_eq!(
+            event(&Response::MailboxData(MailboxDatum::Exists(4))),
+            Some(WatchEvent::Exists(4))
+        );
+        assert_eq!(event(&Response::Expunge(2)), Some(WatchEvent::Expunge(2)));
+        assert_eq!(event(&Response::MailboxData(MailboxDatum::Recent(1))), None);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff(0), Duration::ZERO);
+        assert_eq!(backoff(1), RETRY_MIN);
+        assert_eq!(backoff(3), RETRY_MIN * 4);
+        assert_eq!(backoff(40), RETRY_MAX);
+    }
+}
@@ -28,14 +28,42 @@ fn make_raw_email(from: &str, to: &str, subject: &str, body: &str, date: &str) -
 /// Run the `proton-cli` binary with the given arguments, connecting to
 /// the provided fake IMAP server. Returns `(stdout, stderr, success)`.
 async fn run_cli(server: &FakeImapServer, args: &[&str]) -> (String, String, bool) {
+    run_cli_with_stdin(server, args, b"").await
+}
+
+/// Like [`run_cli`], but feeds `stdin` to the child process.
+async fn run_cli_with_stdin(
+    server: &FakeImapServer,
+    args: &[&str],
+    stdin: &[u8],
+) -> (String, String, bool) {
+    use std::process::Stdio;
+    use tokio::io::AsyncWriteExt;
+
     let bin = env!("CARGO_BIN_EXE_proton-cli");
-    let output = tokio::process::Command::new(bin)
+    let mut child = tokio::process::Command::new(bin)
         .args(args)
+        // Keep a developer's own .env files out of the picture: they
+        // take precedence over the process environment.
+        .current_dir(std::env::temp_dir())
+        .env("XDG_CONFIG_HOME", std::env::temp_dir())
         .env("IMAP_HOST", "127.0.0.1")
         .env("IMAP_PORT", server.port().to_string())
         .env("IMAP_USERNAME", "testuser")
         .env("IMAP_PASSWORD", "testpass")
-        .output()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run proton-cli");
+
+    // The child may exit without reading stdin; that is not an error.
+    let mut child_stdin = child.stdin.take().unwrap();
+    let _ = child_stdin.write_all(stdin).await;
+    drop(child_stdin);
+
+    let output = child
+        .wait_with_output()
         .await
         .expect("failed to run proton-cli");
 
@@ -166,6 +194,182 @@ async fn test_show() {
     assert!(stdout.contains("bob@example.com"));
     assert!(stdout.contains("Hello Bob"));
     assert!(stdout.contains("This is a test email."));
+    assert!(stdout.contains("Flags:   (none)"));
+}
+
+#[tokio::test]
+async fn test_show_flags() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Read already",
+        "Seen and labelled.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email_with_flags(7, &["\\Seen", "Work"], &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    let (stdout, _, success) = run_cli(&server, &["show", "7"]).await;
+    assert!(success, "proton-cli show failed");
+    assert!(stdout.contains("Flags:   \\Seen Work"));
+
+    let (stdout, _, success) = run_cli(&server, &["show", "7", "--json"]).await;
+    assert!(success, "proton-cli show --json failed");
+    let email: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(email["flags"], serde_json::json!(["\\Seen", "Work"]));
+    assert_eq!(email["uid"], 7);
+}
+
+#[tokio::test]
+async fn test_headers() {
+    let relayed = b"Received: from relay.example.com by mx.example.net;\r\n\
+        \tMon, 01 Jan 2024 12:00:05 +0000\r\n\
+        Received: from sender.example.com by relay.example.com;\r\n\
+        \tMon, 01 Jan 2024 12:00:00 +0000\r\n\
+        From: alice@example.com\r\n\
+        To: bob@example.com\r\n\
+        Subject: Relayed\r\n\
+        Date: Mon, 01 Jan 2024 12:00:00 +0000\r\n\
+        \r\n\
+        Received: in the body";
+    let local = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Local",
+        "No hops.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, relayed)
+        .email(2, true, &local)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    let (stdout, _, success) = run_cli(&server, &["headers", "1"]).await;
+    assert!(success, "proton-cli headers failed");
+    assert_eq!(
+        stdout,
+        " 1. from relay.example.com by mx.example.net; Mon, 01 Jan 2024 12:00:05 +0000\n \
+         2. from sender.example.com by relay.example.com; Mon, 01 Jan 2024 12:00:00 +0000\n"
+    );
+
+    let (stdout, _, success) = run_cli(&server, &["headers", "1", "--json"]).await;
+    assert!(success, "proton-cli headers --json failed");
+    let path: Vec<String> = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(path.len(), 2);
+
+    let (stdout, _, success) = run_cli(&server, &["headers", "2"]).await;
+    assert!(success);
+    assert!(stdout.contains("No Received headers found."));
+
+    let (_, _, success) = run_cli(&server, &["headers", "9"]).await;
+    assert!(!success);
+}
+
+#[tokio::test]
+async fn test_thread() {
+    let first = b"From: alice@example.com\r\n\
+        To: bob@example.com\r\n\
+        Subject: Plan\r\n\
+        Date: Mon, 01 Jan 2024 10:00:00 +0000\r\n\
+        Message-ID: <plan@example.com>\r\n\
+        \r\n\
+        Shall we meet?";
+    let reply = b"From: bob@example.com\r\n\
+        To: alice@example.com\r\n\
+        Subject: Re: Plan\r\n\
+        Date: Mon, 01 Jan 2024 11:00:00 +0000\r\n\
+        Message-ID: <reply@example.com>\r\n\
+        In-Reply-To: <plan@example.com>\r\n\
+        References: <plan@example.com>\r\n\
+        \r\n\
+        Yes, Tuesday.";
+    let unrelated = make_raw_email(
+        "carol@example.com",
+        "bob@example.com",
+        "Other",
+        "Not part of it.",
+        "Mon, 01 Jan 2024 10:30:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, reply)
+        .email(2, true, first)
+        .email(3, true, &unrelated)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    let (stdout, _, success) = run_cli(&server, &["thread", "1"]).await;
+    assert!(success, "proton-cli thread failed");
+    let plan = stdout.find("[2] ").expect("thread starter missing");
+    let answer = stdout.find("    [1] ").expect("indented reply missing");
+    assert!(plan < answer, "thread is not oldest first");
+    assert!(stdout.contains("      Yes, Tuesday."));
+    assert!(!stdout.contains("carol@example.com"));
+    assert!(stdout.contains("2 message(s) in thread"));
+
+    let (stdout, _, success) = run_cli(&server, &["thread", "2", "--json"]).await;
+    assert!(success, "proton-cli thread --json failed");
+    let messages: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let messages = messages.as_array().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["uid"], 2);
+    assert_eq!(messages[0]["depth"], 0);
+    assert_eq!(messages[1]["uid"], 1);
+    assert_eq!(messages[1]["depth"], 1);
+}
+
+#[tokio::test]
+async fn test_unsubscribe() {
+    let newsletter = b"From: news@example.com\r\n\
+        To: bob@example.com\r\n\
+        Subject: Weekly\r\n\
+        Date: Mon, 01 Jan 2024 12:00:00 +0000\r\n\
+        List-Unsubscribe: <mailto:leave@example.com>, <https://example.com/u/1>\r\n\
+        List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n\
+        \r\n\
+        News.";
+    let personal = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Hi",
+        "Not a list.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, newsletter)
+        .email(2, false, &personal)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    let (stdout, _, success) = run_cli(&server, &["unsubscribe", "1"]).await;
+    assert!(success, "proton-cli unsubscribe failed");
+    assert!(stdout.contains("Web:   https://example.com/u/1"));
+    assert!(stdout.contains("Email: mailto:leave@example.com"));
+    assert!(stdout.contains("One-click"));
+
+    let (stdout, _, success) = run_cli(&server, &["unsubscribe", "1", "--json"]).await;
+    assert!(success, "proton-cli unsubscribe --json failed");
+    let info: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(info["urls"], serde_json::json!(["https://example.com/u/1"]));
+    assert_eq!(info["one_click"], true);
+
+    let (stdout, _, success) = run_cli(&server, &["unsubscribe", "2"]).await;
+    assert!(success);
+    assert!(stdout.contains("No unsubscribe link found."));
 }
 
 #[tokio::test]
@@ -288,3 +492,175 @@ async fn test_list_date_range() {
     assert!(!stdout.contains("dave@example.com"));
     assert!(stdout.contains("1 email(s)"));
 }
+
+#[tokio::test]
+async fn test_env_file() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").folder("Work").build();
+    let server = FakeImapServer::start(mailbox).await;
+
+    let path = std::env::temp_dir().join(format!("proton-cli-test-{}.env", server.port()));
+    std::fs::write(
+        &path,
+        format!(
+            "IMAP_HOST=127.0.0.1\nIMAP_PORT={}\nIMAP_USERNAME=testuser\nIMAP_PASSWORD=testpass\n",
+            server.port()
+        ),
+    )
+    .unwrap();
+
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_proton-cli"))
+        .args(["--env-file", path.to_str().unwrap(), "folders"])
+        .current_dir(std::env::temp_dir())
+        .env("XDG_CONFIG_HOME", std::env::temp_dir())
+        .env_remove("IMAP_HOST")
+        .env_remove("IMAP_PORT")
+        .env_remove("IMAP_USERNAME")
+        .env_remove("IMAP_PASSWORD")
+        .output()
+        .await
+        .expect("failed to run proton-cli");
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success(), "proton-cli --env-file failed");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Work"));
+}
+
+#[tokio::test]
+async fn test_tls() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").build();
+    let server = FakeImapServer::start(mailbox).await;
+
+    let (stdout, _, success) = run_cli(&server, &["tls"]).await;
+    assert!(success, "proton-cli tls failed");
+    assert!(stdout.contains("Protocol:     TLSv1_3"));
+    assert!(stdout.contains("Cipher suite: TLS13_"));
+
+    let (stdout, _, success) = run_cli(&server, &["tls", "--json"]).await;
+    assert!(success, "proton-cli tls --json failed");
+    assert!(stdout.contains("\"protocol_version\": \"TLSv1_3\""));
+}
+
+#[tokio::test]
+async fn test_move_uids_flag() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Old news",
+        "Archive me.",
+        "Mon, 01 Jan 2024 10:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, false, &raw)
+        .email(3, false, &raw)
+        .folder("Archive")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    // Declining the prompt moves nothing.
+    let args = ["move", "--to", "Archive", "--uids", "1,3"];
+    let (_, stderr, success) = run_cli_with_stdin(&server, &args, b"n\n").await;
+    assert!(!success);
+    assert!(stderr.contains("Move 2 email(s) from INBOX to Archive?"));
+    assert!(stderr.contains("Aborted"));
+
+    let (stdout, _, success) = run_cli_with_stdin(&server, &args, b"y\n").await;
+    assert!(success, "proton-cli move failed");
+    assert!(stdout.contains("Moved 2 email(s) from INBOX to Archive"));
+
+    let (stdout, _, _) = run_cli(&server, &["list", "--folder", "Archive"]).await;
+    assert!(stdout.contains("2 email(s)"));
+    let (stdout, _, _) = run_cli(&server, &["list"]).await;
+    assert!(stdout.contains("1 email(s)"));
+}
+
+#[tokio::test]
+async fn test_move_uids_from_stdin() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Piped",
+        "From a pipeline.",
+        "Mon, 01 Jan 2024 10:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, false, &raw)
+        .folder("Archive")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    let args = ["move", "--to", "Archive", "--yes", "--json"];
+    let (stdout, _, success) = run_cli_with_stdin(&server, &args, b"1\n\n2\n").await;
+    assert!(success, "proton-cli move failed");
+
+    let summary: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(summary["moved"], serde_json::json!([1, 2]));
+    assert_eq!(summary["to"], "Archive");
+
+    let (_, stderr, success) = run_cli_with_stdin(&server, &args, b"abc\n").await;
+    assert!(!success);
+    assert!(stderr.contains("Invalid UID 'abc'"));
+}
+
+#[tokio::test]
+async fn test_move_search() {
+    let news = make_raw_email(
+        "news@example.com",
+        "bob@example.com",
+        "Weekly newsletter",
+        "Read all about it.",
+        "Mon, 01 Jan 2024 10:00:00 +0000",
+    );
+    let personal = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Lunch",
+        "Noon?",
+        "Mon, 01 Jan 2024 11:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &news)
+        .email(2, false, &personal)
+        .email(3, false, &news)
+        .folder("Archive")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    let args = [
+        "move",
+        "--to",
+        "Archive",
+        "--search",
+        "SUBJECT newsletter",
+        "--yes",
+        "--json",
+    ];
+    let (stdout, _, success) = run_cli(&server, &args).await;
+    assert!(success, "proton-cli move --search failed");
+
+    let summary: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(summary["moved"], 2);
+    assert_eq!(summary["search"], "SUBJECT newsletter");
+
+    let (stdout, _, _) = run_cli(&server, &["list"]).await;
+    assert!(stdout.contains("1 email(s)"));
+    assert!(stdout.contains("Lunch"));
+
+    let (_, stderr, success) = run_cli(
+        &server,
+        &["move", "--to", "Archive", "--uids", "2", "--search", "ALL"],
+    )
+    .await;
+    assert!(!success);
+    assert!(stderr.contains("cannot be used with"));
+}
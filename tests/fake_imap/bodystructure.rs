@@ -0,0 +1,246 @@
+//! `BODYSTRUCTURE` rendering for the fake IMAP server.
+//!
+//! Parses just enough MIME to describe a test message (RFC 3501
+//! Section 7.4.2):
+//!
+//! - `Content-Type` with parameters (default `text/plain`),
+//! - `Content-Disposition` with parameters,
+//! - `Content-Transfer-Encoding` (default `7BIT`),
+//! - `multipart/*` bodies split on their boundary, recursively.
+//!
+//! Every part carries the full extension data, since disposition is
+//! only reported there. `message/rfc822` parts are described as
+//! `application/octet-stream`: rendering their envelope is not
+//! supported.
+//!
+//! [`part_body`] uses the same parser to serve `BODY[<part>]`.
+
+/// Render the `BODYSTRUCTURE` of a raw RFC 2822 message.
+pub fn body_structure(raw: &[u8]) -> String {
+    let text = String::from_utf8_lossy(raw);
+    render(&text)
+}
+
+fn render(entity: &str) -> String {
+    let (header, body) = entity.split_once("\r\n\r\n").unwrap_or((entity, ""));
+    let headers = unfold(header);
+    let field = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+
+    let (media, params) =
+        field("Content-Type").map_or_else(|| ("text/plain".to_string(), Vec::new()), parse_value);
+    let (ty, subtype) = match media.split_once('/') {
+        Some(("message", "rfc822")) | None => ("application", "octet-stream"),
+        Some(pair) => pair,
+    };
+    let disposition = field("Content-Disposition").map_or_else(
+        || "NIL".to_string(),
+        |value| {
+            let (kind, params) = parse_value(value);
+            format!("({} {})", quote(&kind), render_params(&params))
+        },
+    );
+
+    if ty == "multipart" {
+        let boundary = params
+            .iter()
+            .find(|(key, _)| key == "boundary")
+            .map_or("", |(_, value)| value.as_str());
+        let parts: String = split_parts(body, boundary)
+            .into_iter()
+            .map(render)
+            .collect();
+        return format!(
+            "({parts} {} {} {disposition} NIL NIL)",
+            quote(subtype),
+            render_params(&params)
+        );
+    }
+
+    let encoding = field("Content-Transfer-Encoding").unwrap_or("7BIT");
+    let fields = format!(
+        "{} {} {} NIL NIL {} {}",
+        quote(ty),
+        quote(subtype),
+        render_params(&params),
+        quote(encoding),
+        body.len()
+    );
+    let lines = if ty == "text" {
+        format!(" {}", body.matches("\r\n").count())
+    } else {
+        String::new()
+    };
+    format!("({fields}{lines} NIL {disposition} NIL NIL)")
+}
+
+/// The body of MIME part `path` (e.g. `[1, 2]` for part `1.2`) of a
+/// raw message, without its MIME headers, as `BODY[<part>]` returns
+/// it (RFC 3501 Section 6.4.5). Part 1 of a single-part message is its
+/// body. `None` if there is no such part.
+pub fn part_body(raw: &[u8], path: &[u32]) -> Option<Vec<u8>> {
+    let text = String::from_utf8_lossy(raw);
+    let mut entity: &str = &text;
+    for &index in path {
+        let (header, body) = entity.split_once("\r\n\r\n").unwrap_or((entity, ""));
+        let headers = unfold(header);
+        let boundary = headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("Content-Type"))
+            .map(|(_, value)| parse_value(value))
+            .filter(|(media, _)| media.starts_with("multipart/"))
+            .and_then(|(_, params)| {
+                params
+                    .into_iter()
+                    .find(|(key, _)| key == "boundary")
+                    .map(|(_, value)| value)
+            });
+        entity = match boundary {
+            Some(boundary) => split_parts(body, &boundary)
+                .get(usize::try_from(index).ok()?.checked_sub(1)?)
+                .copied()?,
+            None if index == 1 => return Some(body.as_bytes().to_vec()),
+            None => return None,
+        };
+    }
+    let (_, body) = entity.split_once("\r\n\r\n").unwrap_or((entity, ""));
+    Some(body.as_bytes().to_vec())
+}
+
+/// Split a header block into `(name, value)` pairs, joining folded
+/// continuation lines.
+fn unfold(header: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for line in header.split("\r\n") {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            fields.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    fields
+}
+
+/// Parse `value; key=val; key2="val 2"` into the lowercased value and
+/// its parameters (keys lowercased, quotes removed).
+fn parse_value(value: &str) -> (String, Vec<(String, String)>) {
+    let mut pieces = value.split(';');
+    let main = pieces
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let params = pieces
+        .filter_map(|piece| {
+            let (key, val) = piece.split_once('=')?;
+            Some((
+                key.trim().to_ascii_lowercase(),
+                val.trim().trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+    (main, params)
+}
+
+/// The body parts between `--boundary` delimiter lines, without the
+/// preamble and epilogue.
+fn split_parts<'a>(body: &'a str, boundary: &str) -> Vec<&'a str> {
+    let delimiter = format!("--{boundary}");
+    body.split(delimiter.as_str())
+        .skip(1)
+        .take_while(|piece| !piece.starts_with("--"))
+        .map(|piece| {
+            let piece = piece.strip_prefix("\r\n").unwrap_or(piece);
+            piece.strip_suffix("\r\n").unwrap_or(piece)
+        })
+        .collect()
+}
+
+/// Render MIME parameters as an IMAP parenthesized list, or `NIL`.
+fn render_params(params: &[(String, String)]) -> String {
+    if params.is_empty() {
+        return "NIL".to_string();
+    }
+    let items: Vec<String> = params
+        .iter()
+        .map(|(key, value)| format!("{} {}", quote(key), quote(value)))
+        .collect();
+    format!("({})", items.join(" "))
+}
+
+/// Quote `s` as an IMAP quoted string.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_text_part() {
+        let raw = b"From: a@b.com\r\nSubject: Hi\r\n\r\nline one\r\nline two\r\n";
+        assert_eq!(
+            body_structure(raw),
+            "(\"text\" \"plain\" NIL NIL NIL \"7BIT\" 20 2 NIL NIL NIL NIL)"
+        );
+    }
+
+    #[test]
+    fn part_bodies() {
+        let raw = b"Content-Type: multipart/alternative; boundary=\"A\"\r\n\
+            \r\n\
+            --A\r\n\
+            Content-Type: text/plain\r\n\
+            \r\n\
+            Plain\r\n\
+            --A\r\n\
+            Content-Type: text/html\r\n\
+            \r\n\
+            <p>Html</p>\r\n\
+            --A--\r\n";
+        assert_eq!(part_body(raw, &[1]).unwrap(), b"Plain");
+        assert_eq!(part_body(raw, &[2]).unwrap(), b"<p>Html</p>");
+        assert_eq!(part_body(raw, &[3]), None);
+        assert_eq!(part_body(raw, &[1, 1]).unwrap(), b"Plain");
+        assert_eq!(
+            part_body(b"Subject: Hi\r\n\r\nBody", &[1]).unwrap(),
+            b"Body"
+        );
+    }
+
+    #[test]
+    fn multipart_with_attachment() {
+        let raw = b"Content-Type: multipart/mixed; boundary=\"XX\"\r\n\
+            \r\n\
+            preamble\r\n\
+            --XX\r\n\
+            Content-Type: text/plain; charset=utf-8\r\n\
+            \r\n\
+            See attached.\r\n\
+            --XX\r\n\
+            Content-Type: application/pdf; name=\"r.pdf\"\r\n\
+            Content-Disposition: attachment;\r\n \
+            filename=\"r.pdf\"\r\n\
+            Content-Transfer-Encoding: base64\r\n\
+            \r\n\
+            JVBERi0=\r\n\
+            --XX--\r\n";
+
+        assert_eq!(
+            body_structure(raw),
+            "((\"text\" \"plain\" (\"charset\" \"utf-8\") NIL NIL \"7BIT\" 13 0 \
+             NIL NIL NIL NIL)\
+             (\"application\" \"pdf\" (\"name\" \"r.pdf\") NIL NIL \"base64\" 8 \
+             NIL (\"attachment\" (\"filename\" \"r.pdf\")) NIL NIL) \
+             \"mixed\" (\"boundary\" \"XX\") NIL NIL NIL)"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! `ENVELOPE` rendering for the fake IMAP server.
+//!
+//! Builds the envelope (RFC 3501 Section 7.4.2) from the message's
+//! own headers: date, subject, from, sender, reply-to, to, cc, bcc,
+//! in-reply-to, and message-id, in that order. `Sender` and
+//! `Reply-To` default to `From`, as the RFC requires.
+//!
+//! Address lists are split on commas, so display names containing a
+//! comma and group syntax are not supported. Non-ASCII values are
+//! sent as literals.
+
+/// Render the `ENVELOPE` of a raw RFC 2822 message.
+pub fn envelope(raw: &[u8]) -> String {
+    let text = String::from_utf8_lossy(raw);
+    let header = text.split("\r\n\r\n").next().unwrap_or_default();
+    let fields = unfold(header);
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+
+    let from = addresses(field("From"));
+    let or_from = |name: &str| field(name).map_or_else(|| from.clone(), |v| addresses(Some(v)));
+
+    format!(
+        "({} {} {from} {} {} {} {} {} {} {})",
+        nstring(field("Date")),
+        nstring(field("Subject")),
+        or_from("Sender"),
+        or_from("Reply-To"),
+        addresses(field("To")),
+        addresses(field("Cc")),
+        addresses(field("Bcc")),
+        nstring(field("In-Reply-To")),
+        nstring(field("Message-ID")),
+    )
+}
+
+/// Header fields as `(name, value)` pairs, with continuation lines
+/// joined.
+fn unfold(header: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for line in header.split("\r\n") {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            fields.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    fields
+}
+
+/// Render an address header as a list of `(name adl mailbox host)`,
+/// or `NIL`.
+fn addresses(value: Option<&str>) -> String {
+    let list: Vec<String> = value
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(address)
+        .collect();
+    if list.is_empty() {
+        "NIL".to_string()
+    } else {
+        format!("({})", list.concat())
+    }
+}
+
+/// Render `Name <mailbox@host>` or `mailbox@host`.
+fn address(spec: &str) -> Option<String> {
+    let spec = spec.trim();
+    let (name, addr) = match spec.rsplit_once('<') {
+        Some((name, addr)) => {
+            let name = name.trim().trim_matches('"');
+            (
+                (!name.is_empty()).then_some(name),
+                addr.trim_end_matches('>'),
+            )
+        }
+        None => (None, spec),
+    };
+    let (mailbox, host) = addr.split_once('@')?;
+    Some(format!(
+        "({} NIL {} {})",
+        nstring(name),
+        nstring(Some(mailbox)),
+        nstring(Some(host))
+    ))
+}
+
+/// `NIL`, a quoted string, or a literal for non-ASCII text.
+fn nstring(value: Option<&str>) -> String {
+    match value {
+        None => "NIL".to_string(),
+        Some(s) if s.is_ascii() => {
+            format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+        Some(s) => format!("{{{}}}\r\n{s}", s.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_headers_in_order() {
+        let raw = b"Date: Mon, 01 Jan 2024 12:00:00 +0000\r\n\
+            From: \"Alice A\" <alice@example.com>\r\n\
+            To: bob@example.com,\r\n carol@example.com\r\n\
+            Subject: Hi\r\n\
+            Message-ID: <1@example.com>\r\n\
+            \r\n\
+            From: not@header.com";
+
+        let from = "((\"Alice A\" NIL \"alice\" \"example.com\"))";
+        assert_eq!(
+            envelope(raw),
+            format!(
+                "(\"Mon, 01 Jan 2024 12:00:00 +0000\" \"Hi\" {from} {from} {from} \
+                 ((NIL NIL \"bob\" \"example.com\")(NIL NIL \"carol\" \"example.com\")) \
+                 NIL NIL NIL \"<1@example.com>\")"
+            )
+        );
+    }
+
+    #[test]
+    fn missing_headers_are_nil() {
+        assert_eq!(
+            envelope(b"\r\nBody"),
+            "(NIL NIL NIL NIL NIL NIL NIL NIL NIL NIL)"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! APPEND command handler.
+//!
+//! Adds a new message to a folder (RFC 3501 Section 6.3.11). The
+//! message arrives as a literal, which the session loop has already
+//! read by the time this handler runs:
+//!
+//! ```text
+//!   Client:  A0005 APPEND "Drafts" (\Draft \Seen) {310}
+//!   Server:  + Ready for literal data
+//!   Client:  <310 bytes of RFC 2822 message>
+//!   Server:  A0005 OK [APPENDUID 1 7] APPEND completed
+//! ```
+//!
+//! The optional flag list is stored on the new message. No folder
+//! needs to be selected.
+
+use crate::fake_imap::io::write_line;
+use crate::fake_imap::mailbox::{Mailbox, TestEmail};
+use imap_codec::imap_types::flag::Flag;
+use std::sync::Mutex;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+
+/// Build the stored message, applying the APPEND flag list.
+fn new_email(uid: u32, flags: &[Flag<'_>], raw: &[u8]) -> TestEmail {
+    let mut email = TestEmail::new(uid, false, raw);
+    for flag in flags {
+        match flag {
+            Flag::Seen => email.seen = true,
+            Flag::Answered => email.answered = true,
+            Flag::Flagged => email.flagged = true,
+            Flag::Deleted => email.deleted = true,
+            Flag::Draft => email.draft = true,
+            Flag::Keyword(kw) => email.keywords.push(kw.inner().to_string()),
+            _ => {}
+        }
+    }
+    email
+}
+
+/// Handle the APPEND command. Stores `raw` in `folder_name` with a
+/// freshly assigned UID.
+pub async fn handle_append<S: AsyncRead + AsyncWrite + Unpin>(
+    tag: &str,
+    folder_name: &str,
+    flags: &[Flag<'_>],
+    raw: &[u8],
+    mailbox: &Mutex<Mailbox>,
+    stream: &mut BufReader<S>,
+) {
+    // Store under lock (no await inside).
+    let uid = {
+        let mut mb = mailbox.lock().unwrap();
+        let uid = mb.get_folder_mut(folder_name).map(|folder| {
+            let uid = folder.next_uid();
+            folder.emails.push(new_email(uid, flags, raw));
+            uid
+        });
+        drop(mb);
+        uid
+    };
+
+    let resp = match uid {
+        Some(uid) => format!("{tag} OK [APPENDUID 1 {uid}] APPEND completed\r\n"),
+        None => format!("{tag} NO [TRYCREATE] Folder not found\r\n"),
+    };
+    let _ = write_line(stream, &resp).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_imap::mailbox::MailboxBuilder;
+    use imap_codec::imap_types::core::Atom;
+    use tokio::io::BufReader;
+
+    fn make_raw_email() -> Vec<u8> {
+        b"From: a@b.com\r\nSubject: Draft\r\n\r\nBody".to_vec()
+    }
+
+    async fn run_append(
+        tag: &str,
+        folder: &str,
+        flags: &[Flag<'_>],
+        raw: &[u8],
+        mailbox: &Mutex<Mailbox>,
+    ) -> String {
+        let (client, server) = tokio::io::duplex(4096);
+        let mut stream = BufReader::new(server);
+
+        handle_append(tag, folder, flags, raw, mailbox, &mut stream).await;
+        drop(stream);
+
+        let mut buf = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut BufReader::new(client), &mut buf)
+            .await
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[tokio::test]
+    async fn append_assigns_next_uid_and_flags() {
+        let raw = make_raw_email();
+        let mb = Mutex::new(
+            MailboxBuilder::new()
+                .folder("Drafts")
+                .email(4, true, &raw)
+                .build(),
+        );
+        let flags = [
+            Flag::Draft,
+            Flag::Seen,
+            Flag::Keyword(Atom::try_from("Work").unwrap()),
+        ];
+
+        let output = run_append("A1", "Drafts", &flags, &raw, &mb).await;
+        assert!(output.contains("A1 OK [APPENDUID 1 5] APPEND completed"));
+
+        let email = mb.lock().unwrap().get_folder("Drafts").unwrap().emails[1].clone();
+        assert_eq!(email.uid, 5);
+        assert!(email.draft);
+        assert!(email.seen);
+        assert_eq!(email.keywords, vec!["Work".to_string()]);
+        assert_eq!(email.raw, raw);
+    }
+
+    #[tokio::test]
+    async fn append_without_flags() {
+        let raw = make_raw_email();
+        let mb = Mutex::new(MailboxBuilder::new().folder("INBOX").build());
+
+        let output = run_append("A1", "INBOX", &[], &raw, &mb).await;
+        assert!(output.contains("A1 OK [APPENDUID 1 1]"));
+
+        assert!(
+            mb.lock().unwrap().get_folder("INBOX").unwrap().emails[0]
+                .flags()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn append_to_missing_folder() {
+        let raw = make_raw_email();
+        let mb = Mutex::new(MailboxBuilder::new().folder("INBOX").build());
+
+        let output = run_append("A1", "Nope", &[Flag::Draft], &raw, &mb).await;
+        assert!(output.contains("A1 NO [TRYCREATE]"));
+    }
+}
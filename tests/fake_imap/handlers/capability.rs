@@ -1,7 +1,8 @@
 //! CAPABILITY command handler.
 //!
 //! Returns the list of capabilities supported by the fake server.
-//! RFC 3501 Section 6.1.1 requires this command.
+//! RFC 3501 Section 6.1.1 requires this command. Optional extensions
+//! are advertised according to the mailbox's `ServerOptions`.
 
 use crate::fake_imap::io::write_line;
 use tokio::io::{AsyncRead, AsyncWrite, BufReader};
@@ -9,9 +10,11 @@ use tokio::io::{AsyncRead, AsyncWrite, BufReader};
 /// Handle the CAPABILITY command.
 pub async fn handle_capability<S: AsyncRead + AsyncWrite + Unpin>(
     tag: &str,
+    capabilities: &[&str],
     stream: &mut BufReader<S>,
 ) {
-    let _ = write_line(stream, "* CAPABILITY IMAP4rev1 STARTTLS\r\n").await;
+    let line = format!("* CAPABILITY {}\r\n", capabilities.join(" "));
+    let _ = write_line(stream, &line).await;
     let resp = format!("{tag} OK CAPABILITY completed\r\n");
     let _ = write_line(stream, &resp).await;
 }
@@ -22,10 +25,14 @@ mod tests {
     use tokio::io::BufReader;
 
     async fn run(tag: &str) -> String {
+        run_with(tag, &["IMAP4rev1", "STARTTLS"]).await
+    }
+
+    async fn run_with(tag: &str, capabilities: &[&str]) -> String {
         let (client, server) = tokio::io::duplex(1024);
         let mut stream = BufReader::new(server);
 
-        handle_capability(tag, &mut stream).await;
+        handle_capability(tag, capabilities, &mut stream).await;
         drop(stream);
 
         let mut buf = Vec::new();
@@ -41,4 +48,10 @@ mod tests {
         assert!(output.contains("* CAPABILITY IMAP4rev1 STARTTLS"));
         assert!(output.contains("A1 OK CAPABILITY completed"));
     }
+
+    #[tokio::test]
+    async fn sends_extra_capabilities() {
+        let output = run_with("A1", &["IMAP4rev1", "STARTTLS", "LITERAL+"]).await;
+        assert!(output.contains("* CAPABILITY IMAP4rev1 STARTTLS LITERAL+\r\n"));
+    }
 }
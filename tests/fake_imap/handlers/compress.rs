@@ -0,0 +1,71 @@
+//! COMPRESS command handler.
+//!
+//! RFC 4978: after a tagged OK, both directions of the connection are
+//! compressed with raw DEFLATE. The stream switch itself happens in
+//! the server loop; this handler only decides whether to allow it.
+
+use crate::fake_imap::io::write_line;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+
+/// Handle the COMPRESS command. Returns `true` if compression starts
+/// after this response.
+///
+/// `available` is whether COMPRESS=DEFLATE is advertised; `active`
+/// is whether the connection is already compressed, which RFC 4978
+/// Section 3 answers with `NO [COMPRESSIONACTIVE]`.
+pub async fn handle_compress<S: AsyncRead + AsyncWrite + Unpin>(
+    tag: &str,
+    available: bool,
+    active: bool,
+    stream: &mut BufReader<S>,
+) -> bool {
+    let resp = if !available {
+        format!("{tag} BAD Unknown command\r\n")
+    } else if active {
+        format!("{tag} NO [COMPRESSIONACTIVE] DEFLATE active via COMPRESS\r\n")
+    } else {
+        format!("{tag} OK DEFLATE active\r\n")
+    };
+    write_line(stream, &resp).await.is_ok() && available && !active
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::BufReader;
+
+    async fn run(tag: &str, available: bool, active: bool) -> (String, bool) {
+        let (client, server) = tokio::io::duplex(1024);
+        let mut stream = BufReader::new(server);
+
+        let started = handle_compress(tag, available, active, &mut stream).await;
+        drop(stream);
+
+        let mut buf = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut BufReader::new(client), &mut buf)
+            .await
+            .unwrap();
+        (String::from_utf8(buf).unwrap(), started)
+    }
+
+    #[tokio::test]
+    async fn starts_compression() {
+        let (output, started) = run("A1", true, false).await;
+        assert!(output.contains("A1 OK DEFLATE active"));
+        assert!(started);
+    }
+
+    #[tokio::test]
+    async fn rejects_second_compress() {
+        let (output, started) = run("A1", true, true).await;
+        assert!(output.contains("A1 NO [COMPRESSIONACTIVE]"));
+        assert!(!started);
+    }
+
+    #[tokio::test]
+    async fn unavailable_without_capability() {
+        let (output, started) = run("A1", false, false).await;
+        assert!(output.contains("A1 BAD Unknown command"));
+        assert!(!started);
+    }
+}
@@ -0,0 +1,81 @@
+//! CREATE command handler.
+//!
+//! Adds an empty folder (RFC 3501 Section 6.3.3). Creating a folder
+//! that already exists fails with `NO [ALREADYEXISTS]` (RFC 5530).
+
+use crate::fake_imap::io::write_line;
+use crate::fake_imap::mailbox::{Folder, Mailbox};
+use std::sync::Mutex;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+
+/// Handle the CREATE command.
+pub async fn handle_create<S: AsyncRead + AsyncWrite + Unpin>(
+    tag: &str,
+    folder_name: &str,
+    mailbox: &Mutex<Mailbox>,
+    stream: &mut BufReader<S>,
+) {
+    // Create under lock (no await inside).
+    let created = {
+        let mut mb = mailbox.lock().unwrap();
+        let exists = mb.get_folder(folder_name).is_some();
+        if !exists {
+            mb.folders.push(Folder {
+                name: folder_name.to_string(),
+                special_use: None,
+                emails: Vec::new(),
+            });
+        }
+        drop(mb);
+        !exists
+    };
+
+    let resp = if created {
+        format!("{tag} OK CREATE completed\r\n")
+    } else {
+        format!("{tag} NO [ALREADYEXISTS] Folder already exists\r\n")
+    };
+    let _ = write_line(stream, &resp).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_imap::mailbox::MailboxBuilder;
+    use tokio::io::BufReader;
+
+    async fn run_create(tag: &str, folder: &str, mailbox: &Mutex<Mailbox>) -> String {
+        let (client, server) = tokio::io::duplex(4096);
+        let mut stream = BufReader::new(server);
+
+        handle_create(tag, folder, mailbox, &mut stream).await;
+        drop(stream);
+
+        let mut buf = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut BufReader::new(client), &mut buf)
+            .await
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[tokio::test]
+    async fn creates_empty_folder() {
+        let mb = Mutex::new(MailboxBuilder::new().folder("INBOX").build());
+
+        let output = run_create("A1", "Labels/Receipts", &mb).await;
+        assert!(output.contains("A1 OK CREATE completed"));
+
+        let mb = mb.lock().unwrap();
+        let folder = mb.get_folder("Labels/Receipts").unwrap();
+        assert!(folder.emails.is_empty());
+    }
+
+    #[tokio::test]
+    async fn existing_folder_returns_alreadyexists() {
+        let mb = Mutex::new(MailboxBuilder::new().folder("INBOX").build());
+
+        let output = run_create("A1", "inbox", &mb).await;
+        assert!(output.contains("A1 NO [ALREADYEXISTS]"));
+        assert_eq!(mb.lock().unwrap().folders.len(), 1);
+    }
+}
@@ -3,7 +3,8 @@
 //! Permanently removes all messages with the `\Deleted` flag from the
 //! selected folder. Sends `* N EXPUNGE` for each removed message
 //! (where N is the original sequence number, adjusted as earlier
-//! messages are removed).
+//! messages are removed). With the `reject_expunge` option, it
+//! answers `NO` and removes nothing.
 
 use crate::fake_imap::io::write_line;
 use crate::fake_imap::mailbox::Mailbox;
@@ -25,19 +26,28 @@ pub async fn handle_expunge<S: AsyncRead + AsyncWrite + Unpin>(
     };
 
     // Check folder exists (quick lock, no await).
-    let folder_exists = {
+    let (folder_exists, reject) = {
         let mb = mailbox.lock().unwrap();
-        mb.get_folder(folder_name).is_some()
+        (
+            mb.get_folder(folder_name).is_some(),
+            mb.options.reject_expunge,
+        )
     };
     if !folder_exists {
         let resp = format!("{tag} BAD Folder not found\r\n");
         let _ = write_line(stream, &resp).await;
         return;
     }
+    if reject {
+        let resp = format!("{tag} NO Expunge not permitted\r\n");
+        let _ = write_line(stream, &resp).await;
+        return;
+    }
 
     // Remove deleted messages under lock (no await inside).
     let expunged_seqs = {
         let mut mb = mailbox.lock().unwrap();
+        let options = mb.options.clone();
         let folder = mb.get_folder_mut(folder_name).unwrap();
 
         let deleted_indices: Vec<usize> = folder
@@ -53,7 +63,7 @@ pub async fn handle_expunge<S: AsyncRead + AsyncWrite + Unpin>(
         for (offset, idx) in deleted_indices.iter().enumerate() {
             // The sequence number the client sees, adjusted for
             // prior removals in this EXPUNGE.
-            let seq = idx + 1 - offset;
+            let seq = options.seq(*idx) - offset;
             seqs.push(seq);
         }
 
@@ -81,7 +91,7 @@ pub async fn handle_expunge<S: AsyncRead + AsyncWrite + Unpin>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::fake_imap::mailbox::{MailboxBuilder, TestEmail};
+    use crate::fake_imap::mailbox::{MailboxBuilder, ServerOptions, TestEmail};
     use tokio::io::BufReader;
 
     fn make_raw_email() -> Vec<u8> {
@@ -152,6 +162,26 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn reject_expunge_keeps_deleted_emails() {
+        let raw = make_raw_email();
+        let mb = Mutex::new(
+            MailboxBuilder::new()
+                .folder("INBOX")
+                .email_with_flags(1, &["\\Deleted"], &raw)
+                .reject_expunge()
+                .build(),
+        );
+
+        let output = run_expunge("A1", &mb, Some("INBOX")).await;
+
+        assert_eq!(output, "A1 NO Expunge not permitted\r\n");
+        assert_eq!(
+            mb.lock().unwrap().get_folder("INBOX").unwrap().emails.len(),
+            1
+        );
+    }
+
     #[tokio::test]
     async fn no_folder_selected_returns_bad() {
         let mb = Mutex::new(MailboxBuilder::new().folder("INBOX").build());
@@ -167,27 +197,20 @@ mod tests {
         let mb = Mutex::new(Mailbox {
             folders: vec![crate::fake_imap::mailbox::Folder {
                 name: "INBOX".to_string(),
+                special_use: None,
                 emails: vec![
                     TestEmail {
-                        uid: 1,
-                        seen: false,
                         deleted: true,
-                        raw: raw.clone(),
-                    },
-                    TestEmail {
-                        uid: 2,
-                        seen: false,
-                        deleted: false,
-                        raw: raw.clone(),
+                        ..TestEmail::new(1, false, &raw)
                     },
+                    TestEmail::new(2, false, &raw),
                     TestEmail {
-                        uid: 3,
-                        seen: false,
                         deleted: true,
-                        raw: raw.clone(),
+                        ..TestEmail::new(3, false, &raw)
                     },
                 ],
             }],
+            options: ServerOptions::default(),
         });
 
         let output = run_expunge("A1", &mb, Some("INBOX")).await;
@@ -0,0 +1,185 @@
+//! IDLE command handler (RFC 2177).
+//!
+//! The server answers with a `+` continuation and the folder's current
+//! `* <n> EXISTS`, so that mail delivered since the client's last
+//! command is not missed. Until the client sends `DONE`, it then
+//! announces messages added to the selected folder (e.g. by an APPEND
+//! on another connection) the same way. The mailbox has no change
+//! notifications, so the folder is polled.
+//!
+//! With `ServerOptions::idle_limit`, an IDLE that lasts longer is
+//! ended with `* BYE` and the connection closed.
+
+use crate::fake_imap::io::write_line;
+use crate::fake_imap::mailbox::Mailbox;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader};
+use tokio::time::Instant;
+
+/// How often the selected folder is checked for new messages.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Handle the IDLE command.
+///
+/// Returns `false` if the session should end: the client went away
+/// or the IDLE hit `idle_limit`.
+pub async fn handle_idle<S: AsyncRead + AsyncWrite + Unpin>(
+    tag: &str,
+    mailbox: &Mutex<Mailbox>,
+    selected_folder: Option<&str>,
+    stream: &mut BufReader<S>,
+) -> bool {
+    let Some(folder_name) = selected_folder else {
+        let resp = format!("{tag} BAD No folder selected\r\n");
+        return write_line(stream, &resp).await.is_ok();
+    };
+
+    let count = || {
+        mailbox
+            .lock()
+            .unwrap()
+            .get_folder(folder_name)
+            .map_or(0, |f| f.emails.len())
+    };
+    let deadline = mailbox
+        .lock()
+        .unwrap()
+        .options
+        .idle_limit
+        .map(|limit| Instant::now() + limit);
+    let mut exists = count();
+
+    let greeting = format!("+ idling\r\n* {exists} EXISTS\r\n");
+    if write_line(stream, &greeting).await.is_err() {
+        return false;
+    }
+
+    // Partial reads are kept in `line` when the poll timer wins.
+    let mut line = Vec::new();
+    loop {
+        let read = tokio::select! {
+            read = stream.read_until(b'\n', &mut line) => Some(read),
+            () = tokio::time::sleep(POLL_INTERVAL) => None,
+        };
+        match read {
+            Some(Ok(n)) if n > 0 && line.ends_with(b"\n") => {
+                if line.trim_ascii().eq_ignore_ascii_case(b"DONE") {
+                    break;
+                }
+                let resp = format!("{tag} BAD Expected DONE\r\n");
+                return write_line(stream, &resp).await.is_ok();
+            }
+            Some(_) => return false,
+            None => {}
+        }
+
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            let _ = write_line(stream, "* BYE IDLE lasted too long\r\n").await;
+            return false;
+        }
+
+        let now = count();
+        if now > exists {
+            let update = format!("* {now} EXISTS\r\n");
+            if write_line(stream, &update).await.is_err() {
+                return false;
+            }
+        }
+        exists = now;
+    }
+
+    let resp = format!("{tag} OK IDLE terminated\r\n");
+    write_line(stream, &resp).await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_imap::mailbox::{MailboxBuilder, TestEmail};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn raw() -> Vec<u8> {
+        b"From: a@b.com\r\nSubject: Test\r\n\r\nBody".to_vec()
+    }
+
+    #[tokio::test]
+    async fn announces_new_mail_until_done() {
+        let mb = Mutex::new(
+            MailboxBuilder::new()
+                .folder("INBOX")
+                .email(1, false, &raw())
+                .build(),
+        );
+        let (mut client, server) = tokio::io::duplex(4096);
+        let mut stream = BufReader::new(server);
+
+        let driver = async {
+            let mut buf = [0; 64];
+            let mut greeting = Vec::new();
+            while !greeting.ends_with(b"EXISTS\r\n") {
+                let n = client.read(&mut buf).await.unwrap();
+                greeting.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(greeting, b"+ idling\r\n* 1 EXISTS\r\n");
+
+            mb.lock()
+                .unwrap()
+                .get_folder_mut("INBOX")
+                .unwrap()
+                .emails
+                .push(TestEmail::new(2, false, &raw()));
+            let n = client.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"* 2 EXISTS\r\n");
+
+            client.write_all(b"DONE\r\n").await.unwrap();
+            let n = client.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"A1 OK IDLE terminated\r\n");
+        };
+        let (open, ()) = tokio::join!(handle_idle("A1", &mb, Some("INBOX"), &mut stream), driver);
+        assert!(open);
+    }
+
+    #[tokio::test]
+    async fn closes_after_limit() {
+        let mb = Mutex::new(
+            MailboxBuilder::new()
+                .folder("INBOX")
+                .idle_limit(Duration::from_millis(30))
+                .build(),
+        );
+        let (client, server) = tokio::io::duplex(4096);
+        let mut stream = BufReader::new(server);
+
+        let open = handle_idle("A1", &mb, Some("INBOX"), &mut stream).await;
+        drop(stream);
+        assert!(!open);
+
+        let mut buf = String::new();
+        BufReader::new(client)
+            .read_to_string(&mut buf)
+            .await
+            .unwrap();
+        assert_eq!(
+            buf,
+            "+ idling\r\n* 0 EXISTS\r\n* BYE IDLE lasted too long\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn no_folder_selected_returns_bad() {
+        let mb = Mutex::new(MailboxBuilder::new().folder("INBOX").build());
+        let (client, server) = tokio::io::duplex(4096);
+        let mut stream = BufReader::new(server);
+
+        assert!(handle_idle("A1", &mb, None, &mut stream).await);
+        drop(stream);
+
+        let mut buf = String::new();
+        BufReader::new(client)
+            .read_to_string(&mut buf)
+            .await
+            .unwrap();
+        assert!(buf.contains("A1 BAD No folder selected"));
+    }
+}
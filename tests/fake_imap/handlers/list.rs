@@ -5,22 +5,50 @@
 //!
 //! ```text
 //! * LIST (\HasNoChildren) "/" "INBOX"
-//! * LIST (\HasNoChildren) "/" "Sent"
+//! * LIST (\HasNoChildren \Sent) "/" "Sent"
 //! A0002 OK LIST completed
 //! ```
+//!
+//! Folders with a SPECIAL-USE role (RFC 6154) carry it as an extra
+//! attribute.
+//!
+//! An empty mailbox pattern (`LIST "" ""`) only asks for the
+//! hierarchy delimiter, which is answered with a single placeholder
+//! line:
+//!
+//! ```text
+//! * LIST (\Noselect) "/" ""
+//! ```
 
 use crate::fake_imap::io::write_line;
 use crate::fake_imap::mailbox::Mailbox;
 use tokio::io::{AsyncRead, AsyncWrite, BufReader};
 
-/// Handle the LIST command. Emits one `* LIST` line per folder.
+/// Handle the LIST command. Emits one `* LIST` line per folder, or
+/// just the delimiter line when `delimiter_only` is set.
 pub async fn handle_list<S: AsyncRead + AsyncWrite + Unpin>(
     tag: &str,
     mailbox: &Mailbox,
+    delimiter_only: bool,
     stream: &mut BufReader<S>,
 ) {
-    for folder in &mailbox.folders {
-        let line = format!("* LIST (\\HasNoChildren) \"/\" \"{}\"\r\n", folder.name);
+    let delimiter = mailbox.options.delimiter();
+    if delimiter_only {
+        let line = format!("* LIST (\\Noselect) \"{delimiter}\" \"\"\r\n");
+        if write_line(stream, &line).await.is_err() {
+            return;
+        }
+    }
+
+    for folder in mailbox.folders.iter().filter(|_| !delimiter_only) {
+        let attributes = folder.special_use.as_ref().map_or_else(
+            || "\\HasNoChildren".to_string(),
+            |attr| format!("\\HasNoChildren {attr}"),
+        );
+        let line = format!(
+            "* LIST ({attributes}) \"{delimiter}\" \"{}\"\r\n",
+            folder.name
+        );
         if write_line(stream, &line).await.is_err() {
             return;
         }
@@ -36,10 +64,14 @@ mod tests {
     use tokio::io::BufReader;
 
     async fn run(tag: &str, mailbox: &Mailbox) -> String {
+        run_pattern(tag, mailbox, false).await
+    }
+
+    async fn run_pattern(tag: &str, mailbox: &Mailbox, delimiter_only: bool) -> String {
         let (client, server) = tokio::io::duplex(4096);
         let mut stream = BufReader::new(server);
 
-        handle_list(tag, mailbox, &mut stream).await;
+        handle_list(tag, mailbox, delimiter_only, &mut stream).await;
         drop(stream);
 
         let mut buf = Vec::new();
@@ -64,6 +96,18 @@ mod tests {
         assert!(output.contains("\"Trash\""));
     }
 
+    #[tokio::test]
+    async fn empty_pattern_returns_delimiter_only() {
+        let mailbox = MailboxBuilder::new().folder("INBOX").delimiter('.').build();
+
+        let output = run_pattern("A1", &mailbox, true).await;
+
+        assert_eq!(
+            output,
+            "* LIST (\\Noselect) \".\" \"\"\r\nA1 OK LIST completed\r\n"
+        );
+    }
+
     #[tokio::test]
     async fn ends_with_tagged_ok() {
         let mailbox = MailboxBuilder::new().folder("INBOX").build();
@@ -87,4 +131,17 @@ mod tests {
 
         assert!(output.contains("\\HasNoChildren"));
     }
+
+    #[tokio::test]
+    async fn lists_special_use_attribute() {
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .folder("Corbeille")
+            .special_use("\\Trash")
+            .build();
+
+        let output = run("A1", &mailbox).await;
+        assert!(output.contains("* LIST (\\HasNoChildren) \"/\" \"INBOX\""));
+        assert!(output.contains("* LIST (\\HasNoChildren \\Trash) \"/\" \"Corbeille\""));
+    }
 }
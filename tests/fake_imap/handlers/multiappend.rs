@@ -0,0 +1,232 @@
+//! APPEND with several messages (MULTIAPPEND, RFC 3502).
+//!
+//! `imap-codec` only decodes single-message APPENDs, so a command
+//! carrying more than one literal is parsed here by hand:
+//!
+//! ```text
+//!   Client:  A0005 APPEND "INBOX" (\Seen) {310+}
+//!   Client:  <310 bytes> {280+}
+//!   Client:  <280 bytes>
+//!   Server:  A0005 OK [APPENDUID 1 7:8] APPEND completed
+//! ```
+//!
+//! Each message may have its own flag list and date; dates are
+//! skipped. The messages are stored all together or, if the folder
+//! does not exist, not at all.
+
+use crate::fake_imap::io::write_line;
+use crate::fake_imap::mailbox::{Mailbox, TestEmail};
+use std::sync::Mutex;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+
+/// A parsed multi-message APPEND.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MultiAppend {
+    pub tag: String,
+    pub folder: String,
+    /// Each message's flag names and raw bytes.
+    pub messages: Vec<(Vec<String>, Vec<u8>)>,
+}
+
+/// Parse a complete APPEND command line (literals included). Returns
+/// `None` if it is not a well-formed APPEND.
+pub fn parse_multiappend(line: &[u8]) -> Option<MultiAppend> {
+    let (tag, rest) = split_word(line)?;
+    let (command, rest) = split_word(rest)?;
+    if !command.eq_ignore_ascii_case(b"APPEND") {
+        return None;
+    }
+    let (folder, mut rest) = mailbox_name(rest)?;
+
+    let mut messages = Vec::new();
+    while rest != b"\r\n" {
+        rest = rest.strip_prefix(b" ")?;
+        let mut flags = Vec::new();
+        if let Some(list) = rest.strip_prefix(b"(") {
+            let end = list.iter().position(|&b| b == b')')?;
+            flags = String::from_utf8_lossy(&list[..end])
+                .split_whitespace()
+                .map(ToString::to_string)
+                .collect();
+            rest = list[end + 1..].strip_prefix(b" ")?;
+        }
+        if let Some(date) = rest.strip_prefix(b"\"") {
+            let end = date.iter().position(|&b| b == b'"')?;
+            rest = date[end + 1..].strip_prefix(b" ")?;
+        }
+
+        let literal = rest.strip_prefix(b"{")?;
+        let end = literal.windows(3).position(|w| w == b"}\r\n")?;
+        let length: usize = std::str::from_utf8(&literal[..end])
+            .ok()?
+            .trim_end_matches('+')
+            .parse()
+            .ok()?;
+        let data = &literal[end + 3..];
+        messages.push((flags, data.get(..length)?.to_vec()));
+        rest = &data[length..];
+    }
+
+    Some(MultiAppend {
+        tag: String::from_utf8_lossy(tag).into_owned(),
+        folder,
+        messages,
+    })
+}
+
+/// Handle a multi-message APPEND.
+pub async fn handle_multiappend<S: AsyncRead + AsyncWrite + Unpin>(
+    append: &MultiAppend,
+    mailbox: &Mutex<Mailbox>,
+    stream: &mut BufReader<S>,
+) {
+    // Store under lock (no await inside).
+    let uids = {
+        let mut mb = mailbox.lock().unwrap();
+        let uids = mb.get_folder_mut(&append.folder).map(|folder| {
+            let first = folder.next_uid();
+            for (flags, raw) in &append.messages {
+                let uid = folder.next_uid();
+                folder.emails.push(new_email(uid, flags, raw));
+            }
+            (first, folder.next_uid() - 1)
+        });
+        drop(mb);
+        uids
+    };
+
+    let tag = &append.tag;
+    let resp = match uids {
+        Some((first, last)) => {
+            format!("{tag} OK [APPENDUID 1 {first}:{last}] APPEND completed\r\n")
+        }
+        None => format!("{tag} NO [TRYCREATE] Folder not found\r\n"),
+    };
+    let _ = write_line(stream, &resp).await;
+}
+
+/// Build a stored message from flag names such as `\Seen`.
+fn new_email(uid: u32, flags: &[String], raw: &[u8]) -> TestEmail {
+    let mut email = TestEmail::new(uid, false, raw);
+    for flag in flags {
+        match flag.as_str() {
+            "\\Seen" => email.seen = true,
+            "\\Answered" => email.answered = true,
+            "\\Flagged" => email.flagged = true,
+            "\\Deleted" => email.deleted = true,
+            "\\Draft" => email.draft = true,
+            kw if !kw.starts_with('\\') => email.keywords.push(kw.to_string()),
+            _ => {}
+        }
+    }
+    email
+}
+
+/// Split off the first space-delimited word.
+fn split_word(line: &[u8]) -> Option<(&[u8], &[u8])> {
+    let end = line.iter().position(|&b| b == b' ')?;
+    Some((&line[..end], &line[end + 1..]))
+}
+
+/// Parse a quoted or atom mailbox name.
+fn mailbox_name(rest: &[u8]) -> Option<(String, &[u8])> {
+    let Some(quoted) = rest.strip_prefix(b"\"") else {
+        let end = rest.iter().position(|&b| b == b' ')?;
+        return Some((
+            String::from_utf8_lossy(&rest[..end]).into_owned(),
+            &rest[end..],
+        ));
+    };
+
+    let mut name = Vec::new();
+    let mut bytes = quoted.iter().enumerate();
+    while let Some((i, &b)) = bytes.next() {
+        match b {
+            b'\\' => name.push(*bytes.next()?.1),
+            b'"' => {
+                return Some((
+                    String::from_utf8_lossy(&name).into_owned(),
+                    &quoted[i + 1..],
+                ));
+            }
+            _ => name.push(b),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_imap::mailbox::MailboxBuilder;
+
+    const LINE: &[u8] = b"A5 APPEND \"My \\\"Box\\\"\" (\\Seen Work) {5+}\r\nfirst \
+        \"01-Jan-2024 00:00:00 +0000\" {6+}\r\nsecond\r\n";
+
+    async fn run(append: &MultiAppend, mailbox: &Mutex<Mailbox>) -> String {
+        let (client, server) = tokio::io::duplex(4096);
+        let mut stream = BufReader::new(server);
+
+        handle_multiappend(append, mailbox, &mut stream).await;
+        drop(stream);
+
+        let mut buf = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut BufReader::new(client), &mut buf)
+            .await
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn parses_each_message() {
+        let append = parse_multiappend(LINE).unwrap();
+        assert_eq!(append.tag, "A5");
+        assert_eq!(append.folder, "My \"Box\"");
+        assert_eq!(
+            append.messages,
+            vec![
+                (vec!["\\Seen".into(), "Work".into()], b"first".to_vec()),
+                (vec![], b"second".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_other_commands_and_short_literals() {
+        assert_eq!(parse_multiappend(b"A1 NOOP\r\n"), None);
+        assert_eq!(
+            parse_multiappend(b"A1 APPEND INBOX {9+}\r\nshort\r\n"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn stores_all_messages_with_consecutive_uids() {
+        let mb = Mutex::new(
+            MailboxBuilder::new()
+                .folder("My \"Box\"")
+                .email(3, false, b"Subject: old\r\n\r\n")
+                .build(),
+        );
+
+        let output = run(&parse_multiappend(LINE).unwrap(), &mb).await;
+        assert!(output.contains("A5 OK [APPENDUID 1 4:5] APPEND completed"));
+
+        let mb = mb.lock().unwrap();
+        let emails = &mb.get_folder("My \"Box\"").unwrap().emails;
+        assert_eq!(emails.len(), 3);
+        assert!(emails[1].seen);
+        assert_eq!(emails[1].keywords, vec!["Work".to_string()]);
+        assert_eq!(emails[2].raw, b"second");
+    }
+
+    #[tokio::test]
+    async fn missing_folder_stores_nothing() {
+        let mb = Mutex::new(MailboxBuilder::new().folder("INBOX").build());
+        let mut append = parse_multiappend(LINE).unwrap();
+        append.folder = "Nowhere".into();
+
+        let output = run(&append, &mb).await;
+        assert!(output.contains("A5 NO [TRYCREATE]"));
+    }
+}
@@ -7,6 +7,9 @@
 //!   UID space is reset (e.g. the folder was deleted and recreated).
 //!   Clients use this to invalidate their UID caches.
 //!
+//! `UIDVALIDITY` and `UIDNEXT` are left out with
+//! `ServerOptions::omit_uidnext`.
+//!
 //! Returns the selected folder name (or `None` if not found).
 
 use crate::fake_imap::io::write_line;
@@ -34,16 +37,18 @@ pub async fn handle_select<S: AsyncRead + AsyncWrite + Unpin>(
         // RFC 3501 Section 6.3.1: required RECENT response
         let _ = write_line(stream, "* 0 RECENT\r\n").await;
 
-        let _ = write_line(stream, "* OK [UIDVALIDITY 1]\r\n").await;
-
-        // RFC 3501 Section 7.1: UIDNEXT
-        let uidnext = folder
-            .emails
-            .iter()
-            .map(|e| e.uid)
-            .max()
-            .map_or(1, |max| max + 1);
-        let _ = write_line(stream, &format!("* OK [UIDNEXT {uidnext}]\r\n")).await;
+        if !mailbox.options.omit_uidnext {
+            let _ = write_line(stream, "* OK [UIDVALIDITY 1]\r\n").await;
+
+            // RFC 3501 Section 7.1: UIDNEXT
+            let uidnext = folder
+                .emails
+                .iter()
+                .map(|e| e.uid)
+                .max()
+                .map_or(1, |max| max + 1);
+            let _ = write_line(stream, &format!("* OK [UIDNEXT {uidnext}]\r\n")).await;
+        }
 
         // RFC 3501 Section 7.1: PERMANENTFLAGS
         let _ = write_line(
@@ -54,14 +59,15 @@ pub async fn handle_select<S: AsyncRead + AsyncWrite + Unpin>(
 
         // RFC 3501 Section 7.1: UNSEEN (first unseen message)
         if let Some(pos) = folder.emails.iter().position(|e| !e.seen) {
-            let _ = write_line(stream, &format!("* OK [UNSEEN {}]\r\n", pos + 1)).await;
+            let seq = mailbox.options.seq(pos);
+            let _ = write_line(stream, &format!("* OK [UNSEEN {seq}]\r\n")).await;
         }
 
         let resp = format!("{tag} OK [READ-WRITE] SELECT completed\r\n");
         let _ = write_line(stream, &resp).await;
         Some(folder_name.to_string())
     } else {
-        let resp = format!("{tag} NO Folder not found\r\n");
+        let resp = format!("{tag} NO [NONEXISTENT] Folder not found\r\n");
         let _ = write_line(stream, &resp).await;
         None
     }
@@ -108,6 +114,21 @@ mod tests {
         assert!(output.contains("A1 OK"));
     }
 
+    #[tokio::test]
+    async fn omits_uid_codes() {
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(1, false, &make_raw_email())
+            .omit_uidnext()
+            .build();
+
+        let (output, _) = run("A1", "INBOX", &mailbox).await;
+
+        assert!(output.contains("* 1 EXISTS"));
+        assert!(!output.contains("UIDVALIDITY"));
+        assert!(!output.contains("UIDNEXT"));
+    }
+
     #[tokio::test]
     async fn returns_none_for_missing_folder() {
         let mailbox = MailboxBuilder::new().folder("INBOX").build();
@@ -115,7 +136,7 @@ mod tests {
         let (output, selected) = run("A1", "NoSuchFolder", &mailbox).await;
 
         assert!(selected.is_none());
-        assert!(output.contains("A1 NO Folder not found"));
+        assert!(output.contains("A1 NO [NONEXISTENT] Folder not found"));
     }
 
     #[tokio::test]
@@ -1,7 +1,8 @@
 //! UID COPY command handler.
 //!
 //! Copies messages from the selected folder to a destination folder.
-//! The original messages remain in the source folder.
+//! The original messages remain in the source folder. Both single
+//! UIDs and ranges (`2:4`, `1:*`) are accepted.
 
 use crate::fake_imap::io::write_line;
 use crate::fake_imap::mailbox::Mailbox;
@@ -9,17 +10,20 @@ use imap_codec::imap_types::sequence::{SeqOrUid, Sequence, SequenceSet};
 use std::sync::Mutex;
 use tokio::io::{AsyncRead, AsyncWrite, BufReader};
 
-/// Extract UIDs from a `SequenceSet` (single values only).
-fn extract_uids(seq_set: &SequenceSet) -> Vec<u32> {
-    seq_set
-        .0
-        .as_ref()
-        .iter()
-        .filter_map(|seq| match seq {
-            Sequence::Single(SeqOrUid::Value(v)) => Some(v.get()),
-            _ => None,
-        })
-        .collect()
+/// Whether `uid` is in `seq_set`. Supports single values and ranges
+/// (e.g. `1,3,5` or `1:*`); `*` is the highest UID in the folder.
+fn contains_uid(seq_set: &SequenceSet, uid: u32, max_uid: u32) -> bool {
+    let value = |v: &SeqOrUid| match v {
+        SeqOrUid::Value(v) => v.get(),
+        SeqOrUid::Asterisk => max_uid,
+    };
+    seq_set.0.as_ref().iter().any(|seq| match seq {
+        Sequence::Single(v) => value(v) == uid,
+        Sequence::Range(a, b) => {
+            let (a, b) = (value(a), value(b));
+            (a.min(b)..=a.max(b)).contains(&uid)
+        }
+    })
 }
 
 /// Handle the UID COPY command. Clones emails into the destination
@@ -38,8 +42,6 @@ pub async fn handle_uid_copy<S: AsyncRead + AsyncWrite + Unpin>(
         return;
     };
 
-    let uids = extract_uids(sequence_set);
-
     // Check folders exist (quick lock, no await).
     let (src_exists, dest_exists) = {
         let mb = mailbox.lock().unwrap();
@@ -65,12 +67,12 @@ pub async fn handle_uid_copy<S: AsyncRead + AsyncWrite + Unpin>(
     // Perform copy under lock (no await inside).
     {
         let mut mb = mailbox.lock().unwrap();
-        let emails_to_copy: Vec<_> = mb
-            .get_folder(folder_name)
-            .unwrap()
+        let source = mb.get_folder(folder_name).unwrap();
+        let max_uid = source.emails.iter().map(|e| e.uid).max().unwrap_or(0);
+        let emails_to_copy: Vec<_> = source
             .emails
             .iter()
-            .filter(|e| uids.contains(&e.uid))
+            .filter(|e| contains_uid(sequence_set, e.uid, max_uid))
             .cloned()
             .collect();
 
@@ -157,6 +159,43 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn copies_uid_range() {
+        let raw = make_raw_email();
+        let mb = Mutex::new(
+            MailboxBuilder::new()
+                .folder("INBOX")
+                .email(1, false, &raw)
+                .email(2, false, &raw)
+                .email(3, false, &raw)
+                .email(5, false, &raw)
+                .folder("Archive")
+                .build(),
+        );
+        let range = SequenceSet(
+            vec![Sequence::Range(
+                SeqOrUid::Value(NonZeroU32::new(2).unwrap()),
+                SeqOrUid::Asterisk,
+            )]
+            .try_into()
+            .unwrap(),
+        );
+
+        let output = run_copy("A1", &range, "Archive", &mb, Some("INBOX")).await;
+
+        assert!(output.contains("A1 OK COPY completed"));
+        let mb = mb.lock().unwrap();
+        let uids: Vec<u32> = mb
+            .get_folder("Archive")
+            .unwrap()
+            .emails
+            .iter()
+            .map(|e| e.uid)
+            .collect();
+        drop(mb);
+        assert_eq!(uids, vec![2, 3, 5]);
+    }
+
     #[tokio::test]
     async fn source_email_remains() {
         let raw = make_raw_email();
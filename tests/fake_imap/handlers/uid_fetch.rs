@@ -13,35 +13,90 @@
 //! "the next `length` bytes are raw data, not IMAP protocol text."
 //! After reading those bytes, the client expects the closing `)`.
 //!
+//! Only the requested data items are returned. We support `UID`,
+//! `FLAGS`, `INTERNALDATE`, `RFC822.SIZE`, `RFC822`, `ENVELOPE` (see
+//! `envelope`), `BODYSTRUCTURE` (see `bodystructure`; `BODY` gets the
+//! same structure, extension data included), the `FAST`/`ALL`/`FULL`
+//! macros, and the `BODY[]` / `BODY[HEADER]` /
+//! `BODY[HEADER.FIELDS (...)]` / `BODY[TEXT]` / `BODY[<part>]`
+//! sections (with or without `.PEEK`). `UID` is always included, as RFC 3501 Section
+//! 6.4.8 requires for UID FETCH.
+//!
+//! Sections may carry a partial range (`BODY[]<origin.count>`); the
+//! response then names the origin (`BODY[]<origin>`) and holds at most
+//! `count` bytes starting there.
+//!
 //! The sequence number is the 1-based index of the message within the
 //! folder, per RFC 3501 Section 7.4.2.
+//!
+//! With `ServerOptions::interleave_exists`, every FETCH response is
+//! followed by an unsolicited `* <n> EXISTS`, which RFC 3501 Section
+//! 7 allows at any time.
+//!
+//! With `ServerOptions::deliver_on_fetch`, the first FETCH that
+//! returns a message also appends the configured message to the folder
+//! and reports the new size with `* <n> EXISTS`.
 
+use crate::fake_imap::bodystructure::{body_structure, part_body};
+use crate::fake_imap::envelope::envelope;
 use crate::fake_imap::io::{write_bytes, write_line};
-use crate::fake_imap::mailbox::Mailbox;
+use crate::fake_imap::mailbox::{Mailbox, TestEmail};
+use imap_codec::imap_types::fetch::{
+    Macro, MacroOrMessageDataItemNames, MessageDataItemName, Section,
+};
 use imap_codec::imap_types::sequence::{SeqOrUid, Sequence, SequenceSet};
+use std::sync::Mutex;
 use tokio::io::{AsyncRead, AsyncWrite, BufReader};
 
-/// Extract UIDs from a `SequenceSet`. We only support single values
-/// (not ranges) since that's what `async-imap` sends for individual
-/// fetches.
-fn extract_uids(seq_set: &SequenceSet) -> Vec<u32> {
-    seq_set
-        .0
-        .as_ref()
-        .iter()
-        .filter_map(|seq| match seq {
-            Sequence::Single(SeqOrUid::Value(v)) => Some(v.get()),
-            _ => None,
-        })
-        .collect()
+/// Extract UIDs from a `SequenceSet`, in the order given.
+///
+/// Single values are taken as they are. A range (e.g. `1:*`) yields
+/// the UIDs of `existing` that fall in it, ascending, with `*`
+/// standing for the highest one.
+fn extract_uids(seq_set: &SequenceSet, existing: &[u32]) -> Vec<u32> {
+    let max_uid = existing.iter().copied().max().unwrap_or(0);
+    let bound = |v: &SeqOrUid| match v {
+        SeqOrUid::Value(v) => v.get(),
+        SeqOrUid::Asterisk => max_uid,
+    };
+    let mut uids = Vec::new();
+    for seq in seq_set.0.as_ref() {
+        match seq {
+            Sequence::Single(SeqOrUid::Value(v)) => uids.push(v.get()),
+            Sequence::Single(SeqOrUid::Asterisk) => uids.push(max_uid),
+            Sequence::Range(a, b) => {
+                let (a, b) = (bound(a), bound(b));
+                let (lo, hi) = (a.min(b), a.max(b));
+                let mut in_range: Vec<u32> = existing
+                    .iter()
+                    .copied()
+                    .filter(|uid| (lo..=hi).contains(uid))
+                    .collect();
+                in_range.sort_unstable();
+                uids.extend(in_range);
+            }
+        }
+    }
+    uids
+}
+
+/// Parsed UID FETCH command arguments.
+pub struct FetchArgs<'a> {
+    pub sequence_set: &'a SequenceSet,
+    pub items: &'a MacroOrMessageDataItemNames<'a>,
 }
 
-/// Handle the UID FETCH command. Returns the email body as an IMAP
-/// literal.
+/// Handle the UID FETCH command. Returns the requested data items for
+/// each matching email.
+///
+/// Fetching a body without `.PEEK` (or `RFC822`/`RFC822.TEXT`) sets
+/// `\Seen` as a side effect (RFC 3501 Section 6.4.5). When that changes
+/// the flags, `FLAGS` is included in the response even if it was not
+/// requested.
 pub async fn handle_uid_fetch<S: AsyncRead + AsyncWrite + Unpin>(
     tag: &str,
-    sequence_set: &SequenceSet,
-    mailbox: &Mailbox,
+    args: &FetchArgs<'_>,
+    mailbox: &Mutex<Mailbox>,
     selected_folder: Option<&str>,
     stream: &mut BufReader<S>,
 ) {
@@ -51,39 +106,250 @@ pub async fn handle_uid_fetch<S: AsyncRead + AsyncWrite + Unpin>(
         return;
     };
 
-    let Some(folder) = mailbox.get_folder(folder_name) else {
+    let items = item_names(args.items);
+    let marks_seen = items.iter().any(sets_seen);
+
+    // Render under lock (no await inside), applying the implicit
+    // \Seen as we go.
+    let responses = {
+        let mut mb = mailbox.lock().unwrap();
+        let interleave_exists = mb.options.interleave_exists;
+        let options = mb.options.clone();
+        let mut responses = mb.get_folder_mut(folder_name).map(|folder| {
+            let existing: Vec<u32> = folder.emails.iter().map(|e| e.uid).collect();
+            let uids = extract_uids(args.sequence_set, &existing);
+            // Pretend a message was just delivered.
+            let exists = format!("* {} EXISTS\r\n", folder.emails.len() + 1);
+            let mut responses = Vec::new();
+            for uid in uids {
+                if let Some((idx, email)) = folder
+                    .emails
+                    .iter_mut()
+                    .enumerate()
+                    .find(|(_, e)| e.uid == uid)
+                {
+                    let flags_changed = marks_seen && !email.seen;
+                    email.seen |= marks_seen;
+
+                    let seq = options.seq(idx);
+                    responses.push(render_fetch(seq, email, &items, flags_changed));
+                    if interleave_exists {
+                        responses.push(exists.clone().into_bytes());
+                    }
+                }
+            }
+            responses
+        });
+        if let Some(responses) = responses.as_mut().filter(|r| !r.is_empty()) {
+            responses.extend(deliver(&mut mb, folder_name).map(String::into_bytes));
+        }
+        drop(mb);
+        responses
+    };
+
+    let Some(responses) = responses else {
         let resp = format!("{tag} BAD Folder not found\r\n");
         let _ = write_line(stream, &resp).await;
         return;
     };
 
-    let uids = extract_uids(sequence_set);
+    for response in responses {
+        if write_bytes(stream, &response).await.is_err() {
+            return;
+        }
+    }
+
+    let resp = format!("{tag} OK FETCH completed\r\n");
+    let _ = write_line(stream, &resp).await;
+}
 
-    for uid in uids {
-        if let Some((idx, email)) = folder.emails.iter().enumerate().find(|(_, e)| e.uid == uid) {
-            let seq = idx + 1; // 1-based sequence number
-            let body_len = email.raw.len();
+/// Deliver the pending `deliver_on_fetch` message, if any, and return
+/// the EXISTS update announcing it.
+fn deliver(mailbox: &mut Mailbox, folder_name: &str) -> Option<String> {
+    let raw = mailbox.options.deliver_on_fetch.take()?;
+    let folder = mailbox.get_folder_mut(folder_name)?;
+    let uid = folder.next_uid();
+    folder.emails.push(TestEmail::new(uid, false, &raw));
+    Some(format!("* {} EXISTS\r\n", folder.emails.len()))
+}
 
-            let header = format!(
-                "* {seq} FETCH (UID {uid} BODY[] \
-                 {{{body_len}}}\r\n"
-            );
-            if write_line(stream, &header).await.is_err() {
-                return;
-            }
+/// Whether fetching `item` implicitly sets `\Seen`.
+const fn sets_seen(item: &MessageDataItemName<'_>) -> bool {
+    matches!(
+        item,
+        MessageDataItemName::BodyExt { peek: false, .. }
+            | MessageDataItemName::Rfc822
+            | MessageDataItemName::Rfc822Text
+    )
+}
 
-            if write_bytes(stream, &email.raw).await.is_err() {
-                return;
+/// Expand the FETCH macros (RFC 3501 Section 6.4.5) into their item
+/// lists.
+fn item_names<'a>(items: &MacroOrMessageDataItemNames<'a>) -> Vec<MessageDataItemName<'a>> {
+    match items {
+        MacroOrMessageDataItemNames::Macro(m) => {
+            let mut names = vec![
+                MessageDataItemName::Flags,
+                MessageDataItemName::InternalDate,
+                MessageDataItemName::Rfc822Size,
+            ];
+            if matches!(m, Macro::All | Macro::Full) {
+                names.push(MessageDataItemName::Envelope);
+            }
+            if matches!(m, Macro::Full) {
+                names.push(MessageDataItemName::Body);
             }
+            names
+        }
+        MacroOrMessageDataItemNames::MessageDataItemNames(names) => names.clone(),
+    }
+}
+
+/// Build the complete `* <seq> FETCH (...)` response for one email.
+///
+/// `flags_changed` appends `FLAGS` if it was not requested.
+fn render_fetch(
+    seq: usize,
+    email: &TestEmail,
+    items: &[MessageDataItemName<'_>],
+    flags_changed: bool,
+) -> Vec<u8> {
+    let mut out = format!("* {seq} FETCH (UID {}", email.uid).into_bytes();
 
-            if write_line(stream, ")\r\n").await.is_err() {
-                return;
+    for item in items {
+        match item {
+            MessageDataItemName::Flags => {
+                let flags = email.flags().join(" ");
+                out.extend_from_slice(format!(" FLAGS ({flags})").as_bytes());
+            }
+            MessageDataItemName::InternalDate => {
+                let date = email.internal_date.format("%d-%b-%Y %H:%M:%S %z");
+                out.extend_from_slice(format!(" INTERNALDATE \"{date}\"").as_bytes());
+            }
+            MessageDataItemName::Rfc822Size => {
+                out.extend_from_slice(format!(" RFC822.SIZE {}", email.raw.len()).as_bytes());
+            }
+            MessageDataItemName::Rfc822 => {
+                push_literal(&mut out, " RFC822", &email.raw);
+            }
+            MessageDataItemName::Envelope => {
+                let envelope = envelope(&email.raw);
+                out.extend_from_slice(format!(" ENVELOPE {envelope}").as_bytes());
+            }
+            MessageDataItemName::Body => {
+                let structure = body_structure(&email.raw);
+                out.extend_from_slice(format!(" BODY {structure}").as_bytes());
+            }
+            MessageDataItemName::BodyStructure => {
+                let structure = body_structure(&email.raw);
+                out.extend_from_slice(format!(" BODYSTRUCTURE {structure}").as_bytes());
             }
+            MessageDataItemName::BodyExt {
+                section, partial, ..
+            } => {
+                if let Some((name, data)) = body_section(email, section.as_ref()) {
+                    match partial {
+                        Some((origin, count)) => {
+                            let data = partial_range(&data, *origin, count.get());
+                            push_literal(&mut out, &format!(" BODY[{name}]<{origin}>"), data);
+                        }
+                        None => push_literal(&mut out, &format!(" BODY[{name}]"), &data),
+                    }
+                }
+            }
+            // UID is always sent first; other items are unsupported.
+            _ => {}
         }
     }
 
-    let resp = format!("{tag} OK FETCH completed\r\n");
-    let _ = write_line(stream, &resp).await;
+    if flags_changed
+        && !items
+            .iter()
+            .any(|i| matches!(i, MessageDataItemName::Flags))
+    {
+        let flags = email.flags().join(" ");
+        out.extend_from_slice(format!(" FLAGS ({flags})").as_bytes());
+    }
+
+    out.extend_from_slice(b")\r\n");
+    out
+}
+
+/// Append `<prefix> {<len>}\r\n<data>` to a FETCH response.
+fn push_literal(out: &mut Vec<u8>, prefix: &str, data: &[u8]) {
+    out.extend_from_slice(format!("{prefix} {{{}}}\r\n", data.len()).as_bytes());
+    out.extend_from_slice(data);
+}
+
+/// The `<origin.count>` slice of `data`, clamped to its end.
+fn partial_range(data: &[u8], origin: u32, count: u32) -> &[u8] {
+    let start = usize::try_from(origin)
+        .unwrap_or(usize::MAX)
+        .min(data.len());
+    let count = usize::try_from(count).unwrap_or(usize::MAX);
+    &data[start..start.saturating_add(count).min(data.len())]
+}
+
+/// Resolve a `BODY[<section>]` request to its response name and data.
+///
+/// Returns `None` for sections the fake server does not support
+/// (e.g. MIME part numbers).
+fn body_section(email: &TestEmail, section: Option<&Section<'_>>) -> Option<(String, Vec<u8>)> {
+    let (header, text) = split_message(&email.raw);
+
+    match section {
+        None => Some((String::new(), email.raw.clone())),
+        Some(Section::Header(None)) => Some(("HEADER".to_string(), header.to_vec())),
+        Some(Section::Text(None)) => Some(("TEXT".to_string(), text.to_vec())),
+        Some(Section::HeaderFields(None, fields)) => {
+            let names: Vec<String> = fields
+                .as_ref()
+                .iter()
+                .map(|f| String::from_utf8_lossy(f.as_ref()).into_owned())
+                .collect();
+            let data = filter_header_fields(header, &names);
+            Some((format!("HEADER.FIELDS ({})", names.join(" ")), data))
+        }
+        Some(Section::Part(part)) => {
+            let path: Vec<u32> = part.0.as_ref().iter().map(|n| n.get()).collect();
+            let data = part_body(&email.raw, &path)?;
+            let name: Vec<String> = path.iter().map(u32::to_string).collect();
+            Some((name.join("."), data))
+        }
+        Some(_) => None,
+    }
+}
+
+/// Split a raw message into its header block (including the blank
+/// separator line) and its text.
+fn split_message(raw: &[u8]) -> (&[u8], &[u8]) {
+    raw.windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map_or((raw, &[][..]), |pos| raw.split_at(pos + 4))
+}
+
+/// Keep only the header fields named in `names` (case-insensitive),
+/// including their folded continuation lines.
+fn filter_header_fields(header: &[u8], names: &[String]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(header);
+    let mut out = String::new();
+    let mut keep = false;
+
+    for line in text.split_inclusive("\r\n") {
+        if line == "\r\n" {
+            break;
+        }
+        if !line.starts_with([' ', '\t']) {
+            let name = line.split(':').next().unwrap_or_default();
+            keep = names.iter().any(|n| n.eq_ignore_ascii_case(name.trim()));
+        }
+        if keep {
+            out.push_str(line);
+        }
+    }
+
+    out.push_str("\r\n");
+    out.into_bytes()
 }
 
 #[cfg(test)]
@@ -107,16 +373,49 @@ mod tests {
         )
     }
 
+    fn body_peek() -> MacroOrMessageDataItemNames<'static> {
+        MacroOrMessageDataItemNames::MessageDataItemNames(vec![MessageDataItemName::BodyExt {
+            section: None,
+            partial: None,
+            peek: true,
+        }])
+    }
+
     async fn run(
         tag: &str,
         sequence_set: &SequenceSet,
         mailbox: &Mailbox,
         selected: Option<&str>,
+    ) -> String {
+        run_items(tag, sequence_set, &body_peek(), mailbox, selected).await
+    }
+
+    async fn run_items(
+        tag: &str,
+        sequence_set: &SequenceSet,
+        items: &MacroOrMessageDataItemNames<'_>,
+        mailbox: &Mailbox,
+        selected: Option<&str>,
+    ) -> String {
+        let mailbox = Mutex::new(mailbox.clone());
+        run_shared(tag, sequence_set, items, &mailbox, selected).await
+    }
+
+    async fn run_shared(
+        tag: &str,
+        sequence_set: &SequenceSet,
+        items: &MacroOrMessageDataItemNames<'_>,
+        mailbox: &Mutex<Mailbox>,
+        selected: Option<&str>,
     ) -> String {
         let (client, server) = tokio::io::duplex(4096);
         let mut stream = BufReader::new(server);
 
-        handle_uid_fetch(tag, sequence_set, mailbox, selected, &mut stream).await;
+        let args = FetchArgs {
+            sequence_set,
+            items,
+        };
+        handle_uid_fetch(tag, &args, mailbox, selected, &mut stream).await;
         drop(stream);
 
         let mut buf = Vec::new();
@@ -142,6 +441,46 @@ mod tests {
         assert!(output.contains("A1 OK FETCH completed"));
     }
 
+    #[tokio::test]
+    async fn seq_base_shifts_sequence_numbers() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(3, false, &raw)
+            .email(7, false, &raw)
+            .seq_base(100)
+            .build();
+
+        let output = run("A1", &uid_set(7), &mailbox, Some("INBOX")).await;
+
+        assert!(output.contains("* 101 FETCH (UID 7 BODY[]"));
+    }
+
+    #[tokio::test]
+    async fn fetches_uid_range() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(3, false, &raw)
+            .email(7, false, &raw)
+            .email(9, false, &raw)
+            .build();
+        let all = SequenceSet(
+            vec![Sequence::Range(
+                SeqOrUid::Value(NonZeroU32::new(4).unwrap()),
+                SeqOrUid::Asterisk,
+            )]
+            .try_into()
+            .unwrap(),
+        );
+
+        let output = run("A1", &all, &mailbox, Some("INBOX")).await;
+
+        assert!(!output.contains("UID 3 "));
+        assert!(output.contains("* 2 FETCH (UID 7 "));
+        assert!(output.contains("* 3 FETCH (UID 9 "));
+    }
+
     #[tokio::test]
     async fn literal_length_matches_body() {
         let raw = make_raw_email();
@@ -175,4 +514,192 @@ mod tests {
 
         assert!(output.contains("A1 BAD No folder selected"));
     }
+
+    #[tokio::test]
+    async fn returns_only_requested_items() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(7, true, &raw)
+            .build();
+
+        let items = MacroOrMessageDataItemNames::MessageDataItemNames(vec![
+            MessageDataItemName::Flags,
+            MessageDataItemName::Rfc822Size,
+        ]);
+        let output = run_items("A1", &uid_set(7), &items, &mailbox, Some("INBOX")).await;
+
+        let expected = format!("* 1 FETCH (UID 7 FLAGS (\\Seen) RFC822.SIZE {})", raw.len());
+        assert!(output.contains(&expected));
+        assert!(!output.contains("BODY[]"));
+    }
+
+    #[tokio::test]
+    async fn returns_bodystructure() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(3, false, &raw)
+            .build();
+
+        let items = MacroOrMessageDataItemNames::MessageDataItemNames(vec![
+            MessageDataItemName::BodyStructure,
+        ]);
+        let output = run_items("A1", &uid_set(3), &items, &mailbox, Some("INBOX")).await;
+
+        assert!(output.contains("* 1 FETCH (UID 3 BODYSTRUCTURE (\"text\" \"plain\" "));
+        assert!(!output.contains("BODY[]"));
+    }
+
+    #[tokio::test]
+    async fn fast_macro_expands_to_flags_date_size() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(1, false, &raw)
+            .build();
+
+        let items = MacroOrMessageDataItemNames::Macro(Macro::Fast);
+        let output = run_items("A1", &uid_set(1), &items, &mailbox, Some("INBOX")).await;
+
+        assert!(output.contains("FLAGS ()"));
+        assert!(output.contains("INTERNALDATE \"01-Jan-2024 00:00:00 +0000\""));
+        assert!(output.contains("RFC822.SIZE"));
+    }
+
+    #[tokio::test]
+    async fn all_macro_includes_envelope() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(1, false, &raw)
+            .build();
+
+        let items = MacroOrMessageDataItemNames::Macro(Macro::All);
+        let output = run_items("A1", &uid_set(1), &items, &mailbox, Some("INBOX")).await;
+
+        assert!(output.contains("RFC822.SIZE"));
+        assert!(output.contains(&format!(" ENVELOPE {}", envelope(&raw))));
+        assert!(!output.contains(" BODY "));
+    }
+
+    #[tokio::test]
+    async fn interleaves_exists_between_fetches() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(1, false, &raw)
+            .interleave_exists()
+            .build();
+
+        let output = run("A1", &uid_set(1), &mailbox, Some("INBOX")).await;
+
+        let fetch = output.find("* 1 FETCH").unwrap();
+        let exists = output.find("* 2 EXISTS").unwrap();
+        let done = output.find("A1 OK FETCH completed").unwrap();
+        assert!(fetch < exists && exists < done);
+    }
+
+    #[tokio::test]
+    async fn delivers_once_during_fetch() {
+        let raw = make_raw_email();
+        let mailbox = Mutex::new(
+            MailboxBuilder::new()
+                .folder("INBOX")
+                .email(1, false, &raw)
+                .deliver_on_fetch(&raw)
+                .build(),
+        );
+
+        let output = run_shared("A1", &uid_set(1), &body_peek(), &mailbox, Some("INBOX")).await;
+        let exists = output.find("* 2 EXISTS").unwrap();
+        assert!(exists < output.find("A1 OK FETCH completed").unwrap());
+
+        let output = run_shared("A2", &uid_set(1), &body_peek(), &mailbox, Some("INBOX")).await;
+        assert!(!output.contains("EXISTS"));
+
+        let mb = mailbox.lock().unwrap();
+        let uids: Vec<u32> = mb.folders[0].emails.iter().map(|e| e.uid).collect();
+        drop(mb);
+        assert_eq!(uids, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn body_without_peek_sets_seen() {
+        let raw = make_raw_email();
+        let mailbox = Mutex::new(
+            MailboxBuilder::new()
+                .folder("INBOX")
+                .email(1, false, &raw)
+                .build(),
+        );
+
+        let items =
+            MacroOrMessageDataItemNames::MessageDataItemNames(vec![MessageDataItemName::BodyExt {
+                section: None,
+                partial: None,
+                peek: false,
+            }]);
+        let output = run_shared("A1", &uid_set(1), &items, &mailbox, Some("INBOX")).await;
+
+        assert!(output.contains("Body FLAGS (\\Seen))"));
+        assert!(mailbox.lock().unwrap().get_folder("INBOX").unwrap().emails[0].seen);
+    }
+
+    #[tokio::test]
+    async fn body_peek_leaves_seen_unset() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(1, false, &raw)
+            .build();
+
+        let output = run("A1", &uid_set(1), &mailbox, Some("INBOX")).await;
+
+        assert!(!output.contains("FLAGS"));
+    }
+
+    #[tokio::test]
+    async fn partial_fetch_returns_range_with_origin() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(1, false, &raw)
+            .build();
+
+        let items =
+            MacroOrMessageDataItemNames::MessageDataItemNames(vec![MessageDataItemName::BodyExt {
+                section: None,
+                partial: Some((6, NonZeroU32::new(5).unwrap())),
+                peek: true,
+            }]);
+        let output = run_items("A1", &uid_set(1), &items, &mailbox, Some("INBOX")).await;
+
+        assert!(output.contains("BODY[]<6> {5}\r\na@b.c)"));
+    }
+
+    #[test]
+    fn partial_range_clamps_to_end() {
+        assert_eq!(partial_range(b"Body", 2, 100), b"dy");
+        assert_eq!(partial_range(b"Body", 10, 1), b"");
+    }
+
+    #[test]
+    fn header_fields_keeps_only_named_headers() {
+        let header = b"From: a@b.com\r\nSubject: Test\r\n continued\r\nTo: c@d.com\r\n\r\n";
+        let names = ["subject".to_string()];
+
+        let filtered = filter_header_fields(header, &names);
+
+        assert_eq!(filtered, b"Subject: Test\r\n continued\r\n\r\n");
+    }
+
+    #[test]
+    fn split_message_separates_header_and_text() {
+        let raw = make_raw_email();
+        let (header, text) = split_message(&raw);
+
+        assert_eq!(header, b"From: a@b.com\r\nSubject: Test\r\n\r\n");
+        assert_eq!(text, b"Body");
+    }
 }
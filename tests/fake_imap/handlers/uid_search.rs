@@ -4,11 +4,25 @@
 //! We support:
 //!
 //! - `All` -- returns every UID in the selected folder
-//! - `Unseen` / `Seen` -- flag-based filtering
-//! - `Since(date)` -- returns UIDs with Date header >= date
-//! - `Before(date)` -- returns UIDs with Date header < date
+//! - `Unseen` / `Seen`, `Unanswered` / `Answered`, `Unflagged` /
+//!   `Flagged`, `Undraft` / `Draft` -- flag-based filtering
+//! - `Since(date)` / `Before(date)` / `On(date)` -- compare the date
+//!   of the message's INTERNALDATE (arrival), as RFC 3501 specifies
+//! - `SentSince(date)` / `SentBefore(date)` / `SentOn(date)` -- the
+//!   same against the Date header; messages without one never match
+//! - `Subject(text)` -- case-insensitive substring of the Subject
+//!   header
+//! - `Header(name, text)` -- case-insensitive substring of the named
+//!   header; an empty `text` matches any message with the header
+//! - `Uid(set)` -- UIDs in a sequence set; `*` is the highest UID in
+//!   the folder
+//! - `SequenceSet(set)` -- message sequence numbers in a set; `*` is
+//!   the last message
 //! - `And`, `Or`, `Not` -- logical combinators
 //!
+//! A `CHARSET` argument is accepted and ignored: strings are always
+//! compared as UTF-8.
+//!
 //! The response format (RFC 3501 Section 7.2.5):
 //!
 //! ```text
@@ -20,6 +34,7 @@ use crate::fake_imap::io::write_line;
 use crate::fake_imap::mailbox::{Mailbox, TestEmail};
 use chrono::NaiveDate;
 use imap_codec::imap_types::search::SearchKey;
+use imap_codec::imap_types::sequence::{SeqOrUid, Sequence, SequenceSet};
 use tokio::io::{AsyncRead, AsyncWrite, BufReader};
 
 /// Handle the UID SEARCH command. Returns matching UIDs from the
@@ -43,11 +58,17 @@ pub async fn handle_uid_search<S: AsyncRead + AsyncWrite + Unpin>(
         return;
     };
 
+    let seq = |index: usize| u32::try_from(mailbox.options.seq(index)).unwrap_or(u32::MAX);
+    let max = Bounds {
+        uid: folder.emails.iter().map(|e| e.uid).max().unwrap_or(0),
+        seq: seq(folder.emails.len().saturating_sub(1)),
+    };
     let uids: Vec<u32> = folder
         .emails
         .iter()
-        .filter(|e| criteria.iter().all(|key| matches_key(e, key)))
-        .map(|e| e.uid)
+        .enumerate()
+        .filter(|&(i, e)| criteria.iter().all(|key| matches_key(e, seq(i), key, max)))
+        .map(|(_, e)| e.uid)
         .collect();
 
     // Format: "* SEARCH uid1 uid2 uid3\r\n"
@@ -59,24 +80,93 @@ pub async fn handle_uid_search<S: AsyncRead + AsyncWrite + Unpin>(
     let _ = write_line(stream, &resp).await;
 }
 
-/// Check if a test email matches a single `SearchKey`.
+/// The highest UID and sequence number in the folder, which `*`
+/// stands for.
+#[derive(Clone, Copy)]
+struct Bounds {
+    uid: u32,
+    seq: u32,
+}
+
+/// Check if a test email, at sequence number `seq`, matches a single
+/// `SearchKey`.
 #[allow(clippy::match_same_arms)]
-fn matches_key(email: &TestEmail, key: &SearchKey<'_>) -> bool {
+fn matches_key(email: &TestEmail, seq: u32, key: &SearchKey<'_>, max: Bounds) -> bool {
     match key {
         SearchKey::All => true,
         SearchKey::Unseen => !email.seen,
         SearchKey::Seen => email.seen,
-        SearchKey::Since(date) => parse_email_date(&email.raw).is_some_and(|d| d >= *date.as_ref()),
-        SearchKey::Before(date) => parse_email_date(&email.raw).is_some_and(|d| d < *date.as_ref()),
-        SearchKey::And(keys) => keys.as_ref().iter().all(|k| matches_key(email, k)),
-        SearchKey::Or(a, b) => matches_key(email, a) || matches_key(email, b),
-        SearchKey::Not(k) => !matches_key(email, k),
+        SearchKey::Unanswered => !email.answered,
+        SearchKey::Answered => email.answered,
+        SearchKey::Unflagged => !email.flagged,
+        SearchKey::Flagged => email.flagged,
+        SearchKey::Undraft => !email.draft,
+        SearchKey::Draft => email.draft,
+        SearchKey::Since(date) => email.internal_date.date_naive() >= *date.as_ref(),
+        SearchKey::Before(date) => email.internal_date.date_naive() < *date.as_ref(),
+        SearchKey::On(date) => email.internal_date.date_naive() == *date.as_ref(),
+        SearchKey::SentSince(date) => {
+            parse_email_date(&email.raw).is_some_and(|d| d >= *date.as_ref())
+        }
+        SearchKey::SentBefore(date) => {
+            parse_email_date(&email.raw).is_some_and(|d| d < *date.as_ref())
+        }
+        SearchKey::SentOn(date) => {
+            parse_email_date(&email.raw).is_some_and(|d| d == *date.as_ref())
+        }
+        SearchKey::Subject(text) => {
+            let needle = String::from_utf8_lossy(text.as_ref()).to_lowercase();
+            header(&email.raw, "Subject").is_some_and(|s| s.to_lowercase().contains(&needle))
+        }
+        SearchKey::Header(name, text) => {
+            let name = String::from_utf8_lossy(name.as_ref());
+            let needle = String::from_utf8_lossy(text.as_ref()).to_lowercase();
+            header(&email.raw, &name).is_some_and(|s| s.to_lowercase().contains(&needle))
+        }
+        SearchKey::Uid(set) => in_set(email.uid, set, max.uid),
+        SearchKey::SequenceSet(set) => in_set(seq, set, max.seq),
+        SearchKey::And(keys) => keys
+            .as_ref()
+            .iter()
+            .all(|k| matches_key(email, seq, k, max)),
+        SearchKey::Or(a, b) => matches_key(email, seq, a, max) || matches_key(email, seq, b, max),
+        SearchKey::Not(k) => !matches_key(email, seq, k, max),
         // Fallback: return all (like current behavior for unknown
         // criteria).
         _ => true,
     }
 }
 
+/// Whether UID or sequence number `n` is in `set`, where `*` is
+/// `max`. Ranges may be given in either order (RFC 3501 Section 9,
+/// `seq-range`).
+fn in_set(n: u32, set: &SequenceSet, max: u32) -> bool {
+    let value = |v: &SeqOrUid| match v {
+        SeqOrUid::Value(v) => v.get(),
+        SeqOrUid::Asterisk => max,
+    };
+    set.0.as_ref().iter().any(|seq| match seq {
+        Sequence::Single(v) => n == value(v),
+        Sequence::Range(a, b) => {
+            let (a, b) = (value(a), value(b));
+            (a.min(b)..=a.max(b)).contains(&n)
+        }
+    })
+}
+
+/// The first header `name` of a raw RFC 2822 email.
+fn header(raw: &[u8], name: &str) -> Option<String> {
+    let text = String::from_utf8_lossy(raw);
+    text.lines()
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| {
+            let (field, value) = line.split_once(':')?;
+            field
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+}
+
 /// Extract the `Date:` header from raw RFC 2822 email bytes and parse
 /// it into a `NaiveDate`.
 fn parse_email_date(raw: &[u8]) -> Option<NaiveDate> {
@@ -174,6 +264,80 @@ mod tests {
         assert!(output.contains("* SEARCH 2\r\n"));
     }
 
+    #[tokio::test]
+    async fn search_unanswered_filters_answered() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email_with_flags(1, &["\\Answered"], &raw)
+            .email(2, true, &raw)
+            .build();
+
+        let output = run("A1", &[SearchKey::Unanswered], &mailbox, Some("INBOX")).await;
+        assert!(output.contains("* SEARCH 2\r\n"));
+
+        let output = run("A1", &[SearchKey::Answered], &mailbox, Some("INBOX")).await;
+        assert!(output.contains("* SEARCH 1\r\n"));
+    }
+
+    #[tokio::test]
+    async fn search_draft_filters_undraft() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("Drafts")
+            .email_with_flags(1, &["\\Draft", "\\Seen"], &raw)
+            .email(2, true, &raw)
+            .build();
+
+        let output = run("A1", &[SearchKey::Draft], &mailbox, Some("Drafts")).await;
+        assert!(output.contains("* SEARCH 1\r\n"));
+
+        let output = run("A1", &[SearchKey::Undraft], &mailbox, Some("Drafts")).await;
+        assert!(output.contains("* SEARCH 2\r\n"));
+    }
+
+    #[tokio::test]
+    async fn search_subject_matches_utf8() {
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(
+                1,
+                false,
+                "From: a@b.com\r\nSubject: Grüße von Müller\r\n\r\nBody".as_bytes(),
+            )
+            .email(2, false, &make_raw_email())
+            .build();
+
+        let key = SearchKey::Subject("müller".try_into().unwrap());
+        let output = run("A1", &[key], &mailbox, Some("INBOX")).await;
+
+        assert!(output.contains("* SEARCH 1\r\n"));
+    }
+
+    #[tokio::test]
+    async fn search_by_header() {
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(
+                1,
+                false,
+                b"From: a@b.com\r\nX-Pm-ConversationID: abc==\r\n\r\nBody",
+            )
+            .email(2, false, &make_raw_email())
+            .build();
+
+        let key = SearchKey::Header(
+            "x-pm-conversationid".try_into().unwrap(),
+            "ABC==".try_into().unwrap(),
+        );
+        let output = run("A1", &[key], &mailbox, Some("INBOX")).await;
+        assert!(output.contains("* SEARCH 1\r\n"));
+
+        let key = SearchKey::Header("From".try_into().unwrap(), "".try_into().unwrap());
+        let output = run("A1", &[key], &mailbox, Some("INBOX")).await;
+        assert!(output.contains("* SEARCH 1 2\r\n"));
+    }
+
     #[tokio::test]
     async fn no_folder_selected_returns_bad() {
         let mailbox = MailboxBuilder::new().folder("INBOX").build();
@@ -320,6 +484,29 @@ mod tests {
         assert!(output.contains("* SEARCH \r\n"));
     }
 
+    #[tokio::test]
+    async fn since_uses_arrival_and_sentsince_the_date_header() {
+        // Written on Jan 1, but only arrived on Jan 20.
+        let migrated = make_dated_email("Mon, 01 Jan 2024 10:00:00 +0000");
+
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(1, true, &migrated)
+            .internal_date("Sat, 20 Jan 2024 08:00:00 +0000")
+            .build();
+
+        let cases = [
+            (SearchKey::Since(date(2024, 1, 10)), "* SEARCH 1\r\n"),
+            (SearchKey::On(date(2024, 1, 20)), "* SEARCH 1\r\n"),
+            (SearchKey::SentSince(date(2024, 1, 10)), "* SEARCH \r\n"),
+            (SearchKey::SentBefore(date(2024, 1, 10)), "* SEARCH 1\r\n"),
+        ];
+        for (key, expected) in cases {
+            let output = run("A1", &[key], &mailbox, Some("INBOX")).await;
+            assert!(output.contains(expected), "{output}");
+        }
+    }
+
     #[tokio::test]
     async fn emails_without_date_header_excluded() {
         let no_date = make_raw_email(); // no Date: header
@@ -333,7 +520,7 @@ mod tests {
 
         let output = run(
             "A1",
-            &[SearchKey::Since(date(2024, 1, 1))],
+            &[SearchKey::SentSince(date(2024, 1, 1))],
             &mailbox,
             Some("INBOX"),
         )
@@ -343,6 +530,54 @@ mod tests {
         assert!(output.contains("* SEARCH 2\r\n"));
     }
 
+    #[tokio::test]
+    async fn search_uid_range_to_highest() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(1, true, &raw)
+            .email(2, false, &raw)
+            .email(5, true, &raw)
+            .build();
+        let from = |start: u32| {
+            let start = SeqOrUid::Value(start.try_into().unwrap());
+            SearchKey::Uid(SequenceSet(
+                vec![Sequence::Range(start, SeqOrUid::Asterisk)]
+                    .try_into()
+                    .unwrap(),
+            ))
+        };
+
+        let output = run("A1", &[from(2)], &mailbox, Some("INBOX")).await;
+        assert!(output.contains("* SEARCH 2 5\r\n"));
+
+        // `*` is the highest UID, so `9:*` means `5:9`.
+        let output = run("A1", &[from(9)], &mailbox, Some("INBOX")).await;
+        assert!(output.contains("* SEARCH 5\r\n"));
+    }
+
+    #[tokio::test]
+    async fn search_sequence_number_returns_its_uid() {
+        let raw = make_raw_email();
+        let mailbox = MailboxBuilder::new()
+            .folder("INBOX")
+            .email(4, true, &raw)
+            .email(7, false, &raw)
+            .email(9, false, &raw)
+            .build();
+        let seq = |seq: SeqOrUid| {
+            SearchKey::SequenceSet(SequenceSet(vec![Sequence::Single(seq)].try_into().unwrap()))
+        };
+
+        let second = seq(SeqOrUid::Value(2.try_into().unwrap()));
+        let output = run("A1", &[second], &mailbox, Some("INBOX")).await;
+        assert!(output.contains("* SEARCH 7\r\n"));
+
+        // `*` is the last message.
+        let output = run("A1", &[seq(SeqOrUid::Asterisk)], &mailbox, Some("INBOX")).await;
+        assert!(output.contains("* SEARCH 9\r\n"));
+    }
+
     #[test]
     fn parse_email_date_extracts_date() {
         let raw = make_dated_email("Mon, 01 Jan 2024 12:00:00 +0000");
@@ -6,11 +6,16 @@
 //! - `-FLAGS (...)` -- remove flags
 //! - `FLAGS (...)` -- replace flags
 //!
+//! `\Seen`, `\Answered`, `\Flagged`, `\Deleted`, `\Draft`, and
+//! keyword flags are tracked; other system flags are accepted but
+//! ignored.
+//!
 //! Responds with `* N FETCH (FLAGS (...))` per modified message,
-//! then the tagged OK.
+//! then the tagged OK. With the `reject_store` option, every STORE
+//! gets a tagged `NO` instead.
 
 use crate::fake_imap::io::write_line;
-use crate::fake_imap::mailbox::Mailbox;
+use crate::fake_imap::mailbox::{Mailbox, TestEmail};
 use imap_codec::imap_types::flag::{Flag, StoreResponse, StoreType};
 use imap_codec::imap_types::sequence::{SeqOrUid, Sequence, SequenceSet};
 use std::sync::Mutex;
@@ -54,6 +59,70 @@ pub struct StoreArgs<'a> {
     pub flags: &'a [Flag<'a>],
 }
 
+/// The flags named in a STORE command.
+struct StoreFlags {
+    seen: bool,
+    answered: bool,
+    flagged: bool,
+    deleted: bool,
+    draft: bool,
+    keywords: Vec<String>,
+}
+
+impl StoreFlags {
+    fn parse(flags: &[Flag<'_>]) -> Self {
+        Self {
+            seen: flags.contains(&Flag::Seen),
+            answered: flags.contains(&Flag::Answered),
+            flagged: flags.contains(&Flag::Flagged),
+            deleted: flags.contains(&Flag::Deleted),
+            draft: flags.contains(&Flag::Draft),
+            keywords: flags
+                .iter()
+                .filter_map(|f| match f {
+                    Flag::Keyword(kw) => Some(kw.inner().to_string()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Add, remove, or replace the flags on `email`.
+    fn apply(&self, email: &mut TestEmail, kind: &StoreType) {
+        let system = [
+            (self.seen, &mut email.seen),
+            (self.answered, &mut email.answered),
+            (self.flagged, &mut email.flagged),
+            (self.deleted, &mut email.deleted),
+            (self.draft, &mut email.draft),
+        ];
+        match kind {
+            StoreType::Add => {
+                for (wanted, flag) in system {
+                    *flag |= wanted;
+                }
+                for kw in &self.keywords {
+                    if !email.keywords.contains(kw) {
+                        email.keywords.push(kw.clone());
+                    }
+                }
+            }
+            StoreType::Remove => {
+                for (wanted, flag) in system {
+                    *flag &= !wanted;
+                }
+                email.keywords.retain(|kw| !self.keywords.contains(kw));
+            }
+            StoreType::Replace => {
+                for (wanted, flag) in system {
+                    *flag = wanted;
+                }
+                email.keywords.clone_from(&self.keywords);
+            }
+        }
+    }
+}
+
 /// Handle the UID STORE command. Modifies flags on matching emails.
 pub async fn handle_uid_store<S: AsyncRead + AsyncWrite + Unpin>(
     tag: &str,
@@ -68,24 +137,31 @@ pub async fn handle_uid_store<S: AsyncRead + AsyncWrite + Unpin>(
         return;
     };
 
-    // Determine which flags the client wants to set/unset.
-    let wants_seen = args.flags.iter().any(|f| matches!(f, Flag::Seen));
-    let wants_deleted = args.flags.iter().any(|f| matches!(f, Flag::Deleted));
+    let wanted = StoreFlags::parse(args.flags);
 
     // Check folder exists (quick lock, no await).
-    let folder_exists = {
+    let (folder_exists, reject) = {
         let mb = mailbox.lock().unwrap();
-        mb.get_folder(folder_name).is_some()
+        (
+            mb.get_folder(folder_name).is_some(),
+            mb.options.reject_store,
+        )
     };
     if !folder_exists {
         let resp = format!("{tag} BAD Folder not found\r\n");
         let _ = write_line(stream, &resp).await;
         return;
     }
+    if reject {
+        let resp = format!("{tag} NO Flags cannot be changed\r\n");
+        let _ = write_line(stream, &resp).await;
+        return;
+    }
 
     // Mutate flags under lock (no await inside).
     let results = {
         let mut mb = mailbox.lock().unwrap();
+        let options = mb.options.clone();
         let folder = mb.get_folder_mut(folder_name).unwrap();
 
         let max_uid = folder.emails.iter().map(|e| e.uid).max().unwrap_or(0);
@@ -100,39 +176,10 @@ pub async fn handle_uid_store<S: AsyncRead + AsyncWrite + Unpin>(
                 .enumerate()
                 .find(|(_, e)| e.uid == uid)
             {
-                match args.kind {
-                    StoreType::Add => {
-                        if wants_seen {
-                            email.seen = true;
-                        }
-                        if wants_deleted {
-                            email.deleted = true;
-                        }
-                    }
-                    StoreType::Remove => {
-                        if wants_seen {
-                            email.seen = false;
-                        }
-                        if wants_deleted {
-                            email.deleted = false;
-                        }
-                    }
-                    StoreType::Replace => {
-                        email.seen = wants_seen;
-                        email.deleted = wants_deleted;
-                    }
-                }
+                wanted.apply(email, args.kind);
 
-                let mut current = Vec::new();
-                if email.seen {
-                    current.push("\\Seen".to_string());
-                }
-                if email.deleted {
-                    current.push("\\Deleted".to_string());
-                }
-
-                let seq = idx + 1;
-                results.push((seq, uid, current));
+                let seq = options.seq(idx);
+                results.push((seq, uid, email.flags()));
             }
         }
         drop(mb);
@@ -161,6 +208,7 @@ pub async fn handle_uid_store<S: AsyncRead + AsyncWrite + Unpin>(
 mod tests {
     use super::*;
     use crate::fake_imap::mailbox::MailboxBuilder;
+    use imap_codec::imap_types::core::Atom;
     use imap_codec::imap_types::sequence::SequenceSet;
     use std::num::NonZeroU32;
     use tokio::io::BufReader;
@@ -301,4 +349,76 @@ mod tests {
 
         assert!(output.contains("A1 BAD No folder selected"));
     }
+
+    #[tokio::test]
+    async fn add_and_remove_keyword() {
+        let raw = make_raw_email();
+        let mb = Mutex::new(
+            MailboxBuilder::new()
+                .folder("INBOX")
+                .email(1, false, &raw)
+                .build(),
+        );
+        let work = Flag::Keyword(Atom::try_from("Work").unwrap());
+
+        let output = run_store(
+            "A1",
+            &uid_set(1),
+            &StoreType::Add,
+            &StoreResponse::Answer,
+            std::slice::from_ref(&work),
+            &mb,
+            Some("INBOX"),
+        )
+        .await;
+
+        assert!(output.contains("FLAGS (Work)"));
+        assert_eq!(
+            mb.lock().unwrap().get_folder("INBOX").unwrap().emails[0].keywords,
+            vec!["Work".to_string()]
+        );
+
+        let _output = run_store(
+            "A2",
+            &uid_set(1),
+            &StoreType::Remove,
+            &StoreResponse::Answer,
+            &[work],
+            &mb,
+            Some("INBOX"),
+        )
+        .await;
+
+        assert!(
+            mb.lock().unwrap().get_folder("INBOX").unwrap().emails[0]
+                .keywords
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_store_leaves_flags_unchanged() {
+        let raw = make_raw_email();
+        let mb = Mutex::new(
+            MailboxBuilder::new()
+                .folder("INBOX")
+                .email(1, false, &raw)
+                .reject_store()
+                .build(),
+        );
+
+        let output = run_store(
+            "A1",
+            &uid_set(1),
+            &StoreType::Add,
+            &StoreResponse::Answer,
+            &[Flag::Seen],
+            &mb,
+            Some("INBOX"),
+        )
+        .await;
+
+        assert_eq!(output, "A1 NO Flags cannot be changed\r\n");
+        assert!(!mb.lock().unwrap().get_folder("INBOX").unwrap().emails[0].seen);
+    }
 }
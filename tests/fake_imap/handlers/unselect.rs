@@ -0,0 +1,56 @@
+//! UNSELECT command handler.
+//!
+//! RFC 3691: leaves the selected state like CLOSE, but without
+//! expunging `\Deleted` messages.
+
+use crate::fake_imap::io::write_line;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+
+/// Handle the UNSELECT command. Clears `selected_folder` on success.
+pub async fn handle_unselect<S: AsyncRead + AsyncWrite + Unpin>(
+    tag: &str,
+    selected_folder: &mut Option<String>,
+    stream: &mut BufReader<S>,
+) {
+    let resp = if selected_folder.take().is_some() {
+        format!("{tag} OK UNSELECT completed\r\n")
+    } else {
+        format!("{tag} BAD No folder selected\r\n")
+    };
+    let _ = write_line(stream, &resp).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::BufReader;
+
+    async fn run(tag: &str, selected: &mut Option<String>) -> String {
+        let (client, server) = tokio::io::duplex(1024);
+        let mut stream = BufReader::new(server);
+
+        handle_unselect(tag, selected, &mut stream).await;
+        drop(stream);
+
+        let mut buf = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut BufReader::new(client), &mut buf)
+            .await
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[tokio::test]
+    async fn clears_selected_folder() {
+        let mut selected = Some("INBOX".to_string());
+        let output = run("A1", &mut selected).await;
+
+        assert!(output.contains("A1 OK UNSELECT completed"));
+        assert!(selected.is_none());
+    }
+
+    #[tokio::test]
+    async fn no_folder_selected_returns_bad() {
+        let output = run("A1", &mut None).await;
+        assert!(output.contains("A1 BAD No folder selected"));
+    }
+}
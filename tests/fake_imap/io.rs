@@ -3,8 +3,18 @@
 //! These are thin wrappers around `AsyncWriteExt` that flush after
 //! every write. Real IMAP servers would batch writes for performance,
 //! but flushing eagerly keeps the test server simple and deterministic.
+//!
+//! `LowercaseStatus` wraps a stream to rewrite status keywords in
+//! lowercase on the way out, for `ServerOptions::lowercase_status`.
+//! `DeflateStream` compresses a stream after COMPRESS DEFLATE.
 
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use async_compression::tokio::bufread::DeflateDecoder;
+use async_compression::tokio::write::DeflateEncoder;
+use std::borrow::Cow;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf, ReadHalf, WriteHalf};
 
 /// Write a string to the stream and flush.
 pub async fn write_line<S: AsyncRead + AsyncWrite + Unpin>(
@@ -23,3 +33,178 @@ pub async fn write_bytes<S: AsyncRead + AsyncWrite + Unpin>(
     stream.get_mut().write_all(data).await?;
     stream.get_mut().flush().await
 }
+
+/// Status keywords that start a response after the tag (RFC 3501
+/// Section 7.1).
+const STATUS_KEYWORDS: [&[u8]; 5] = [b"OK", b"NO", b"BAD", b"BYE", b"PREAUTH"];
+
+/// Lowercase the status keyword of a `<tag> OK ...` style response
+/// line when `enabled`. Other lines are returned unchanged.
+///
+/// The output always has the same length as the input, so it can be
+/// substituted inside a `poll_write` without confusing the caller's
+/// byte count.
+pub fn lowercase_status(line: &[u8], enabled: bool) -> Cow<'_, [u8]> {
+    if !enabled {
+        return Cow::Borrowed(line);
+    }
+    let Some(tag_end) = line.iter().position(|&b| b == b' ') else {
+        return Cow::Borrowed(line);
+    };
+    if line[..tag_end] == *b"+" {
+        return Cow::Borrowed(line);
+    }
+
+    let start = tag_end + 1;
+    let rest = &line[start..];
+    let word_len = rest
+        .iter()
+        .position(|b| !b.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    if !STATUS_KEYWORDS.contains(&&rest[..word_len]) {
+        return Cow::Borrowed(line);
+    }
+
+    let mut out = line.to_vec();
+    out[start..start + word_len].make_ascii_lowercase();
+    Cow::Owned(out)
+}
+
+/// A stream whose writes go through [`lowercase_status`].
+///
+/// Every `write_line`/`write_bytes` call starts a new response, so
+/// checking the start of each buffer is enough for the fake server.
+pub struct LowercaseStatus<S> {
+    inner: S,
+}
+
+impl<S> LowercaseStatus<S> {
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for LowercaseStatus<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for LowercaseStatus<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let line = lowercase_status(buf, true);
+        Pin::new(&mut self.inner).poll_write(cx, &line)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// A stream compressed with raw DEFLATE in both directions (RFC 4978).
+///
+/// Every flush is a sync flush, so each response reaches the client
+/// as soon as `write_line`/`write_bytes` returns.
+pub struct DeflateStream<S> {
+    reader: DeflateDecoder<BufReader<ReadHalf<S>>>,
+    writer: DeflateEncoder<WriteHalf<S>>,
+}
+
+impl<S: AsyncRead + AsyncWrite> DeflateStream<S> {
+    pub fn new(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self {
+            reader: DeflateDecoder::new(BufReader::new(reader)),
+            writer: DeflateEncoder::new(writer),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncRead for DeflateStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.reader).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncWrite for DeflateStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn deflate_stream_round_trips() {
+        let (a, b) = tokio::io::duplex(4096);
+        let mut a = BufReader::new(DeflateStream::new(a));
+        let mut b = BufReader::new(DeflateStream::new(b));
+
+        write_line(&mut a, "A1 NOOP\r\n").await.unwrap();
+        let mut line = String::new();
+        tokio::io::AsyncBufReadExt::read_line(&mut b, &mut line)
+            .await
+            .unwrap();
+        assert_eq!(line, "A1 NOOP\r\n");
+    }
+
+    #[test]
+    fn lowercases_tagged_and_untagged_status() {
+        assert_eq!(
+            &*lowercase_status(b"A0001 OK LOGIN completed\r\n", true),
+            b"A0001 ok LOGIN completed\r\n"
+        );
+        assert_eq!(
+            &*lowercase_status(b"* OK [UIDNEXT 3]\r\n", true),
+            b"* ok [UIDNEXT 3]\r\n"
+        );
+        assert_eq!(
+            &*lowercase_status(b"A2 NO [TRYCREATE]\r\n", true),
+            b"A2 no [TRYCREATE]\r\n"
+        );
+    }
+
+    #[test]
+    fn leaves_data_responses_alone() {
+        let lines: [&[u8]; 4] = [
+            b"* 3 EXISTS\r\n",
+            b"* LIST () \"/\" INBOX\r\n",
+            b"+ Ready for literal data\r\n",
+            b"A0001 OK done\r\n",
+        ];
+        for line in &lines[..3] {
+            assert_eq!(&*lowercase_status(line, true), *line);
+        }
+        assert_eq!(&*lowercase_status(lines[3], false), lines[3]);
+    }
+}
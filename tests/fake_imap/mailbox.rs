@@ -12,15 +12,119 @@
 //!     .build();
 //! ```
 //!
+//! Connection-level behaviour (e.g. a slow or dropped greeting) is
+//! configured on the same builder and stored in `Mailbox::options`.
+//!
 //! The `Mailbox` is shared with the fake IMAP server via
 //! `Arc<Mutex<_>>` so the server can read and modify mailbox state
 //! (folders, emails, flags).
 
+use chrono::{DateTime, FixedOffset};
+use std::time::Duration;
+
 /// A complete mailbox: a collection of named folders, each holding
 /// zero or more test emails.
 #[derive(Debug, Clone)]
 pub struct Mailbox {
     pub folders: Vec<Folder>,
+    pub options: ServerOptions,
+}
+
+/// Knobs that change how the fake server behaves on the wire, used to
+/// simulate a misbehaving bridge.
+///
+/// - `greeting_delay`: wait this long before sending the greeting.
+/// - `drop_before_greeting`: close the connection without sending a
+///   greeting at all.
+/// - `literal_plus`: advertise LITERAL+ (RFC 7888). Non-synchronizing
+///   literals are accepted either way.
+/// - `delimiter`: the hierarchy delimiter reported by LIST (default
+///   `/`).
+/// - `interleave_exists`: send an unsolicited `* <n> EXISTS` after
+///   every FETCH response, as a server does when mail is delivered
+///   mid-command.
+/// - `deliver_on_fetch`: append this message to the selected folder
+///   while answering the first UID FETCH that returns anything, and
+///   announce it with `* <n> EXISTS`, simulating concurrent delivery.
+///   It is delivered once.
+/// - `compress`: advertise and accept COMPRESS=DEFLATE (RFC 4978).
+/// - `lowercase_status`: spell status keywords in lowercase (`* ok`,
+///   `A0001 no`), which RFC 3501 Section 9 permits.
+/// - `omit_uidnext`: leave `[UIDNEXT]` and `[UIDVALIDITY]` out of
+///   SELECT responses.
+/// - `idle_limit`: end an IDLE that has lasted this long with `* BYE`
+///   and close the connection, as the bridge does after 29 minutes.
+/// - `no_starttls`: leave STARTTLS out of the capabilities and refuse
+///   it with `BAD`, as a plaintext-only server does.
+/// - `implicit_tls`: expect the TLS handshake right after connecting
+///   and send the greeting over TLS, as an IMAPS port does.
+/// - `handshake_timeout`: with `implicit_tls`, if the client has not
+///   started the handshake after this long (e.g. because it waits for
+///   a plaintext greeting), send a fatal TLS alert and hang up.
+/// - `multiappend`: advertise MULTIAPPEND (RFC 3502) and accept
+///   APPENDs carrying several messages.
+/// - `reject_store`: answer every UID STORE with a tagged `NO` and
+///   leave the flags unchanged.
+/// - `reject_expunge`: answer every EXPUNGE with a tagged `NO` and
+///   remove nothing.
+/// - `seq_base`: the sequence number reported for the first message
+///   of a folder (default 1), so that sequence numbers no longer
+///   match positions. Real servers always start at 1; this catches
+///   client code that relies on sequence numbers instead of UIDs.
+/// - `refuse_reconnects`: after the first connection, close this many
+///   more before the greeting, as a restarting bridge does.
+#[derive(Debug, Clone, Default)]
+pub struct ServerOptions {
+    pub greeting_delay: Option<Duration>,
+    pub drop_before_greeting: bool,
+    pub literal_plus: bool,
+    pub interleave_exists: bool,
+    pub deliver_on_fetch: Option<Vec<u8>>,
+    pub delimiter: Option<char>,
+    pub lowercase_status: bool,
+    pub compress: bool,
+    pub omit_uidnext: bool,
+    pub idle_limit: Option<Duration>,
+    pub no_starttls: bool,
+    pub implicit_tls: bool,
+    pub handshake_timeout: Option<Duration>,
+    pub multiappend: bool,
+    pub reject_store: bool,
+    pub reject_expunge: bool,
+    pub seq_base: Option<usize>,
+    pub refuse_reconnects: usize,
+}
+
+impl ServerOptions {
+    /// The hierarchy delimiter reported by LIST.
+    pub fn delimiter(&self) -> char {
+        self.delimiter.unwrap_or('/')
+    }
+
+    /// The sequence number reported for the message at `index` in its
+    /// folder.
+    pub fn seq(&self, index: usize) -> usize {
+        self.seq_base.unwrap_or(1) + index
+    }
+
+    /// The capabilities to advertise in response to CAPABILITY.
+    pub fn capabilities(&self) -> Vec<&'static str> {
+        let mut caps = vec!["IMAP4rev1"];
+        if !self.no_starttls && !self.implicit_tls {
+            caps.push("STARTTLS");
+        }
+        caps.push("IDLE");
+        if self.literal_plus {
+            caps.push("LITERAL+");
+        }
+        if self.compress {
+            caps.push("COMPRESS=DEFLATE");
+        }
+        if self.multiappend {
+            caps.push("MULTIAPPEND");
+        }
+        caps
+    }
 }
 
 impl Mailbox {
@@ -51,30 +155,113 @@ impl Mailbox {
 }
 
 /// A single IMAP folder (e.g. "INBOX", "Sent", "Trash").
+///
+/// `special_use` is an optional RFC 6154 attribute such as `\Junk`,
+/// reported in LIST responses.
 #[derive(Debug, Clone)]
 pub struct Folder {
     pub name: String,
+    pub special_use: Option<String>,
     pub emails: Vec<TestEmail>,
 }
 
+impl Folder {
+    /// The UID the next message added to this folder will get.
+    pub fn next_uid(&self) -> u32 {
+        self.emails.iter().map(|e| e.uid).max().unwrap_or(0) + 1
+    }
+}
+
 /// A test email stored in a folder.
 ///
 /// - `uid`: IMAP UID -- a unique-per-folder number that never changes
 ///   (unlike sequence numbers which shift on delete).
 /// - `seen`: whether the `\Seen` flag is set. IMAP uses this to track
 ///   read/unread state. The UNSEEN search returns emails without it.
+/// - `answered`: whether the `\Answered` flag is set (the message
+///   has been replied to).
+/// - `flagged`: whether the `\Flagged` flag is set (starred).
 /// - `deleted`: whether the `\Deleted` flag is set. EXPUNGE removes
 ///   emails with this flag.
+/// - `draft`: whether the `\Draft` flag is set.
+/// - `keywords`: user-defined keyword flags (no `\` prefix). Proton
+///   Bridge exposes labels this way.
+/// - `internal_date`: the server arrival time, returned for
+///   INTERNALDATE. Defaults to the `Date:` header when it parses.
 /// - `raw`: the complete RFC 2822 message (headers + body) as bytes.
 ///   This is what gets returned in a FETCH BODY[] response.
 #[derive(Debug, Clone)]
 pub struct TestEmail {
     pub uid: u32,
     pub seen: bool,
+    pub answered: bool,
+    pub flagged: bool,
     pub deleted: bool,
+    pub draft: bool,
+    pub keywords: Vec<String>,
+    pub internal_date: DateTime<FixedOffset>,
     pub raw: Vec<u8>,
 }
 
+impl TestEmail {
+    /// Create an email with no flags other than (optionally) `\Seen`.
+    pub fn new(uid: u32, seen: bool, raw: &[u8]) -> Self {
+        Self {
+            uid,
+            seen,
+            answered: false,
+            flagged: false,
+            deleted: false,
+            draft: false,
+            keywords: Vec::new(),
+            internal_date: parse_date_header(raw).unwrap_or_else(default_internal_date),
+            raw: raw.to_vec(),
+        }
+    }
+
+    /// The IMAP flags currently set, in wire format.
+    pub fn flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if self.seen {
+            flags.push("\\Seen".to_string());
+        }
+        if self.answered {
+            flags.push("\\Answered".to_string());
+        }
+        if self.flagged {
+            flags.push("\\Flagged".to_string());
+        }
+        if self.deleted {
+            flags.push("\\Deleted".to_string());
+        }
+        if self.draft {
+            flags.push("\\Draft".to_string());
+        }
+        flags.extend(self.keywords.iter().cloned());
+        flags
+    }
+}
+
+/// Extract and parse the `Date:` header from raw RFC 2822 bytes.
+pub fn parse_date_header(raw: &[u8]) -> Option<DateTime<FixedOffset>> {
+    let text = std::str::from_utf8(raw).ok()?;
+
+    for line in text.lines() {
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Date:") {
+            return DateTime::parse_from_rfc2822(value.trim()).ok();
+        }
+    }
+    None
+}
+
+/// Arrival time for messages without a parseable `Date:` header.
+fn default_internal_date() -> DateTime<FixedOffset> {
+    DateTime::parse_from_rfc3339("2024-01-01T00:00:00+00:00").unwrap()
+}
+
 /// Builder for constructing a `Mailbox` step by step.
 ///
 /// Call `.folder(name)` to start a new folder, then chain
@@ -82,25 +269,180 @@ pub struct TestEmail {
 /// Finish with `.build()` to get the final `Mailbox`.
 pub struct MailboxBuilder {
     folders: Vec<Folder>,
+    options: ServerOptions,
 }
 
 impl MailboxBuilder {
     pub const fn new() -> Self {
         Self {
             folders: Vec::new(),
+            options: ServerOptions {
+                greeting_delay: None,
+                drop_before_greeting: false,
+                literal_plus: false,
+                interleave_exists: false,
+                deliver_on_fetch: None,
+                delimiter: None,
+                lowercase_status: false,
+                compress: false,
+                omit_uidnext: false,
+                idle_limit: None,
+                no_starttls: false,
+                implicit_tls: false,
+                handshake_timeout: None,
+                multiappend: false,
+                reject_store: false,
+                reject_expunge: false,
+                seq_base: None,
+                refuse_reconnects: 0,
+            },
         }
     }
 
+    /// Delay the server greeting by `delay`, simulating a bridge that
+    /// is slow to respond.
+    pub const fn delay_greeting(mut self, delay: Duration) -> Self {
+        self.options.greeting_delay = Some(delay);
+        self
+    }
+
+    /// Advertise the LITERAL+ extension.
+    pub const fn literal_plus(mut self) -> Self {
+        self.options.literal_plus = true;
+        self
+    }
+
+    /// Report `delimiter` as the folder hierarchy delimiter.
+    pub const fn delimiter(mut self, delimiter: char) -> Self {
+        self.options.delimiter = Some(delimiter);
+        self
+    }
+
+    /// Interleave unsolicited EXISTS updates with FETCH responses.
+    pub const fn interleave_exists(mut self) -> Self {
+        self.options.interleave_exists = true;
+        self
+    }
+
+    /// Deliver `raw` to the selected folder in the middle of the first
+    /// UID FETCH that returns a message.
+    pub fn deliver_on_fetch(mut self, raw: &[u8]) -> Self {
+        self.options.deliver_on_fetch = Some(raw.to_vec());
+        self
+    }
+
+    /// Advertise and accept COMPRESS=DEFLATE.
+    pub const fn compress(mut self) -> Self {
+        self.options.compress = true;
+        self
+    }
+
+    /// Leave `[UIDNEXT]` and `[UIDVALIDITY]` out of SELECT responses,
+    /// simulating a minimal server that only reports the counts.
+    pub const fn omit_uidnext(mut self) -> Self {
+        self.options.omit_uidnext = true;
+        self
+    }
+
+    /// Drop the connection once an IDLE has lasted `limit`,
+    /// simulating the bridge's cap on IDLE duration.
+    pub const fn idle_limit(mut self, limit: Duration) -> Self {
+        self.options.idle_limit = Some(limit);
+        self
+    }
+
+    /// Send status keywords (`OK`, `NO`, `BAD`, ...) in lowercase.
+    pub const fn lowercase_status(mut self) -> Self {
+        self.options.lowercase_status = true;
+        self
+    }
+
+    /// Refuse STARTTLS, simulating a server that only speaks
+    /// plaintext before login.
+    pub const fn no_starttls(mut self) -> Self {
+        self.options.no_starttls = true;
+        self
+    }
+
+    /// Speak TLS from the first byte (IMAPS) instead of waiting for
+    /// STARTTLS.
+    pub const fn implicit_tls(mut self) -> Self {
+        self.options.implicit_tls = true;
+        self
+    }
+
+    /// Give up on a client that has not started the TLS handshake
+    /// within `timeout` (see `implicit_tls`).
+    pub const fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.options.handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Advertise the MULTIAPPEND extension.
+    pub const fn multiappend(mut self) -> Self {
+        self.options.multiappend = true;
+        self
+    }
+
+    /// Refuse every UID STORE, simulating a server that will not
+    /// change flags (e.g. on a read-only folder).
+    pub const fn reject_store(mut self) -> Self {
+        self.options.reject_store = true;
+        self
+    }
+
+    /// Refuse every EXPUNGE, so messages marked `\Deleted` stay.
+    pub const fn reject_expunge(mut self) -> Self {
+        self.options.reject_expunge = true;
+        self
+    }
+
+    /// Number messages from `base` instead of 1 in FETCH, STORE,
+    /// EXPUNGE, SELECT, and SEARCH responses.
+    pub const fn seq_base(mut self, base: usize) -> Self {
+        self.options.seq_base = Some(base);
+        self
+    }
+
+    /// Hang up on the `count` connections after the first one, before
+    /// the greeting, simulating a bridge that is restarting.
+    pub const fn refuse_reconnects(mut self, count: usize) -> Self {
+        self.options.refuse_reconnects = count;
+        self
+    }
+
+    /// Close every connection before sending the greeting, simulating
+    /// a bridge that accepts and immediately hangs up.
+    pub const fn drop_before_greeting(mut self) -> Self {
+        self.options.drop_before_greeting = true;
+        self
+    }
+
     /// Add a new folder. Subsequent `.email()` calls add to this
     /// folder.
     pub fn folder(mut self, name: &str) -> Self {
         self.folders.push(Folder {
             name: name.to_string(),
+            special_use: None,
             emails: Vec::new(),
         });
         self
     }
 
+    /// Tag the most recently added folder with a SPECIAL-USE
+    /// attribute (e.g. `"\\Trash"`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if called before any `.folder()` call.
+    pub fn special_use(mut self, attribute: &str) -> Self {
+        self.folders
+            .last_mut()
+            .expect("call .folder() before .special_use()")
+            .special_use = Some(attribute.to_string());
+        self
+    }
+
     /// Add an email to the most recently added folder.
     ///
     /// # Panics
@@ -111,12 +453,52 @@ impl MailboxBuilder {
             .last_mut()
             .expect("call .folder() before .email()")
             .emails
-            .push(TestEmail {
-                uid,
-                seen,
-                deleted: false,
-                raw: raw.to_vec(),
-            });
+            .push(TestEmail::new(uid, seen, raw));
+        self
+    }
+
+    /// Set the arrival time (INTERNALDATE) of the most recently added
+    /// email, given in RFC 2822 format, e.g. for a message whose
+    /// `Date:` header is far from when it arrived.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no email has been added yet or `date` does not parse.
+    pub fn internal_date(mut self, date: &str) -> Self {
+        self.folders
+            .last_mut()
+            .and_then(|folder| folder.emails.last_mut())
+            .expect("call .email() before .internal_date()")
+            .internal_date = DateTime::parse_from_rfc2822(date).expect("RFC 2822 date");
+        self
+    }
+
+    /// Add an email with an explicit flag list, in wire format
+    /// (e.g. `["\\Seen", "Work"]`). Unknown system flags are ignored;
+    /// anything without a `\` prefix becomes a keyword.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called before any `.folder()` call.
+    pub fn email_with_flags(mut self, uid: u32, flags: &[&str], raw: &[u8]) -> Self {
+        let mut email = TestEmail::new(uid, false, raw);
+        for flag in flags {
+            match *flag {
+                "\\Seen" => email.seen = true,
+                "\\Answered" => email.answered = true,
+                "\\Flagged" => email.flagged = true,
+                "\\Deleted" => email.deleted = true,
+                "\\Draft" => email.draft = true,
+                kw if !kw.starts_with('\\') => email.keywords.push(kw.to_string()),
+                _ => {}
+            }
+        }
+
+        self.folders
+            .last_mut()
+            .expect("call .folder() before .email_with_flags()")
+            .emails
+            .push(email);
         self
     }
 
@@ -124,6 +506,7 @@ impl MailboxBuilder {
     pub fn build(self) -> Mailbox {
         Mailbox {
             folders: self.folders,
+            options: self.options,
         }
     }
 }
@@ -11,7 +11,17 @@
 //! - `handlers/` -- one file per IMAP command (LIST, SELECT, etc.)
 //! - `mailbox` -- test data model (folders, emails, builder)
 //! - `io` -- shared write helpers
+//! - `bodystructure` -- MIME parsing for FETCH BODYSTRUCTURE
+//! - `envelope` -- header parsing for FETCH ENVELOPE
+//!
+//! Each test crate (`integration_test`, `cli_test`) compiles its own
+//! copy of this module and uses a different subset of the builder and
+//! server helpers, so unused items are expected.
+
+#![allow(dead_code)]
 
+mod bodystructure;
+mod envelope;
 mod handlers;
 mod io;
 pub mod mailbox;
@@ -62,22 +62,39 @@
 //!
 //! This is how async-imap knows when the message body ends -- it reads
 //! exactly `bytecount` bytes, then expects the closing `)`.
+//!
+//! Clients use the same encoding in the other direction, e.g. for the
+//! message in APPEND. A synchronizing literal makes the client wait
+//! for a `+` continuation before sending the bytes:
+//!
+//! ```text
+//!   Client:  A0003 APPEND "Drafts" (\Draft) {1234}
+//!   Server:  + Ready for literal data
+//!   Client:  <exactly 1234 bytes>
+//!   Server:  A0003 OK APPEND completed
+//! ```
+//!
+//! A client that sends the bytes without waiting is cut off with
+//! `* BAD`, as a strict server would.
 
 use super::handlers::{
-    StoreArgs, handle_capability, handle_expunge, handle_list, handle_login, handle_logout,
+    FetchArgs, StoreArgs, handle_append, handle_capability, handle_compress, handle_create,
+    handle_expunge, handle_idle, handle_list, handle_login, handle_logout, handle_multiappend,
     handle_noop, handle_select, handle_uid_copy, handle_uid_fetch, handle_uid_search,
-    handle_uid_store,
+    handle_uid_store, handle_unselect, parse_multiappend,
 };
-use super::io::write_line;
-use super::mailbox::Mailbox;
+use super::io::{DeflateStream, LowercaseStatus, lowercase_status, write_bytes, write_line};
+use super::mailbox::{Mailbox, ServerOptions};
 use imap_codec::CommandCodec;
-use imap_codec::decode::Decoder;
+use imap_codec::decode::{CommandDecodeError, Decoder};
 use imap_codec::imap_types::command::CommandBody;
-use imap_codec::imap_types::mailbox::Mailbox as ImapMailbox;
+use imap_codec::imap_types::core::{LiteralMode, LiteralOrLiteral8};
+use imap_codec::imap_types::mailbox::{ListMailbox, Mailbox as ImapMailbox};
 use rcgen::generate_simple_self_signed;
 use rustls::pki_types::PrivatePkcs8KeyDer;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::net::TcpListener;
 use tokio_rustls::TlsAcceptor;
 
@@ -89,6 +106,8 @@ use tokio_rustls::TlsAcceptor;
 /// greeting -> STARTTLS -> TLS -> LOGIN -> commands -> LOGOUT.
 pub struct FakeImapServer {
     port: u16,
+    /// Counters shared by all connections.
+    stats: Arc<ServerStats>,
     /// Handle to the background task so it lives as long as the server.
     _handle: tokio::task::JoinHandle<()>,
 }
@@ -104,6 +123,20 @@ impl FakeImapServer {
     /// The server runs until the `FakeImapServer` is dropped (the
     /// tokio task is aborted).
     pub async fn start(mailbox: Mailbox) -> Self {
+        Self::launch(mailbox, ServerStats::default()).await
+    }
+
+    /// Start a server that also records every command it receives,
+    /// for [`FakeImapServer::commands`].
+    pub async fn start_recording(mailbox: Mailbox) -> Self {
+        let stats = ServerStats {
+            commands: Some(Mutex::new(Vec::new())),
+            ..ServerStats::default()
+        };
+        Self::launch(mailbox, stats).await
+    }
+
+    async fn launch(mailbox: Mailbox, stats: ServerStats) -> Self {
         // Ensure the ring crypto provider is installed
         // process-wide. Multiple tests may race to install it, so
         // we ignore the error if it's already set.
@@ -131,6 +164,8 @@ impl FakeImapServer {
 
         let acceptor = TlsAcceptor::from(Arc::new(tls_config));
         let mailbox = Arc::new(Mutex::new(mailbox));
+        let stats = Arc::new(stats);
+        let server_stats = stats.clone();
 
         // Spawn the accept loop. Each incoming connection gets its
         // own task that runs the IMAP state machine.
@@ -141,14 +176,18 @@ impl FakeImapServer {
                 };
                 let acceptor = acceptor.clone();
                 let mailbox = mailbox.clone();
+                let stats = server_stats.clone();
+                stats.open.fetch_add(1, Ordering::SeqCst);
                 tokio::spawn(async move {
-                    handle_connection(stream, acceptor, &mailbox).await;
+                    handle_connection(stream, acceptor, &mailbox, &stats).await;
+                    stats.open.fetch_sub(1, Ordering::SeqCst);
                 });
             }
         });
 
         Self {
             port,
+            stats,
             _handle: handle,
         }
     }
@@ -157,51 +196,122 @@ impl FakeImapServer {
     pub const fn port(&self) -> u16 {
         self.port
     }
+
+    /// The commands received so far, in order, across all
+    /// connections, when started with
+    /// [`FakeImapServer::start_recording`]; empty otherwise.
+    ///
+    /// Each entry is the command line without its tag or line ending
+    /// (e.g. `UID FETCH 1:5 (BODY.PEEK[])`), with any literal data
+    /// inline.
+    pub fn commands(&self) -> Vec<String> {
+        self.stats
+            .commands
+            .as_ref()
+            .map(|commands| commands.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    /// How many connections have turned on COMPRESS=DEFLATE.
+    pub fn compress_count(&self) -> usize {
+        self.stats.compressions.load(Ordering::SeqCst)
+    }
+
+    /// How many SELECT commands the server has received so far.
+    pub fn select_count(&self) -> usize {
+        self.stats.selects.load(Ordering::SeqCst)
+    }
+
+    /// How many `+` continuations the server has sent for
+    /// synchronizing literals so far.
+    pub fn continuation_count(&self) -> usize {
+        self.stats.continuations.load(Ordering::SeqCst)
+    }
+
+    /// How many client connections are currently open.
+    pub fn open_connections(&self) -> usize {
+        self.stats.open.load(Ordering::SeqCst)
+    }
+
+    /// Wait up to `timeout` for every client connection to close.
+    /// Returns whether they all did.
+    pub async fn wait_until_idle(&self, timeout: std::time::Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.open_connections() > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        true
+    }
 }
 
 /// Handle a single IMAP client connection.
 ///
 /// This function implements the full IMAP lifecycle:
-/// 1. Send the server greeting (pre-TLS, on the raw TCP stream)
+/// 1. Send the server greeting (pre-TLS, on the raw TCP stream),
+///    after the configured delay or not at all if the mailbox is set
+///    to drop connections (or this reconnect) before greeting
 /// 2. Wait for the STARTTLS command and upgrade to TLS
 /// 3. Process authenticated commands (LOGIN, LIST, SELECT, etc.)
+///
+/// With `implicit_tls`, the TLS handshake comes first and the
+/// greeting is sent over TLS. With `handshake_timeout` as well, a
+/// client that sends nothing in time gets a fatal alert instead.
 async fn handle_connection(
     stream: tokio::net::TcpStream,
     acceptor: TlsAcceptor,
     mailbox: &Mutex<Mailbox>,
+    stats: &ServerStats,
 ) {
-    // Phase 1: Pre-TLS communication
-    let mut reader = BufReader::new(stream);
-
-    // RFC 3501 Section 7.1.1: Server greeting
-    if write_line(&mut reader, "* OK IMAP4rev1 Fake server ready\r\n")
-        .await
-        .is_err()
-    {
+    let options = mailbox.lock().unwrap().options.clone();
+    let index = stats.accepted.fetch_add(1, Ordering::SeqCst);
+    if options.drop_before_greeting || (1..=options.refuse_reconnects).contains(&index) {
         return;
     }
-
-    // Read the STARTTLS command.
-    let mut line = String::new();
-    if reader.read_line(&mut line).await.is_err() {
-        return;
+    if let Some(delay) = options.greeting_delay {
+        tokio::time::sleep(delay).await;
     }
 
-    let parts: Vec<&str> = line.trim().splitn(2, ' ').collect();
-    if parts.len() < 2 {
+    // RFC 3501 Section 7.1.1: Server greeting
+    let greeting = lowercase_status(
+        b"* OK IMAP4rev1 Fake server ready\r\n",
+        options.lowercase_status,
+    );
+
+    if options.implicit_tls {
+        if let Some(timeout) = options.handshake_timeout {
+            let mut byte = [0u8; 1];
+            if tokio::time::timeout(timeout, stream.peek(&mut byte))
+                .await
+                .is_err()
+            {
+                let mut stream = stream;
+                // A fatal handshake_failure alert (RFC 8446 Section 6).
+                let _ = stream
+                    .write_all(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28])
+                    .await;
+                return;
+            }
+        }
+        let Ok(tls_stream) = acceptor.accept(stream).await else {
+            return;
+        };
+        let mut reader = BufReader::new(tls_stream);
+        if write_bytes(&mut reader, &greeting).await.is_err() {
+            return;
+        }
+        serve(reader.into_inner(), &options, mailbox, stats).await;
         return;
     }
-    let tag = parts[0];
-    let command = parts[1].to_uppercase();
 
-    if command != "STARTTLS" {
-        let resp = format!("{tag} BAD Expected STARTTLS\r\n");
-        let _ = write_line(&mut reader, &resp).await;
+    // Phase 1: Pre-TLS communication
+    let mut reader = BufReader::new(stream);
+    if write_bytes(&mut reader, &greeting).await.is_err() {
         return;
     }
-
-    let resp = format!("{tag} OK Begin TLS negotiation now\r\n");
-    if write_line(&mut reader, &resp).await.is_err() {
+    if !await_starttls(&mut reader, &options, stats).await {
         return;
     }
 
@@ -212,7 +322,88 @@ async fn handle_connection(
     };
 
     // Phase 3: Authenticated IMAP session
-    handle_imap_session(tls_stream, mailbox).await;
+    serve(tls_stream, &options, mailbox, stats).await;
+}
+
+/// Answer pre-TLS commands until the client sends STARTTLS.
+///
+/// CAPABILITY is answered; STARTTLS is refused with `BAD` when
+/// `no_starttls` is set. Anything else ends the connection. Returns
+/// whether to go on with the TLS handshake.
+async fn await_starttls<S: AsyncRead + AsyncWrite + Unpin>(
+    reader: &mut BufReader<S>,
+    options: &ServerOptions,
+    stats: &ServerStats,
+) -> bool {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.is_err() {
+            return false;
+        }
+
+        let parts: Vec<&str> = line.trim().splitn(2, ' ').collect();
+        if parts.len() < 2 {
+            return false;
+        }
+        let tag = parts[0];
+        stats.record(parts[1]);
+        let command = parts[1].to_uppercase();
+
+        if command == "CAPABILITY" {
+            handle_capability(tag, &options.capabilities(), reader).await;
+        } else if command == "STARTTLS" && options.no_starttls {
+            let resp = format!("{tag} BAD STARTTLS not supported\r\n");
+            if write_line(reader, &resp).await.is_err() {
+                return false;
+            }
+        } else if command == "STARTTLS" {
+            let resp = format!("{tag} OK Begin TLS negotiation now\r\n");
+            let resp = lowercase_status(resp.as_bytes(), options.lowercase_status);
+            return write_bytes(reader, &resp).await.is_ok();
+        } else {
+            let resp = format!("{tag} BAD Expected STARTTLS\r\n");
+            let _ = write_line(reader, &resp).await;
+            return false;
+        }
+    }
+}
+
+/// Run the IMAP session on an established TLS stream.
+async fn serve<S: AsyncRead + AsyncWrite + Unpin>(
+    tls_stream: S,
+    options: &ServerOptions,
+    mailbox: &Mutex<Mailbox>,
+    stats: &ServerStats,
+) {
+    if options.lowercase_status {
+        handle_imap_session(LowercaseStatus::new(tls_stream), mailbox, stats).await;
+    } else {
+        handle_imap_session(tls_stream, mailbox, stats).await;
+    }
+}
+
+/// Counters the server keeps so tests can assert on how the client
+/// talked to it.
+#[derive(Default)]
+struct ServerStats {
+    selects: AtomicUsize,
+    continuations: AtomicUsize,
+    /// Connections accepted and not yet closed.
+    open: AtomicUsize,
+    /// Connections accepted so far.
+    accepted: AtomicUsize,
+    compressions: AtomicUsize,
+    /// Commands received, when recording.
+    commands: Option<Mutex<Vec<String>>>,
+}
+
+impl ServerStats {
+    /// Record a command (without its tag) if recording is on.
+    fn record(&self, command: &str) {
+        if let Some(commands) = &self.commands {
+            commands.lock().unwrap().push(command.to_string());
+        }
+    }
 }
 
 /// Extract the folder name from a parsed `imap_types::Mailbox`.
@@ -226,8 +417,29 @@ fn mailbox_name(mb: &ImapMailbox<'_>) -> String {
     }
 }
 
-/// Run the authenticated IMAP command loop over an established
-/// stream.
+/// Run the authenticated IMAP session over an established stream.
+///
+/// If the client turns on COMPRESS=DEFLATE, the rest of the session
+/// runs over a deflate-wrapped stream (RFC 4978 Section 4).
+async fn handle_imap_session<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    mailbox: &Mutex<Mailbox>,
+    stats: &ServerStats,
+) {
+    let mut selected_folder: Option<String> = None;
+
+    let reader = BufReader::new(stream);
+    let Some(reader) = run_commands(reader, mailbox, stats, &mut selected_folder, false).await
+    else {
+        return;
+    };
+
+    let reader = BufReader::new(DeflateStream::new(reader.into_inner()));
+    run_commands(reader, mailbox, stats, &mut selected_folder, true).await;
+}
+
+/// Run the IMAP command loop until the client disconnects, logs out,
+/// or asks for compression.
 ///
 /// Uses `imap-codec`'s `CommandCodec` to parse each client command
 /// into a strongly-typed `Command`, then dispatches to the
@@ -236,29 +448,34 @@ fn mailbox_name(mb: &ImapMailbox<'_>) -> String {
 /// Read handlers receive a snapshot (`Mailbox` clone) taken under
 /// lock. Write handlers receive `&Mutex<Mailbox>` and lock briefly
 /// to mutate state.
-async fn handle_imap_session<S: AsyncRead + AsyncWrite + Unpin>(
-    stream: S,
+///
+/// Returns the stream after a successful COMPRESS, so the caller can
+/// wrap it. The client waits for the tagged OK before sending
+/// compressed data, so nothing is left in the read buffer.
+async fn run_commands<S: AsyncRead + AsyncWrite + Unpin>(
+    mut reader: BufReader<S>,
     mailbox: &Mutex<Mailbox>,
-) {
-    let mut reader = BufReader::new(stream);
-    let mut selected_folder: Option<String> = None;
+    stats: &ServerStats,
+    selected_folder: &mut Option<String>,
+    compressed: bool,
+) -> Option<BufReader<S>> {
     let codec = CommandCodec::default();
 
-    loop {
-        let mut line = String::new();
-        match reader.read_line(&mut line).await {
-            Ok(0) | Err(_) => break,
-            Ok(_) => {}
-        }
-
-        let trimmed = line.trim();
+    while let Some(line) = read_command(&mut reader, &codec, stats).await {
+        let text = String::from_utf8_lossy(&line);
+        let trimmed = text.trim();
         if trimmed.is_empty() {
             continue;
         }
+        stats.record(trimmed.split_once(' ').map_or("", |(_, rest)| rest));
 
-        // Parse the command line using imap-codec.
-        let line_bytes = line.as_bytes();
-        let Ok((_, command)) = codec.decode(line_bytes) else {
+        // Parse the command (including any literals) using imap-codec.
+        let Ok((_, command)) = codec.decode(&line) else {
+            let multiappend = mailbox.lock().unwrap().options.multiappend;
+            if let Some(append) = parse_multiappend(&line).filter(|_| multiappend) {
+                handle_multiappend(&append, mailbox, &mut reader).await;
+                continue;
+            }
             let tag = trimmed.split_whitespace().next().unwrap_or("*");
             let resp = format!("{tag} BAD Parse error\r\n");
             if write_line(&mut reader, &resp).await.is_err() {
@@ -267,11 +484,22 @@ async fn handle_imap_session<S: AsyncRead + AsyncWrite + Unpin>(
             continue;
         };
 
+        if matches!(command.body, CommandBody::Compress { .. }) {
+            let available = mailbox.lock().unwrap().options.compress;
+            let tag = command.tag.inner();
+            if handle_compress(tag, available, compressed, &mut reader).await {
+                stats.compressions.fetch_add(1, Ordering::SeqCst);
+                return Some(reader);
+            }
+            continue;
+        }
+
         let result = dispatch_command(
             &command.body,
             command.tag.inner(),
             mailbox,
-            &mut selected_folder,
+            stats,
+            selected_folder,
             &mut reader,
         )
         .await;
@@ -280,6 +508,71 @@ async fn handle_imap_session<S: AsyncRead + AsyncWrite + Unpin>(
             break;
         }
     }
+    None
+}
+
+/// Read one complete command from the client.
+///
+/// Reads a line at a time. When `imap-codec` reports that the line
+/// ends in a literal announcement (`{N}` or `{N+}`), sends the `+`
+/// continuation for synchronizing literals, reads exactly `N` bytes,
+/// and keeps reading until the command is complete.
+///
+/// Returns `None` when the client disconnects.
+async fn read_command<S: AsyncRead + AsyncWrite + Unpin>(
+    reader: &mut BufReader<S>,
+    codec: &CommandCodec,
+    stats: &ServerStats,
+) -> Option<Vec<u8>> {
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => return None,
+            Ok(_) => {}
+        }
+
+        let literal = match codec.decode(&buf) {
+            Err(CommandDecodeError::LiteralFound { length, mode, .. }) => Some((length, mode)),
+            Err(CommandDecodeError::Incomplete) => None,
+            // imap-codec does not know MULTIAPPEND, so a second
+            // message literal looks like garbage to it.
+            Err(_) => match trailing_literal(&buf) {
+                Some(literal) => Some(literal),
+                None => return Some(buf),
+            },
+            Ok(_) => return Some(buf),
+        };
+
+        if let Some((length, mode)) = literal {
+            // Non-synchronizing literals (`{N+}`, RFC 7888) are sent
+            // without waiting for a continuation.
+            if matches!(mode, LiteralMode::Sync) {
+                if !reader.buffer().is_empty() {
+                    let _ = write_line(reader, "* BAD Literal sent before continuation\r\n").await;
+                    return None;
+                }
+                stats.continuations.fetch_add(1, Ordering::SeqCst);
+                write_line(reader, "+ Ready for literal data\r\n")
+                    .await
+                    .ok()?;
+            }
+            let start = buf.len();
+            buf.resize(start + usize::try_from(length).ok()?, 0);
+            reader.read_exact(&mut buf[start..]).await.ok()?;
+        }
+    }
+}
+
+/// The literal announced at the end of `buf` (`{N}\r\n` or
+/// `{N+}\r\n`), if any.
+fn trailing_literal(buf: &[u8]) -> Option<(u32, LiteralMode)> {
+    let announcement = buf.strip_suffix(b"}\r\n")?;
+    let start = announcement.iter().rposition(|&b| b == b'{')?;
+    let count = std::str::from_utf8(&announcement[start + 1..]).ok()?;
+    match count.strip_suffix('+') {
+        Some(count) => Some((count.parse().ok()?, LiteralMode::NonSync)),
+        None => Some((count.parse().ok()?, LiteralMode::Sync)),
+    }
 }
 
 /// Dispatch a single parsed IMAP command to the appropriate handler.
@@ -289,6 +582,7 @@ async fn dispatch_command<S: AsyncRead + AsyncWrite + Unpin>(
     body: &CommandBody<'_>,
     tag: &str,
     mailbox: &Mutex<Mailbox>,
+    stats: &ServerStats,
     selected_folder: &mut Option<String>,
     reader: &mut BufReader<S>,
 ) -> bool {
@@ -297,7 +591,7 @@ async fn dispatch_command<S: AsyncRead + AsyncWrite + Unpin>(
 
     match *body {
         CommandBody::Capability => {
-            handle_capability(tag, reader).await;
+            handle_capability(tag, &snap.options.capabilities(), reader).await;
         }
         CommandBody::Noop => {
             handle_noop(tag, reader).await;
@@ -307,12 +601,19 @@ async fn dispatch_command<S: AsyncRead + AsyncWrite + Unpin>(
                 return false;
             }
         }
-        CommandBody::List { .. } => {
-            handle_list(tag, &snap, reader).await;
+        CommandBody::List {
+            ref mailbox_wildcard,
+            ..
+        } => {
+            // `LIST "" ""` asks for the hierarchy delimiter only.
+            let delimiter_only =
+                matches!(mailbox_wildcard, ListMailbox::String(s) if s.as_ref().is_empty());
+            handle_list(tag, &snap, delimiter_only, reader).await;
         }
         CommandBody::Select {
             mailbox: ref mb, ..
         } => {
+            stats.selects.fetch_add(1, Ordering::SeqCst);
             let name = mailbox_name(mb);
             *selected_folder = handle_select(tag, &name, &snap, reader).await;
         }
@@ -332,10 +633,15 @@ async fn dispatch_command<S: AsyncRead + AsyncWrite + Unpin>(
         }
         CommandBody::Fetch {
             ref sequence_set,
+            ref macro_or_item_names,
             uid: true,
             ..
         } => {
-            handle_uid_fetch(tag, sequence_set, &snap, selected_folder.as_deref(), reader).await;
+            let args = FetchArgs {
+                sequence_set,
+                items: macro_or_item_names,
+            };
+            handle_uid_fetch(tag, &args, mailbox, selected_folder.as_deref(), reader).await;
         }
         CommandBody::Store {
             ref sequence_set,
@@ -370,9 +676,31 @@ async fn dispatch_command<S: AsyncRead + AsyncWrite + Unpin>(
             )
             .await;
         }
+        CommandBody::Append {
+            mailbox: ref mb,
+            ref flags,
+            ref message,
+            ..
+        } => {
+            let name = mailbox_name(mb);
+            let LiteralOrLiteral8::Literal(literal) = message else {
+                let resp = format!("{tag} BAD Unsupported literal\r\n");
+                return write_line(reader, &resp).await.is_ok();
+            };
+            handle_append(tag, &name, flags, literal.as_ref(), mailbox, reader).await;
+        }
+        CommandBody::Create { mailbox: ref mb } => {
+            handle_create(tag, &mailbox_name(mb), mailbox, reader).await;
+        }
         CommandBody::Expunge => {
             handle_expunge(tag, mailbox, selected_folder.as_deref(), reader).await;
         }
+        CommandBody::Idle => {
+            return handle_idle(tag, mailbox, selected_folder.as_deref(), reader).await;
+        }
+        CommandBody::Unselect => {
+            handle_unselect(tag, selected_folder, reader).await;
+        }
         CommandBody::Logout => {
             handle_logout(tag, reader).await;
             return false;
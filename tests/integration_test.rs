@@ -9,7 +9,13 @@
 mod fake_imap;
 
 use fake_imap::{FakeImapServer, MailboxBuilder};
-use protonmail_client::{Flag, Folder, ImapConfig, ProtonClient, ReadWrite};
+use protonmail_client::{
+    BodyPreference, ConnectionSecurity, Error, FetchMacro, FetchOrder, Flag, Folder, ImapConfig,
+    ImapResponseCode, MetricEvent, MissingFolderBehavior, MoveOutcome, ProtonClient, ReadWrite,
+    SearchQuery, WatchEvent,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Build a minimal valid RFC 2822 email.
 ///
@@ -40,12 +46,7 @@ fn writer_for(server: &FakeImapServer) -> ProtonClient<ReadWrite> {
 }
 
 fn config_for(server: &FakeImapServer) -> ImapConfig {
-    ImapConfig {
-        host: "127.0.0.1".to_string(),
-        port: server.port(),
-        username: "testuser".to_string(),
-        password: "testpass".to_string(),
-    }
+    ImapConfig::new("127.0.0.1", server.port(), "testuser", "testpass")
 }
 
 // ── Tests ──────────────────────────────────────────────────────────
@@ -89,6 +90,119 @@ async fn test_fetch_uid() {
     assert_eq!(email.subject.original, "Hello Bob");
 }
 
+#[tokio::test]
+async fn test_fetch_uids_map() {
+    let raw = |subject: &str| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "Body.",
+            "Mon, 01 Jan 2024 12:00:00 +0000",
+        )
+    };
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(3, false, &raw("Three"))
+        .email(5, true, &raw("Five"))
+        .email(8, false, &raw("Eight"))
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client = client_for(&server);
+
+    let emails = client
+        .fetch_uids_map(&Folder::Inbox, &[8, 3, 4])
+        .await
+        .unwrap();
+    assert_eq!(emails.len(), 2);
+    assert_eq!(emails[&3].subject.original, "Three");
+    assert_eq!(emails[&8].subject.original, "Eight");
+    assert!(!emails.contains_key(&4));
+
+    let fetches = server
+        .commands()
+        .iter()
+        .filter(|c| c.starts_with("UID FETCH") && c.contains("BODY.PEEK[]"))
+        .count();
+    assert_eq!(fetches, 1);
+
+    let none = client.fetch_uids_map(&Folder::Inbox, &[]).await.unwrap();
+    assert!(none.is_empty());
+}
+
+#[tokio::test]
+async fn test_fetch_macro() {
+    let raw = make_raw_email(
+        "Alice <alice@example.com>",
+        "bob@example.com",
+        "Macro",
+        "Body.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(4, true, &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let fast = client
+        .fetch_macro(&Folder::Inbox, &[4, 9], FetchMacro::Fast)
+        .await
+        .unwrap();
+    assert_eq!(fast.len(), 1);
+    assert_eq!(fast[0].uid, 4);
+    assert_eq!(fast[0].flags, vec![Flag::Seen]);
+    assert_eq!(fast[0].size, Some(u32::try_from(raw.len()).unwrap()));
+    assert!(fast[0].internal_date.is_some());
+    assert!(fast[0].envelope.is_none());
+    assert!(fast[0].body.is_none());
+
+    let all = client
+        .fetch_macro(&Folder::Inbox, &[4], FetchMacro::All)
+        .await
+        .unwrap();
+    let envelope = all[0].envelope.as_ref().unwrap();
+    assert_eq!(envelope.subject.as_deref(), Some("Macro"));
+    assert_eq!(envelope.from[0].name.as_deref(), Some("Alice"));
+    assert_eq!(envelope.from[0].address, "alice@example.com");
+    assert_eq!(envelope.to[0].address, "bob@example.com");
+    assert_eq!(
+        envelope.message_id.as_deref(),
+        Some("<test-Macro@fake.test>")
+    );
+
+    let none = client
+        .fetch_macro(&Folder::Inbox, &[], FetchMacro::Full)
+        .await
+        .unwrap();
+    assert!(none.is_empty());
+}
+
+#[tokio::test]
+async fn test_fetch_uid_missing() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let err = client.fetch_uid(&Folder::Inbox, 999).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::MessageNotFound {
+                ref folder,
+                uid: 999,
+            } if *folder == Folder::Inbox
+        ),
+        "unexpected error: {err}"
+    );
+}
+
 #[tokio::test]
 async fn test_fetch_unseen() {
     let seen_email = make_raw_email(
@@ -123,6 +237,102 @@ async fn test_fetch_unseen() {
     assert_eq!(emails[0].from.address, "charlie@example.com");
 }
 
+#[tokio::test]
+async fn test_fetch_unseen_decryptable() {
+    let plain = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Readable",
+        "Plain text.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let inline = make_raw_email(
+        "carol@example.com",
+        "bob@example.com",
+        "Inline PGP",
+        "-----BEGIN PGP MESSAGE-----\r\n\r\nhQEMA\r\n-----END PGP MESSAGE-----",
+        "Tue, 02 Jan 2024 12:00:00 +0000",
+    );
+    let pgp_mime = b"From: dave@example.com\r\n\
+        To: bob@example.com\r\n\
+        Subject: PGP/MIME\r\n\
+        Date: Wed, 03 Jan 2024 12:00:00 +0000\r\n\
+        Message-ID: <pgp-mime@fake.test>\r\n\
+        Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\";\r\n\
+        \tboundary=\"x\"\r\n\
+        \r\n\
+        --x\r\n\
+        Content-Type: application/pgp-encrypted\r\n\
+        \r\n\
+        Version: 1\r\n\
+        --x\r\n\
+        Content-Type: application/octet-stream\r\n\
+        \r\n\
+        -----BEGIN PGP MESSAGE-----\r\n\
+        \r\n\
+        hQEMA\r\n\
+        -----END PGP MESSAGE-----\r\n\
+        --x--\r\n";
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &plain)
+        .email(2, false, &inline)
+        .email(3, false, pgp_mime)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let emails = client
+        .fetch_unseen_decryptable(&Folder::Inbox)
+        .await
+        .unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].uid, 1);
+    assert!(!protonmail_client::is_encrypted(&emails[0]));
+}
+
+#[tokio::test]
+async fn test_fetch_unseen_human() {
+    let human = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Re: Ticket 42",
+        "Thanks, that fixed it.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let vacation = b"From: carol@example.com\r\n\
+        To: bob@example.com\r\n\
+        Subject: Out of office\r\n\
+        Date: Tue, 02 Jan 2024 12:00:00 +0000\r\n\
+        Auto-Submitted: auto-replied\r\n\
+        \r\n\
+        Back next week.";
+    let bounce = b"From: MAILER-DAEMON@example.com\r\n\
+        To: bob@example.com\r\n\
+        Subject: Undelivered Mail Returned to Sender\r\n\
+        Date: Wed, 03 Jan 2024 12:00:00 +0000\r\n\
+        Return-Path: <>\r\n\
+        \r\n\
+        Delivery failed.";
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &human)
+        .email(2, false, vacation)
+        .email(3, false, bounce)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let emails = client.fetch_unseen_human(&Folder::Inbox).await.unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].uid, 1);
+    assert_eq!(client.fetch_unseen(&Folder::Inbox).await.unwrap().len(), 3);
+}
+
 #[tokio::test]
 async fn test_fetch_all() {
     let email1 = make_raw_email(
@@ -228,6 +438,94 @@ async fn test_search() {
     assert_eq!(emails.len(), 2);
 }
 
+#[tokio::test]
+async fn test_search_query_or() {
+    let raw = |subject: &str| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "Body",
+            "Mon, 01 Jan 2024 10:00:00 +0000",
+        )
+    };
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email_with_flags(1, &["\\Flagged", "\\Seen"], &raw("Flagged read"))
+        .email_with_flags(2, &[], &raw("Unflagged unread"))
+        .email_with_flags(3, &["\\Seen"], &raw("Unflagged read"))
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let query = SearchQuery::new().flagged().or(SearchQuery::new().unseen());
+    assert_eq!(query.to_string(), "OR FLAGGED UNSEEN");
+    let emails = client
+        .search(&Folder::Inbox, &query.to_string())
+        .await
+        .unwrap();
+    let mut uids: Vec<u32> = emails.iter().map(|e| e.uid).collect();
+    uids.sort_unstable();
+    assert_eq!(uids, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn test_fetch_sent_and_arrival_range() {
+    let fresh = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Fresh",
+        "Sent and received on Jan 10.",
+        "Wed, 10 Jan 2024 10:00:00 +0000",
+    );
+    let migrated = make_raw_email(
+        "carol@example.com",
+        "bob@example.com",
+        "Migrated",
+        "Written long ago, imported on Jan 10.",
+        "Fri, 01 Jan 2021 09:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &fresh)
+        .email(2, true, &migrated)
+        .internal_date("Wed, 10 Jan 2024 11:00:00 +0000")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+    let uids = |emails: Vec<protonmail_client::Email>| {
+        let mut uids: Vec<u32> = emails.iter().map(|e| e.uid).collect();
+        uids.sort_unstable();
+        uids
+    };
+
+    let since = chrono::NaiveDate::from_ymd_opt(2024, 1, 5).unwrap();
+    let before = chrono::NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+
+    let arrived = client
+        .fetch_arrival_range(&Folder::Inbox, since, before)
+        .await
+        .unwrap();
+    assert_eq!(uids(arrived), [1, 2]);
+
+    let sent = client
+        .fetch_sent_range(&Folder::Inbox, since, before)
+        .await
+        .unwrap();
+    assert_eq!(uids(sent), [1]);
+
+    let old_since = chrono::NaiveDate::from_ymd_opt(2020, 12, 1).unwrap();
+    let old_before = chrono::NaiveDate::from_ymd_opt(2021, 2, 1).unwrap();
+    let sent = client
+        .fetch_sent_range(&Folder::Inbox, old_since, old_before)
+        .await
+        .unwrap();
+    assert_eq!(uids(sent), [2]);
+}
+
 #[tokio::test]
 async fn test_fetch_date_range() {
     let jan1 = make_raw_email(
@@ -252,17 +550,29 @@ async fn test_fetch_date_range() {
         "Sat, 20 Jan 2024 10:00:00 +0000",
     );
 
+    let backdated = make_raw_email(
+        "erin@example.com",
+        "bob@example.com",
+        "Backdated",
+        "Dated Jan 11, delivered Jan 20.",
+        "Thu, 11 Jan 2024 10:00:00 +0000",
+    );
+
     let mailbox = MailboxBuilder::new()
         .folder("INBOX")
         .email(1, true, &jan1)
         .email(2, true, &jan10)
         .email(3, true, &jan20)
+        .email(4, true, &backdated)
+        .internal_date("Sat, 20 Jan 2024 12:00:00 +0000")
         .build();
 
     let server = FakeImapServer::start(mailbox).await;
     let client = client_for(&server);
 
     // Range [Jan 5, Jan 15) should only include the Jan 10 email.
+    // The range is on arrival, so the backdated email is out even
+    // though its Date header is in range.
     let since = chrono::NaiveDate::from_ymd_opt(2024, 1, 5).unwrap();
     let before = chrono::NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
     let emails = client
@@ -275,6 +585,61 @@ async fn test_fetch_date_range() {
     assert_eq!(emails[0].subject.original, "Mid January");
 }
 
+#[tokio::test]
+async fn test_fetch_items() {
+    let email1 = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "First",
+        "First email.",
+        "Mon, 01 Jan 2024 10:00:00 +0000",
+    );
+    let email2 = make_raw_email(
+        "charlie@example.com",
+        "bob@example.com",
+        "Second",
+        "Second email.",
+        "Mon, 01 Jan 2024 11:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &email1)
+        .email(2, false, &email2)
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client = client_for(&server);
+
+    let fetches = client
+        .fetch_items(
+            &Folder::Inbox,
+            &[2, 1, 99],
+            "(FLAGS RFC822.SIZE BODY.PEEK[])",
+        )
+        .await
+        .unwrap();
+
+    // UID 99 does not exist and is simply absent.
+    assert_eq!(fetches.len(), 2);
+
+    assert_eq!(fetches[0].uid, 1);
+    assert_eq!(fetches[0].flags, vec![Flag::Seen]);
+    assert_eq!(fetches[0].size, Some(u32::try_from(email1.len()).unwrap()));
+    assert_eq!(fetches[0].body.as_deref(), Some(email1.as_slice()));
+    assert!(fetches[0].header.is_none());
+
+    assert_eq!(fetches[1].uid, 2);
+    assert!(fetches[1].flags.is_empty());
+
+    // The UIDs go out as a compact set.
+    assert!(
+        server
+            .commands()
+            .contains(&"UID FETCH 1:2,99 (FLAGS RFC822.SIZE BODY.PEEK[])".to_string())
+    );
+}
+
 #[tokio::test]
 async fn test_empty_mailbox() {
     let mailbox = MailboxBuilder::new().folder("INBOX").build();
@@ -326,135 +691,3148 @@ async fn test_add_flag() {
 }
 
 #[tokio::test]
-async fn test_remove_flag() {
+async fn test_rejected_store_is_an_error() {
     let raw = make_raw_email(
         "alice@example.com",
         "bob@example.com",
-        "Read message",
-        "Unmark me.",
+        "Read-only",
+        "Flags stay put.",
         "Mon, 01 Jan 2024 12:00:00 +0000",
     );
 
     let mailbox = MailboxBuilder::new()
         .folder("INBOX")
-        .email(1, true, &raw) // starts seen
+        .email(1, false, &raw)
+        .reject_store()
         .build();
 
     let server = FakeImapServer::start(mailbox).await;
     let writer = writer_for(&server);
 
-    // Remove \Seen flag.
-    writer
-        .remove_flag(1, &Folder::Inbox, &Flag::Seen)
+    let err = writer
+        .add_flag(1, &Folder::Inbox, &Flag::Seen)
         .await
-        .unwrap();
+        .unwrap_err();
+    match err {
+        Error::Imap(text) => assert!(text.starts_with("Store failed"), "got {text}"),
+        other => panic!("expected Error::Imap, got {other:?}"),
+    }
 
-    // Verify: fetching unseen should now return this email.
     let client = client_for(&server);
-    let unseen = client.fetch_unseen(&Folder::Inbox).await.unwrap();
-    assert_eq!(unseen.len(), 1);
-    assert_eq!(unseen[0].uid, 1);
+    assert_eq!(client.fetch_unseen(&Folder::Inbox).await.unwrap().len(), 1);
 }
 
 #[tokio::test]
-async fn test_move_to_folder() {
+async fn test_rejected_expunge_is_an_error() {
     let raw = make_raw_email(
         "alice@example.com",
         "bob@example.com",
-        "Move me",
-        "Moving to trash.",
+        "Sticky",
+        "Cannot be expunged.",
         "Mon, 01 Jan 2024 12:00:00 +0000",
     );
 
     let mailbox = MailboxBuilder::new()
         .folder("INBOX")
         .email(1, false, &raw)
-        .folder("Trash")
+        .folder("Archive")
+        .reject_expunge()
         .build();
 
     let server = FakeImapServer::start(mailbox).await;
     let writer = writer_for(&server);
 
-    // Move from INBOX to Trash.
-    writer
-        .move_to_folder(1, &Folder::Inbox, &Folder::Trash)
+    let err = writer
+        .move_to_folder(1, &Folder::Inbox, &Folder::Archive)
         .await
-        .unwrap();
+        .unwrap_err();
+    match err {
+        Error::Imap(text) => assert!(text.starts_with("Expunge failed"), "got {text}"),
+        other => panic!("expected Error::Imap, got {other:?}"),
+    }
 
-    // Verify: INBOX should be empty, Trash should have the email.
     let client = client_for(&server);
-    let inbox = client.fetch_all(&Folder::Inbox).await.unwrap();
-    assert!(inbox.is_empty());
-
-    let trash = client.fetch_all(&Folder::Trash).await.unwrap();
-    assert_eq!(trash.len(), 1);
-    assert_eq!(trash[0].from.address, "alice@example.com");
+    assert_eq!(client.fetch_all(&Folder::Inbox).await.unwrap().len(), 1);
 }
 
 #[tokio::test]
-async fn test_archive() {
+async fn test_remove_flag() {
     let raw = make_raw_email(
         "alice@example.com",
         "bob@example.com",
-        "Archive me",
-        "Please archive.",
+        "Read message",
+        "Unmark me.",
         "Mon, 01 Jan 2024 12:00:00 +0000",
     );
 
     let mailbox = MailboxBuilder::new()
         .folder("INBOX")
-        .email(1, false, &raw)
-        .folder("Archive")
+        .email(1, true, &raw) // starts seen
         .build();
 
     let server = FakeImapServer::start(mailbox).await;
     let writer = writer_for(&server);
 
-    writer.archive(1, &Folder::Inbox).await.unwrap();
+    // Remove \Seen flag.
+    writer
+        .remove_flag(1, &Folder::Inbox, &Flag::Seen)
+        .await
+        .unwrap();
 
+    // Verify: fetching unseen should now return this email.
     let client = client_for(&server);
-    let inbox = client.fetch_all(&Folder::Inbox).await.unwrap();
-    assert!(inbox.is_empty());
+    let unseen = client.fetch_unseen(&Folder::Inbox).await.unwrap();
+    assert_eq!(unseen.len(), 1);
+    assert_eq!(unseen[0].uid, 1);
+}
 
-    let archive = client.fetch_all(&Folder::Archive).await.unwrap();
-    assert_eq!(archive.len(), 1);
+#[tokio::test]
+async fn test_star_and_unstar() {
+    let raw = |subject: &str| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "Body",
+            "Mon, 01 Jan 2024 10:00:00 +0000",
+        )
+    };
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email_with_flags(1, &["\\Flagged"], &raw("Starred"))
+        .email_with_flags(2, &["\\Seen"], &raw("Plain"))
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+    let writer = writer_for(&server);
+    let starred = || async {
+        let mut uids: Vec<u32> = client
+            .fetch_starred(&Folder::Inbox)
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| e.uid)
+            .collect();
+        uids.sort_unstable();
+        uids
+    };
+
+    assert_eq!(starred().await, vec![1]);
+
+    writer.star(2, &Folder::Inbox).await.unwrap();
+    assert_eq!(starred().await, vec![1, 2]);
+
+    writer.unstar(1, &Folder::Inbox).await.unwrap();
+    assert_eq!(starred().await, vec![2]);
 }
 
 #[tokio::test]
-async fn test_unmark_all_read() {
-    let email1 = make_raw_email(
-        "alice@example.com",
-        "bob@example.com",
-        "First read",
-        "Was read.",
-        "Mon, 01 Jan 2024 10:00:00 +0000",
-    );
-    let email2 = make_raw_email(
-        "charlie@example.com",
-        "bob@example.com",
-        "Second read",
-        "Also read.",
-        "Mon, 01 Jan 2024 11:00:00 +0000",
+async fn test_first_unseen() {
+    let raw = |subject: &str| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "Body",
+            "Mon, 01 Jan 2024 10:00:00 +0000",
+        )
+    };
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(3, true, &raw("Read"))
+        .email(5, false, &raw("Oldest unread"))
+        .email(8, false, &raw("Newer unread"))
+        .folder("Archive")
+        .email(1, true, &raw("Done"))
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client = client_for(&server);
+
+    let email = client.first_unseen(&Folder::Inbox).await.unwrap().unwrap();
+    assert_eq!(email.uid, 5);
+    assert_eq!(email.subject.original, "Oldest unread");
+
+    // Found from SELECT's [UNSEEN 2], so only one body is fetched.
+    let commands = server.commands();
+    assert!(commands.iter().any(|c| c == "UID SEARCH 2"));
+    let fetches = commands
+        .iter()
+        .filter(|c| c.starts_with("UID FETCH") && c.contains("BODY.PEEK[]"))
+        .count();
+    assert_eq!(fetches, 1);
+
+    assert!(
+        client
+            .first_unseen(&Folder::Archive)
+            .await
+            .unwrap()
+            .is_none()
     );
+}
 
+#[tokio::test]
+async fn test_client_relies_on_uids_not_sequence_numbers() {
+    let raw = |subject: &str| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "Body",
+            "Mon, 01 Jan 2024 10:00:00 +0000",
+        )
+    };
     let mailbox = MailboxBuilder::new()
         .folder("INBOX")
-        .email(1, true, &email1) // seen
-        .email(2, true, &email2) // seen
+        .email(3, true, &raw("Read"))
+        .email(5, false, &raw("Unread"))
+        .email(8, false, &raw("Moving"))
+        .folder("Archive")
+        .seq_base(40)
         .build();
 
     let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
     let writer = writer_for(&server);
 
-    // Before: no unseen emails.
-    let client = client_for(&server);
-    let unseen = client.fetch_unseen(&Folder::Inbox).await.unwrap();
-    assert!(unseen.is_empty());
+    assert_eq!(client.fetch_all(&Folder::Inbox).await.unwrap().len(), 3);
+    let first = client.first_unseen(&Folder::Inbox).await.unwrap().unwrap();
+    assert_eq!(first.uid, 5);
+
+    writer
+        .add_flag(5, &Folder::Inbox, &Flag::Seen)
+        .await
+        .unwrap();
+    writer
+        .move_to_folder(8, &Folder::Inbox, &Folder::Archive)
+        .await
+        .unwrap();
+
+    let inbox = client.flag_snapshot(&Folder::Inbox).await.unwrap();
+    assert_eq!(inbox.len(), 2);
+    assert!(inbox[&5].contains(&Flag::Seen));
+    assert_eq!(client.fetch_all(&Folder::Archive).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_unified_unseen() {
+    let raw = |subject: &str, date: &str| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "Body",
+            date,
+        )
+    };
+    let labelled = raw("Labelled", "Mon, 01 Jan 2024 10:00:00 +0000");
+    let inboxed = raw("Inboxed", "Wed, 03 Jan 2024 10:00:00 +0000");
+    let archived = raw("Archived", "Tue, 02 Jan 2024 10:00:00 +0000");
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &inboxed)
+        .folder("Tous les messages")
+        .special_use("\\All")
+        .email(1, false, &labelled)
+        .email(2, false, &inboxed)
+        .email(3, false, &archived)
+        .email(4, true, &raw("Read", "Thu, 04 Jan 2024 10:00:00 +0000"))
+        .folder("Labels")
+        .special_use("\\Noselect")
+        .folder("Labels/Work")
+        .email(8, false, &labelled)
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client = client_for(&server);
+
+    let unified: Vec<(Folder, String)> = client
+        .unified_unseen()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(folder, email)| (folder, email.subject.original))
+        .collect();
+    assert_eq!(
+        unified,
+        vec![
+            (Folder::Inbox, "Inboxed".to_string()),
+            (Folder::custom("Tous les messages"), "Archived".to_string()),
+            (Folder::custom("Labels/Work"), "Labelled".to_string()),
+        ]
+    );
+
+    // The \Noselect parent is never selected.
+    let commands = server.commands();
+    let selected: Vec<&str> = commands
+        .iter()
+        .filter_map(|c| c.strip_prefix("SELECT "))
+        .map(|folder| folder.trim_matches('"'))
+        .collect();
+    assert!(selected.contains(&"Labels/Work"));
+    assert!(!selected.contains(&"Labels"));
+}
+
+#[tokio::test]
+async fn test_move_to_folder() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Move me",
+        "Moving to trash.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .folder("Trash")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+
+    // Move from INBOX to Trash.
+    let outcome = writer
+        .move_to_folder(1, &Folder::Inbox, &Folder::Trash)
+        .await
+        .unwrap();
+    assert_eq!(outcome, MoveOutcome::Moved);
+
+    // Verify: INBOX should be empty, Trash should have the email.
+    let client = client_for(&server);
+    let inbox = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert!(inbox.is_empty());
+
+    let trash = client.fetch_all(&Folder::Trash).await.unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].from.address, "alice@example.com");
+}
+
+#[tokio::test]
+async fn test_retried_archive_does_not_duplicate() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Half moved",
+        "The first attempt copied but did not clean up.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &raw)
+        .folder("Archive")
+        .email(1, true, &raw)
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let writer = writer_for(&server);
+
+    let outcome = writer.archive(1, &Folder::Inbox).await.unwrap();
+    assert_eq!(outcome, MoveOutcome::AlreadyInDestination);
+    assert!(!server.commands().iter().any(|c| c.starts_with("UID COPY")));
+
+    let client = client_for(&server);
+    assert!(client.fetch_all(&Folder::Inbox).await.unwrap().is_empty());
+    let archive = client.fetch_all(&Folder::Archive).await.unwrap();
+    assert_eq!(archive.len(), 1);
+    assert_eq!(archive[0].subject.original, "Half moved");
+}
+
+#[tokio::test]
+async fn test_fetch_uid_full() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Flagged",
+        "With flags.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email_with_flags(3, &["\\Answered", "Work"], &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let (email, flags) = client.fetch_uid_full(&Folder::Inbox, 3).await.unwrap();
+    assert_eq!(email.subject.original, "Flagged");
+    assert_eq!(
+        flags,
+        vec![Flag::Answered, Flag::Keyword("Work".to_string())]
+    );
+
+    // BODY.PEEK leaves the message unread.
+    let (_, flags) = client.fetch_uid_full(&Folder::Inbox, 3).await.unwrap();
+    assert!(!flags.contains(&Flag::Seen));
+
+    let err = client.fetch_uid_full(&Folder::Inbox, 99).await.unwrap_err();
+    assert!(matches!(err, Error::MessageNotFound { uid: 99, .. }));
+}
+
+#[tokio::test]
+async fn test_flag_delta_since() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Hello",
+        "Body",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, true, &raw)
+        .email(3, false, &raw)
+        .folder("Archive")
+        .folder("Empty")
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let writer = writer_for(&server);
+
+    let before = writer.flag_snapshot(&Folder::Inbox).await.unwrap();
+    assert_eq!(before.len(), 3);
+    assert_eq!(before[&2], vec![Flag::Seen]);
+
+    // Another device reads 1, marks 2 unread, stars 3, and moves 3
+    // away after it was starred.
+    writer
+        .add_flag(1, &Folder::Inbox, &Flag::Seen)
+        .await
+        .unwrap();
+    writer
+        .remove_flag(2, &Folder::Inbox, &Flag::Seen)
+        .await
+        .unwrap();
+    writer
+        .add_flag(3, &Folder::Inbox, &Flag::Flagged)
+        .await
+        .unwrap();
+    writer
+        .move_to_folder(3, &Folder::Inbox, &Folder::from("Archive"))
+        .await
+        .unwrap();
+    writer.append(&Folder::Inbox, &raw, &[]).await.unwrap();
+
+    let commands_before = server.commands().len();
+    let delta = writer
+        .flag_delta_since(&Folder::Inbox, &before)
+        .await
+        .unwrap();
+    assert_eq!(delta.newly_seen, vec![1]);
+    assert_eq!(delta.newly_unseen, vec![2]);
+    assert_eq!(delta.changed, vec![1, 2]);
+    assert_eq!(delta.added, vec![4]);
+    assert_eq!(delta.removed, vec![3]);
+
+    // One FLAGS fetch for the whole folder.
+    let fetches: Vec<String> = server.commands()[commands_before..]
+        .iter()
+        .filter(|c| c.starts_with("UID FETCH"))
+        .cloned()
+        .collect();
+    assert_eq!(fetches, vec!["UID FETCH 1:* (FLAGS)"]);
+
+    let again = writer
+        .flag_delta_since(&Folder::Inbox, &delta.snapshot)
+        .await
+        .unwrap();
+    assert!(again.is_empty());
+
+    let empty = writer.flag_snapshot(&Folder::from("Empty")).await.unwrap();
+    assert!(empty.is_empty());
+}
+
+#[tokio::test]
+async fn test_watch_renews_idle() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Hello",
+        "Body",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client: ProtonClient =
+        ProtonClient::new(config_for(&server).with_idle_renew(Duration::from_millis(30)));
+
+    let mut events = client.watch(&Folder::Inbox);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // IDLE was re-issued on the same connection.
+    let idles = server.commands().iter().filter(|c| *c == "IDLE").count();
+    assert!(idles >= 3, "only {idles} IDLE commands");
+    assert_eq!(server.select_count(), 1);
+
+    writer_for(&server)
+        .append(&Folder::Inbox, &raw, &[])
+        .await
+        .unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(event, WatchEvent::Exists(2));
+
+    // Dropping the receiver ends the watch and its connection.
+    drop(events);
+    assert!(server.wait_until_idle(Duration::from_secs(5)).await);
+}
+
+#[tokio::test]
+async fn test_watch_reconnects_after_idle_cap() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Hello",
+        "Body",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .idle_limit(Duration::from_millis(50))
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let mut events = client.watch(&Folder::Inbox);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(server.select_count() >= 2);
+
+    // Delivery is still reported, whether it lands during an IDLE or
+    // while reconnecting.
+    writer_for(&server)
+        .append(&Folder::Inbox, &raw, &[])
+        .await
+        .unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(event, WatchEvent::Exists(2));
+}
+
+#[tokio::test]
+async fn test_watch_reports_missing_folder() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").build();
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let mut events = client.watch(&Folder::from("Nope"));
+    let first = tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(first.is_err());
+
+    // The watch keeps retrying, and stops once the receiver is gone.
+    let second = tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(second.is_err());
+    drop(events);
+    assert!(server.wait_until_idle(Duration::from_secs(5)).await);
+}
+
+#[tokio::test]
+async fn test_watch_survives_refused_reconnects() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Hello",
+        "Body",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .idle_limit(Duration::from_millis(50))
+        .refuse_reconnects(2)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    // The first IDLE is cut off and the next two connections are
+    // refused; the watch backs off and gets through on the third.
+    let mut events = client.watch(&Folder::Inbox);
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert!(server.select_count() >= 2);
+    assert!(events.try_recv().is_err());
+
+    writer_for(&server)
+        .append(&Folder::Inbox, &raw, &[])
+        .await
+        .unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(event, WatchEvent::Exists(2));
+}
+
+#[tokio::test]
+async fn test_fetch_body_text() {
+    let alternative = b"From: alice@example.com\r\n\
+        To: bob@example.com\r\n\
+        Subject: Both\r\n\
+        Date: Mon, 01 Jan 2024 12:00:00 +0000\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/alternative; boundary=\"ALT\"\r\n\
+        \r\n\
+        --ALT\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        Caf=C3=A9 tonight?\r\n\
+        --ALT\r\n\
+        Content-Type: text/html; charset=utf-8\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        PHA+Q2Fmw6kgdG9uaWdodD88L3A+\r\n\
+        --ALT--\r\n";
+    let plain = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Plain",
+        "Just text.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, alternative)
+        .email(2, false, &plain)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let html = client
+        .fetch_body_text(&Folder::Inbox, 1, BodyPreference::Html)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(html.html);
+    assert_eq!(html.text, "<p>Café tonight?</p>");
+
+    let text = client
+        .fetch_body_text(&Folder::Inbox, 1, BodyPreference::Plain)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!text.html);
+    assert_eq!(text.text, "Café tonight?");
+
+    // No HTML alternative: fall back to the plain text.
+    let fallback = client
+        .fetch_body_text(&Folder::Inbox, 2, BodyPreference::Html)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!fallback.html);
+    assert!(fallback.text.contains("Just text."));
+
+    let err = client
+        .fetch_body_text(&Folder::Inbox, 9, BodyPreference::Plain)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::MessageNotFound { uid: 9, .. }));
+}
+
+#[tokio::test]
+async fn test_fetch_body_section_resumable() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Large",
+        "A body that was cut off halfway.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    // Resume the whole message after a dropped download.
+    let mut buf = raw[..40].to_vec();
+    let appended = client
+        .fetch_body_section_resumable(&Folder::Inbox, 1, "", buf.len(), &mut buf)
+        .await
+        .unwrap();
+    assert_eq!(appended, raw.len() - 40);
+    assert_eq!(buf, raw);
+
+    // Nothing left to fetch.
+    let appended = client
+        .fetch_body_section_resumable(&Folder::Inbox, 1, "", buf.len(), &mut buf)
+        .await
+        .unwrap();
+    assert_eq!(appended, 0);
+    assert_eq!(buf, raw);
+
+    // A named section resumes the same way.
+    let mut text = b"A body that ".to_vec();
+    client
+        .fetch_body_section_resumable(&Folder::Inbox, 1, "TEXT", text.len(), &mut text)
+        .await
+        .unwrap();
+    assert_eq!(text, b"A body that was cut off halfway.");
+
+    let err = client
+        .fetch_body_section_resumable(&Folder::Inbox, 9, "", 0, &mut Vec::new())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::MessageNotFound { uid: 9, .. }));
+
+    // The message was only peeked at.
+    let flags = client
+        .fetch_items(&Folder::Inbox, &[1], "(FLAGS)")
+        .await
+        .unwrap();
+    assert!(flags[0].flags.is_empty());
+}
+
+#[tokio::test]
+async fn test_move_uids() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Batch",
+        "Moving in bulk.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, false, &raw)
+        .email(3, false, &raw)
+        .folder("Trash")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+
+    writer
+        .move_uids(&[1, 3], &Folder::Inbox, &Folder::Trash)
+        .await
+        .unwrap();
+    writer
+        .move_uids(&[], &Folder::Inbox, &Folder::Trash)
+        .await
+        .unwrap();
+
+    let client = client_for(&server);
+    let inbox = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0].uid, 2);
+    assert_eq!(client.fetch_all(&Folder::Trash).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_move_uids_consecutive_run() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Run",
+        "Sent as a range.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, false, &raw)
+        .email(3, false, &raw)
+        .email(4, false, &raw)
+        .folder("Trash")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    writer_for(&server)
+        .move_uids(&[3, 1, 2], &Folder::Inbox, &Folder::Trash)
+        .await
+        .unwrap();
+
+    let client = client_for(&server);
+    let inbox = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0].uid, 4);
+    assert_eq!(client.fetch_all(&Folder::Trash).await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_move_matching() {
+    let news = make_raw_email(
+        "news@example.com",
+        "bob@example.com",
+        "Newsletter",
+        "Read all about it.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let personal = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Lunch",
+        "Noon?",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &news)
+        .email(2, false, &personal)
+        .email(3, false, &news)
+        .folder("Archive")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+
+    let moved = writer
+        .move_matching(&Folder::Inbox, "SUBJECT \"Newsletter\"", &Folder::Archive)
+        .await
+        .unwrap();
+    assert_eq!(moved, 2);
+
+    let moved = writer
+        .move_matching(&Folder::Inbox, "SUBJECT \"Newsletter\"", &Folder::Archive)
+        .await
+        .unwrap();
+    assert_eq!(moved, 0);
+
+    let client = client_for(&server);
+    let inbox = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0].uid, 2);
+    assert_eq!(client.fetch_all(&Folder::Archive).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_batched_commands_are_recorded() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Batch",
+        "One command for all.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &raw)
+        .email(2, true, &raw)
+        .email(3, true, &raw)
+        .folder("Trash")
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let writer = writer_for(&server);
+
+    writer.unmark_all_read(&Folder::Inbox).await.unwrap();
+    writer
+        .move_uids(&[3, 2, 1], &Folder::Inbox, &Folder::Trash)
+        .await
+        .unwrap();
+    assert_eq!(writer.fetch_all(&Folder::Trash).await.unwrap().len(), 3);
+
+    let commands = server.commands();
+    assert_eq!(commands.first().map(String::as_str), Some("STARTTLS"));
+    let sent = |prefix: &str| commands.iter().filter(|c| c.starts_with(prefix)).count();
+    assert_eq!(sent("UID STORE 1:3 -FLAGS (\\Seen)"), 1);
+    assert_eq!(sent("UID COPY 1:3 "), 1);
+    assert_eq!(sent("UID STORE 1:3 +FLAGS (\\Deleted)"), 1);
+    assert_eq!(sent("UID STORE"), 2);
+    assert_eq!(sent("UID FETCH"), 1, "{commands:#?}");
+
+    // A server started without recording keeps nothing.
+    let quiet = FakeImapServer::start(MailboxBuilder::new().folder("INBOX").build()).await;
+    client_for(&quiet).list_folders().await.unwrap();
+    assert!(quiet.commands().is_empty());
+}
+
+#[tokio::test]
+async fn test_cached_fetch_uid_skips_server() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Cached",
+        "Read twice.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &raw)
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client: ProtonClient = ProtonClient::new(config_for(&server)).with_cache(8);
+
+    let first = client.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    let sent = server.commands().len();
+    assert!(sent > 0);
+
+    let second = client.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    assert_eq!(server.commands().len(), sent);
+    assert_eq!(second.subject.original, first.subject.original);
+
+    // Without a cache every call goes to the server.
+    let uncached = client_for(&server);
+    uncached.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    assert!(server.commands().len() > sent);
+}
+
+#[tokio::test]
+async fn test_moved_message_leaves_cache() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Cached",
+        "Moved after reading.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &raw)
+        .folder("Archive")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client: ProtonClient<ReadWrite> = ProtonClient::new(config_for(&server)).with_cache(8);
+
+    client.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    client
+        .move_to_folder(1, &Folder::Inbox, &Folder::Archive)
+        .await
+        .unwrap();
+
+    let err = client.fetch_uid(&Folder::Inbox, 1).await.unwrap_err();
+    assert!(matches!(err, Error::MessageNotFound { uid: 1, .. }));
+}
+
+#[tokio::test]
+async fn test_dropping_client_aborts_prefetch() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Listed",
+        "Never opened.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .delay_greeting(Duration::from_millis(100))
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client: ProtonClient = ProtonClient::new(config_for(&server))
+        .with_cache(8)
+        .with_prefetch(1);
+
+    client.fetch_headers(&Folder::Inbox, &[1]).await.unwrap();
+    // The prefetch is still waiting for its greeting.
+    drop(client);
+
+    assert!(server.wait_until_idle(Duration::from_secs(5)).await);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(
+        !server
+            .commands()
+            .iter()
+            .any(|c| c.contains("(BODY.PEEK[])"))
+    );
+}
+
+#[tokio::test]
+async fn test_fetch_headers_prefetches_bodies() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Listed",
+        "Opened later.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &raw)
+        .email(2, false, &raw)
+        .email(3, false, &raw)
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client: ProtonClient = ProtonClient::new(config_for(&server))
+        .with_cache(8)
+        .with_prefetch(2);
+
+    let listed = client
+        .fetch_headers(&Folder::Inbox, &[1, 2, 3])
+        .await
+        .unwrap();
+    assert_eq!(listed.len(), 3);
+    assert!(
+        listed
+            .iter()
+            .all(|raw| raw.header.is_some() && raw.body.is_none())
+    );
+
+    // Wait for the background prefetch to fetch and disconnect.
+    let prefetched = |commands: &[String]| commands.iter().any(|c| c.contains("(BODY.PEEK[])"));
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !prefetched(&server.commands()) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(server.wait_until_idle(Duration::from_secs(5)).await);
+
+    // The two newest bodies are served from the cache; UID 1 is not.
+    let sent = server.commands().len();
+    client.fetch_uid(&Folder::Inbox, 3).await.unwrap();
+    client.fetch_uid(&Folder::Inbox, 2).await.unwrap();
+    assert_eq!(server.commands().len(), sent);
+    client.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    assert!(server.commands().len() > sent);
+
+    // Everything requested is already cached.
+    assert_eq!(
+        client.prefetch(&Folder::Inbox, &[1, 2, 3]).await.unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_archive() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Archive me",
+        "Please archive.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .folder("Archive")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+
+    writer.archive(1, &Folder::Inbox).await.unwrap();
+
+    let client = client_for(&server);
+    let inbox = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert!(inbox.is_empty());
+
+    let archive = client.fetch_all(&Folder::Archive).await.unwrap();
+    assert_eq!(archive.len(), 1);
+}
+
+#[tokio::test]
+async fn test_unmark_all_read() {
+    let email1 = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "First read",
+        "Was read.",
+        "Mon, 01 Jan 2024 10:00:00 +0000",
+    );
+    let email2 = make_raw_email(
+        "charlie@example.com",
+        "bob@example.com",
+        "Second read",
+        "Also read.",
+        "Mon, 01 Jan 2024 11:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &email1) // seen
+        .email(2, true, &email2) // seen
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+
+    // Before: no unseen emails.
+    let client = client_for(&server);
+    let unseen = client.fetch_unseen(&Folder::Inbox).await.unwrap();
+    assert!(unseen.is_empty());
 
     // Unmark all as read.
     writer.unmark_all_read(&Folder::Inbox).await.unwrap();
 
-    // After: both emails should be unseen.
-    let unseen = client.fetch_unseen(&Folder::Inbox).await.unwrap();
-    assert_eq!(unseen.len(), 2);
+    // After: both emails should be unseen.
+    let unseen = client.fetch_unseen(&Folder::Inbox).await.unwrap();
+    assert_eq!(unseen.len(), 2);
+}
+
+#[tokio::test]
+async fn test_labels() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Labelled",
+        "Filed under Work.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email_with_flags(1, &["\\Seen", "Work", "$Junk"], &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+    let writer = writer_for(&server);
+
+    let labels = client.labels(&Folder::Inbox, 1).await.unwrap();
+    assert_eq!(labels, vec!["Work".to_string()]);
+
+    writer.add_label(1, &Folder::Inbox, "Urgent").await.unwrap();
+    writer
+        .remove_label(1, &Folder::Inbox, "Work")
+        .await
+        .unwrap();
+
+    let labels = client.labels(&Folder::Inbox, 1).await.unwrap();
+    assert_eq!(labels, vec!["Urgent".to_string()]);
+
+    let err = writer
+        .add_label(1, &Folder::Inbox, "Two words")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidLabel(_)));
+}
+
+#[tokio::test]
+async fn test_connect_timeout() {
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .delay_greeting(Duration::from_secs(5))
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let config = config_for(&server).with_connect_timeout(Duration::from_millis(200));
+    let client: ProtonClient = ProtonClient::new(config);
+
+    let err = client.list_folders().await.unwrap_err();
+    assert!(matches!(err, Error::Timeout(_)), "got {err:?}");
+}
+
+#[tokio::test]
+async fn test_connection_closed_before_greeting() {
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .drop_before_greeting()
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let err = client.list_folders().await.unwrap_err();
+    assert!(matches!(err, Error::ConnectionClosed), "got {err:?}");
+}
+
+#[tokio::test]
+async fn test_starttls_refused() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").no_starttls().build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client = client_for(&server);
+
+    let err = client.list_folders().await.unwrap_err();
+    match err {
+        Error::Tls(msg) => assert!(msg.contains("consider ImplicitTls mode"), "got {msg}"),
+        other => panic!("expected Error::Tls, got {other:?}"),
+    }
+    assert_eq!(server.commands(), ["STARTTLS", "CAPABILITY"]);
+}
+
+#[tokio::test]
+async fn test_implicit_tls() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").implicit_tls().build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let config = config_for(&server).with_connection_security(ConnectionSecurity::ImplicitTls);
+    let client: ProtonClient = ProtonClient::new(config);
+
+    let folders = client.list_folders().await.unwrap();
+    assert_eq!(folders.len(), 1);
+}
+
+#[tokio::test]
+async fn test_tls_mode_mismatch() {
+    let expect_mismatch = |err: Error| match err {
+        Error::Tls(msg) => assert!(msg.starts_with("TLS mode mismatch"), "got {msg}"),
+        other => panic!("expected Error::Tls, got {other:?}"),
+    };
+
+    // Implicit TLS against a port that greets in plaintext.
+    let server = FakeImapServer::start(MailboxBuilder::new().folder("INBOX").build()).await;
+    let config = config_for(&server).with_connection_security(ConnectionSecurity::ImplicitTls);
+    let client: ProtonClient = ProtonClient::new(config);
+    expect_mismatch(client.list_folders().await.unwrap_err());
+
+    // STARTTLS against an IMAPS port, which gives up waiting for a
+    // handshake.
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .implicit_tls()
+        .handshake_timeout(Duration::from_millis(50))
+        .build();
+    let server = FakeImapServer::start(mailbox).await;
+    expect_mismatch(client_for(&server).list_folders().await.unwrap_err());
+}
+
+#[tokio::test]
+async fn test_slow_greeting_within_timeout() {
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .delay_greeting(Duration::from_millis(100))
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let folders = client.list_folders().await.unwrap();
+    assert_eq!(folders.len(), 1);
+}
+
+#[tokio::test]
+async fn test_append_draft() {
+    let raw = make_raw_email(
+        "bob@example.com",
+        "alice@example.com",
+        "Work in progress",
+        "Not sent yet.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .folder("Drafts")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+
+    writer.append_draft(&Folder::Drafts, &raw).await.unwrap();
+
+    let client = client_for(&server);
+    let fetched = client
+        .fetch_items(&Folder::Drafts, &[1], "(FLAGS BODY.PEEK[])")
+        .await
+        .unwrap();
+    assert_eq!(fetched.len(), 1);
+    assert!(fetched[0].flags.contains(&Flag::Draft));
+    assert!(fetched[0].flags.contains(&Flag::Seen));
+    assert_eq!(fetched[0].body.as_deref(), Some(raw.as_slice()));
+
+    let email = client.fetch_uid(&Folder::Drafts, 1).await.unwrap();
+    assert_eq!(email.subject.original, "Work in progress");
+}
+
+#[tokio::test]
+async fn test_fetch_drafts() {
+    let raw = |subject: &str| {
+        make_raw_email(
+            "bob@example.com",
+            "alice@example.com",
+            subject,
+            "Unfinished.",
+            "Mon, 01 Jan 2024 12:00:00 +0000",
+        )
+    };
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw("Received"))
+        .email_with_flags(2, &["\\Draft"], &raw("Stray draft"))
+        .folder("Drafts")
+        .email_with_flags(1, &["\\Draft", "\\Seen"], &raw("Reply"))
+        .email(2, true, &raw("Sent copy"))
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let inbox = client.fetch_drafts(&Folder::Inbox).await.unwrap();
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0].subject.original, "Stray draft");
+
+    let drafts = client.all_drafts().await.unwrap();
+    assert_eq!(drafts.len(), 1);
+    assert_eq!(drafts[0].subject.original, "Reply");
+}
+
+#[tokio::test]
+async fn test_append_many() {
+    let raw = |subject: &str| {
+        make_raw_email(
+            "bob@example.com",
+            "alice@example.com",
+            subject,
+            "Imported.",
+            "Mon, 01 Jan 2024 12:00:00 +0000",
+        )
+    };
+    let messages = vec![
+        (raw("One"), vec![Flag::Seen]),
+        (raw("Two"), vec![]),
+        (raw("Three"), vec![Flag::Flagged, Flag::Seen]),
+    ];
+    let appends = |server: &FakeImapServer| {
+        server
+            .commands()
+            .iter()
+            .filter(|c| c.starts_with("APPEND"))
+            .count()
+    };
+
+    // With MULTIAPPEND: one command, UIDs from APPENDUID.
+    let mailbox = MailboxBuilder::new()
+        .folder("Import")
+        .email(7, false, &raw("Existing"))
+        .literal_plus()
+        .multiappend()
+        .build();
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let writer = writer_for(&server);
+    let import = Folder::from("Import");
+
+    let uids = writer.append_many(&import, &messages).await.unwrap();
+    assert_eq!(uids, vec![Some(8), Some(9), Some(10)]);
+    assert_eq!(appends(&server), 1);
+
+    let client = client_for(&server);
+    let fetched = client
+        .fetch_items(&import, &[8, 9, 10], "(FLAGS BODY.PEEK[])")
+        .await
+        .unwrap();
+    assert_eq!(fetched.len(), 3);
+    assert_eq!(fetched[0].flags, vec![Flag::Seen]);
+    assert!(fetched[1].flags.is_empty());
+    assert!(fetched[2].flags.contains(&Flag::Flagged));
+    assert_eq!(fetched[1].body.as_deref(), Some(messages[1].0.as_slice()));
+
+    // 8-bit messages still go in one APPEND, without LITERAL+ as
+    // synchronizing literals.
+    let latin1 = |subject: &[u8]| {
+        let mut raw = b"From: bob@example.com\r\nSubject: ".to_vec();
+        raw.extend_from_slice(subject);
+        raw.extend_from_slice(b"\r\n\r\nCaf\xe9.\r\n");
+        raw
+    };
+    let eight_bit = vec![(latin1(b"Caf\xe9"), vec![]), (latin1(b"Cr\xe8me"), vec![])];
+    assert!(std::str::from_utf8(&eight_bit[0].0).is_err());
+    let mailbox = MailboxBuilder::new().folder("Import").multiappend().build();
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let writer = writer_for(&server);
+
+    let uids = writer.append_many(&import, &eight_bit).await.unwrap();
+    assert_eq!(uids, vec![Some(1), Some(2)]);
+    assert_eq!(appends(&server), 1);
+    assert_eq!(server.continuation_count(), 2);
+    let fetched = client_for(&server)
+        .fetch_items(&import, &[2], "(BODY.PEEK[])")
+        .await
+        .unwrap();
+    assert_eq!(fetched[0].body.as_deref(), Some(eight_bit[1].0.as_slice()));
+
+    // Without it: one APPEND per message, UIDs unknown.
+    let mailbox = MailboxBuilder::new().folder("Import").build();
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let writer = writer_for(&server);
+
+    let uids = writer.append_many(&import, &messages).await.unwrap();
+    assert_eq!(uids, vec![None, None, None]);
+    assert_eq!(appends(&server), 3);
+    assert_eq!(
+        client_for(&server).fetch_all(&import).await.unwrap().len(),
+        3
+    );
+
+    assert!(writer.append_many(&import, &[]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_append_to_missing_folder() {
+    let raw = make_raw_email(
+        "bob@example.com",
+        "alice@example.com",
+        "Nowhere",
+        "No folder.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new().folder("INBOX").build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+
+    let err = writer.append(&Folder::Drafts, &raw, &[]).await.unwrap_err();
+    assert_eq!(err.response_code(), Some(&ImapResponseCode::TryCreate));
+}
+
+#[tokio::test]
+async fn test_move_to_missing_folder_reports_trycreate() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Stays put",
+        "No destination.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+
+    let archive = Folder::from("Archive/2024");
+    let err = writer
+        .move_to_folder(1, &Folder::Inbox, &archive)
+        .await
+        .unwrap_err();
+    match err {
+        Error::ImapCoded { code, text } => {
+            assert_eq!(code, ImapResponseCode::TryCreate);
+            assert!(text.starts_with("Copy failed"), "got {text}");
+        }
+        other => panic!("expected Error::ImapCoded, got {other:?}"),
+    }
+
+    let client = client_for(&server);
+    assert_eq!(client.fetch_uid(&Folder::Inbox, 1).await.unwrap().uid, 1);
+}
+
+#[tokio::test]
+async fn test_missing_folder_behavior() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let missing = Folder::from("Not/Yet/Created");
+
+    let err = client_for(&server).fetch_all(&missing).await.unwrap_err();
+    assert_eq!(err.response_code(), Some(&ImapResponseCode::NonExistent));
+
+    let lenient: ProtonClient = ProtonClient::new(
+        config_for(&server).with_on_missing_folder(MissingFolderBehavior::EmptyResult),
+    );
+    assert!(lenient.fetch_all(&missing).await.unwrap().is_empty());
+    assert!(lenient.flag_snapshot(&missing).await.unwrap().is_empty());
+    assert!(lenient.fetch_uid(&missing, 1).await.is_err());
+}
+
+#[tokio::test]
+async fn test_move_to_folder_creating() {
+    let raw = |subject| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "New label.",
+            "Mon, 01 Jan 2024 12:00:00 +0000",
+        )
+    };
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw("File me"))
+        .email(2, false, &raw("File me too"))
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let writer = writer_for(&server);
+    let receipts = Folder::from("Labels/Receipts");
+
+    let first = writer
+        .move_to_folder_creating(1, &Folder::Inbox, &receipts)
+        .await
+        .unwrap();
+    let second = writer
+        .move_to_folder_creating(2, &Folder::Inbox, &receipts)
+        .await
+        .unwrap();
+    assert_eq!(first, MoveOutcome::Moved);
+    assert_eq!(second, MoveOutcome::Moved);
+
+    let creates = server
+        .commands()
+        .iter()
+        .filter(|c| c.starts_with("CREATE"))
+        .count();
+    assert_eq!(creates, 1);
+
+    let client = client_for(&server);
+    let moved = client.fetch_all(&receipts).await.unwrap();
+    assert_eq!(moved.len(), 2);
+    assert!(client.fetch_all(&Folder::Inbox).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_metrics_callback() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Measured",
+        "Count my bytes.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    let config = config_for(&server).with_metrics(Arc::new(move |event: MetricEvent| {
+        sink.lock().unwrap().push(event);
+    }));
+    let client: ProtonClient = ProtonClient::new(config);
+
+    client.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    client.fetch_uid(&Folder::Inbox, 999).await.unwrap_err();
+    client.list_folders().await.unwrap();
+
+    let events = events.lock().unwrap().clone();
+    assert_eq!(events.len(), 3);
+
+    assert_eq!(events[0].op, "fetch_uid");
+    assert_eq!(events[0].folder, Some(Folder::Inbox));
+    assert_eq!(events[0].bytes, raw.len() as u64);
+    assert!(events[0].success);
+
+    assert_eq!(events[1].op, "fetch_uid");
+    assert_eq!(events[1].bytes, 0);
+    assert!(!events[1].success);
+
+    assert_eq!(events[2].op, "list_folders");
+    assert_eq!(events[2].folder, None);
+    assert!(events[2].success);
+}
+
+#[tokio::test]
+async fn test_metrics_name_the_called_method() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Measured",
+        "Which method?",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .build();
+    let server = FakeImapServer::start(mailbox).await;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    let config = config_for(&server).with_metrics(Arc::new(move |event: MetricEvent| {
+        sink.lock().unwrap().push(event);
+    }));
+    let client: ProtonClient = ProtonClient::new(config);
+
+    let inbox = &Folder::Inbox;
+    client.fetch_unseen(inbox).await.unwrap();
+    client.fetch_all(inbox).await.unwrap();
+    client.fetch_starred(inbox).await.unwrap();
+    client.fetch_with_header(inbox, "From", None).await.unwrap();
+    let jan = |d| chrono::NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+    client
+        .fetch_date_range(inbox, jan(1), jan(2))
+        .await
+        .unwrap();
+    client.search(inbox, "ALL").await.unwrap();
+
+    let ops: Vec<&str> = events.lock().unwrap().iter().map(|e| e.op).collect();
+    assert_eq!(
+        ops,
+        [
+            "fetch_unseen",
+            "fetch_all",
+            "fetch_starred",
+            "fetch_with_header",
+            "fetch_date_range",
+            "search"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_metrics_report_connect_timing() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&events);
+    let metrics = Arc::new(move |event: MetricEvent| {
+        sink.lock().unwrap().push(event);
+    });
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .delay_greeting(Duration::from_millis(100))
+        .build();
+    let server = FakeImapServer::start(mailbox).await;
+    let client: ProtonClient = ProtonClient::new(config_for(&server).with_metrics(metrics.clone()));
+    client.list_folders().await.unwrap();
+
+    let mailbox = MailboxBuilder::new().folder("INBOX").implicit_tls().build();
+    let server = FakeImapServer::start(mailbox).await;
+    let config = config_for(&server)
+        .with_connection_security(ConnectionSecurity::ImplicitTls)
+        .with_metrics(metrics);
+    let client: ProtonClient = ProtonClient::new(config);
+    client.list_folders().await.unwrap();
+
+    let events = events.lock().unwrap().clone();
+    assert_eq!(events.len(), 2);
+
+    // The greeting delay counts towards the STARTTLS phase.
+    let starttls = events[0].connect.unwrap();
+    assert!(starttls.starttls.unwrap() >= Duration::from_millis(100));
+
+    let implicit = events[1].connect.unwrap();
+    assert_eq!(implicit.starttls, None);
+    assert!(implicit.tls_handshake > Duration::ZERO);
+}
+
+#[tokio::test]
+async fn test_session_skips_reselect() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Session",
+        "One connection.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, false, &raw)
+        .folder("Archive")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+    let mut session = writer.session().await.unwrap();
+
+    session.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    session
+        .add_flag(1, &Folder::Inbox, &Flag::Seen)
+        .await
+        .unwrap();
+    assert_eq!(server.select_count(), 1);
+
+    // Forcing re-selects even though INBOX is current.
+    session.select(&Folder::Inbox, true).await.unwrap();
+    assert_eq!(server.select_count(), 2);
+
+    // A different folder always selects.
+    session.search(&Folder::Archive, "ALL").await.unwrap();
+    assert_eq!(server.select_count(), 3);
+
+    session.logout().await.unwrap();
+
+    let unseen = client_for(&server)
+        .fetch_unseen(&Folder::Inbox)
+        .await
+        .unwrap();
+    assert_eq!(unseen.len(), 1);
+    assert_eq!(unseen[0].uid, 2);
+}
+
+#[tokio::test]
+async fn test_message_dates() {
+    let day1_morning = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Morning",
+        "First.",
+        "Mon, 01 Jan 2024 08:00:00 +0000",
+    );
+    let day1_evening = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Evening",
+        "Second.",
+        "Mon, 01 Jan 2024 20:00:00 +0000",
+    );
+    let day3 = make_raw_email(
+        "carol@example.com",
+        "bob@example.com",
+        "Later",
+        "Third.",
+        "Wed, 03 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &day1_morning)
+        .email(2, true, &day1_evening)
+        .email(3, false, &day3)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let dates = client.message_dates(&Folder::Inbox).await.unwrap();
+    let jan = |d| chrono::NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+    assert_eq!(dates, vec![jan(1), jan(1), jan(3)]);
+}
+
+#[tokio::test]
+async fn test_session_current_folder() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Sidebar",
+        "Highlight me.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .folder("Archive")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+    let mut session = client.session().await.unwrap();
+
+    assert_eq!(session.current_folder(), None);
+    let folders = session.list_folders().await.unwrap();
+    assert!(folders.iter().all(|f| !f.selected));
+
+    session.search(&Folder::Archive, "ALL").await.unwrap();
+    assert_eq!(session.current_folder(), Some(&Folder::Archive));
+
+    let folders = session.list_folders().await.unwrap();
+    let selected: Vec<&str> = folders
+        .iter()
+        .filter(|f| f.selected)
+        .map(|f| f.name.as_str())
+        .collect();
+    assert_eq!(selected, vec!["Archive"]);
+
+    // A failed SELECT leaves nothing selected.
+    assert!(
+        session
+            .select(&Folder::from("Missing"), false)
+            .await
+            .is_err()
+    );
+    assert_eq!(session.current_folder(), None);
+
+    session.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    assert_eq!(session.current_folder(), Some(&Folder::Inbox));
+
+    session.unselect().await.unwrap();
+    assert_eq!(session.current_folder(), None);
+    // Unselecting again is a no-op.
+    session.unselect().await.unwrap();
+
+    // The next operation has to SELECT again.
+    let selects = server.select_count();
+    session.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    assert_eq!(server.select_count(), selects + 1);
+
+    session.logout().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_session_append_uses_literal_plus() {
+    let raw = make_raw_email(
+        "bob@example.com",
+        "alice@example.com",
+        "Imported",
+        "Bulk import.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .folder("Drafts")
+        .literal_plus()
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+    let mut session = writer.session().await.unwrap();
+
+    assert!(session.has_capability("LITERAL+").await.unwrap());
+    session.append(&Folder::Inbox, &raw, &[]).await.unwrap();
+    session.append_draft(&Folder::Drafts, &raw).await.unwrap();
+    session.logout().await.unwrap();
+
+    assert_eq!(server.continuation_count(), 0);
+
+    let client = client_for(&server);
+    let inbox = client.fetch_uid(&Folder::Inbox, 1).await.unwrap();
+    assert_eq!(inbox.subject.original, "Imported");
+    let drafts = client
+        .fetch_items(&Folder::Drafts, &[1], "(FLAGS)")
+        .await
+        .unwrap();
+    assert!(drafts[0].flags.contains(&Flag::Draft));
+}
+
+#[tokio::test]
+async fn test_session_append_without_literal_plus() {
+    let raw = make_raw_email(
+        "bob@example.com",
+        "alice@example.com",
+        "Imported",
+        "Bulk import.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new().folder("INBOX").build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+    let mut session = writer.session().await.unwrap();
+
+    assert!(!session.has_capability("LITERAL+").await.unwrap());
+    session.append(&Folder::Inbox, &raw, &[]).await.unwrap();
+    session.logout().await.unwrap();
+
+    assert_eq!(server.continuation_count(), 1);
+}
+
+#[tokio::test]
+async fn test_special_use_folders() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Archive me",
+        "Please archive.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .folder("Corbeille")
+        .special_use("\\Trash")
+        .folder("Pourriel")
+        .special_use("\\Junk")
+        .folder("Archives")
+        .special_use("\\Archive")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+
+    assert_eq!(
+        writer.trash_folder().await.unwrap(),
+        Folder::custom("Corbeille")
+    );
+    assert_eq!(
+        writer.junk_folder().await.unwrap(),
+        Folder::custom("Pourriel")
+    );
+    // Not advertised: falls back to the Proton default.
+    assert_eq!(writer.sent_folder().await.unwrap(), Folder::Sent);
+
+    writer.archive(1, &Folder::Inbox).await.unwrap();
+
+    let client = client_for(&server);
+    let inbox = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert!(inbox.is_empty());
+
+    let archive = client.fetch_all(&Folder::custom("Archives")).await.unwrap();
+    assert_eq!(archive.len(), 1);
+}
+
+#[tokio::test]
+async fn test_mark_spam_and_not_spam() {
+    let raw = make_raw_email(
+        "spammer@example.com",
+        "bob@example.com",
+        "Cheap watches",
+        "Buy now.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .folder("Pourriel")
+        .special_use("\\Junk")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+    let client = client_for(&server);
+
+    writer.mark_spam(1, &Folder::Inbox).await.unwrap();
+
+    assert!(client.fetch_all(&Folder::Inbox).await.unwrap().is_empty());
+    let spam = client.fetch_all(&Folder::custom("Pourriel")).await.unwrap();
+    assert_eq!(spam.len(), 1);
+
+    writer.mark_not_spam(spam[0].uid).await.unwrap();
+
+    let inbox = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0].subject.original, "Cheap watches");
+    assert!(
+        client
+            .fetch_all(&Folder::custom("Pourriel"))
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_search_uids_chunked() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Bulk",
+        "One of many.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mut builder = MailboxBuilder::new().folder("INBOX");
+    for uid in 1..=5 {
+        builder = builder.email(uid, false, &raw);
+    }
+    let server = FakeImapServer::start(builder.build()).await;
+    let client = client_for(&server);
+
+    let chunks: Vec<Vec<u32>> = client
+        .search_uids_chunked(&Folder::Inbox, "ALL", 2)
+        .await
+        .unwrap()
+        .collect();
+    assert_eq!(chunks, vec![vec![5, 4], vec![3, 2], vec![1]]);
+
+    let first = client
+        .fetch_items(&Folder::Inbox, &chunks[0], "(FLAGS)")
+        .await
+        .unwrap();
+    assert_eq!(first.len(), 2);
+
+    let none = client
+        .search_uids_chunked(&Folder::Inbox, "UNSEEN", 0)
+        .await
+        .unwrap();
+    assert_eq!(none.count(), 5);
+}
+
+#[tokio::test]
+async fn test_max_message_size_skips_large_messages() {
+    let small = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Small",
+        "Short body.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let large = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Large",
+        &"x".repeat(4096),
+        "Tue, 02 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &small)
+        .email(2, false, &large)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client: ProtonClient = ProtonClient::new(config_for(&server).with_max_message_size(1024));
+
+    let emails = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].uid, 1);
+
+    let unseen = client.fetch_unseen(&Folder::Inbox).await.unwrap();
+    assert_eq!(unseen.len(), 1);
+
+    // Without a limit both are fetched.
+    let emails = client_for(&server).fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(emails.len(), 2);
+}
+
+#[tokio::test]
+async fn test_fetch_by_uids_reports_skipped() {
+    let small = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Small",
+        "Short body.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let large = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Large",
+        &"x".repeat(4096),
+        "Tue, 02 Jan 2024 12:00:00 +0000",
+    );
+    let broken = b"Subject: No sender\r\n\r\nBody.".to_vec();
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &small)
+        .email(2, false, &large)
+        .email(3, false, &broken)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client: ProtonClient = ProtonClient::new(config_for(&server).with_max_message_size(1024));
+
+    let batch = client
+        .fetch_by_uids(&Folder::Inbox, &[1, 2, 3, 4])
+        .await
+        .unwrap();
+    assert_eq!(batch.emails.len(), 1);
+    assert_eq!(batch.emails[0].uid, 1);
+
+    let mut failed = batch.failed;
+    failed.sort_by_key(|(uid, _)| *uid);
+    assert_eq!(failed.len(), 2);
+    assert!(matches!(
+        failed[0],
+        (
+            2,
+            Error::TooLarge {
+                uid: 2,
+                limit: 1024,
+                ..
+            }
+        )
+    ));
+    assert!(matches!(failed[1], (3, Error::Parse(_))));
+
+    let empty = client.fetch_by_uids(&Folder::Inbox, &[]).await.unwrap();
+    assert!(empty.emails.is_empty() && empty.failed.is_empty());
+}
+
+#[tokio::test]
+async fn test_fetch_with_interleaved_exists() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Hello",
+        "Body.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, true, &raw)
+        .interleave_exists()
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let emails = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(emails.len(), 2);
+
+    let email = client.fetch_uid(&Folder::Inbox, 2).await.unwrap();
+    assert_eq!(email.uid, 2);
+
+    let mut session = client.session().await.unwrap();
+    for _ in 0..3 {
+        let fetches = session
+            .fetch_items(&Folder::Inbox, &[1, 2], "(FLAGS)")
+            .await
+            .unwrap();
+        assert_eq!(fetches.len(), 2);
+    }
+    session.logout().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_search_in_range() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Window",
+        "Body",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, true, &raw)
+        .email(3, false, &raw)
+        .email(4, false, &raw)
+        .email(5, true, &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+    let uids = |emails: &[protonmail_client::Email]| {
+        let mut uids: Vec<u32> = emails.iter().map(|e| e.uid).collect();
+        uids.sort_unstable();
+        uids
+    };
+
+    let unseen = client
+        .search_in_range(&Folder::Inbox, 3, None, "UNSEEN")
+        .await
+        .unwrap();
+    assert_eq!(uids(&unseen), vec![3, 4]);
+
+    let window = client
+        .search_in_range(&Folder::Inbox, 4, Some(2), "")
+        .await
+        .unwrap();
+    assert_eq!(uids(&window), vec![2, 3, 4]);
+
+    // `9:*` would match UID 5 on the wire; it is not in the range.
+    let beyond = client
+        .search_in_range(&Folder::Inbox, 9, None, "ALL")
+        .await
+        .unwrap();
+    assert!(beyond.is_empty());
+}
+
+#[tokio::test]
+async fn test_search_follow_new() {
+    let raw = make_raw_email(
+        "a@b.com",
+        "c@d.com",
+        "Old",
+        "Body",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let late = make_raw_email(
+        "a@b.com",
+        "c@d.com",
+        "Late",
+        "Body",
+        "Tue, 02 Jan 2024 12:00:00 +0000",
+    );
+    let mailbox = || {
+        MailboxBuilder::new()
+            .folder("INBOX")
+            .email(1, false, &raw)
+            .email(2, true, &raw)
+            .deliver_on_fetch(&late)
+            .build()
+    };
+
+    // By default the delivery is only logged.
+    let server = FakeImapServer::start(mailbox()).await;
+    let emails = client_for(&server).fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(emails.len(), 2);
+
+    let server = FakeImapServer::start(mailbox()).await;
+    let client: ProtonClient = ProtonClient::new(config_for(&server).with_follow_new(true));
+    let emails = client.fetch_all(&Folder::Inbox).await.unwrap();
+    let uids: Vec<u32> = emails.iter().map(|e| e.uid).collect();
+    assert_eq!(uids, vec![3, 1, 2]);
+    assert_eq!(emails[0].subject.original, "Late");
+
+    // A CHARSET stays in front of the UID range of the follow-up.
+    let server = FakeImapServer::start(mailbox()).await;
+    let client: ProtonClient = ProtonClient::new(config_for(&server).with_follow_new(true));
+    let emails = client
+        .search(&Folder::Inbox, "CHARSET UTF-8 ALL")
+        .await
+        .unwrap();
+    let uids: Vec<u32> = emails.iter().map(|e| e.uid).collect();
+    assert_eq!(uids, vec![3, 1, 2]);
+}
+
+/// Build a multipart/mixed email with one extra part after the text.
+fn make_email_with_part(subject: &str, part_headers: &str) -> Vec<u8> {
+    format!(
+        "From: alice@example.com\r\n\
+         To: bob@example.com\r\n\
+         Subject: {subject}\r\n\
+         Date: Mon, 01 Jan 2024 12:00:00 +0000\r\n\
+         Content-Type: multipart/mixed; boundary=\"b1\"\r\n\
+         \r\n\
+         --b1\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\
+         \r\n\
+         See below.\r\n\
+         --b1\r\n\
+         {part_headers}\r\n\
+         Content-Transfer-Encoding: base64\r\n\
+         \r\n\
+         JVBERi0=\r\n\
+         --b1--\r\n"
+    )
+    .into_bytes()
+}
+
+#[tokio::test]
+async fn test_fetch_with_attachments() {
+    let plain = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "No files",
+        "Just text.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let with_pdf = make_email_with_part(
+        "Report",
+        "Content-Type: application/pdf; name=\"report.pdf\"\r\n\
+         Content-Disposition: attachment; filename=\"report.pdf\"",
+    );
+    let inline_image = make_email_with_part(
+        "Logo",
+        "Content-Type: image/png; name=\"logo.png\"\r\n\
+         Content-Disposition: inline; filename=\"logo.png\"",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &plain)
+        .email(2, false, &with_pdf)
+        .email(3, false, &inline_image)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let found = client.fetch_with_attachments(&Folder::Inbox).await.unwrap();
+    assert_eq!(found.len(), 1);
+
+    let (email, attachments) = &found[0];
+    assert_eq!(email.uid, 2);
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].part, "2");
+    assert_eq!(attachments[0].content_type, "application/pdf");
+    assert_eq!(attachments[0].filename.as_deref(), Some("report.pdf"));
+    assert_eq!(attachments[0].size, 8);
+}
+
+#[tokio::test]
+async fn test_fetch_unanswered() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Question",
+        "Any news?",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email_with_flags(1, &["\\Seen", "\\Answered"], &raw)
+        .email(2, true, &raw)
+        .email(3, false, &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let unanswered = || async {
+        let mut uids: Vec<u32> = client
+            .fetch_unanswered(&Folder::Inbox)
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| e.uid)
+            .collect();
+        uids.sort_unstable();
+        uids
+    };
+    assert_eq!(unanswered().await, vec![2, 3]);
+
+    writer_for(&server)
+        .mark_answered(3, &Folder::Inbox)
+        .await
+        .unwrap();
+    assert_eq!(unanswered().await, vec![2]);
+}
+
+#[tokio::test]
+async fn test_tls_info() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").build();
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let info = client.tls_info().await.unwrap();
+    assert_eq!(info.protocol_version, "TLSv1_3");
+    assert!(info.cipher_suite.starts_with("TLS13_"));
+}
+
+#[tokio::test]
+async fn test_fetch_unsubscribe_info() {
+    let newsletter = b"From: news@example.com\r\n\
+        Subject: Weekly\r\n\
+        List-Unsubscribe: <mailto:leave@example.com?subject=stop>,\r\n \
+        <https://example.com/u/1>\r\n\
+        \r\n\
+        News.";
+    let plain = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Hi",
+        "Not a list.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, newsletter)
+        .email(2, false, &plain)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let info = client
+        .fetch_unsubscribe_info(&Folder::Inbox, 1)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.mailto, ["mailto:leave@example.com?subject=stop"]);
+    assert_eq!(info.urls, ["https://example.com/u/1"]);
+    assert!(!info.one_click);
+
+    assert_eq!(
+        client
+            .fetch_unsubscribe_info(&Folder::Inbox, 2)
+            .await
+            .unwrap(),
+        None
+    );
+    let err = client
+        .fetch_unsubscribe_info(&Folder::Inbox, 99)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::MessageNotFound { uid: 99, .. }));
+}
+
+#[tokio::test]
+async fn test_verify_hostname() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").build();
+    let server = FakeImapServer::start(mailbox).await;
+
+    // The fake server's certificate is issued to 127.0.0.1.
+    let client: ProtonClient = ProtonClient::new(config_for(&server).with_verify_hostname(true));
+    assert_eq!(client.list_folders().await.unwrap(), vec!["INBOX"]);
+
+    let mismatched = ImapConfig::new("localhost", server.port(), "testuser", "testpass");
+    let client: ProtonClient = ProtonClient::new(mismatched.clone());
+    assert!(client.list_folders().await.is_ok());
+
+    let client: ProtonClient = ProtonClient::new(mismatched.with_verify_hostname(true));
+    let err = client.list_folders().await.unwrap_err();
+    assert!(matches!(err, Error::Tls(_)), "{err:?}");
+}
+
+#[tokio::test]
+async fn test_fetch_thread_headers() {
+    let starter = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Plans",
+        "Lunch?",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let reply = b"From: bob@example.com\r\n\
+        To: alice@example.com\r\n\
+        Subject: Re: Re: Plans\r\n\
+        Date: Mon, 01 Jan 2024 14:00:00 +0000\r\n\
+        Message-ID: <c@example.com>\r\n\
+        In-Reply-To: <b@example.com>\r\n\
+        References: <a@example.com>\r\n <b@example.com>\r\n\
+        \r\n\
+        Sure."
+        .to_vec();
+    let bare_reply = b"From: bob@example.com\r\n\
+        To: alice@example.com\r\n\
+        Subject: Re: Plans\r\n\
+        Date: Mon, 01 Jan 2024 13:00:00 +0000\r\n\
+        In-Reply-To: <a@example.com>\r\n\
+        \r\n\
+        Noon?"
+        .to_vec();
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &starter)
+        .email(2, true, &bare_reply)
+        .email(3, false, &reply)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let fetches = client
+        .fetch_thread_headers(&Folder::Inbox, &[1, 2, 3])
+        .await
+        .unwrap();
+    assert_eq!(fetches.len(), 3);
+    assert!(fetches.iter().all(|f| f.body.is_none()));
+
+    assert_eq!(fetches[0].reply_depth(), 0);
+    assert_eq!(fetches[1].reply_depth(), 1);
+    assert_eq!(
+        fetches[2].references(),
+        vec!["<a@example.com>", "<b@example.com>"]
+    );
+    assert_eq!(fetches[2].reply_depth(), 2);
+}
+
+#[tokio::test]
+async fn test_fetch_and_mark_read() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Unread",
+        "Read me.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, false, &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+    let client = client_for(&server);
+
+    // A plain fetch peeks and leaves the message unread.
+    client.fetch_uid(&Folder::Inbox, 2).await.unwrap();
+
+    let email = writer.fetch_and_mark_read(&Folder::Inbox, 1).await.unwrap();
+    assert_eq!(email.subject.original, "Unread");
+
+    let unseen = client.fetch_unseen(&Folder::Inbox).await.unwrap();
+    assert_eq!(unseen.len(), 1);
+    assert_eq!(unseen[0].uid, 2);
+
+    let err = writer
+        .fetch_and_mark_read(&Folder::Inbox, 99)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::MessageNotFound { uid: 99, .. }));
+}
+
+#[tokio::test]
+async fn test_fetch_uids_marking_read() {
+    let raw = |subject: &str| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "Read me.",
+            "Mon, 01 Jan 2024 12:00:00 +0000",
+        )
+    };
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw("One"))
+        .email(2, false, &raw("Two"))
+        .email(3, false, &raw("Three"))
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let writer = writer_for(&server);
+
+    let emails = writer
+        .fetch_uids_marking_read(&Folder::Inbox, &[3, 1, 7])
+        .await
+        .unwrap();
+    let uids: Vec<u32> = emails.iter().map(|e| e.uid).collect();
+    assert_eq!(uids, vec![1, 3]);
+
+    // One non-peek FETCH, and no STORE.
+    let commands = server.commands();
+    assert!(
+        commands
+            .iter()
+            .any(|c| c.starts_with("UID FETCH") && c.contains("BODY[]"))
+    );
+    assert!(!commands.iter().any(|c| c.starts_with("UID STORE")));
+
+    let unseen = client_for(&server)
+        .fetch_unseen(&Folder::Inbox)
+        .await
+        .unwrap();
+    let uids: Vec<u32> = unseen.iter().map(|e| e.uid).collect();
+    assert_eq!(uids, vec![2]);
+}
+
+#[tokio::test]
+async fn test_session_shutdown_closes_connection() {
+    let mailbox = MailboxBuilder::new().folder("INBOX").build();
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let mut sessions = Vec::new();
+    for _ in 0..3 {
+        let mut session = client.session().await.unwrap();
+        session.select(&Folder::Inbox, false).await.unwrap();
+        sessions.push(session);
+    }
+    assert_eq!(server.open_connections(), 3);
+
+    for session in sessions {
+        session.shutdown().await.unwrap();
+    }
+    assert!(server.wait_until_idle(Duration::from_secs(1)).await);
+}
+
+#[tokio::test]
+async fn test_session_shutdown_stops_background_tasks() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Listed",
+        "Prefetched.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .build();
+    let server = FakeImapServer::start(mailbox).await;
+    let client: ProtonClient = ProtonClient::new(config_for(&server))
+        .with_cache(8)
+        .with_prefetch(1);
+
+    let mut watch = client.watch(&Folder::Inbox);
+    client.fetch_headers(&Folder::Inbox, &[1]).await.unwrap();
+    assert!(client.background_tasks() > 0);
+
+    let session = client.session().await.unwrap();
+    session.shutdown().await.unwrap();
+    assert_eq!(client.background_tasks(), 0);
+
+    // The watch task is gone, and its sender with it.
+    let closed = tokio::time::timeout(Duration::from_secs(1), async {
+        while watch.recv().await.is_some() {}
+    })
+    .await;
+    assert!(closed.is_ok());
+    assert!(server.wait_until_idle(Duration::from_secs(5)).await);
+}
+
+#[tokio::test]
+async fn test_search_non_ascii_subject() {
+    let umlaut = make_raw_email(
+        "hans@example.de",
+        "bob@example.com",
+        "Grüße von Müller",
+        "Hallo.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let plain = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Hello from Mueller",
+        "Hi.",
+        "Mon, 01 Jan 2024 13:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &umlaut)
+        .email(2, false, &plain)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let emails = client
+        .search(&Folder::Inbox, "SUBJECT \"Müller\"")
+        .await
+        .unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].uid, 1);
+    // The literal waited for the server's continuation.
+    assert_eq!(server.continuation_count(), 1);
+
+    let mut session = client.session().await.unwrap();
+    let emails = session
+        .search(&Folder::Inbox, "SUBJECT Grüße")
+        .await
+        .unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(server.continuation_count(), 2);
+    session.logout().await.unwrap();
+
+    // The CHARSET goes in front of the UID range.
+    let emails = client
+        .search_in_range(&Folder::Inbox, 1, Some(2), "SUBJECT \"Müller\"")
+        .await
+        .unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].uid, 1);
+    assert_eq!(server.continuation_count(), 3);
+}
+
+#[tokio::test]
+async fn test_apply_rule() {
+    let raw = make_raw_email(
+        "newsletter@example.com",
+        "bob@example.com",
+        "Weekly digest",
+        "News.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, false, &raw)
+        .folder("Newsletters")
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let writer = writer_for(&server);
+    let client = client_for(&server);
+    let newsletters = Folder::custom("Newsletters");
+
+    let outcome = writer
+        .apply_rule(1, &Folder::Inbox, &[Flag::Seen], Some(&newsletters))
+        .await
+        .unwrap();
+    assert_eq!(outcome.flags_set, vec![Flag::Seen]);
+    assert_eq!(outcome.moved_to, Some(newsletters.clone()));
+
+    let moved = client.fetch_all(&newsletters).await.unwrap();
+    assert_eq!(moved.len(), 1);
+    assert!(client.fetch_unseen(&newsletters).await.unwrap().is_empty());
+
+    // Flags only: the message stays put.
+    let label = Flag::Keyword("Digest".to_string());
+    let outcome = writer
+        .apply_rule(2, &Folder::Inbox, std::slice::from_ref(&label), None)
+        .await
+        .unwrap();
+    assert_eq!(outcome.moved_to, None);
+
+    let fetches = client
+        .fetch_items(&Folder::Inbox, &[2], "(FLAGS)")
+        .await
+        .unwrap();
+    assert_eq!(fetches[0].flags, vec![label]);
+}
+
+#[tokio::test]
+async fn test_folder_delimiter() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Nested",
+        "In a subfolder.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .folder("Folders.Work")
+        .email(1, false, &raw)
+        .delimiter('.')
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let delimiter = client.folder_delimiter().await.unwrap();
+    assert_eq!(delimiter, '.');
+
+    let work = Folder::nested(&["Folders", "Work"], delimiter);
+    assert_eq!(work.components(delimiter), vec!["Folders", "Work"]);
+    assert_eq!(client.fetch_all(&work).await.unwrap().len(), 1);
+
+    // The default fake server uses "/", like Proton Bridge.
+    let server = FakeImapServer::start(MailboxBuilder::new().folder("INBOX").build()).await;
+    assert_eq!(client_for(&server).folder_delimiter().await.unwrap(), '/');
+}
+
+#[tokio::test]
+async fn test_refresh() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Poll",
+        "Polling.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &raw)
+        .email(2, false, &raw)
+        .email(5, false, &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let status = client.refresh(&Folder::Inbox).await.unwrap();
+    assert_eq!(status.exists, 3);
+    assert_eq!(status.unseen, 2);
+    assert_eq!(status.first_unseen, Some(2));
+    assert_eq!(status.uid_next, Some(6));
+
+    // A change made by another client shows up on the next poll.
+    writer_for(&server)
+        .add_flag(2, &Folder::Inbox, &Flag::Seen)
+        .await
+        .unwrap();
+    let status = client.refresh(&Folder::Inbox).await.unwrap();
+    assert_eq!(status.unseen, 1);
+    assert_eq!(status.first_unseen, Some(3));
+}
+
+#[tokio::test]
+async fn test_refresh_without_uid_codes() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Sparse",
+        "Minimal server.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &raw)
+        .email(2, false, &raw)
+        .omit_uidnext()
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let status = client.refresh(&Folder::Inbox).await.unwrap();
+    assert_eq!(status.exists, 2);
+    assert_eq!(status.unseen, 1);
+    assert_eq!(status.uid_next, None);
+    assert_eq!(status.uid_validity, None);
+
+    // Everything that selects the folder keeps working.
+    let emails = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(emails.len(), 2);
+}
+
+#[tokio::test]
+async fn test_lowercase_status_responses() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Quiet",
+        "Lowercase server.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .literal_plus()
+        .lowercase_status()
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    let emails = client_for(&server).fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(emails.len(), 1);
+
+    let writer = writer_for(&server);
+    writer
+        .add_flag(1, &Folder::Inbox, &Flag::Seen)
+        .await
+        .unwrap();
+
+    let mut session = writer.session().await.unwrap();
+    assert!(session.has_capability("literal+").await.unwrap());
+    session.logout().await.unwrap();
+
+    let result = client_for(&server)
+        .fetch_all(&Folder::from("Missing"))
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_compress_deflate() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Squeezed",
+        "Compressed on the wire.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .email(2, false, &raw)
+        .compress()
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    // Off by default.
+    client_for(&server).fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(server.compress_count(), 0);
+
+    let client: ProtonClient = ProtonClient::new(config_for(&server).with_compress(true));
+    let emails = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(emails.len(), 2);
+    assert_eq!(emails[0].subject.original, "Squeezed");
+    assert_eq!(server.compress_count(), 1);
+
+    let info = client.tls_info().await.unwrap();
+    assert!(!info.protocol_version.is_empty());
+    assert_eq!(server.compress_count(), 2);
+}
+
+#[tokio::test]
+async fn test_compress_without_server_support() {
+    let raw = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Plain",
+        "Not compressed.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, &raw)
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+
+    let client: ProtonClient = ProtonClient::new(config_for(&server).with_compress(true));
+    let emails = client.fetch_all(&Folder::Inbox).await.unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(server.compress_count(), 0);
+}
+
+#[tokio::test]
+async fn test_fetch_with_header() {
+    let list = |uid: u32, list_id: &str| {
+        format!(
+            "From: list@example.com\r\nTo: bob@example.com\r\nSubject: Digest {uid}\r\n\
+             Date: Mon, 01 Jan 2024 12:00:00 +0000\r\nList-Id: {list_id}\r\n\r\nBody"
+        )
+    };
+    let direct = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Direct",
+        "Hi",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, false, list(1, "<dev.lists.example.com>").as_bytes())
+        .email(2, false, &direct)
+        .email(3, false, list(3, "<Announce.lists.example.com>").as_bytes())
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let mut uids: Vec<u32> = client
+        .fetch_with_header(&Folder::Inbox, "List-Id", None)
+        .await
+        .unwrap()
+        .iter()
+        .map(|e| e.uid)
+        .collect();
+    uids.sort_unstable();
+    assert_eq!(uids, [1, 3]);
+
+    let announce = client
+        .fetch_with_header(&Folder::Inbox, "list-id", Some("announce.lists"))
+        .await
+        .unwrap();
+    assert_eq!(announce.len(), 1);
+    assert_eq!(announce[0].uid, 3);
+
+    let none = client
+        .fetch_with_header(&Folder::Inbox, "X-Mailer", None)
+        .await
+        .unwrap();
+    assert!(none.is_empty());
+}
+
+#[tokio::test]
+async fn test_fetch_conversation() {
+    let message = |conversation: &str, subject: &str, date: &str| {
+        format!(
+            "From: alice@example.com\r\n\
+             To: bob@example.com\r\n\
+             Subject: {subject}\r\n\
+             Date: {date}\r\n\
+             X-Pm-ConversationID: {conversation}\r\n\
+             \r\n\
+             Body"
+        )
+        .into_bytes()
+    };
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(
+            1,
+            true,
+            &message("conv-1==", "Re: Plan", "Tue, 02 Jan 2024 12:00:00 +0000"),
+        )
+        .email(
+            2,
+            true,
+            &message("conv-2==", "Other", "Mon, 01 Jan 2024 13:00:00 +0000"),
+        )
+        .email(
+            3,
+            true,
+            &message("conv-1==", "Plan", "Mon, 01 Jan 2024 12:00:00 +0000"),
+        )
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let fetches = client
+        .fetch_items(
+            &Folder::Inbox,
+            &[1],
+            "(BODY.PEEK[HEADER.FIELDS (X-PM-CONVERSATIONID)])",
+        )
+        .await
+        .unwrap();
+    let id = fetches[0].conversation_id().unwrap();
+    assert_eq!(id, "conv-1==");
+
+    let emails = client
+        .fetch_conversation(&Folder::Inbox, &id)
+        .await
+        .unwrap();
+    let uids: Vec<u32> = emails.iter().map(|e| e.uid).collect();
+    assert_eq!(uids, vec![3, 1]);
+
+    // The same conversation, resolved from one of its messages.
+    let thread = client.fetch_thread(&Folder::Inbox, 1).await.unwrap();
+    let uids: Vec<u32> = thread.iter().map(|m| m.email.uid).collect();
+    assert_eq!(uids, vec![3, 1]);
+
+    let err = client.fetch_thread(&Folder::Inbox, 9).await.unwrap_err();
+    assert!(matches!(err, Error::MessageNotFound { uid: 9, .. }));
+}
+
+#[tokio::test]
+async fn test_export_mbox_order() {
+    let email = |subject: &str, date: &str| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "From the archive.",
+            date,
+        )
+    };
+
+    // Imported mail: UIDs do not follow the dates.
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &email("Third", "Wed, 03 Jan 2024 12:00:00 +0000"))
+        .email(2, true, &email("First", "Mon, 01 Jan 2024 12:00:00 +0000"))
+        .email(3, true, &email("Second", "Tue, 02 Jan 2024 12:00:00 +0000"))
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+    let subjects = |mbox: &[u8]| -> Vec<String> {
+        String::from_utf8_lossy(mbox)
+            .lines()
+            .filter_map(|line| line.strip_prefix("Subject: "))
+            .map(str::to_string)
+            .collect()
+    };
+
+    let mut mbox = Vec::new();
+    let written = client
+        .export_mbox(&Folder::Inbox, FetchOrder::Uid, &mut mbox)
+        .await
+        .unwrap();
+    assert_eq!(written, 3);
+    assert_eq!(subjects(&mbox), ["Third", "First", "Second"]);
+    assert!(mbox.starts_with(b"From MAILER-DAEMON Wed Jan  3 12:00:00 2024\n"));
+    assert!(!mbox.contains(&b'\r'));
+
+    let mut mbox = Vec::new();
+    client
+        .export_mbox(&Folder::Inbox, FetchOrder::OldestFirst, &mut mbox)
+        .await
+        .unwrap();
+    assert_eq!(subjects(&mbox), ["First", "Second", "Third"]);
+}
+
+#[tokio::test]
+async fn test_export_tar() {
+    let email = |subject: &str| {
+        make_raw_email(
+            "alice@example.com",
+            "bob@example.com",
+            subject,
+            "Backed up.",
+            "Mon, 01 Jan 2024 12:00:00 +0000",
+        )
+    };
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(1, true, &email("One"))
+        .email_with_flags(2, &["\\Flagged"], &email("Two"))
+        .build();
+
+    let server = FakeImapServer::start(mailbox).await;
+    let client = client_for(&server);
+
+    let mut tar = Vec::new();
+    let written = client.export_tar(&Folder::Inbox, &mut tar).await.unwrap();
+    assert_eq!(written, 2);
+    assert_eq!(tar.len() % 512, 0);
+
+    // Walk the entries: a 512-byte header, then the padded contents.
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while tar[offset] != 0 {
+        let header = &tar[offset..offset + 512];
+        let name = String::from_utf8_lossy(&header[..100])
+            .trim_end_matches('\0')
+            .to_string();
+        let size = std::str::from_utf8(&header[124..135]).unwrap();
+        let size = usize::from_str_radix(size, 8).unwrap();
+        entries.push((name, tar[offset + 512..offset + 512 + size].to_vec()));
+        offset += 512 + size.div_ceil(512) * 512;
+    }
+    assert!(tar[offset..].iter().all(|&b| b == 0));
+
+    let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["1.eml", "2.eml", "manifest.json"]);
+    assert_eq!(entries[1].1, email("Two"));
+
+    let manifest: serde_json::Value = serde_json::from_slice(&entries[2].1).unwrap();
+    assert_eq!(manifest[0]["uid"], 1);
+    assert_eq!(manifest[0]["flags"][0], "\\Seen");
+    assert_eq!(manifest[1]["flags"][0], "\\Flagged");
+    assert!(manifest[1]["date"].is_string());
+}
+
+#[tokio::test]
+async fn test_list_view() {
+    let older = make_raw_email(
+        "carol@example.com",
+        "bob@example.com",
+        "Too old",
+        "Outside the window.",
+        "Sun, 31 Dec 2023 12:00:00 +0000",
+    );
+    let plain = make_raw_email(
+        "alice@example.com",
+        "bob@example.com",
+        "Plain",
+        "Just text.",
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let with_pdf = make_email_with_part(
+        "Report",
+        "Content-Type: application/pdf; name=\"report.pdf\"\r\n\
+         Content-Disposition: attachment; filename=\"report.pdf\"",
+    );
+
+    let mailbox = MailboxBuilder::new()
+        .folder("INBOX")
+        .email(3, true, &older)
+        .email(5, true, &plain)
+        .email_with_flags(9, &["\\Flagged"], &with_pdf)
+        .build();
+
+    let server = FakeImapServer::start_recording(mailbox).await;
+    let client = client_for(&server);
+
+    let items = client.list_view(&Folder::Inbox, 2).await.unwrap();
+    let uids: Vec<u32> = items.iter().map(|item| item.uid).collect();
+    assert_eq!(uids, [9, 5]);
+
+    assert_eq!(items[0].subject.as_deref(), Some("Report"));
+    assert_eq!(items[0].flags, vec![Flag::Flagged]);
+    assert!(items[0].has_attachments);
+    assert_eq!(items[0].size, u32::try_from(with_pdf.len()).ok());
+
+    assert_eq!(items[1].subject.as_deref(), Some("Plain"));
+    assert_eq!(items[1].from.as_ref().unwrap().address, "alice@example.com");
+    assert_eq!(items[1].flags, vec![Flag::Seen]);
+    assert!(!items[1].has_attachments);
+    assert_eq!(
+        items[1].date.unwrap().to_rfc3339(),
+        "2024-01-01T12:00:00+00:00"
+    );
+
+    // One FETCH for the whole window, and no bodies.
+    let fetches: Vec<String> = server
+        .commands()
+        .into_iter()
+        .filter(|c| c.starts_with("UID FETCH"))
+        .collect();
+    assert_eq!(fetches.len(), 1);
+    assert!(fetches[0].contains("ENVELOPE"));
+    assert!(!fetches[0].contains("BODY["));
+    assert!(!fetches[0].contains("BODY.PEEK"));
+
+    assert!(
+        client
+            .list_view(&Folder::Inbox, 0)
+            .await
+            .unwrap()
+            .is_empty()
+    );
 }